@@ -0,0 +1,38 @@
+#ifndef STDOUT_ROTATOR_H
+#define STDOUT_ROTATOR_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Built with: cargo build --release --features ffi, as libstdout_rotator.so and
+   libstdout_rotator.a */
+
+typedef struct Rotator Rotator;
+
+/* max_size is the command line default when 0 and max_history when negative, a max_history
+   of 0 keeping no rotation. compression is "gzip", "zstd", "xz", "lz4" or NULL. Returns NULL
+   on error */
+Rotator *rotator_open(const char *output_file, uint64_t max_size, int64_t max_history,
+                      const char *compression);
+
+/* The functions below return 0 on success and -1 on error, and can be called from several
+   threads. Lines are never split by a rotation, a trailing partial line waits for its end
+   or for rotator_flush */
+int rotator_write(const Rotator *rotator, const uint8_t *data, size_t length);
+int rotator_flush(const Rotator *rotator);
+
+/* Flushes and frees the rotator, waiting for the compression of its last rotations */
+int rotator_close(Rotator *rotator);
+
+/* The error of the last call failing on this thread, NULL if none did */
+const char *rotator_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,100 @@
+use log4rs::append::Append;
+use log4rs::encode::pattern::PatternEncoder;
+use log4rs::encode::writer::simple::SimpleWriter;
+use log4rs::encode::Encode;
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::writer::RotatingWriter;
+
+thread_local! {
+    static WRITING: Cell<bool> = const { Cell::new(false) };
+}
+
+// A RotatingWriter shared by every thread logging through it, as a log4rs appender or as the
+// writer of a tracing subscriber
+#[derive(Clone, Debug)]
+pub struct SharedWriter {
+    writer: Arc<Mutex<RotatingWriter>>,
+}
+
+impl SharedWriter {
+    pub fn new(writer: RotatingWriter) -> SharedWriter {
+        SharedWriter {
+            writer: Arc::new(Mutex::new(writer)),
+        }
+    }
+
+    // What tracing_subscriber's with_writer takes, as it is implemented for any Fn() -> Write
+    pub fn make_writer(&self) -> impl Fn() -> SharedWriter + Send + Sync + 'static {
+        let shared = self.clone();
+        move || shared.clone()
+    }
+
+    // Logging done by the rotation itself, as it rotates in the middle of a write, would come
+    // back to the writer it holds locked, so it is dropped
+    fn with_writer<T>(
+        &self,
+        default: T,
+        write: impl FnOnce(&mut RotatingWriter) -> io::Result<T>,
+    ) -> io::Result<T> {
+        if WRITING.with(|writing| writing.replace(true)) {
+            return Ok(default);
+        }
+        let result = match self.writer.lock() {
+            Ok(mut writer) => write(&mut writer),
+            Err(_) => Err(io::Error::other("Rotating writer poisoned by a panic")),
+        };
+        WRITING.with(|writing| writing.set(false));
+        result
+    }
+}
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.with_writer(buf.len(), |writer| writer.write(buf))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.with_writer((), |writer| writer.write_all(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.with_writer((), |writer| writer.flush())
+    }
+}
+
+#[derive(Debug)]
+pub struct RotatingAppender {
+    writer: SharedWriter,
+    encoder: Box<dyn Encode>,
+}
+
+impl RotatingAppender {
+    // Encoded as log4rs' file appender does by default
+    pub fn new(writer: RotatingWriter) -> RotatingAppender {
+        RotatingAppender::with_encoder(writer, Box::<PatternEncoder>::default())
+    }
+
+    pub fn with_encoder(writer: RotatingWriter, encoder: Box<dyn Encode>) -> RotatingAppender {
+        RotatingAppender {
+            writer: SharedWriter::new(writer),
+            encoder,
+        }
+    }
+}
+
+impl Append for RotatingAppender {
+    // Encoded first, so that a record is written at once and never split by a rotation
+    fn append(&self, record: &log::Record) -> anyhow::Result<()> {
+        let mut encoded = SimpleWriter(vec![]);
+        self.encoder.encode(&mut encoded, record)?;
+        self.writer.clone().write_all(&encoded.0)?;
+        Ok(())
+    }
+
+    fn flush(&self) {
+        let _ = self.writer.clone().flush();
+    }
+}
@@ -0,0 +1,249 @@
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+
+use crate::writer::RotatingWriter;
+
+// Writes accepted before the writer thread catches up, further ones wait for it
+const MAX_QUEUED: usize = 4 * 1024 * 1024;
+
+enum Request {
+    Write(Vec<u8>),
+    Flush(u64),
+}
+
+#[derive(Default)]
+struct State {
+    queued: usize,
+    flushed: u64,
+    error: Option<io::Error>,
+    stopped: bool,
+    waker: Option<Waker>,
+}
+
+impl State {
+    fn wait(&mut self, cx: &Context<'_>) {
+        self.waker = Some(cx.waker().clone());
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+// A RotatingWriter driven from async code: rotations, compression and uploads happen on a
+// thread of its own, so that a poll never blocks the task writing. The poll methods are those
+// of tokio's AsyncWrite, which it implements with the tokio feature
+pub struct AsyncRotatingWriter {
+    sender: Option<Sender<Request>>,
+    state: Arc<Mutex<State>>,
+    flushes: u64,
+    flushing: Option<u64>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl AsyncRotatingWriter {
+    pub fn new(mut writer: RotatingWriter) -> AsyncRotatingWriter {
+        let (sender, receiver) = channel();
+        let state = Arc::new(Mutex::new(State::default()));
+        let worker_state = state.clone();
+        let worker = thread::spawn(move || {
+            for request in receiver {
+                let (result, written, flushed) = match request {
+                    Request::Write(data) => (writer.write_all(&data), data.len(), None),
+                    Request::Flush(flush) => (writer.flush(), 0, Some(flush)),
+                };
+                let mut state = lock(&worker_state);
+                state.queued -= written;
+                if let Some(flush) = flushed {
+                    state.flushed = flush;
+                }
+                if let Err(op) = result {
+                    state.error = Some(op);
+                }
+                state.wake();
+            }
+            // Rotations still being compressed are finished before shutting down completes
+            drop(writer);
+            let mut state = lock(&worker_state);
+            state.stopped = true;
+            state.wake();
+        });
+        AsyncRotatingWriter {
+            sender: Some(sender),
+            state,
+            flushes: 0,
+            flushing: None,
+            worker: Some(worker),
+        }
+    }
+
+    fn send(&self, request: Request) -> io::Result<()> {
+        self.sender
+            .as_ref()
+            .and_then(|sender| sender.send(request).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "Rotating writer shut down"))
+    }
+
+    pub fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let mut state = lock(&this.state);
+        if let Some(err) = state.error.take() {
+            return Poll::Ready(Err(err));
+        }
+        if state.queued >= MAX_QUEUED {
+            state.wait(cx);
+            return Poll::Pending;
+        }
+        state.queued += buf.len();
+        drop(state);
+        this.send(Request::Write(buf.to_vec()))?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    // Ready once everything written before is in the output file
+    pub fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let flush = match this.flushing {
+            Some(flush) => flush,
+            None => {
+                this.flushes += 1;
+                this.send(Request::Flush(this.flushes))?;
+                this.flushing = Some(this.flushes);
+                this.flushes
+            }
+        };
+        let mut state = lock(&this.state);
+        if let Some(err) = state.error.take() {
+            this.flushing = None;
+            return Poll::Ready(Err(err));
+        }
+        if state.flushed < flush {
+            state.wait(cx);
+            return Poll::Pending;
+        }
+        this.flushing = None;
+        Poll::Ready(Ok(()))
+    }
+
+    // Ready once the writer is closed and its rotations compressed and uploaded
+    pub fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.sender.is_some() {
+            match self.as_mut().poll_flush(cx) {
+                Poll::Ready(Ok(())) => {}
+                other => return other,
+            }
+            self.sender = None;
+        }
+        let mut state = lock(&self.state);
+        if !state.stopped {
+            state.wait(cx);
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for AsyncRotatingWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        AsyncRotatingWriter::poll_write(self, cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        AsyncRotatingWriter::poll_flush(self, cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        AsyncRotatingWriter::poll_shutdown(self, cx)
+    }
+}
+
+impl std::fmt::Debug for AsyncRotatingWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncRotatingWriter")
+            .field("queued", &lock(&self.state).queued)
+            .finish()
+    }
+}
+
+fn lock(state: &Mutex<State>) -> std::sync::MutexGuard<'_, State> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Drop for AsyncRotatingWriter {
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::RotatorBuilder;
+    use std::fs;
+    use tokio::io::AsyncWriteExt;
+
+    #[test]
+    fn writes_are_rotated_under_a_tokio_runtime() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = directory.path().join("out.log");
+        let writer = RotatorBuilder::new(output_file.to_str().unwrap())
+            .max_size(100)
+            .max_history(100)
+            .build()
+            .unwrap();
+        let mut writer = AsyncRotatingWriter::new(writer);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            for line in 0..100 {
+                writer
+                    .write_all(format!("line {}\n", line).as_bytes())
+                    .await
+                    .unwrap();
+            }
+            writer.flush().await.unwrap();
+            writer.shutdown().await.unwrap();
+            assert!(writer.write_all(b"late\n").await.is_err());
+        });
+        let mut rotations: Vec<(u64, String)> = fs::read_dir(directory.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter_map(|name| {
+                let index = name.strip_prefix("out.log.")?.parse().ok()?;
+                Some((
+                    index,
+                    fs::read_to_string(directory.path().join(&name)).unwrap(),
+                ))
+            })
+            .collect();
+        rotations.sort();
+        assert!(rotations.len() > 1);
+        let content: String = rotations.into_iter().map(|(_, content)| content).collect();
+        let expected: String = (0..100).map(|line| format!("line {}\n", line)).collect();
+        assert_eq!(
+            content + &fs::read_to_string(&output_file).unwrap(),
+            expected
+        );
+    }
+}
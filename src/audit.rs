@@ -0,0 +1,49 @@
+use log::warn;
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::webhook::event_payload;
+use crate::RotatorError;
+
+const LOGGER: &str = "audit";
+
+// Every event is appended as a single JSON line and synced before going on, so that the trail
+// survives a crash. Writers, the compressor and the uploader share the same file
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    file: Arc<Mutex<File>>,
+}
+
+impl AuditLog {
+    pub fn open(path: &str) -> Result<AuditLog, RotatorError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|op| format!("Error during opening of audit file '{}': {}", path, op))?;
+        Ok(AuditLog {
+            path: PathBuf::from(path),
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    // Failing to audit never stops the logs from being written
+    pub fn record(&self, event: &str, details: Value) {
+        let mut line = event_payload(event, details).to_string();
+        line.push('\n');
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(op) = file
+            .write_all(line.as_bytes())
+            .and_then(|_| file.sync_data())
+        {
+            warn!(target: LOGGER, "Error while writing to audit file '{}': {}", self.path.display(), op);
+        }
+    }
+}
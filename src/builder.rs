@@ -0,0 +1,165 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+use crate::compress::CompressionFormat;
+use crate::writer::{RotatingWriter, WriterOptions};
+
+#[derive(Debug)]
+pub enum BuildError {
+    MissingOutputFile,
+    ZeroMaxSize,
+    ZeroMaxLines,
+    ZeroInterval(&'static str),
+    CompressionLevel {
+        format: CompressionFormat,
+        level: u32,
+        min: u32,
+        max: u32,
+    },
+    // Compression level set without a compression
+    MissingCompression,
+    // The output file could not be locked, opened or prepared for rotation
+    Open {
+        output_file: String,
+        reason: String,
+    },
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::MissingOutputFile => write!(f, "Missing output file"),
+            BuildError::ZeroMaxSize => write!(f, "Maximum size must be above 0 bytes"),
+            BuildError::ZeroMaxLines => write!(f, "Maximum number of lines must be above 0"),
+            BuildError::ZeroInterval(setting) => write!(f, "{} must be above 0", setting),
+            BuildError::CompressionLevel {
+                format,
+                level,
+                min,
+                max,
+            } => write!(
+                f,
+                "Compression level {} is outside of the range {}-{} supported by {:?}",
+                level, min, max, format
+            ),
+            BuildError::MissingCompression => {
+                write!(f, "Compression level set without a compression")
+            }
+            BuildError::Open {
+                output_file,
+                reason,
+            } => write!(f, "Error while opening '{}': {}", output_file, reason),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+// Configures a RotatingWriter in code, checking the settings the command line would reject
+#[derive(Clone, Debug)]
+pub struct RotatorBuilder {
+    output_file: String,
+    options: WriterOptions,
+}
+
+impl RotatorBuilder {
+    pub fn new(output_file: &str) -> RotatorBuilder {
+        RotatorBuilder {
+            output_file: output_file.to_string(),
+            options: WriterOptions::default(),
+        }
+    }
+
+    pub fn max_size(mut self, bytes: u64) -> RotatorBuilder {
+        self.options.max_size = bytes;
+        self
+    }
+
+    pub fn max_history(mut self, rotations: u32) -> RotatorBuilder {
+        self.options.max_history = rotations;
+        self
+    }
+
+    pub fn max_total_size(mut self, bytes: u64) -> RotatorBuilder {
+        self.options.max_total_size = Some(bytes);
+        self
+    }
+
+    pub fn max_age(mut self, age: Duration) -> RotatorBuilder {
+        self.options.max_age = Some(age);
+        self
+    }
+
+    pub fn rotate_every(mut self, interval: Duration) -> RotatorBuilder {
+        self.options.rotate_every = Some(interval);
+        self
+    }
+
+    pub fn max_file_age(mut self, age: Duration) -> RotatorBuilder {
+        self.options.max_file_age = Some(age);
+        self
+    }
+
+    pub fn max_lines(mut self, lines: u64) -> RotatorBuilder {
+        self.options.max_lines = Some(lines);
+        self
+    }
+
+    pub fn compression(mut self, format: CompressionFormat) -> RotatorBuilder {
+        self.options.compression = Some(format);
+        self
+    }
+
+    pub fn compression_level(mut self, level: u32) -> RotatorBuilder {
+        self.options.compression_level = Some(level);
+        self
+    }
+
+    pub fn append(mut self, append: bool) -> RotatorBuilder {
+        self.options.append = append;
+        self
+    }
+
+    fn validate(&self) -> Result<(), BuildError> {
+        let options = &self.options;
+        if self.output_file.trim().is_empty() {
+            return Err(BuildError::MissingOutputFile);
+        }
+        if options.max_size == 0 {
+            return Err(BuildError::ZeroMaxSize);
+        }
+        if options.max_lines == Some(0) {
+            return Err(BuildError::ZeroMaxLines);
+        }
+        if options.rotate_every == Some(Duration::ZERO) {
+            return Err(BuildError::ZeroInterval("Rotation interval"));
+        }
+        if options.max_file_age == Some(Duration::ZERO) {
+            return Err(BuildError::ZeroInterval("Maximum file age"));
+        }
+        match (options.compression, options.compression_level) {
+            (None, Some(_)) => return Err(BuildError::MissingCompression),
+            (Some(format), Some(level)) => {
+                let (min, max, _) = format.levels();
+                if level < min || level > max {
+                    return Err(BuildError::CompressionLevel {
+                        format,
+                        level,
+                        min,
+                        max,
+                    });
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<RotatingWriter, BuildError> {
+        self.validate()?;
+        RotatingWriter::open(&self.output_file, self.options).map_err(|op| BuildError::Open {
+            output_file: self.output_file.clone(),
+            reason: op.to_string(),
+        })
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::SystemTime;
+use stdout_rotator::index::{open_rotations_since, open_since};
+use stdout_rotator::naming::{Naming, NamingArgs};
+use stdout_rotator::{timestamp, RotatorError};
+
+use crate::ring::RingFile;
+
+#[derive(clap::Args, Debug)]
+pub struct CatArgs {
+    #[arg(
+        help = "File to print after its rotations, oldest first. Ring files written with --ring-file are printed oldest line first"
+    )]
+    file: String,
+    #[arg(long, default_value = None, help = "Directory where rotated files are saved. If not provided, the same directory of the file will be used")]
+    rotation_directory: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Only print the file itself, not its rotations"
+    )]
+    active_only: bool,
+    #[arg(long, value_parser = timestamp, help = "Skip content written before this time (RFC 3339 timestamp or duration ago), using the index written with --index, or the timestamps of the lines without one. Whole rotations are skipped after the write times recorded with --manifest, or their modification time. The index granularity can include some earlier lines")]
+    since: Option<SystemTime>,
+}
+
+fn print_rotations(args: &CatArgs, output: &mut dyn Write) -> Result<bool, RotatorError> {
+    let rotations = open_rotations_since(
+        &args.file,
+        args.rotation_directory.as_deref(),
+        &Naming::new(&args.naming),
+        args.since,
+    )?;
+    let rotated = !rotations.is_empty();
+    for (rotation, mut reader) in rotations {
+        io::copy(&mut reader, output)
+            .map_err(|op| format!("Error while printing '{}': {}", rotation.display(), op))?;
+    }
+    Ok(rotated)
+}
+
+fn print_active(args: &CatArgs, output: &mut dyn Write) -> Result<(), RotatorError> {
+    let mut reader = open_since(Path::new(&args.file), args.since, None)?;
+    io::copy(&mut reader, output)
+        .map_err(|op| format!("Error while printing '{}': {}", args.file, op))?;
+    Ok(())
+}
+
+pub fn run(args: CatArgs) -> Result<(), RotatorError> {
+    let mut stdout = io::stdout().lock();
+    let active = Path::new(&args.file);
+    let ring = match active.exists() {
+        true => RingFile::open_existing(&args.file)?,
+        false => None,
+    };
+    match ring {
+        Some(_) if args.since.is_some() => {
+            return Err(RotatorError::new("--since is not supported for ring files"))
+        }
+        Some(mut ring) => ring.linearize(&mut stdout)?,
+        None => {
+            let rotated = !args.active_only && print_rotations(&args, &mut stdout)?;
+            // Only rotations are left once the output file was cleaned up
+            if !rotated || active.exists() {
+                print_active(&args, &mut stdout)?;
+            }
+        }
+    }
+    stdout
+        .flush()
+        .map_err(|op| format!("Error while flushing stdout: {}", op))?;
+    Ok(())
+}
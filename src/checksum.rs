@@ -0,0 +1,100 @@
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::RotatorError;
+
+pub const CHECKSUM_EXTENSION: &str = ".sha256";
+
+pub fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn file_digest(path: &Path) -> io::Result<[u8; 32]> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.finalize().into());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+pub fn checksum_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(CHECKSUM_EXTENSION);
+    PathBuf::from(sidecar)
+}
+
+// Written in the format of sha256sum, so that 'sha256sum -c' verifies the rotation
+pub fn rotation_checksum(path: &Path) -> Result<String, RotatorError> {
+    let digest = file_digest(path).map_err(|op| {
+        format!(
+            "Error while computing checksum of '{}': {}",
+            path.display(),
+            op
+        )
+    })?;
+    Ok(hex(&digest))
+}
+
+pub fn write_checksum(path: &Path) -> Result<(), RotatorError> {
+    let checksum = rotation_checksum(path)?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let sidecar = checksum_path(path);
+    fs::write(&sidecar, format!("{}  {}\n", checksum, name))
+        .map_err(|op| format!("Error while writing '{}': {}", sidecar.display(), op))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_checksum(content: &[u8]) -> String {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("rotation");
+        fs::write(&path, content).unwrap();
+        rotation_checksum(&path).unwrap()
+    }
+
+    #[test]
+    fn checksums_match_the_nist_vectors() {
+        assert_eq!(
+            file_checksum(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            file_checksum(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            file_checksum(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        // Read in several buffers
+        assert_eq!(
+            file_checksum(&vec![b'a'; 1_000_000]),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[test]
+    fn checksums_are_written_as_sha256sum_does() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("out.log.1");
+        fs::write(&path, b"abc").unwrap();
+        write_checksum(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(checksum_path(&path)).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  out.log.1\n"
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use stdout_rotator::latest::latest_path;
+use stdout_rotator::lock::{lock_path, OutputLock};
+use stdout_rotator::manifest::{manifest_path, update_manifest};
+use stdout_rotator::naming::{Naming, NamingArgs};
+use stdout_rotator::{
+    all_rotations, duration, existing_sidecars, rotations_directory, RotatorError,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct CleanArgs {
+    #[arg(
+        long,
+        default_value = "output.log",
+        help = "Output file whose managed artifacts are removed"
+    )]
+    output_file: String,
+    #[arg(long, default_value = None, help = "Directory where rotated files are saved. If not provided, the same directory of the output file will be used")]
+    rotation_directory: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
+    #[arg(long, default_value = "0s", value_parser = duration, help = "Only remove files last modified longer ago than this duration")]
+    older_than: Duration,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Also remove the active output file, the manifest of its rotations and its lock file. Refused while another instance writes the output file"
+    )]
+    include_active: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Print the files which would be removed without removing them"
+    )]
+    dry_run: bool,
+}
+
+fn old_enough(path: &Path, older_than: Duration) -> Result<bool, RotatorError> {
+    if older_than.is_zero() {
+        return Ok(true);
+    }
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|op| {
+            format!(
+                "Error while reading metadata of '{}': {}",
+                path.display(),
+                op
+            )
+        })?;
+    Ok(SystemTime::now()
+        .duration_since(modified)
+        .is_ok_and(|age| age >= older_than))
+}
+
+// The latest rotation pointer is left dangling once what it points to is removed
+fn stale_latest(output_file: &str, removed: &[PathBuf], include_active: bool) -> Option<PathBuf> {
+    let link = latest_path(output_file);
+    let target = fs::read_link(&link).ok()?;
+    let points_to_removed = removed
+        .iter()
+        .any(|path| path.file_name() == target.file_name());
+    let dangling = !link.exists();
+    (include_active || points_to_removed || dangling).then_some(link)
+}
+
+pub fn run(args: CleanArgs) -> Result<(), RotatorError> {
+    // The lock is held until everything is removed, and its file removed once released
+    let lock_file = lock_path(&args.output_file);
+    let stale_lock = args.include_active && lock_file.exists();
+    let lock = match (args.include_active, args.dry_run) {
+        (true, false) => Some(OutputLock::acquire(&args.output_file, false)?),
+        (true, true) => {
+            OutputLock::check_free(&args.output_file)?;
+            None
+        }
+        (false, _) => None,
+    };
+    let mut managed: Vec<PathBuf> = all_rotations(
+        &args.output_file,
+        args.rotation_directory.as_deref(),
+        &Naming::new(&args.naming),
+    )?;
+    if args.include_active {
+        let active = PathBuf::from(&args.output_file);
+        if active.exists() {
+            managed.push(active);
+        }
+        let manifest = manifest_path(&args.output_file);
+        if manifest.exists() {
+            managed.push(manifest);
+        }
+    }
+    let mut to_remove = vec![];
+    for path in managed {
+        if old_enough(&path, args.older_than)? {
+            to_remove.extend(existing_sidecars(&path));
+            to_remove.push(path);
+        }
+    }
+    if let Some(link) = stale_latest(&args.output_file, &to_remove, args.include_active) {
+        to_remove.push(link);
+    }
+    for path in &to_remove {
+        if args.dry_run {
+            println!("would remove {}", path.display());
+        } else {
+            fs::remove_file(path)
+                .map_err(|op| format!("Error while removing '{}': {}", path.display(), op))?;
+            println!("removed {}", path.display());
+        }
+    }
+    if args.dry_run {
+        if stale_lock {
+            println!("would remove {}", lock_file.display());
+        }
+        return Ok(());
+    }
+    drop(lock);
+    if stale_lock {
+        println!("removed {}", lock_file.display());
+    }
+    // Also dropping the rotations removed by hand
+    let directory = rotations_directory(&args.output_file, args.rotation_directory.as_deref());
+    update_manifest(&args.output_file, false, |manifest| {
+        manifest.forget(&to_remove);
+        manifest.prune(&directory);
+    })
+}
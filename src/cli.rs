@@ -0,0 +1,2079 @@
+use clap::{FromArgMatches, Parser, Subcommand};
+use log::{self, debug, error, info, warn, LevelFilter};
+use log4rs::append::console::ConsoleAppender;
+use log4rs::config::{Appender, Root};
+use log4rs::encode::pattern::PatternEncoder;
+use log4rs::Config;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+use stdout_rotator::audit::AuditLog;
+use stdout_rotator::compress::compressor;
+use stdout_rotator::encrypt::Recipient;
+use stdout_rotator::hooks::{Alerter, HookFailure, PreDeleteHook, PreRotateHook};
+use stdout_rotator::index::IndexWriter;
+use stdout_rotator::metrics::Counters;
+use stdout_rotator::naming::{Naming, NamingArgs};
+use stdout_rotator::permissions::{parse_mode, Access, Owner};
+use stdout_rotator::pipeline::{Batch, LineFramer, Pipeline, Record};
+use stdout_rotator::reload::{ReloadCursor, Reloader};
+use stdout_rotator::retention::KeepPolicy;
+use stdout_rotator::schedule::{start_rotation_scheduler, CronSchedule};
+use stdout_rotator::signals::{
+    install_shutdown_handlers, shutdown_signal, start_signal_watcher, Signal, SignalCursor,
+};
+use stdout_rotator::space::FreeSpace;
+use stdout_rotator::sync::{Durability, SyncPolicy};
+use stdout_rotator::upload::{Remote, S3Location, SftpLocation, Uploader};
+use stdout_rotator::webhook::Webhook;
+use stdout_rotator::{
+    cleanup_rotations, duration, ensure_free_space, file_size, lock_output, next_file,
+    notify_error, open_output, perform_rotation, prepare_rotations, secure_output, write_batch,
+    ArchiveFormat, CompressionFormat, OpenOutput, RotationConfig, RotatorError, Trigger,
+};
+
+use crate::cat::CatArgs;
+use crate::clean::CleanArgs;
+use crate::convert::{Conversion, ConvertStage};
+use crate::encode::{EncodeStage, Encoding};
+use crate::exec::{ExecArgs, Wrapped};
+use crate::filter::FilterStage;
+use crate::follow::Follower;
+use crate::format::{Format, FormatStage};
+use crate::geoip::GeoIpStage;
+use crate::grep::GrepArgs;
+use crate::grok::{GrokLibrary, GrokStage};
+use crate::input::Input;
+use crate::inspect::InspectArgs;
+use crate::listen::{start_listener, ListenAddress, Protocol};
+use crate::ls::LsArgs;
+use crate::merge::{spawn_source, Merger, SourceSpec};
+use crate::output::OutputSpec;
+use crate::overflow::{start_drop_oldest_bridge, Overflow, QueueSender};
+use crate::prefix::{Prefix, PrefixStage};
+use crate::prune::PruneArgs;
+use crate::recompress::CompressArgs;
+use crate::redact::{RedactStage, Redaction};
+use crate::replay::ReplayArgs;
+use crate::ring::{start_ring_writing, RingFile};
+use crate::route::{
+    start_routed_writing, DemuxRouter, LevelHistory, LevelRouter, Router, DEFAULT_LEVEL_PATTERN,
+};
+use crate::sinks::journald::{start_journald_sink, JournaldSink};
+use crate::sinks::parse_severity;
+use crate::sinks::sqlite::{start_sqlite_sink, SqliteSink};
+use crate::sinks::syslog::{
+    parse_facility, start_syslog_sink, SyslogFormat, SyslogSink, Transport,
+};
+use crate::sinks::tee::{start_tee_sink, TeeExit, TeeSink};
+use crate::split::SplitArgs;
+use crate::stats::{start_stats_recorder, StatsStore};
+use crate::statsd::{start_statsd_reporter, StatsdClient};
+use crate::summary::write_summary;
+use crate::systemd::{start_watchdog, watchdog_interval, Notifier};
+use crate::tail::TailArgs;
+use crate::verify::VerifyArgs;
+use crate::volume::{start_volume_monitor, VolumeConfig};
+use crate::{
+    cat, clean, config, grep, inspect, ls, prune, recompress, replay, split, tail, verify,
+};
+
+#[cfg(feature = "tokio")]
+use crate::relay;
+
+const LOGGER: &str = "rotator";
+
+#[derive(Parser, Debug)]
+#[command(name = "stdout-rotator")]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(about = "Log-rotate console output programs to specific location")]
+#[command(long_about = r#"
+stdout-rotator replicates its standard input to standard output and to a file and standard output, applying maximum size based log-rotation to it. It can be used to pipe the standard output of a process to a file which is automatically rotated without requiring the program to support log rotation. 
+"#)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    pub args: Args,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(about = "Report the throughput statistics recorded with --stats-db")]
+    Inspect(InspectArgs),
+    #[command(about = "Print a file managed by stdout-rotator, linearizing ring files")]
+    Cat(CatArgs),
+    #[command(
+        about = "Remove the rotations and sidecar files managed by stdout-rotator for an output file"
+    )]
+    Clean(CleanArgs),
+    #[command(
+        about = "Split an existing file into rotations, as if it had been written by stdout-rotator"
+    )]
+    Split(SplitArgs),
+    #[command(about = "Search a file managed by stdout-rotator and all its rotations")]
+    Grep(GrepArgs),
+    #[command(about = "List a file managed by stdout-rotator and its rotations")]
+    Ls(LsArgs),
+    #[command(
+        about = "Apply retention policies to the rotations of a file managed by stdout-rotator"
+    )]
+    Prune(PruneArgs),
+    #[command(about = "Compress or recompress the rotations of a file managed by stdout-rotator")]
+    Compress(CompressArgs),
+    #[command(about = "Check the integrity of the rotations of a file managed by stdout-rotator")]
+    Verify(VerifyArgs),
+    #[command(
+        about = "Print the last lines of a file managed by stdout-rotator, following it across rotations"
+    )]
+    Tail(TailArgs),
+    #[command(
+        about = "Print a file managed by stdout-rotator and its rotations at the pace they were written"
+    )]
+    Replay(ReplayArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[arg(
+        long,
+        help = "TOML, YAML or JSON file with settings named after the long options, e.g. 'max-size = \"10MB\"'. Options given on the command line take precedence. SIGHUP re-reads the file and applies the sizes, retention and compression of future rotations"
+    )]
+    config: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value = "output.log",
+        value_parser = OutputSpec::parse,
+        help = "Path to the file where the standard input is re-directed and rotated. Can be repeated to persist the input to several files, each optionally followed by its own sizes, e.g. '/mnt/archive/app.log,max-size=1GB,max-history=50'"
+    )]
+    output_file: Vec<OutputSpec>,
+    #[arg(
+        long,
+        value_parser = SourceSpec::parse,
+        conflicts_with = "command",
+        help = "Named pipe read instead of the standard input, created when missing. It is reopened whenever its writers disconnect, so that it is read until SIGTERM or SIGINT. Can be repeated, and given as name=path to prefix its lines with [name]"
+    )]
+    input: Vec<SourceSpec>,
+    #[arg(
+        long,
+        value_parser = SourceSpec::parse,
+        conflicts_with = "command",
+        help = "File read instead of the standard input as it grows, as tail -F does, following it through truncation and recreation. Reading starts at its end. Can be repeated along with --input, and given as name=path to prefix its lines with [name]"
+    )]
+    follow: Vec<SourceSpec>,
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "follow",
+        help = "Read the followed file from its beginning instead of its end"
+    )]
+    follow_from_start: bool,
+    #[arg(
+        long,
+        value_parser = ListenAddress::parse,
+        conflicts_with = "command",
+        help = "Address the lines to rotate are received on instead of the standard input, as tcp://host:port, udp://host:port or unix:/path. Every TCP or unix socket connection is framed on its own, every UDP datagram holds whole lines. Can be repeated along with --input and --follow"
+    )]
+    listen: Vec<ListenAddress>,
+    #[arg(
+        long,
+        value_parser = ListenAddress::parse,
+        conflicts_with = "command",
+        help = "Address syslog messages are received on, as --listen takes it, each RFC 3164 or RFC 5424 message being normalised into a line as <timestamp> <host> <SEVERITY> <app>[<pid>]: <message>. TCP and unix socket streams are framed by octet counting or by newlines. Can be repeated along with the other inputs"
+    )]
+    listen_syslog: Vec<ListenAddress>,
+    #[arg(
+        long,
+        value_parser = ListenAddress::parse,
+        conflicts_with = "command",
+        help = "Address Fluentd forward protocol clients such as fluent-bit send their events to, as tcp://host:port or unix:/path. Every event is written as a JSON line with its time and tag, and chunks asking for it are acknowledged. Can be repeated along with the other inputs"
+    )]
+    listen_forward: Vec<ListenAddress>,
+    #[arg(
+        long,
+        default_value_t = 64,
+        help = "Maximum number of connections accepted at once on each TCP or unix socket listen address, further ones being closed"
+    )]
+    listen_max_connections: usize,
+    #[arg(
+        short,
+        long,
+        default_value_t = false,
+        help = "Activates gunzip compression of rotated files"
+    )]
+    gunzip: bool,
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with = "gunzip",
+        help = "Compression applied to rotated files. --gunzip is equivalent to --compress gzip"
+    )]
+    compress: Option<CompressionFormat>,
+    #[arg(
+        long,
+        help = "Compression level, or preset for xz. Defaults to 6 for gzip and xz, 3 for zstd and 0 for lz4"
+    )]
+    compress_level: Option<u32>,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Keep the most recent rotation uncompressed and compress it on the following rotation"
+    )]
+    delay_compress: bool,
+    #[arg(long, value_enum, default_value_t = ArchiveFormat::Text, conflicts_with_all = ["gunzip", "compress"], help = "Format of the rotated files. Parquet archives store the timestamp, level, message and fields parsed from every line")]
+    archive_format: ArchiveFormat,
+    #[arg(long, default_value_t = false, conflicts_with_all = ["gunzip", "compress", "archive_format"], help = "Write the output file as a single preallocated circular file of --max-size bytes, its header included, instead of rotating it")]
+    ring_file: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "ring_file",
+        help = "Append to an existing output file instead of truncating it at startup"
+    )]
+    append: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["ring_file", "append"],
+        help = "Rotate an existing non-empty output file into the rotated files at startup instead of truncating it"
+    )]
+    rotate_on_start: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "ring_file",
+        help = "Rotate the output file after the input was drained on SIGTERM or SIGINT"
+    )]
+    rotate_on_shutdown: bool,
+    #[arg(
+        long,
+        help = "Exit code after a graceful shutdown on SIGTERM or SIGINT. Defaults to 128 plus the signal number"
+    )]
+    shutdown_exit_code: Option<i32>,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "ring_file",
+        help = "Maintain a sidecar index of timestamp to byte offset checkpoints for the output file and its rotations, which cat, grep and tail --since seek with"
+    )]
+    index: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "ring_file",
+        help = "Write a SHA-256 checksum sidecar next to every rotated file, e.g. output.log.3.gz.sha256, which 'sha256sum -c' verifies"
+    )]
+    checksum: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "ring_file",
+        help = "Maintain output.log.manifest.json recording the file name, byte range, first and last write times, line count, SHA-256 checksum and compression of every rotation"
+    )]
+    manifest: bool,
+    #[arg(
+        long,
+        value_parser = Recipient::parse,
+        conflicts_with = "ring_file",
+        help = "Encrypt every rotated file to this recipient with age, for recipients starting with 'age1', or GPG otherwise, e.g. output.log.3.gz.age. The 'age:' and 'gpg:' prefixes select the tool explicitly"
+    )]
+    encrypt_recipient: Option<Recipient>,
+    #[arg(
+        long,
+        value_parser = parse_mode,
+        help = "Permissions of the output file and rotated files, e.g. 0640, set regardless of the umask"
+    )]
+    file_mode: Option<u32>,
+    #[arg(
+        long,
+        value_parser = parse_mode,
+        help = "Permissions of the directories created for the output file and the rotation directory, e.g. 0750"
+    )]
+    dir_mode: Option<u32>,
+    #[arg(
+        long,
+        value_name = "USER:GROUP",
+        value_parser = Owner::parse,
+        help = "Owner of the output file, rotated files and created directories, e.g. 'www-data:adm', as with the create directive of logrotate. Usually requires running as root"
+    )]
+    chown: Option<Owner>,
+    #[arg(
+        long,
+        value_parser = SyncPolicy::parse,
+        default_value = "never",
+        help = "When the output file is synchronised to disk: never, leaving it to the operating system, every-write, every-n-bytes=<size> or interval=<duration>, e.g. 'interval=5s'"
+    )]
+    sync_policy: SyncPolicy,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Wait for another stdout-rotator instance writing the same output file to exit, instead of failing at startup"
+    )]
+    wait_lock: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Maintain a symbolic link <output-file>.latest-rotation pointing at the most recent rotated file"
+    )]
+    latest_symlink: bool,
+    #[arg(
+        long,
+        value_parser = S3Location::parse,
+        group = "upload",
+        conflicts_with = "ring_file",
+        help = "Upload every completed rotation to S3, e.g. 's3://bucket/logs/', with the AWS CLI and the credentials it is configured with"
+    )]
+    s3_upload: Option<S3Location>,
+    #[arg(
+        long,
+        requires = "s3_upload",
+        help = "Endpoint URL of an S3-compatible object storage to upload to, e.g. 'http://minio:9000'"
+    )]
+    s3_endpoint: Option<String>,
+    #[arg(
+        long,
+        value_parser = SftpLocation::parse,
+        group = "upload",
+        conflicts_with = "ring_file",
+        help = "Copy every completed rotation over SFTP with scp, e.g. 'logs@backup:/srv/logs', authenticating with a key"
+    )]
+    sftp_upload: Option<SftpLocation>,
+    #[arg(
+        long,
+        requires = "sftp_upload",
+        help = "Private key used for the SFTP uploads, instead of the ones configured for ssh"
+    )]
+    sftp_identity: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value_t = 5,
+        requires = "upload",
+        help = "Number of times a failed upload is retried, with an exponential backoff between attempts"
+    )]
+    upload_retries: u32,
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "upload",
+        help = "Remove rotated files locally once they were uploaded"
+    )]
+    upload_delete_local: bool,
+    #[arg(
+        long,
+        conflicts_with = "ring_file",
+        help = "Command executed through the shell before the output file is rotated, e.g. to quiesce its readers. The output file and the reason of the rotation are exposed as STDOUT_ROTATOR_OUTPUT and STDOUT_ROTATOR_TRIGGER"
+    )]
+    pre_rotate_cmd: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "abort",
+        requires = "pre_rotate_cmd",
+        help = "What a failing pre-rotation command does: abort postpones the rotation, continue rotates anyway"
+    )]
+    pre_rotate_failure: HookFailure,
+    #[arg(
+        long,
+        conflicts_with = "ring_file",
+        help = "Command executed through the shell before retention removes a rotated file, with {file} replaced by its quoted path, e.g. 'aws s3 ls s3://bucket/{file}'. The file is kept when the command fails"
+    )]
+    pre_delete_cmd: Option<String>,
+    #[arg(long, default_value = "1MB", value_parser = file_size, requires = "index", help = "Minimum number of bytes between two checkpoints of the index")]
+    index_every: u64,
+    #[arg(long, default_value = None, help = "Directory where rotated files are saved. If not provided, the same directory of the output file will be used")]
+    rotation_directory: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "ring_file",
+        help = "Watch the output and rotation directories for external changes (e.g. logrotate), reopening the output file and re-applying retention when files are moved, removed or added"
+    )]
+    watch_external: bool,
+    #[arg(
+        short,
+        long,
+        default_value_t = 5,
+        help = "Maximum number of rotated files retained"
+    )]
+    max_history: u32,
+    #[arg(long, value_parser = KeepPolicy::parse, conflicts_with = "max_history", help = "Thin rotations over time instead of keeping the last --max-history ones, keeping the newest rotation of each of the most recent periods of every tier, e.g. 'daily=7,weekly=4,monthly=12'. Tiers are hourly, daily, weekly, monthly and yearly")]
+    keep: Option<KeepPolicy>,
+    #[arg(long, value_parser = file_size, help = "Maximum combined size of the rotated files retained, independently of --max-history. The oldest rotations are removed first")]
+    max_total_size: Option<u64>,
+    #[arg(long, value_parser = duration, help = "Remove rotated files last modified longer ago than this duration (e.g. 14d), at startup and after every rotation")]
+    max_age: Option<Duration>,
+    #[arg(long, value_parser = FreeSpace::parse, conflicts_with = "ring_file", help = "Free space (e.g. 10GB or 5%) kept on the filesystem of the rotation directory: the oldest rotations are removed before writing while it runs lower")]
+    min_free_space: Option<FreeSpace>,
+    #[arg(long, default_value = None, help = "Configuration to log4rs logging configuration. If not provided the default logging configuration is used, using stderr")]
+    log_config: Option<String>,
+    #[arg(long, default_value = "50MB", value_parser = file_size, help = "Size of the output file which triggers rotation")]
+    max_size: u64,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "ring_file",
+        help = "Split the input so that the output file never exceeds --max-size, instead of rotating after the write which crossed it"
+    )]
+    strict_max_size: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["ring_file", "strict_max_size"],
+        help = "Never split a line across two files: a trailing partial line is written with the rest of the line"
+    )]
+    rotate_on_line_boundary: bool,
+    #[arg(long, value_parser = duration, help = "Also rotate the output file every interval (e.g. 1h, 1day, 1week), aligned to multiples of the interval since the Unix epoch. Empty files are not rotated")]
+    rotate_every: Option<Duration>,
+    #[arg(long, value_parser = CronSchedule::parse, conflicts_with = "ring_file", help = "Also rotate the output file at the wall-clock times of a cron expression (e.g. '0 0 * * *' or '@hourly'), evaluated in local time, even when no input arrives. Empty files are not rotated")]
+    rotate_cron: Option<CronSchedule>,
+    #[arg(long, value_parser = duration, help = "Also rotate the output file once its first line is older than this age, whichever of --max-size and --max-file-age comes first")]
+    max_file_age: Option<Duration>,
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), conflicts_with = "ring_file", help = "Also rotate the output file once it holds this number of lines")]
+    max_lines: Option<u64>,
+    #[arg(long, default_value_t = 4096, help = "Read buffer size")]
+    buffer_size: u32,
+    #[cfg(feature = "tokio")]
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Relay the standard input on a tokio runtime through the AsyncRotatingWriter of the library, replicating it as --no-stdout and --mirror set. Only the rotation and retention options of the library can be combined with it: --max-size, --max-history, --max-total-size, --max-age, --rotate-every, --max-file-age, --max-lines, --compress, --compress-level and --append, with a single --output-file"
+    )]
+    async_relay: bool,
+    #[arg(long, value_enum, default_value_t = Overflow::Block, help = "Behaviour when the output file falls behind the input. With drop-oldest the file is fed from a bounded queue whose oldest chunks are discarded when full, instead of blocking the input")]
+    file_overflow: Overflow,
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Number of chunks queued for the output file with --file-overflow drop-oldest"
+    )]
+    file_queue_size: usize,
+    #[arg(
+        long,
+        value_enum,
+        help = "Conversion applied to every line before it is replicated to standard output and to the output file. Lines which cannot be converted are left untouched"
+    )]
+    convert: Option<Conversion>,
+    #[arg(
+        long,
+        help = "Grok expression (e.g. '%{COMMONAPACHELOG}') used to extract named fields from every line. Can be repeated, the first matching expression wins"
+    )]
+    grok: Vec<String>,
+    #[arg(
+        long,
+        help = "File of additional grok pattern definitions, one 'NAME regex' per line. Can be repeated"
+    )]
+    grok_patterns: Vec<String>,
+    #[arg(
+        long,
+        help = "MaxMind database (country, city or ASN) used for GeoIP enrichment. Can be repeated"
+    )]
+    geoip_db: Vec<String>,
+    #[arg(
+        long,
+        help = "Extracted field holding an IP address to annotate with GeoIP data, as 'field' or 'field=country,asn'. Can be repeated"
+    )]
+    geoip_field: Vec<String>,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["stdout_format", "stdout_encoding"],
+        help = "Do not replicate the input to standard output, only write it to the output file and the sinks"
+    )]
+    no_stdout: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Mirror::Stdout,
+        conflicts_with = "no_stdout",
+        help = "Stream the input is replicated to, stderr keeping standard output clean for a downstream consumer"
+    )]
+    mirror: Mirror,
+    #[arg(long, value_enum, default_value_t = Format::Raw, help = "Formatting applied to JSON lines replicated to standard output")]
+    stdout_format: Format,
+    #[arg(long, value_enum, default_value_t = Format::Raw, help = "Formatting applied to JSON lines written to the output file")]
+    file_format: Format,
+    #[arg(long, value_enum, default_value_t = Encoding::Raw, help = "Encoding of the lines replicated to standard output")]
+    stdout_encoding: Encoding,
+    #[arg(long, value_enum, default_value_t = Encoding::Raw, help = "Encoding of the lines written to the output file")]
+    file_encoding: Encoding,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "file_encoding",
+        help = "Wrap each line written to the output file into a JSON object with its timestamp, stream and message, as with --file-encoding json"
+    )]
+    json_wrap: bool,
+    #[arg(
+        long,
+        value_parser = Prefix::parse,
+        help = "Prefix prepended to each line written to the output file, e.g. '{hostname} {pid} myservice | '. The placeholders {hostname} and {pid} are expanded at startup"
+    )]
+    prefix: Option<Prefix>,
+    #[arg(
+        long,
+        value_parser = regex::bytes::Regex::new,
+        help = "Only write the lines matching this regular expression to the output file and the sinks. All lines are still replicated to standard output"
+    )]
+    file_include_regex: Option<regex::bytes::Regex>,
+    #[arg(
+        long,
+        value_parser = regex::bytes::Regex::new,
+        help = "Do not write the lines matching this regular expression to the output file and the sinks, e.g. health checks. All lines are still replicated to standard output"
+    )]
+    file_exclude_regex: Option<regex::bytes::Regex>,
+    #[arg(
+        long,
+        value_parser = Redaction::parse,
+        help = "Replace the matches of a regular expression in the lines written to the output file and the sinks, e.g. 'token=[^ ]+=>token=***'. The replacement can refer to capture groups as $1 or ${name}. Can be repeated"
+    )]
+    redact: Vec<Redaction>,
+    #[arg(
+        long,
+        value_name = "REGEX",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_LEVEL_PATTERN,
+        value_parser = regex::bytes::Regex::new,
+        conflicts_with = "ring_file",
+        help = "Route the lines to a rotated file per level, e.g. output.error.log, by the first group of this regular expression or the common level names by default. Lines without a level stay in the output file"
+    )]
+    split_by_level: Option<regex::bytes::Regex>,
+    #[arg(
+        long,
+        value_parser = LevelHistory::parse,
+        requires = "split_by_level",
+        help = "Number of rotations kept for the file of a level, e.g. 'error=30'. Can be repeated"
+    )]
+    level_max_history: Vec<LevelHistory>,
+    #[arg(
+        long,
+        value_parser = regex::bytes::Regex::new,
+        help = "Regular expression matching the first line of a record, e.g. '^\\d{4}-'. Other lines, such as the frames of a stack trace, are kept with the record before them when rotating, filtering and timestamping"
+    )]
+    multiline_start_regex: Option<regex::bytes::Regex>,
+    #[arg(
+        long,
+        value_parser = regex::bytes::Regex::new,
+        requires = "demux_template",
+        conflicts_with_all = ["ring_file", "split_by_level"],
+        help = "Route the lines matching this regular expression to the rotated file named by --demux-template, e.g. '^\\[(?P<svc>[a-z]+)\\]'. Other lines stay in the output file"
+    )]
+    demux_regex: Option<regex::bytes::Regex>,
+    #[arg(
+        long,
+        requires = "demux_regex",
+        help = "Path of the file a demultiplexed line is routed to, with the groups of --demux-regex as placeholders, e.g. '{svc}.log'. Files are created on the first line routed to them"
+    )]
+    demux_template: Option<String>,
+    #[arg(
+        long,
+        help = "Command executed through the shell when an alert fires. The alert kind and message are exposed as STDOUT_ROTATOR_ALERT and STDOUT_ROTATOR_ALERT_MESSAGE"
+    )]
+    alert_cmd: Option<String>,
+    #[arg(
+        long,
+        help = "URL the rotation, cleanup, write error and disk full events are posted to as JSON, with curl"
+    )]
+    webhook_url: Option<String>,
+    #[arg(
+        long,
+        help = "File every rotation, deletion, compression, upload and error is appended to as a JSON line, e.g. rotations.audit"
+    )]
+    audit_file: Option<String>,
+    #[arg(
+        long,
+        help = "Alert when the input volume of an interval exceeds the rolling baseline by this multiplier"
+    )]
+    volume_spike_factor: Option<f64>,
+    #[arg(
+        long,
+        help = "Alert when the input volume of an interval falls below the rolling baseline divided by this multiplier"
+    )]
+    volume_drop_factor: Option<f64>,
+    #[arg(long, default_value = "1m", value_parser = duration, help = "Length of the intervals the input volume is measured over")]
+    volume_interval: Duration,
+    #[arg(
+        long,
+        default_value_t = 15,
+        help = "Number of intervals forming the rolling volume baseline"
+    )]
+    volume_window: usize,
+    #[arg(
+        long,
+        help = "SQLite database where per-interval throughput statistics are persisted"
+    )]
+    stats_db: Option<String>,
+    #[arg(long, default_value = "1m", value_parser = duration, help = "Length of the intervals persisted to the statistics database")]
+    stats_interval: Duration,
+    #[arg(
+        long,
+        help = "UDP address of a statsd or DogStatsD daemon the throughput counters are pushed to, e.g. 'localhost:8125'"
+    )]
+    statsd_addr: Option<String>,
+    #[arg(
+        long,
+        default_value = "stdout_rotator",
+        requires = "statsd_addr",
+        help = "Prefix of the names of the metrics pushed to statsd"
+    )]
+    statsd_prefix: String,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        requires = "statsd_addr",
+        help = "DogStatsD tags attached to the metrics pushed to statsd, e.g. 'service:web,env:prod'"
+    )]
+    statsd_tags: Vec<String>,
+    #[arg(long, default_value = "10s", value_parser = duration, requires = "statsd_addr", help = "Interval between pushes of metrics to statsd")]
+    statsd_interval: Duration,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Print a JSON summary of the run to standard error on exit: duration, bytes and lines processed, rotations, deleted files and errors"
+    )]
+    summary: bool,
+    #[arg(
+        long,
+        help = "Write the JSON summary printed by --summary to this file instead of standard error"
+    )]
+    summary_file: Option<String>,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Notify systemd through NOTIFY_SOCKET once the pipelines are running, and ping its watchdog while the file writers respond when WatchdogSec is set"
+    )]
+    systemd_notify: bool,
+    #[arg(
+        long,
+        help = "SQLite database where every line is also appended with its timestamp, level and extracted fields"
+    )]
+    sqlite_sink: Option<String>,
+    #[arg(long, value_parser = file_size, help = "Size of the SQLite sink database above which the oldest lines are pruned")]
+    sqlite_max_size: Option<u64>,
+    #[arg(long, value_enum, default_value_t = Encoding::Raw, requires = "sqlite_sink", help = "Encoding of the lines stored as messages of the SQLite sink")]
+    sqlite_encoding: Encoding,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Also submit every line to systemd-journald"
+    )]
+    to_journald: bool,
+    #[arg(
+        long,
+        default_value = "stdout-rotator",
+        requires = "to_journald",
+        help = "SYSLOG_IDENTIFIER of the lines submitted to journald"
+    )]
+    journald_identifier: String,
+    #[arg(
+        long,
+        value_parser = parse_severity,
+        requires = "to_journald",
+        help = "Priority of the lines submitted to journald, from 0 or emerg to 7 or debug. Defaults to the level of each line, or info"
+    )]
+    journald_priority: Option<u8>,
+    #[arg(long, value_enum, default_value_t = Encoding::Raw, requires = "to_journald", help = "Encoding of the lines submitted to journald")]
+    journald_encoding: Encoding,
+    #[arg(
+        long,
+        value_parser = Transport::parse,
+        help = "Also forward every line to a syslog collector, e.g. 'udp://localhost:514' or 'tcp://collector:601'"
+    )]
+    syslog_target: Option<Transport>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "rfc5424",
+        requires = "syslog_target",
+        help = "Format of the messages forwarded to syslog"
+    )]
+    syslog_format: SyslogFormat,
+    #[arg(
+        long,
+        default_value = "user",
+        value_parser = parse_facility,
+        requires = "syslog_target",
+        help = "Facility of the messages forwarded to syslog, from 0 to 23 or a name as user, daemon or local0"
+    )]
+    syslog_facility: u8,
+    #[arg(
+        long,
+        value_parser = parse_severity,
+        requires = "syslog_target",
+        help = "Severity of the messages forwarded to syslog, from 0 or emerg to 7 or debug. Defaults to the level of each line, or info"
+    )]
+    syslog_severity: Option<u8>,
+    #[arg(
+        long,
+        default_value = "stdout-rotator",
+        requires = "syslog_target",
+        help = "APP-NAME, or tag in RFC 3164, of the messages forwarded to syslog"
+    )]
+    syslog_app_name: String,
+    #[arg(
+        long,
+        help = "Also pipe the input into this command, executed through the shell, e.g. 'grep ERROR | alerter'"
+    )]
+    tee_cmd: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "ignore",
+        requires = "tee_cmd",
+        help = "What happens when the --tee-cmd command exits before the input ends: ignore stops copying to it, restart spawns it again, fail stops reading the input"
+    )]
+    tee_exit: TeeExit,
+    #[arg(long, value_enum, default_value_t = Encoding::Raw, requires = "tee_cmd", help = "Encoding of the lines piped into the --tee-cmd command")]
+    tee_encoding: Encoding,
+    #[command(flatten)]
+    exec: ExecArgs,
+    #[arg(long, requires = "command", conflicts_with_all = ["capture_stderr", "ring_file"], help = "Capture the standard error of the spawned command into its own rotated file, while its standard output goes to --output-file. The standard error is still replicated to standard error")]
+    stderr_file: Option<String>,
+    #[arg(long, value_parser = file_size, requires = "stderr_file", help = "Size of --stderr-file which triggers rotation. Defaults to --max-size")]
+    stderr_max_size: Option<u64>,
+    #[arg(
+        long,
+        requires = "stderr_file",
+        help = "Maximum number of rotations of --stderr-file retained. Defaults to --max-history"
+    )]
+    stderr_max_history: Option<u32>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mirror {
+    Stdout,
+    Stderr,
+}
+
+pub fn rotation_config(args: &Args) -> RotationConfig {
+    RotationConfig {
+        max_history: args.output_file[0].max_history.unwrap_or(args.max_history),
+        keep: args.keep.clone(),
+        max_total_size: args.max_total_size,
+        max_age: args.max_age,
+        min_free_space: args.min_free_space,
+        max_size: args.output_file[0].max_size.unwrap_or(args.max_size),
+        strict_max_size: args.strict_max_size,
+        rotate_on_line_boundary: args.rotate_on_line_boundary,
+        rotate_every: args.rotate_every,
+        rotate_cron: args.rotate_cron.clone(),
+        max_file_age: args.max_file_age,
+        max_lines: args.max_lines,
+        compression: args
+            .compress
+            .or(args.gunzip.then_some(CompressionFormat::Gzip)),
+        compression_level: args.compress_level,
+        compression_queue: None,
+        delay_compress: args.delay_compress,
+        archive_format: args.archive_format,
+        index_every: args.index.then_some(args.index_every),
+        checksum: args.checksum,
+        manifest: args.manifest,
+        encryption: args.encrypt_recipient.clone(),
+        access: Access {
+            file_mode: args.file_mode,
+            dir_mode: args.dir_mode,
+            owner: args.chown,
+        },
+        sync_policy: args.sync_policy,
+        latest_symlink: args.latest_symlink,
+        upload: args
+            .s3_upload
+            .clone()
+            .map(|location| Remote::S3 {
+                location,
+                endpoint: args.s3_endpoint.clone(),
+            })
+            .or_else(|| {
+                args.sftp_upload.clone().map(|location| Remote::Sftp {
+                    location,
+                    identity: args.sftp_identity.clone(),
+                })
+            })
+            .map(|remote| Uploader {
+                remote,
+                retries: args.upload_retries,
+                delete_local: args.upload_delete_local,
+            }),
+        upload_queue: None,
+        pre_rotate: args.pre_rotate_cmd.clone().map(|command| PreRotateHook {
+            command,
+            on_failure: args.pre_rotate_failure,
+        }),
+        pre_delete: args
+            .pre_delete_cmd
+            .clone()
+            .map(|command| PreDeleteHook { command }),
+        webhook: None,
+        audit: None,
+        reloader: None,
+        counters: Arc::new(Counters::default()),
+        heartbeat: Arc::default(),
+        output_file: args.output_file[0].path.clone(),
+        rotation_directory: args.rotation_directory.clone(),
+        naming: Naming::new(&args.naming),
+        watch_external: args.watch_external,
+        append: args.append,
+        rotate_on_start: args.rotate_on_start,
+        rotate_on_shutdown: args.rotate_on_shutdown,
+    }
+}
+
+fn shared_pipeline(args: &Args) -> Result<Pipeline, RotatorError> {
+    let mut pipeline = Pipeline::new();
+    if let Some(conversion) = args.convert {
+        pipeline.push(Box::new(ConvertStage::new(conversion)));
+    }
+    if !args.grok.is_empty() {
+        let mut library = GrokLibrary::new();
+        for patterns in &args.grok_patterns {
+            library.load_file(patterns)?;
+        }
+        let patterns = args
+            .grok
+            .iter()
+            .map(|expression| library.compile(expression))
+            .collect::<Result<Vec<_>, RotatorError>>()?;
+        pipeline.push(Box::new(GrokStage::new(patterns)));
+    }
+    if !args.geoip_field.is_empty() {
+        pipeline.push(Box::new(GeoIpStage::new(
+            &args.geoip_db,
+            &args.geoip_field,
+        )?));
+    }
+    Ok(pipeline)
+}
+
+#[derive(Clone)]
+pub struct FileStages {
+    include: Option<regex::bytes::Regex>,
+    exclude: Option<regex::bytes::Regex>,
+    redactions: Vec<Redaction>,
+    format: Format,
+    encoding: Encoding,
+    prefix: Option<Prefix>,
+}
+
+impl FileStages {
+    pub fn new(args: &Args) -> FileStages {
+        FileStages {
+            include: args.file_include_regex.clone(),
+            exclude: args.file_exclude_regex.clone(),
+            redactions: args.redact.clone(),
+            format: args.file_format,
+            encoding: if args.json_wrap {
+                Encoding::Json
+            } else {
+                args.file_encoding
+            },
+            prefix: args.prefix.clone(),
+        }
+    }
+
+    // Also run in front of every sink, so that filtered lines and secrets never reach them
+    pub fn filtering(&self) -> Pipeline {
+        let mut pipeline = Pipeline::new();
+        if self.include.is_some() || self.exclude.is_some() {
+            pipeline.push(Box::new(FilterStage::new(
+                self.include.clone(),
+                self.exclude.clone(),
+            )));
+        }
+        if !self.redactions.is_empty() {
+            pipeline.push(Box::new(RedactStage::new(self.redactions.clone())));
+        }
+        pipeline
+    }
+
+    pub fn pipeline(&self, stream: &str) -> Pipeline {
+        let mut pipeline = self.filtering();
+        pipeline.extend(destination_pipeline(self.format, self.encoding, stream));
+        if let Some(prefix) = &self.prefix {
+            pipeline.push(Box::new(PrefixStage::new(prefix.clone())));
+        }
+        pipeline
+    }
+}
+
+fn router(args: &Args) -> Result<Option<Box<dyn Router>>, RotatorError> {
+    if let Some(pattern) = &args.split_by_level {
+        return Ok(Some(Box::new(LevelRouter::new(
+            pattern.clone(),
+            args.level_max_history.clone(),
+        ))));
+    }
+    match (&args.demux_regex, &args.demux_template) {
+        (Some(pattern), Some(template)) => {
+            Ok(Some(Box::new(DemuxRouter::new(pattern.clone(), template)?)))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn open_input(args: &Args) -> Result<Input, RotatorError> {
+    let mut sources = Vec::<(SourceSpec, Input)>::new();
+    for source in &args.input {
+        let path = &source.path;
+        log::info!(target: LOGGER, "Reading from named pipe '{}'", path.display());
+        let input = Input::fifo(path).map_err(|op| {
+            RotatorError::from(format!(
+                "Error while opening input '{}': {}",
+                path.display(),
+                op
+            ))
+        })?;
+        sources.push((source.clone(), input));
+    }
+    for source in &args.follow {
+        let path = &source.path;
+        log::info!(target: LOGGER, "Following '{}'", path.display());
+        let follower = Follower::open(path, args.follow_from_start).map_err(|op| {
+            RotatorError::from(format!(
+                "Error while opening followed file '{}': {}",
+                path.display(),
+                op
+            ))
+        })?;
+        sources.push((source.clone(), Input::Follow(follower)));
+    }
+    let listened = args.listen.len() + args.listen_syslog.len() + args.listen_forward.len();
+    match (sources.len(), listened) {
+        (0, 0) => return Ok(Input::Stdin),
+        (1, 0) if sources[0].0.name.is_none() => return Ok(sources.remove(0).1),
+        (1, 0) | (0, 1) => {}
+        (count, listened) => {
+            log::info!(target: LOGGER, "Merging {} inputs", count + listened)
+        }
+    }
+    let (merger, sender) = Merger::new();
+    for address in &args.listen {
+        start_listener(
+            address,
+            Protocol::Lines,
+            args.listen_max_connections,
+            sender.clone(),
+        )?;
+    }
+    for address in &args.listen_syslog {
+        start_listener(
+            address,
+            Protocol::Syslog,
+            args.listen_max_connections,
+            sender.clone(),
+        )?;
+    }
+    for address in &args.listen_forward {
+        start_listener(
+            address,
+            Protocol::Forward,
+            args.listen_max_connections,
+            sender.clone(),
+        )?;
+    }
+    for (source, input) in sources {
+        spawn_source(source, input, sender.clone());
+    }
+    Ok(Input::Merged(merger))
+}
+
+fn line_framer(args: &Args, needs_framing: bool) -> Option<LineFramer> {
+    match &args.multiline_start_regex {
+        Some(start) => Some(LineFramer::multiline(start.clone())),
+        None if needs_framing => Some(LineFramer::new()),
+        None => None,
+    }
+}
+
+fn destination_pipeline(format: Format, encoding: Encoding, stream: &str) -> Pipeline {
+    let mut pipeline = Pipeline::new();
+    if format != Format::Raw {
+        pipeline.push(Box::new(FormatStage::new(format)));
+    }
+    if encoding != Encoding::Raw {
+        pipeline.push(Box::new(EncodeStage::new(encoding, stream)));
+    }
+    pipeline
+}
+
+fn start_stdout_writing(
+    mut stdout: Box<dyn Write + Send>,
+    mut pipeline: Pipeline,
+    rxstdout: Receiver<Batch>,
+    txcomplete: Sender<bool>,
+    counters: Arc<Counters>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut stop = false;
+        let logger = "stdout_writer";
+        while !stop {
+            let read_result = rxstdout.recv();
+            if let Err(result) = read_result {
+                stop = true;
+                warn!(target: logger, "Error while reading result: {}", result);
+                continue;
+            }
+            let read = pipeline.render(&read_result.unwrap());
+            // Flushed right away, the standard output holding anything after the last newline
+            if let Err(result) = stdout.write_all(&read).and_then(|_| stdout.flush()) {
+                stop = true;
+                counters.record_error();
+                error!(target: logger, "Error while writing result: {}", result);
+                continue;
+            }
+            if let Err(result) = txcomplete.send(true) {
+                stop = true;
+                warn!(target: logger, "Error while sending acknowledgment: {}", result);
+            }
+        }
+    })
+}
+
+pub fn start_file_writing(
+    config: RotationConfig,
+    mut pipeline: Pipeline,
+    rxfile: Receiver<Batch>,
+    txcomplete: Sender<bool>,
+    counters: Arc<Counters>,
+) -> Result<JoinHandle<()>, RotatorError> {
+    let OpenOutput {
+        mut config,
+        mut file,
+        mut active,
+        mut index,
+        watcher,
+        workers,
+    } = open_output(config, &counters)?;
+    let handle = thread::spawn(move || {
+        let mut stop: bool = false;
+        let logger = "file_writer";
+        let mut held: Vec<u8> = vec![];
+        let mut signals = SignalCursor::default();
+        let mut durability = Durability::new(config.sync_policy);
+        let mut reload = config.reloader.clone().map(ReloadCursor::new);
+        while !stop {
+            let read_result = match durability.wait() {
+                Some(wait) => rxfile.recv_timeout(wait),
+                None => rxfile.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let batch = match read_result {
+                Ok(batch) => {
+                    config.heartbeat.fetch_add(1, Ordering::Release);
+                    batch
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Err(result) = durability.sync(&file) {
+                        stop = true;
+                        counters.record_error();
+                        error!(target: logger, "Error while synchronising file: {}", result);
+                    }
+                    continue;
+                }
+                Err(result) => {
+                    stop = true;
+                    warn!(target: logger, "Error while reading result: {}", result);
+                    continue;
+                }
+            };
+            if let Some(signal) = signals.take_rotation() {
+                if signal == Signal::Hangup {
+                    // The output file may have been moved by an external rotation
+                    if let Err(result) = resync_output(&mut file, &config, true, index.as_mut()) {
+                        stop = true;
+                        counters.record_error();
+                        error!(target: logger, "Error while reopening file: {}", result);
+                        continue;
+                    }
+                }
+                // With a configuration file SIGHUP reloads it instead of rotating
+                if signal != Signal::Hangup || config.reloader.is_none() {
+                    active.requested = Some(Trigger::Signal(signal));
+                }
+            }
+            if let Some(reload) = reload.as_mut() {
+                if reload.update(&mut config) {
+                    info!(target: logger, "Applied the reloaded configuration to '{}'", config.output_file);
+                }
+            }
+            if batch.is_empty() {
+                // Scheduler ticks only give a chance to rotate and are not acknowledged
+                match perform_rotation(&mut file, &config, index.as_mut(), &mut active) {
+                    Ok(true) => counters.record_rotation(),
+                    Ok(false) => {}
+                    Err(result) => {
+                        stop = true;
+                        counters.record_error();
+                        notify_error(&config, "rotation_error", &result.msg);
+                        error!(target: logger, "Error while rotating file: {}", result);
+                    }
+                }
+                continue;
+            }
+            if let Some(watcher) = watcher.as_ref() {
+                let resync =
+                    resync_output(&mut file, &config, watcher.take_changed(), index.as_mut());
+                if let Err(result) = resync {
+                    stop = true;
+                    counters.record_error();
+                    error!(target: logger, "Error while synchronising with external changes: {}", result);
+                    continue;
+                }
+            }
+            if let Err(result) = ensure_free_space(&config) {
+                stop = true;
+                counters.record_error();
+                error!(target: logger, "Error while freeing disk space: {}", result);
+                continue;
+            }
+            let time = batch
+                .first()
+                .map(|r| r.received)
+                .unwrap_or_else(SystemTime::now);
+            let mut read = pipeline.render(&batch);
+            if config.rotate_on_line_boundary {
+                // Only complete lines are written, a trailing partial line waits for its end
+                let mut data = std::mem::take(&mut held);
+                data.append(&mut read);
+                let complete = data
+                    .iter()
+                    .rposition(|b| *b == b'\n')
+                    .map(|position| position + 1)
+                    .unwrap_or(0);
+                if (data.len() - complete) as u64 <= config.max_size {
+                    held = data.split_off(complete);
+                }
+                read = data;
+            }
+            let write = write_batch(
+                &mut file,
+                &read,
+                time,
+                &config,
+                &mut index,
+                &mut active,
+                &counters,
+            );
+            if let Err(result) = write {
+                stop = true;
+                counters.record_error();
+                notify_error(&config, "write_error", &result.msg);
+                error!(target: logger, "Error while writing result to file: {}", result);
+                continue;
+            }
+            if let Err(result) = durability.written(&file, read.len() as u64) {
+                stop = true;
+                counters.record_error();
+                error!(target: logger, "Error while synchronising file: {}", result);
+                continue;
+            }
+            if let Err(result) = txcomplete.send(true) {
+                stop = true;
+                warn!(target: logger, "Error while sending confirmation: {}", result);
+            }
+        }
+        if !held.is_empty() {
+            let write = write_batch(
+                &mut file,
+                &held,
+                SystemTime::now(),
+                &config,
+                &mut index,
+                &mut active,
+                &counters,
+            );
+            if let Err(result) = write {
+                counters.record_error();
+                notify_error(&config, "write_error", &result.msg);
+                error!(target: "file_writer", "Error while writing last partial line: {}", result);
+            }
+        }
+        if let Err(result) = file.flush() {
+            counters.record_error();
+            notify_error(&config, "write_error", &result.to_string());
+            error!(target: "file_writer", "Error while flushing file: {}", result);
+        }
+        if config.sync_policy != SyncPolicy::Never {
+            if let Err(result) = durability.sync(&file) {
+                counters.record_error();
+                error!(target: "file_writer", "Error while synchronising file: {}", result);
+            }
+        }
+        if let (true, Some(signal)) = (config.rotate_on_shutdown, shutdown_signal()) {
+            active.requested = Some(Trigger::Signal(signal));
+            match perform_rotation(&mut file, &config, index.as_mut(), &mut active) {
+                Ok(true) => counters.record_rotation(),
+                Ok(false) => {}
+                Err(result) => {
+                    counters.record_error();
+                    notify_error(&config, "rotation_error", &result.msg);
+                    error!(target: "file_writer", "Error while rotating file on shutdown: {}", result);
+                }
+            }
+        }
+        drop(config);
+        workers.join();
+    });
+    Ok(handle)
+}
+
+fn resync_output(
+    file: &mut File,
+    config: &RotationConfig,
+    changed: bool,
+    index: Option<&mut IndexWriter>,
+) -> Result<(), RotatorError> {
+    let output_file = config.output_file.as_str();
+    let position = file
+        .stream_position()
+        .map_err(|op| format!("Error while reading position of {}: {}", output_file, op))?;
+    let length = file
+        .metadata()
+        .map_err(|op| format!("Error while reading metadata of {}: {}", output_file, op))?
+        .len();
+    let mut reset_index = false;
+    if length < position {
+        info!(target: LOGGER, "'{}' was truncated externally, continuing at byte {}", output_file, length);
+        file.seek(io::SeekFrom::Start(length))
+            .map_err(|op| format!("Error while seeking {}: {}", output_file, op))?;
+        reset_index = true;
+    }
+    if changed {
+        if !is_same_file(file, output_file) {
+            info!(target: LOGGER, "'{}' was moved or removed externally, reopening", output_file);
+            let mut reopened = File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(output_file)
+                .map_err(|op| {
+                    format!(
+                        "Error during reopening of target file '{}', {}",
+                        output_file, op
+                    )
+                })?;
+            reopened
+                .seek(io::SeekFrom::End(0))
+                .map_err(|op| format!("Error while seeking {}: {}", output_file, op))?;
+            *file = reopened;
+            secure_output(config)?;
+            reset_index = true;
+        }
+        let rotation_result = next_file(
+            &config.extension(),
+            output_file,
+            config.rotation_directory.as_deref(),
+            &config.naming,
+        )?;
+        debug!(target: LOGGER, "Rescanned rotations after external change: {:?}", rotation_result.existing_rotated);
+        cleanup_rotations(config.max_history, 0, config, &rotation_result)?;
+    }
+    if let (true, Some(index)) = (reset_index, index) {
+        index.reset()?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &str) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(open), Ok(current)) => open.dev() == current.dev() && open.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, path: &str) -> bool {
+    Path::new(path).exists()
+}
+
+enum Target {
+    Acknowledged(Sender<Batch>),
+    Queued(QueueSender),
+}
+
+struct Destination {
+    name: String,
+    target: Target,
+    stages: Option<Pipeline>,
+    framed: bool,
+}
+
+impl Destination {
+    fn new(name: &str, sender: Sender<Batch>) -> Destination {
+        Destination {
+            name: name.to_string(),
+            target: Target::Acknowledged(sender),
+            stages: None,
+            framed: true,
+        }
+    }
+
+    fn queued(name: &str, queue: QueueSender) -> Destination {
+        Destination {
+            name: name.to_string(),
+            target: Target::Queued(queue),
+            stages: None,
+            framed: true,
+        }
+    }
+
+    // For the destinations which do not run their own pipeline, such as the sinks
+    fn through(self, stages: Pipeline) -> Destination {
+        Destination {
+            stages: (!stages.is_empty()).then_some(stages),
+            ..self
+        }
+    }
+
+    // For the mirrors which write the input as is, so that a partial line such as a prompt
+    // shows up right away rather than once the framer completes it
+    fn unframed(self, unframed: bool) -> Destination {
+        Destination {
+            framed: !unframed,
+            ..self
+        }
+    }
+}
+
+const SHUTDOWN_POLL: Duration = Duration::from_millis(100);
+
+fn start_read_cycle(
+    mut input: Input,
+    buffer_size: u32,
+    mut framer: Option<LineFramer>,
+    mut shared: Pipeline,
+    mut destinations: Vec<Destination>,
+    rxcomplete: Receiver<bool>,
+    counters: &Counters,
+) -> Result<(), RotatorError> {
+    let mut buffer: Box<[u8]> = vec![0; buffer_size.try_into().unwrap()].into_boxed_slice();
+    let mut stop: bool = false;
+    let mut draining = false;
+    while !stop {
+        if !draining && input.drains_on_shutdown() {
+            if let Some(signal) = shutdown_signal() {
+                info!(target: LOGGER, "Received {}, draining the input", signal);
+                draining = true;
+            }
+        }
+        // Shutting down stops at the first read which would block
+        let wait = if draining {
+            Duration::ZERO
+        } else {
+            SHUTDOWN_POLL
+        };
+        let readable = input
+            .wait_readable(wait)
+            .map_err(|op| format!("Impossible to wait for input: {}", op))?;
+        if !readable && !draining {
+            if let Some(grouped) = framer.as_mut().and_then(|f| f.flush()) {
+                let records = shared.apply(&[grouped]);
+                if !records.is_empty() {
+                    dispatch(records, None, &mut destinations, &rxcomplete)?;
+                }
+            }
+            continue;
+        }
+        let read_data = if readable {
+            match input.read(&mut buffer) {
+                Ok(read) => read,
+                // Nothing to read after all, e.g. while a named pipe has no writer
+                Err(op) if op.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(op) => {
+                    return Err(RotatorError::from(format!(
+                        "Impossible to read from input: {}",
+                        op
+                    )))
+                }
+            }
+        } else {
+            0
+        };
+        if read_data == 0 {
+            stop = true;
+            if let Some(last) = framer.as_mut().map(|f| f.finish()) {
+                let records = shared.apply(&last);
+                if !records.is_empty() {
+                    dispatch(records, None, &mut destinations, &rxcomplete)?;
+                }
+            }
+            continue;
+        }
+        let chunk = &buffer[0..read_data];
+        counters.record_input(chunk);
+        let records = match framer.as_mut() {
+            Some(framer) => shared.apply(&framer.feed(chunk)),
+            None => vec![Record::new(chunk.to_vec(), false)],
+        };
+        dispatch(records, Some(chunk), &mut destinations, &rxcomplete)?;
+    }
+    Ok(())
+}
+
+// The unframed destinations get the `chunk` read, when there is one, rather than the records
+fn dispatch(
+    records: Vec<Record>,
+    chunk: Option<&[u8]>,
+    destinations: &mut [Destination],
+    rxcomplete: &Receiver<bool>,
+) -> Result<(), RotatorError> {
+    let unframed: Option<Batch> =
+        chunk.map(|chunk| Arc::new(vec![Record::new(chunk.to_vec(), false)]));
+    let batch: Batch = Arc::new(records);
+    let mut pending = vec![];
+    for (position, destination) in destinations.iter_mut().enumerate() {
+        let batch = match (destination.framed, &unframed) {
+            (true, _) if batch.is_empty() => continue,
+            (true, _) => batch.clone(),
+            (false, Some(unframed)) => unframed.clone(),
+            (false, None) => continue,
+        };
+        let batch = match destination.stages.as_mut() {
+            Some(stages) => Arc::new(stages.apply(&batch)),
+            None => batch,
+        };
+        match &destination.target {
+            Target::Acknowledged(sender) => {
+                sender.send(batch).map_err(|op| {
+                    format!(
+                        "Error while sending last chunk to {}: {}",
+                        destination.name, op
+                    )
+                })?;
+                pending.push(position);
+            }
+            Target::Queued(queue) => queue.push(batch),
+        }
+    }
+    for position in pending {
+        rxcomplete.recv().map_err(|op| {
+            format!(
+                "Error while receiving confirmation from {}: {}",
+                destinations[position].name, op
+            )
+        })?;
+    }
+    Ok(())
+}
+
+fn config_logger(maybe_config: &Option<String>) -> Result<(), RotatorError> {
+    match maybe_config {
+        None => {
+            let stderr_logger = ConsoleAppender::builder()
+                .target(log4rs::append::console::Target::Stderr)
+                .encoder(Box::new(PatternEncoder::new(
+                    "{d(%Y-%m-%dT%H:%M:%S%Z)(utc)} {l:>8} {t:>10.15} - {m}{n}",
+                )))
+                .build();
+            let config = Config::builder()
+                .appender(Appender::builder().build("console", Box::new(stderr_logger)))
+                .build(Root::builder().appender("console").build(LevelFilter::Info))
+                .map_err(|op| {
+                    format!(
+                        "Error during initialisation of default console logger: {}",
+                        op
+                    )
+                })?;
+            log4rs::init_config(config).map_err(|op| {
+                format!("Error during initialising of logger configuration: {}", op)
+            })?;
+            Ok(())
+        }
+        Some(log_config) => {
+            log4rs::init_file(log_config, Default::default()).map_err(|op| {
+                format!(
+                    "Error during load of logging configuration from '{}': {}",
+                    log_config, op
+                )
+            })?;
+            Ok(())
+        }
+    }
+}
+
+struct Capture {
+    name: String,
+    stream: String,
+    config: RotationConfig,
+    channel: (Sender<Batch>, Receiver<Batch>),
+    mirror: Option<Box<dyn Write + Send>>,
+}
+
+impl Capture {
+    fn new(
+        name: &str,
+        stream: &str,
+        config: RotationConfig,
+        mirror: Option<Box<dyn Write + Send>>,
+    ) -> Capture {
+        Capture {
+            name: name.to_string(),
+            stream: stream.to_string(),
+            config,
+            channel: mpsc::channel::<Batch>(),
+            mirror,
+        }
+    }
+}
+
+fn start_capture(
+    args: &Args,
+    capture: Capture,
+    input: Input,
+    counters: Arc<Counters>,
+) -> Result<JoinHandle<()>, RotatorError> {
+    let shared = shared_pipeline(args)?;
+    let mirror_pipeline =
+        destination_pipeline(args.stdout_format, args.stdout_encoding, &capture.stream);
+    let file_pipeline = FileStages::new(args).pipeline(&capture.stream);
+    let unframed_mirror = shared.is_empty() && mirror_pipeline.is_empty();
+    let framer = line_framer(
+        args,
+        !shared.is_empty() || !mirror_pipeline.is_empty() || !file_pipeline.is_empty(),
+    );
+    let (txfile, rxfile) = capture.channel;
+    let (txcomplete, rxcomplete) = mpsc::channel::<bool>();
+    let mut destinations = vec![];
+    let mut handles = vec![];
+    if let Some(mirror) = capture.mirror {
+        let (txmirror, rxmirror) = mpsc::channel::<Batch>();
+        handles.push(start_stdout_writing(
+            mirror,
+            mirror_pipeline,
+            rxmirror,
+            txcomplete.clone(),
+            counters.clone(),
+        ));
+        destinations.push(
+            Destination::new(&format!("{} mirror", capture.name), txmirror)
+                .unframed(unframed_mirror),
+        );
+    }
+    handles.push(start_file_writing(
+        capture.config,
+        file_pipeline,
+        rxfile,
+        txcomplete,
+        counters.clone(),
+    )?);
+    destinations.push(Destination::new(&format!("{} file", capture.name), txfile));
+    let buffer_size = args.buffer_size;
+    let name = capture.name;
+    Ok(thread::spawn(move || {
+        let read = start_read_cycle(
+            input,
+            buffer_size,
+            framer,
+            shared,
+            destinations,
+            rxcomplete,
+            &counters,
+        );
+        if let Err(result) = read {
+            counters.record_error();
+            error!(target: LOGGER, "Error while capturing {}: {}", name, result);
+        }
+        for handle in handles {
+            if handle.join().is_err() {
+                error!(target: LOGGER, "Error on join of {} writers", name);
+            }
+        }
+    }))
+}
+
+fn app(args: Args) -> Result<i32, RotatorError> {
+    let started = Instant::now();
+    config_logger(&args.log_config)?;
+    install_shutdown_handlers();
+    log::info!(target: LOGGER, "Parsed command line arguments: {:?}", args);
+    #[cfg(feature = "tokio")]
+    if args.async_relay {
+        if args.output_file.len() > 1 {
+            return Err(RotatorError::new(
+                "--async-relay writes a single --output-file",
+            ));
+        }
+        let mirror = (!args.no_stdout).then_some(args.mirror);
+        return relay::run(&rotation_config(&args), mirror, args.buffer_size);
+    }
+    log::debug!(target: LOGGER, "Cleaning up rotations");
+    let mut rotation_config = rotation_config(&args);
+    let webhook = args.webhook_url.as_deref().map(|url| {
+        log::info!(target: LOGGER, "Posting events to '{}'", url);
+        Webhook::start(url)
+    });
+    rotation_config.webhook = webhook.as_ref().map(|(webhook, _)| webhook.clone());
+    if let Some(audit_file) = &args.audit_file {
+        log::info!(target: LOGGER, "Appending events to '{}'", audit_file);
+        rotation_config.audit = Some(AuditLog::open(audit_file)?);
+    }
+    if args.config.is_some() {
+        rotation_config.reloader = Some(Arc::new(Reloader::new(
+            Box::new(config::Arguments::new(std::env::args_os().collect())),
+            &rotation_config,
+        )));
+    }
+    if let Some(format) = rotation_config.compression {
+        compressor(format, rotation_config.compression_level)?;
+    }
+    if rotation_config.strict_max_size && rotation_config.max_size == 0 {
+        return Err(RotatorError::new(
+            "--strict-max-size requires a --max-size greater than 0",
+        ));
+    }
+    if rotation_config.naming.renumbers() && !rotation_config.naming.has_index() {
+        return Err(RotatorError::new(
+            "Renumbering rotations requires an {index} in the rotation names",
+        ));
+    }
+    if rotation_config.naming.renumbers() && rotation_config.delay_compress {
+        return Err(RotatorError::new(
+            "--delay-compress requires --numbering increment",
+        ));
+    }
+    if args.ring_file && args.output_file.len() > 1 {
+        return Err(RotatorError::new(
+            "--ring-file supports a single --output-file",
+        ));
+    }
+    // Every further output file gets its own writer, fed the same lines as the first one
+    let mut outputs = vec![];
+    for output in args.output_file.iter().skip(1) {
+        let mut config = rotation_config.clone();
+        config.output_file = output.path.clone();
+        config.heartbeat = Arc::default();
+        config.max_size = output.max_size.unwrap_or(args.max_size);
+        config.max_history = output.max_history.unwrap_or(args.max_history);
+        outputs.push((config, mpsc::channel::<Batch>()));
+    }
+    // Captured streams of a spawned command: its standard error first, then its descriptors
+    let mut captures = vec![];
+    if let Some(stderr_file) = &args.stderr_file {
+        let mut config = rotation_config.clone();
+        config.output_file = stderr_file.clone();
+        config.heartbeat = Arc::default();
+        config.max_size = args.stderr_max_size.unwrap_or(args.max_size);
+        config.max_history = args.stderr_max_history.unwrap_or(args.max_history);
+        captures.push(Capture::new(
+            "standard error",
+            "stderr",
+            config,
+            Some(Box::new(io::stderr())),
+        ));
+    }
+    for capture in args.exec.captured_fds() {
+        let mut config = rotation_config.clone();
+        config.output_file = capture.output_file.clone();
+        config.heartbeat = Arc::default();
+        captures.push(Capture::new(
+            &format!("descriptor {}", capture.fd),
+            &format!("fd{}", capture.fd),
+            config,
+            None,
+        ));
+    }
+    let mut locks = vec![lock_output(&rotation_config, args.wait_lock)?];
+    for (config, _) in &outputs {
+        locks.push(lock_output(config, args.wait_lock)?);
+    }
+    for capture in &captures {
+        locks.push(lock_output(&capture.config, args.wait_lock)?);
+    }
+    if !args.ring_file {
+        prepare_rotations(&rotation_config)?;
+    }
+    for (config, _) in &outputs {
+        prepare_rotations(config)?;
+    }
+    for capture in &captures {
+        prepare_rotations(&capture.config)?;
+    }
+    let counters = rotation_config.counters.clone();
+    let alerter = Alerter::new(args.alert_cmd.clone());
+    if args.volume_spike_factor.is_some() || args.volume_drop_factor.is_some() {
+        log::info!(target: LOGGER, "Starting volume monitoring");
+        start_volume_monitor(
+            VolumeConfig {
+                interval: args.volume_interval,
+                window: args.volume_window.max(1),
+                spike_factor: args.volume_spike_factor,
+                drop_factor: args.volume_drop_factor,
+            },
+            counters.clone(),
+            alerter.clone(),
+        );
+    }
+    let stats_recorder = match &args.stats_db {
+        Some(path) => {
+            log::info!(target: LOGGER, "Recording statistics to '{}'", path);
+            Some(start_stats_recorder(
+                StatsStore::open(path)?,
+                args.stats_interval,
+                counters.clone(),
+            ))
+        }
+        None => None,
+    };
+    let statsd_reporter = match &args.statsd_addr {
+        Some(address) => {
+            log::info!(target: LOGGER, "Pushing metrics to statsd at '{}'", address);
+            Some(start_statsd_reporter(
+                StatsdClient::connect(address, &args.statsd_prefix, args.statsd_tags.clone())?,
+                args.statsd_interval,
+                counters.clone(),
+            ))
+        }
+        None => None,
+    };
+    let shared = shared_pipeline(&args)?;
+    let stdout_pipeline = destination_pipeline(args.stdout_format, args.stdout_encoding, "stdout");
+    let file_pipeline = FileStages::new(&args).pipeline("stdout");
+    let router = router(&args)?;
+    let needs_framing = !shared.is_empty()
+        || !stdout_pipeline.is_empty()
+        || !file_pipeline.is_empty()
+        || args.sqlite_sink.is_some()
+        || args.to_journald
+        || args.syslog_target.is_some()
+        || args.tee_encoding != Encoding::Raw
+        || router.is_some();
+    let unframed_stdout = shared.is_empty() && stdout_pipeline.is_empty();
+    let framer = line_framer(&args, needs_framing);
+    let (txstdout, rxstdout) = mpsc::channel::<Batch>();
+    let (txfile, rxfile) = mpsc::channel::<Batch>();
+    let (txcomplete, rxcomplete) = mpsc::channel::<bool>();
+    let mut txfiles = vec![];
+    let mut heartbeats = vec![];
+    if !args.ring_file {
+        txfiles.push(txfile.clone());
+        heartbeats.push(rotation_config.heartbeat.clone());
+    }
+    for (config, (txoutput, _)) in &outputs {
+        txfiles.push(txoutput.clone());
+        heartbeats.push(config.heartbeat.clone());
+    }
+    for capture in &captures {
+        txfiles.push(capture.channel.0.clone());
+        heartbeats.push(capture.config.heartbeat.clone());
+    }
+    // Every rotating output follows the schedule, the routes through the output file
+    let scheduler = match &args.rotate_cron {
+        Some(schedule) if !txfiles.is_empty() => {
+            log::info!(target: LOGGER, "Scheduling rotations at '{}'", schedule);
+            Some(start_rotation_scheduler(schedule.clone(), txfiles.clone()))
+        }
+        _ => None,
+    };
+    let signal_watcher = if args.ring_file {
+        None
+    } else {
+        Some(start_signal_watcher(
+            txfiles.clone(),
+            rotation_config.reloader.clone(),
+        ))
+    };
+    let notifier = match args.systemd_notify {
+        true => Notifier::from_env()?.map(Arc::new),
+        false => None,
+    };
+    if args.systemd_notify && notifier.is_none() {
+        log::warn!(target: LOGGER, "NOTIFY_SOCKET is not set, not notifying systemd");
+    }
+    // The senders are dropped along with the closure when there is no watchdog
+    let watchdog = notifier
+        .as_ref()
+        .zip(watchdog_interval())
+        .map(|(notifier, interval)| {
+            start_watchdog(notifier.clone(), interval, txfiles, heartbeats)
+        });
+    // Every destination acknowledges its batches, so leaving out stdout only leaves one less
+    let mut destinations = vec![];
+    let stdout_handle = if args.no_stdout {
+        None
+    } else {
+        let (name, mirror): (&str, Box<dyn Write + Send>) = match args.mirror {
+            Mirror::Stdout => ("stdout", Box::new(io::stdout())),
+            Mirror::Stderr => ("stderr", Box::new(io::stderr())),
+        };
+        log::info!(target: LOGGER, "Starting {} writing", name);
+        destinations.push(Destination::new(name, txstdout).unframed(unframed_stdout));
+        Some(start_stdout_writing(
+            mirror,
+            stdout_pipeline,
+            rxstdout,
+            txcomplete.clone(),
+            counters.clone(),
+        ))
+    };
+    let mut bridge_handle = None;
+    let txfilecomplete = match args.file_overflow {
+        Overflow::Block => {
+            destinations.push(Destination::new("file", txfile));
+            txcomplete.clone()
+        }
+        Overflow::DropOldest => {
+            log::info!(target: LOGGER, "Queueing up to {} chunks for the file, dropping the oldest on overload", args.file_queue_size);
+            let (queue, txbridge, handle) =
+                start_drop_oldest_bridge(args.file_queue_size, counters.clone(), txfile);
+            destinations.push(Destination::queued("file", queue));
+            bridge_handle = Some(handle);
+            txbridge
+        }
+    };
+    log::info!(target: LOGGER, "Starting file writing");
+    let file_handle = if args.ring_file {
+        let ring = RingFile::open(
+            &rotation_config.output_file,
+            rotation_config.max_size,
+            &rotation_config.access,
+        )?;
+        rotation_config
+            .access
+            .apply_file(Path::new(&rotation_config.output_file))?;
+        start_ring_writing(
+            ring,
+            file_pipeline,
+            rxfile,
+            txfilecomplete,
+            counters.clone(),
+        )
+    } else if let Some(router) = router {
+        start_routed_writing(
+            router,
+            rotation_config,
+            FileStages::new(&args),
+            rxfile,
+            txfilecomplete,
+            counters.clone(),
+        )?
+    } else {
+        start_file_writing(
+            rotation_config,
+            file_pipeline,
+            rxfile,
+            txfilecomplete,
+            counters.clone(),
+        )?
+    };
+    let mut output_handles = vec![];
+    for (config, (txoutput, rxoutput)) in outputs {
+        log::info!(target: LOGGER, "Starting file writing to '{}'", config.output_file);
+        output_handles.push(start_file_writing(
+            config,
+            FileStages::new(&args).pipeline("stdout"),
+            rxoutput,
+            txcomplete.clone(),
+            counters.clone(),
+        )?);
+        destinations.push(Destination::new("file", txoutput));
+    }
+    let mut sink_handles = vec![];
+    // The syslog sink has a format of its own, the others choose their encoding
+    let file_stages = FileStages::new(&args);
+    let sink_stages = |encoding: Encoding| {
+        let mut pipeline = file_stages.filtering();
+        pipeline.extend(destination_pipeline(Format::Raw, encoding, "stdout"));
+        pipeline
+    };
+    if let Some(path) = &args.sqlite_sink {
+        log::info!(target: LOGGER, "Starting SQLite sink writing to '{}'", path);
+        let (txsqlite, rxsqlite) = mpsc::channel::<Batch>();
+        sink_handles.push(start_sqlite_sink(
+            SqliteSink::open(path, args.sqlite_max_size)?,
+            rxsqlite,
+            txcomplete.clone(),
+        ));
+        destinations
+            .push(Destination::new("sqlite", txsqlite).through(sink_stages(args.sqlite_encoding)));
+    }
+    if args.to_journald {
+        log::info!(target: LOGGER, "Starting journald sink as '{}'", args.journald_identifier);
+        let (txjournald, rxjournald) = mpsc::channel::<Batch>();
+        sink_handles.push(start_journald_sink(
+            JournaldSink::open(&args.journald_identifier, args.journald_priority)?,
+            rxjournald,
+            txcomplete.clone(),
+        ));
+        destinations.push(
+            Destination::new("journald", txjournald).through(sink_stages(args.journald_encoding)),
+        );
+    }
+    if let Some(target) = &args.syslog_target {
+        log::info!(target: LOGGER, "Starting syslog sink forwarding to {:?}", target);
+        let (txsyslog, rxsyslog) = mpsc::channel::<Batch>();
+        sink_handles.push(start_syslog_sink(
+            SyslogSink::open(
+                target,
+                args.syslog_format,
+                args.syslog_facility,
+                args.syslog_severity,
+                &args.syslog_app_name,
+            )?,
+            rxsyslog,
+            txcomplete.clone(),
+        ));
+        destinations.push(Destination::new("syslog", txsyslog).through(file_stages.filtering()));
+    }
+    if let Some(command) = &args.tee_cmd {
+        log::info!(target: LOGGER, "Starting tee into '{}'", command);
+        let (txtee, rxtee) = mpsc::channel::<Batch>();
+        sink_handles.push(start_tee_sink(
+            TeeSink::spawn(command, args.tee_exit)?,
+            rxtee,
+            txcomplete.clone(),
+        ));
+        destinations.push(Destination::new("tee", txtee).through(sink_stages(args.tee_encoding)));
+    }
+    drop(txcomplete);
+    let mut capture_handles = vec![];
+    let (input, wrapped) = if args.exec.is_enabled() {
+        let (wrapped, streams) = Wrapped::spawn(&args.exec, args.stderr_file.is_some())?;
+        let inputs = streams.stderr.into_iter().chain(streams.descriptors);
+        for (capture, input) in captures.into_iter().zip(inputs) {
+            log::info!(target: LOGGER, "Starting capture of {} to '{}'", capture.name, capture.config.output_file);
+            capture_handles.push(start_capture(&args, capture, input, counters.clone())?);
+        }
+        (streams.stdout, Some(wrapped))
+    } else {
+        (open_input(&args)?, None)
+    };
+    log::info!(target: LOGGER, "Starting stdout reading");
+    if let Some(notifier) = &notifier {
+        notifier.notify("READY=1");
+    }
+    start_read_cycle(
+        input,
+        args.buffer_size,
+        framer,
+        shared,
+        destinations,
+        rxcomplete,
+        &counters,
+    )?;
+    if let Some(notifier) = &notifier {
+        notifier.notify("STOPPING=1");
+    }
+    if let Some((txstop, handle)) = watchdog {
+        drop(txstop);
+        handle
+            .join()
+            .map_err(|_| "Error on join of systemd watchdog".to_string())?;
+    }
+    if let Some((txstop, handle)) = scheduler {
+        drop(txstop);
+        handle
+            .join()
+            .map_err(|_| "Error on join of rotation scheduler".to_string())?;
+    }
+    if let Some((txstop, handle)) = signal_watcher {
+        drop(txstop);
+        handle
+            .join()
+            .map_err(|_| "Error on join of signal watcher".to_string())?;
+    }
+    for handle in capture_handles {
+        handle
+            .join()
+            .map_err(|_| "Error on join of capture".to_string())?;
+    }
+    if let Some(handle) = stdout_handle {
+        handle
+            .join()
+            .map_err(|_| "Error on join of stdout".to_string())?;
+    }
+    if let Some(handle) = bridge_handle {
+        handle
+            .join()
+            .map_err(|_| "Error on join of file queue".to_string())?;
+    }
+    file_handle
+        .join()
+        .map_err(|_| "Error on join of file".to_string())?;
+    for handle in output_handles {
+        handle
+            .join()
+            .map_err(|_| "Error on join of file".to_string())?;
+    }
+    for handle in sink_handles {
+        handle
+            .join()
+            .map_err(|_| "Error on join of sink".to_string())?;
+    }
+    if let Some((txstop, handle)) = stats_recorder {
+        drop(txstop);
+        handle
+            .join()
+            .map_err(|_| "Error on join of statistics recorder".to_string())?;
+    }
+    if let Some((txstop, handle)) = statsd_reporter {
+        drop(txstop);
+        handle
+            .join()
+            .map_err(|_| "Error on join of statsd reporter".to_string())?;
+    }
+    if let Some((webhook, handle)) = webhook {
+        webhook.stop();
+        handle
+            .join()
+            .map_err(|_| "Error on join of webhook".to_string())?;
+    }
+    let exit_code = match wrapped {
+        Some(wrapped) => Some(wrapped.wait()?),
+        None => None,
+    };
+    // The spawned command handled the forwarded signal itself and reports how it went
+    let exit_code = match (shutdown_signal(), exit_code) {
+        (Some(signal), None) => args.shutdown_exit_code.unwrap_or(signal.exit_code()),
+        (Some(_), Some(code)) => args.shutdown_exit_code.unwrap_or(code),
+        (None, code) => code.unwrap_or(0),
+    };
+    if args.summary || args.summary_file.is_some() {
+        write_summary(args.summary_file.as_deref(), started, &counters, exit_code)?;
+    }
+    Ok(exit_code)
+}
+
+pub fn run() {
+    let command = config::command();
+    let argv = match config::check_environment(&command)
+        .and_then(|_| config::with_config_file(&command, std::env::args_os().collect()))
+    {
+        Ok(argv) => argv,
+        Err(err) => {
+            eprintln!("{}", err.msg);
+            exit(1);
+        }
+    };
+    let cli = Cli::from_arg_matches(&command.get_matches_from(argv)).unwrap_or_else(|op| op.exit());
+    let result = match cli.command {
+        Some(Command::Inspect(args)) => inspect::run(args).map(|_| 0),
+        Some(Command::Cat(args)) => cat::run(args).map(|_| 0),
+        Some(Command::Clean(args)) => clean::run(args).map(|_| 0),
+        Some(Command::Split(args)) => split::run(args).map(|_| 0),
+        Some(Command::Grep(args)) => grep::run(args),
+        Some(Command::Ls(args)) => ls::run(args).map(|_| 0),
+        Some(Command::Prune(args)) => prune::run(args).map(|_| 0),
+        Some(Command::Compress(args)) => recompress::run(args).map(|_| 0),
+        Some(Command::Verify(args)) => verify::run(args),
+        Some(Command::Tail(args)) => tail::run(args).map(|_| 0),
+        Some(Command::Replay(args)) => replay::run(args).map(|_| 0),
+        None => app(cli.args),
+    };
+    match result {
+        Ok(code) => exit(code),
+        Err(err) => {
+            log::error!(target: LOGGER, "{}", err.msg);
+            eprintln!("{}", err.msg);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+pub struct CivilTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub weekday: u32,
+}
+
+#[cfg(unix)]
+pub fn civil_time(seconds: i64) -> CivilTime {
+    let time = seconds as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&time, &mut tm) };
+    CivilTime {
+        year: tm.tm_year as i64 + 1900,
+        month: tm.tm_mon as u32 + 1,
+        day: tm.tm_mday as u32,
+        hour: tm.tm_hour as u32,
+        minute: tm.tm_min as u32,
+        second: tm.tm_sec as u32,
+        weekday: tm.tm_wday as u32,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn civil_time(seconds: i64) -> CivilTime {
+    let days = seconds.div_euclid(86400);
+    let of_day = seconds.rem_euclid(86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    CivilTime {
+        year: yoe + era * 400 + i64::from(month <= 2),
+        month: month as u32,
+        day: (doy - (153 * mp + 2) / 5 + 1) as u32,
+        hour: (of_day / 3600) as u32,
+        minute: (of_day / 60 % 60) as u32,
+        second: (of_day % 60) as u32,
+        weekday: (days + 4).rem_euclid(7) as u32,
+    }
+}
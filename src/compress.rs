@@ -0,0 +1,324 @@
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use log::{debug, error, info};
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use xz2::read::XzDecoder;
+use xz2::write::XzEncoder;
+
+use crate::audit::AuditLog;
+use crate::checksum::{rotation_checksum, write_checksum};
+use crate::encrypt::{encrypt, Recipient};
+use crate::latest::retarget_latest;
+use crate::manifest::{replace_rotation, update_manifest};
+use crate::metrics::Counters;
+use crate::permissions::Access;
+use crate::{move_sidecars, secure_rotation, RotatorError};
+
+const LOGGER: &str = "compressor";
+const PARTIAL_EXTENSION: &str = ".partial";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum CompressionFormat {
+    Gzip,
+    Zstd,
+    Xz,
+    Lz4,
+}
+
+impl CompressionFormat {
+    pub const ALL: &'static [CompressionFormat] = &[
+        CompressionFormat::Gzip,
+        CompressionFormat::Zstd,
+        CompressionFormat::Xz,
+        CompressionFormat::Lz4,
+    ];
+
+    // As given on the command line and recorded in the manifests
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompressionFormat::Gzip => "gzip",
+            CompressionFormat::Zstd => "zstd",
+            CompressionFormat::Xz => "xz",
+            CompressionFormat::Lz4 => "lz4",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CompressionFormat::Gzip => ".gz",
+            CompressionFormat::Zstd => ".zst",
+            CompressionFormat::Xz => ".xz",
+            CompressionFormat::Lz4 => ".lz4",
+        }
+    }
+
+    pub fn levels(&self) -> (u32, u32, u32) {
+        match self {
+            CompressionFormat::Gzip => (0, 9, 6),
+            CompressionFormat::Zstd => (1, 22, zstd::DEFAULT_COMPRESSION_LEVEL as u32),
+            CompressionFormat::Xz => (0, 9, 6),
+            CompressionFormat::Lz4 => (0, 16, 0),
+        }
+    }
+}
+
+pub fn rotation_format(path: &Path) -> Option<CompressionFormat> {
+    let name = path.to_string_lossy();
+    CompressionFormat::ALL
+        .iter()
+        .copied()
+        .find(|format| name.ends_with(format.extension()))
+}
+
+// Reads back a plain or compressed rotation, its format told by its extension
+pub fn open_rotation(path: &Path) -> Result<Box<dyn Read>, RotatorError> {
+    let name = path.to_string_lossy();
+    if name.ends_with(".age") || name.ends_with(".gpg") {
+        return Err(RotatorError::from(format!(
+            "'{}' is encrypted and cannot be read",
+            name
+        )));
+    }
+    if name.ends_with(".parquet") {
+        return Err(RotatorError::from(format!(
+            "'{}' is a parquet archive and cannot be read as text",
+            name
+        )));
+    }
+    let file =
+        File::open(path).map_err(|op| format!("Error during opening of '{}': {}", name, op))?;
+    let reader: Box<dyn Read> = match rotation_format(path) {
+        None => Box::new(file),
+        Some(CompressionFormat::Gzip) => Box::new(MultiGzDecoder::new(file)),
+        Some(CompressionFormat::Zstd) => Box::new(
+            zstd::Decoder::new(file)
+                .map_err(|op| format!("Error while decompressing '{}': {}", name, op))?,
+        ),
+        Some(CompressionFormat::Xz) => Box::new(XzDecoder::new_multi_decoder(file)),
+        Some(CompressionFormat::Lz4) => Box::new(
+            lz4::Decoder::new(file)
+                .map_err(|op| format!("Error while decompressing '{}': {}", name, op))?,
+        ),
+    };
+    Ok(reader)
+}
+
+pub trait Compressor {
+    fn compress(&self, input: &mut dyn Read, output: File) -> io::Result<()>;
+}
+
+struct Gzip {
+    level: u32,
+}
+
+impl Compressor for Gzip {
+    fn compress(&self, input: &mut dyn Read, output: File) -> io::Result<()> {
+        let mut encoder = GzEncoder::new(output, flate2::Compression::new(self.level));
+        io::copy(input, &mut encoder)?;
+        encoder.finish()?.flush()
+    }
+}
+
+struct Zstd {
+    level: u32,
+}
+
+impl Compressor for Zstd {
+    fn compress(&self, input: &mut dyn Read, output: File) -> io::Result<()> {
+        let mut encoder = zstd::Encoder::new(output, self.level as i32)?;
+        // Lets verify tell a corrupted frame from a valid one
+        encoder.include_checksum(true)?;
+        io::copy(input, &mut encoder)?;
+        encoder.finish()?.flush()
+    }
+}
+
+struct Xz {
+    preset: u32,
+}
+
+impl Compressor for Xz {
+    fn compress(&self, input: &mut dyn Read, output: File) -> io::Result<()> {
+        let mut encoder = XzEncoder::new(output, self.preset);
+        io::copy(input, &mut encoder)?;
+        encoder.finish()?.flush()
+    }
+}
+
+struct Lz4 {
+    level: u32,
+}
+
+impl Compressor for Lz4 {
+    fn compress(&self, input: &mut dyn Read, output: File) -> io::Result<()> {
+        let mut encoder = lz4::EncoderBuilder::new().level(self.level).build(output)?;
+        io::copy(input, &mut encoder)?;
+        let (mut output, result) = encoder.finish();
+        result?;
+        output.flush()
+    }
+}
+
+pub fn compressor(
+    format: CompressionFormat,
+    level: Option<u32>,
+) -> Result<Box<dyn Compressor>, RotatorError> {
+    let (min, max, default) = format.levels();
+    let level = level.unwrap_or(default);
+    if level < min || level > max {
+        return Err(RotatorError::from(format!(
+            "Compression level {} is outside of the range {}-{} supported by {:?}",
+            level, min, max, format
+        )));
+    }
+    Ok(match format {
+        CompressionFormat::Gzip => Box::new(Gzip { level }),
+        CompressionFormat::Zstd => Box::new(Zstd { level }),
+        CompressionFormat::Xz => Box::new(Xz { preset: level }),
+        CompressionFormat::Lz4 => Box::new(Lz4 { level }),
+    })
+}
+
+#[derive(Debug)]
+pub struct CompressionJob {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub format: CompressionFormat,
+    pub level: Option<u32>,
+    pub checksum: bool,
+    pub encryption: Option<Recipient>,
+    pub access: Access,
+    // Output file whose latest rotation link follows the compressed file
+    pub latest: Option<String>,
+    // Output file whose manifest records the compressed file
+    pub manifest: Option<String>,
+    pub upload_queue: Option<Sender<PathBuf>>,
+}
+
+impl CompressionJob {
+    // The file the rotation ends up in, unless it was removed meanwhile
+    fn run(&self) -> Result<Option<PathBuf>, RotatorError> {
+        let mut partial = self.target.as_os_str().to_owned();
+        partial.push(PARTIAL_EXTENSION);
+        let partial = PathBuf::from(partial);
+        let mut source = match File::open(&self.source) {
+            Ok(source) => source,
+            Err(op) if op.kind() == io::ErrorKind::NotFound => {
+                debug!(target: LOGGER, "'{}' was removed before compression", self.source.display());
+                return Ok(None);
+            }
+            Err(op) => {
+                return Err(RotatorError::from(format!(
+                    "Error during opening of '{}': {}",
+                    self.source.display(),
+                    op
+                )))
+            }
+        };
+        let output = File::create(&partial)
+            .map_err(|op| format!("Error during opening of '{}': {}", partial.display(), op))?;
+        if let Err(op) = compressor(self.format, self.level)?.compress(&mut source, output) {
+            let _ = fs::remove_file(&partial);
+            return Err(RotatorError::from(format!(
+                "Error while compressing '{}': {}",
+                self.source.display(),
+                op
+            )));
+        }
+        if !self.source.exists() {
+            // Retention removed the rotation while it was being compressed
+            debug!(target: LOGGER, "'{}' was removed during compression", self.source.display());
+            return fs::remove_file(&partial).map(|_| None).map_err(|op| {
+                RotatorError::from(format!(
+                    "Error while removing '{}': {}",
+                    partial.display(),
+                    op
+                ))
+            });
+        }
+        fs::rename(&partial, &self.target).map_err(|op| {
+            format!(
+                "Error while renaming '{}' to '{}': {}",
+                partial.display(),
+                self.target.display(),
+                op
+            )
+        })?;
+        move_sidecars(&self.source, &self.target)?;
+        replace_rotation(self.manifest.as_deref(), &self.source, &self.target)?;
+        let rotated = match &self.encryption {
+            Some(recipient) => encrypt(recipient, &self.target)?,
+            None => self.target.clone(),
+        };
+        // The checksum of the plain rotation was moved along with the other sidecars
+        if self.checksum {
+            write_checksum(&rotated)?;
+        }
+        secure_rotation(&self.access, &rotated)?;
+        if let Some(output_file) = &self.latest {
+            retarget_latest(output_file, &self.source, &rotated)?;
+        }
+        if let Some(output_file) = &self.manifest {
+            let sha256 = rotation_checksum(&rotated)?;
+            update_manifest(output_file, false, |manifest| {
+                if let Some(entry) = manifest.entry_mut(&self.target) {
+                    entry.stored_as(&rotated, Some(sha256));
+                }
+            })?;
+        }
+        if let Some(queue) = &self.upload_queue {
+            queue
+                .send(rotated.clone())
+                .map_err(|op| format!("Error while queueing upload: {}", op))?;
+        }
+        Ok(Some(rotated))
+    }
+}
+
+pub fn start_compression_worker(
+    counters: Arc<Counters>,
+    audit: Option<AuditLog>,
+) -> (Sender<CompressionJob>, JoinHandle<()>) {
+    let (txjob, rxjob) = mpsc::channel::<CompressionJob>();
+    let handle = thread::spawn(move || {
+        for job in rxjob {
+            debug!(target: LOGGER, "Compressing '{}' to '{}'", job.source.display(), job.target.display());
+            match job.run() {
+                Ok(Some(rotated)) => {
+                    info!(target: LOGGER, "Compressed '{}'", job.target.display());
+                    if let Some(audit) = &audit {
+                        audit.record(
+                            "compression",
+                            json!({
+                                "source": job.source.to_string_lossy(),
+                                "rotation": rotated.to_string_lossy(),
+                            }),
+                        );
+                    }
+                }
+                Ok(None) => {}
+                Err(result) => {
+                    counters.record_error();
+                    error!(target: LOGGER, "Error while compressing rotation: {}", result);
+                    if let Some(audit) = &audit {
+                        audit.record(
+                            "compression_error",
+                            json!({
+                                "source": job.source.to_string_lossy(),
+                                "error": result.msg,
+                            }),
+                        );
+                    }
+                }
+            }
+        }
+    });
+    (txjob, handle)
+}
@@ -0,0 +1,446 @@
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, Command, CommandFactory, FromArgMatches};
+use serde_json::{Map, Value};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use stdout_rotator::hooks::HOOK_VARIABLES;
+use stdout_rotator::reload::{Settings, Source};
+use stdout_rotator::RotatorError;
+
+use crate::cli::{rotation_config, Cli};
+
+#[cfg(feature = "tokio")]
+use crate::relay;
+
+const CONFIG_FLAG: &str = "--config";
+const ENV_PREFIX: &str = "STDOUT_ROTATOR_";
+const FILE_ARGUMENTS: &[&str] = &["file", "output_file"];
+
+fn env_name(long: &str) -> String {
+    format!("{}{}", ENV_PREFIX, long.replace('-', "_").to_uppercase())
+}
+
+// Every option can also be set through an environment variable named after it, e.g.
+// STDOUT_ROTATOR_MAX_SIZE for --max-size, in the subcommands as well
+pub fn command() -> Command {
+    let command = with_env(Cli::command());
+    #[cfg(feature = "tokio")]
+    let command = relay::restrict(command);
+    let subcommands: Vec<String> = command
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_string())
+        .collect();
+    subcommands.iter().fold(command, |command, name| {
+        command.mut_subcommand(name, with_env)
+    })
+}
+
+// The file a subcommand works on is the output file of the run which wrote it
+fn with_env(command: Command) -> Command {
+    command.mut_args(|arg| {
+        let name = match arg.get_long() {
+            Some(long) if !arg.is_positional() => env_name(long),
+            None if FILE_ARGUMENTS.contains(&arg.get_id().as_str()) => env_name("output-file"),
+            _ => return arg,
+        };
+        arg.env(name)
+    })
+}
+
+fn arguments(command: &Command) -> impl Iterator<Item = &Arg> {
+    command.get_arguments().chain(
+        command
+            .get_subcommands()
+            .flat_map(|subcommand| subcommand.get_arguments()),
+    )
+}
+
+// A misspelt variable would otherwise be silently ignored
+pub fn check_environment(command: &Command) -> Result<(), RotatorError> {
+    for (name, _) in env::vars_os() {
+        let name = name.to_string_lossy();
+        if !name.starts_with(ENV_PREFIX) || HOOK_VARIABLES.contains(&name.as_ref()) {
+            continue;
+        }
+        let known =
+            arguments(command).any(|arg| arg.get_env().is_some_and(|env| env == name.as_ref()));
+        if !known {
+            return Err(RotatorError::from(format!(
+                "Unknown environment variable {}",
+                name
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Finds the value of --config in the command line, ignoring the arguments of a spawned command
+fn config_path(argv: &[OsString]) -> Option<OsString> {
+    config_flag(argv).or_else(|| env::var_os(env_name("config")))
+}
+
+fn config_flag(argv: &[OsString]) -> Option<OsString> {
+    let mut arguments = argv.iter().skip(1);
+    while let Some(argument) = arguments.next() {
+        let argument = argument.to_string_lossy();
+        if argument == "--" {
+            return None;
+        }
+        if argument == CONFIG_FLAG {
+            return arguments.next().cloned();
+        }
+        if let Some(path) = argument.strip_prefix("--config=") {
+            return Some(OsString::from(path));
+        }
+    }
+    None
+}
+
+// Turns the settings of the configuration file into command line arguments placed before the
+// ones given by the user, skipping those the command line or the environment already set
+pub fn with_config_file(
+    command: &Command,
+    argv: Vec<OsString>,
+) -> Result<Vec<OsString>, RotatorError> {
+    let path = match config_path(&argv) {
+        Some(path) => path,
+        None => return Ok(argv),
+    };
+    // Errors are left to the actual parse, which sees the settings of the file as well
+    let matches = match command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(&argv)
+    {
+        Ok(matches) if matches.subcommand().is_none() => matches,
+        _ => return Ok(argv),
+    };
+    let settings = load(Path::new(&path))?;
+    let given = |id: &str| {
+        matches!(
+            matches.value_source(id),
+            Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable)
+        )
+    };
+    let mut arguments = vec![];
+    for (key, value) in settings {
+        let long = key.replace('_', "-");
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long.as_str()) && !arg.is_positional())
+            .filter(|arg| arg.get_id() != "config")
+            .ok_or_else(|| {
+                format!(
+                    "Unknown setting '{}' in '{}'",
+                    key,
+                    Path::new(&path).display()
+                )
+            })?;
+        // Settings conflicting with the command line are overridden by it as well
+        let conflicting = command.get_arguments().any(|other| {
+            given(other.get_id().as_str())
+                && (command
+                    .get_arg_conflicts_with(arg)
+                    .iter()
+                    .any(|conflict| conflict.get_id() == other.get_id())
+                    || command
+                        .get_arg_conflicts_with(other)
+                        .iter()
+                        .any(|conflict| conflict.get_id() == arg.get_id()))
+        });
+        if given(arg.get_id().as_str()) || conflicting {
+            continue;
+        }
+        let flag = format!("--{}", long);
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::Null | Value::Bool(false) => {}
+                Value::Bool(true) if matches!(arg.get_action(), ArgAction::SetTrue) => {
+                    arguments.push(OsString::from(&flag))
+                }
+                Value::Bool(true) if arg.get_num_args().is_some_and(|num| num.min_values() == 0) => {
+                    arguments.push(OsString::from(&flag))
+                }
+                Value::String(value) => arguments.push(OsString::from(format!("{}={}", flag, value))),
+                Value::Number(value) => arguments.push(OsString::from(format!("{}={}", flag, value))),
+                Value::Bool(true) => arguments.push(OsString::from(format!("{}=true", flag))),
+                Value::Array(_) | Value::Object(_) => {
+                    return Err(RotatorError::from(format!(
+                        "Invalid value of setting '{}' in '{}', expected a string, number, boolean or list of those",
+                        key,
+                        Path::new(&path).display()
+                    )))
+                }
+            }
+        }
+    }
+    let mut argv = argv.into_iter();
+    Ok(argv
+        .next()
+        .into_iter()
+        .chain(arguments)
+        .chain(argv)
+        .collect())
+}
+
+fn load(path: &Path) -> Result<Map<String, Value>, RotatorError> {
+    let content = fs::read_to_string(path).map_err(|op| {
+        format!(
+            "Error while reading configuration file '{}': {}",
+            path.display(),
+            op
+        )
+    })?;
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let document = match extension.as_deref() {
+        Some("yaml") | Some("yml") => {
+            serde_yaml::from_str::<Value>(&content).map_err(|op| op.to_string())
+        }
+        Some("json") => serde_json::from_str::<Value>(&content).map_err(|op| op.to_string()),
+        _ => toml::from_str::<Value>(&content).map_err(|op| op.to_string()),
+    }
+    .map_err(|op| {
+        format!(
+            "Error while parsing configuration file '{}': {}",
+            path.display(),
+            op
+        )
+    })?;
+    match document {
+        Value::Object(settings) => Ok(settings),
+        Value::Null => Ok(Map::new()),
+        _ => Err(RotatorError::from(format!(
+            "Configuration file '{}' must contain a mapping of settings",
+            path.display()
+        ))),
+    }
+}
+
+// The command line of the running process, with the configuration file it names read again
+#[derive(Debug)]
+pub struct Arguments {
+    argv: Vec<OsString>,
+}
+
+impl Arguments {
+    pub fn new(argv: Vec<OsString>) -> Arguments {
+        Arguments { argv }
+    }
+}
+
+impl Source for Arguments {
+    fn settings(&self) -> Result<Settings, RotatorError> {
+        let command = command();
+        let argv = with_config_file(&command, self.argv.clone())?;
+        let cli = command
+            .try_get_matches_from(argv)
+            .and_then(|matches| Cli::from_arg_matches(&matches))
+            .map_err(|op| {
+                let message = op.to_string();
+                let reason = message.lines().next().unwrap_or_default();
+                format!(
+                    "Error while parsing configuration: {}",
+                    reason.trim_start_matches("error: ")
+                )
+            })?;
+        Ok(Settings::of(&rotation_config(&cli.args)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // The environment is shared by every test of the process
+    static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+    fn matches(config: Option<(&str, &str)>, args: &[&str]) -> clap::ArgMatches {
+        let directory = tempfile::tempdir().unwrap();
+        let mut argv: Vec<OsString> = vec!["stdout-rotator".into()];
+        if let Some((name, content)) = config {
+            let path = directory.path().join(name);
+            fs::write(&path, content).unwrap();
+            argv.push(CONFIG_FLAG.into());
+            argv.push(path.into_os_string());
+        }
+        argv.extend(args.iter().map(OsString::from));
+        let command = command();
+        let argv = with_config_file(&command, argv).unwrap();
+        command.try_get_matches_from(argv).unwrap()
+    }
+
+    fn with_environment<T>(variables: &[(&str, &str)], test: impl FnOnce() -> T) -> T {
+        let _environment = ENVIRONMENT
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for (name, value) in variables {
+            env::set_var(name, value);
+        }
+        let result = test();
+        for (name, _) in variables {
+            env::remove_var(name);
+        }
+        result
+    }
+
+    const TOML: &str = "
+# Rotation
+max_history = 3
+max-size = \"1KB\"
+strict_max_size = true
+log_config = 'log4rs.yaml'
+";
+
+    #[test]
+    fn config_files_set_defaults() {
+        let settings = with_environment(&[], || matches(Some(("config.toml", TOML)), &[]));
+        assert_eq!(settings.get_one::<u32>("max_history"), Some(&3));
+        assert_eq!(settings.get_one::<u64>("max_size"), Some(&1000));
+        assert!(settings.get_flag("strict_max_size"));
+        assert_eq!(
+            settings.get_one::<String>("log_config").map(String::as_str),
+            Some("log4rs.yaml")
+        );
+    }
+
+    #[test]
+    fn yaml_and_json_files_are_read_as_toml_ones() {
+        let yaml = "max_history: 3\nstrict_max_size: true\n";
+        let json = r#"{"max_history": 3, "strict_max_size": true}"#;
+        for (name, content) in [("config.yaml", yaml), ("config.json", json)] {
+            let settings = with_environment(&[], || matches(Some((name, content)), &[]));
+            assert_eq!(settings.get_one::<u32>("max_history"), Some(&3));
+            assert!(settings.get_flag("strict_max_size"));
+        }
+    }
+
+    #[test]
+    fn the_command_line_overrides_the_environment_and_the_config_file() {
+        let settings = with_environment(
+            &[
+                ("STDOUT_ROTATOR_MAX_HISTORY", "7"),
+                ("STDOUT_ROTATOR_MAX_SIZE", "2KB"),
+            ],
+            || matches(Some(("config.toml", TOML)), &["--max-history", "9"]),
+        );
+        assert_eq!(settings.get_one::<u32>("max_history"), Some(&9));
+        assert_eq!(settings.get_one::<u64>("max_size"), Some(&2000));
+        assert_eq!(
+            settings.value_source("max_size"),
+            Some(ValueSource::EnvVariable)
+        );
+        assert!(settings.get_flag("strict_max_size"));
+    }
+
+    #[test]
+    fn settings_conflicting_with_the_command_line_are_dropped() {
+        let config = Some(("config.toml", "gunzip = true\n"));
+        let settings = with_environment(&[], || matches(config, &["--compress", "zstd"]));
+        assert!(!settings.get_flag("gunzip"));
+        let settings = with_environment(&[], || matches(config, &[]));
+        assert!(settings.get_flag("gunzip"));
+    }
+
+    #[test]
+    fn the_config_file_can_come_from_the_environment() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("config.toml");
+        fs::write(&path, "max_history = 4\n").unwrap();
+        let settings =
+            with_environment(&[("STDOUT_ROTATOR_CONFIG", path.to_str().unwrap())], || {
+                matches(None, &[])
+            });
+        assert_eq!(settings.get_one::<u32>("max_history"), Some(&4));
+    }
+
+    #[test]
+    fn arguments_of_the_spawned_command_are_not_read() {
+        let argv: Vec<OsString> = ["stdout-rotator", "--", "app", "--config", "app.toml"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        assert_eq!(config_flag(&argv), None);
+        let argv: Vec<OsString> = ["stdout-rotator", "--config=a.toml", "-m", "2"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        assert_eq!(config_flag(&argv), Some(OsString::from("a.toml")));
+    }
+
+    #[test]
+    fn invalid_config_files_are_rejected() {
+        let directory = tempfile::tempdir().unwrap();
+        let command = command();
+        for (name, content) in [
+            ("unknown.toml", "no_such_setting = 1\n"),
+            ("table.toml", "[rotation]\nmax_history = 3\n"),
+            ("duplicate.toml", "max_history = 3\nmax_history = 4\n"),
+            ("unquoted.toml", "log_config = log4rs.yaml\n"),
+            ("list.yaml", "- max_history\n"),
+        ] {
+            let path = directory.path().join(name);
+            fs::write(&path, content).unwrap();
+            let argv = vec![
+                OsString::from("stdout-rotator"),
+                OsString::from(CONFIG_FLAG),
+                path.into_os_string(),
+            ];
+            let result = with_environment(&[], || with_config_file(&command, argv));
+            assert!(result.is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn unknown_environment_variables_are_rejected() {
+        let command = command();
+        with_environment(&[("STDOUT_ROTATOR_MAX_HISTORY", "3")], || {
+            assert!(check_environment(&command).is_ok());
+        });
+        with_environment(&[("STDOUT_ROTATOR_MAX_HISTROY", "3")], || {
+            assert!(check_environment(&command).is_err());
+        });
+    }
+
+    #[test]
+    fn the_variables_of_the_hooks_are_accepted() {
+        let command = command();
+        with_environment(&[("STDOUT_ROTATOR_FILE", "out.log.1")], || {
+            assert!(check_environment(&command).is_ok());
+        });
+    }
+
+    #[test]
+    fn subcommands_read_the_environment() {
+        let settings = with_environment(
+            &[
+                ("STDOUT_ROTATOR_OUTPUT_FILE", "out.log"),
+                ("STDOUT_ROTATOR_ROTATION_DIRECTORY", "archive"),
+            ],
+            || {
+                assert!(check_environment(&command()).is_ok());
+                matches(None, &["clean", "--dry-run"])
+            },
+        );
+        let (name, clean) = settings.subcommand().unwrap();
+        assert_eq!(name, "clean");
+        assert_eq!(
+            clean.get_one::<String>("output_file").map(String::as_str),
+            Some("out.log")
+        );
+        assert_eq!(
+            clean
+                .get_one::<String>("rotation_directory")
+                .map(String::as_str),
+            Some("archive")
+        );
+    }
+}
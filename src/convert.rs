@@ -0,0 +1,136 @@
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use stdout_rotator::pipeline::{Record, Stage};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conversion {
+    LogfmtToJson,
+    JsonToLogfmt,
+}
+
+pub struct ConvertStage {
+    conversion: Conversion,
+}
+
+impl ConvertStage {
+    pub fn new(conversion: Conversion) -> ConvertStage {
+        ConvertStage { conversion }
+    }
+}
+
+impl Stage for ConvertStage {
+    fn apply(&mut self, record: Record) -> Option<Record> {
+        let text = String::from_utf8_lossy(&record.data);
+        let converted = match self.conversion {
+            Conversion::LogfmtToJson => parse_logfmt(text.trim_end_matches('\r'))
+                .map(|pairs| logfmt_to_json(pairs).to_string()),
+            Conversion::JsonToLogfmt => match serde_json::from_str::<Value>(&text) {
+                Ok(Value::Object(entries)) => Some(json_to_logfmt(&entries)),
+                _ => None,
+            },
+        };
+        match converted {
+            Some(converted) => Some(record.with_data(converted.into_bytes())),
+            None => Some(record),
+        }
+    }
+}
+
+pub fn parse_logfmt(line: &str) -> Option<Vec<(String, String)>> {
+    let mut pairs = vec![];
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && !c.is_whitespace()) {
+            if c == '"' {
+                return None;
+            }
+            key.push(c);
+        }
+        if key.is_empty() || chars.next() != Some('=') {
+            return None;
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            let mut closed = false;
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => {
+                        closed = true;
+                        break;
+                    }
+                    '\\' => match chars.next()? {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        'r' => value.push('\r'),
+                        escaped => value.push(escaped),
+                    },
+                    c => value.push(c),
+                }
+            }
+            if !closed || chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                return None;
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+        pairs.push((key, value));
+    }
+    if pairs.is_empty() {
+        None
+    } else {
+        Some(pairs)
+    }
+}
+
+fn logfmt_to_json(pairs: Vec<(String, String)>) -> Value {
+    let mut entries = Map::new();
+    for (key, value) in pairs {
+        entries.insert(key, Value::String(value));
+    }
+    Value::Object(entries)
+}
+
+pub fn json_to_logfmt(entries: &Map<String, Value>) -> String {
+    entries
+        .iter()
+        .map(|(key, value)| {
+            let rendered = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            format!("{}={}", key, quote_logfmt(&rendered))
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn quote_logfmt(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '=' || c == '"' || c == '\\');
+    if !needs_quotes {
+        return value.to_string();
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,42 @@
+use std::fs::File;
+use std::io;
+
+#[cfg(target_os = "linux")]
+pub fn copy_file(source: &mut File, target: &mut File) -> io::Result<u64> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut copied = 0u64;
+    loop {
+        let result = unsafe {
+            libc::copy_file_range(
+                source.as_raw_fd(),
+                std::ptr::null_mut(),
+                target.as_raw_fd(),
+                std::ptr::null_mut(),
+                1 << 30,
+                0,
+            )
+        };
+        if result == 0 {
+            return Ok(copied);
+        }
+        if result > 0 {
+            copied += result as u64;
+            continue;
+        }
+        let error = io::Error::last_os_error();
+        match error.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            // Not supported between these files: the offsets are untouched, copy the rest in userspace
+            Some(libc::ENOSYS | libc::EXDEV | libc::EINVAL | libc::EOPNOTSUPP | libc::EPERM) => {
+                return Ok(copied + io::copy(source, target)?)
+            }
+            _ => return Err(error),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn copy_file(source: &mut File, target: &mut File) -> io::Result<u64> {
+    io::copy(source, target)
+}
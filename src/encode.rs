@@ -0,0 +1,149 @@
+use clap::ValueEnum;
+use serde_json::{Map, Number, Value};
+use std::time::UNIX_EPOCH;
+use stdout_rotator::host::hostname;
+use stdout_rotator::pipeline::{Record, Stage};
+
+use crate::sinks::timestamp;
+
+pub const APP_NAME: &str = "stdout-rotator";
+const STRUCTURED_DATA_ID: &str = "fields@32473";
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Raw,
+    Json,
+    #[value(name = "syslog-5424")]
+    Syslog5424,
+    Gelf,
+}
+
+pub fn severity(level: Option<&str>) -> u8 {
+    match level {
+        Some("FATAL") => 0,
+        Some("CRITICAL") => 2,
+        Some("ERROR") => 3,
+        Some("WARN") => 4,
+        Some("NOTICE") => 5,
+        Some("DEBUG") | Some("TRACE") => 7,
+        _ => 6,
+    }
+}
+
+pub struct EncodeStage {
+    encoding: Encoding,
+    stream: String,
+}
+
+impl EncodeStage {
+    pub fn new(encoding: Encoding, stream: &str) -> EncodeStage {
+        EncodeStage {
+            encoding,
+            stream: stream.to_string(),
+        }
+    }
+}
+
+impl Stage for EncodeStage {
+    fn apply(&mut self, record: Record) -> Option<Record> {
+        let encoded = match self.encoding {
+            Encoding::Raw => return Some(record),
+            Encoding::Json => encode_json(&record, &self.stream),
+            Encoding::Syslog5424 => encode_syslog_5424(&record, 1, APP_NAME),
+            Encoding::Gelf => encode_gelf(&record),
+        };
+        Some(record.with_data(encoded.into_bytes()))
+    }
+}
+
+pub fn encode_json(record: &Record, stream: &str) -> String {
+    let mut entries = Map::new();
+    entries.insert("ts".to_string(), Value::String(timestamp(record.received)));
+    entries.insert("stream".to_string(), Value::String(stream.to_string()));
+    if let Some(level) = record.level() {
+        entries.insert("level".to_string(), Value::String(level));
+    }
+    entries.insert("msg".to_string(), Value::String(record.text()));
+    for (key, value) in &record.fields {
+        entries.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+    Value::Object(entries).to_string()
+}
+
+pub fn encode_syslog_5424(record: &Record, facility: u8, app_name: &str) -> String {
+    let priority = facility as u32 * 8 + severity(record.level().as_deref()) as u32;
+    let structured_data = if record.fields.is_empty() {
+        "-".to_string()
+    } else {
+        let parameters = record
+            .fields
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                format!("{}=\"{}\"", sd_name(key), escape_sd_value(&value))
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        format!("[{} {}]", STRUCTURED_DATA_ID, parameters)
+    };
+    format!(
+        "<{}>1 {} {} {} {} - {} {}",
+        priority,
+        timestamp(record.received),
+        hostname(),
+        app_name,
+        std::process::id(),
+        structured_data,
+        record.text()
+    )
+}
+
+fn sd_name(key: &str) -> String {
+    key.chars()
+        .filter(|c| c.is_ascii_graphic() && !matches!(c, '=' | ' ' | ']' | '"'))
+        .take(32)
+        .collect()
+}
+
+fn escape_sd_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn encode_gelf(record: &Record) -> String {
+    let seconds = record
+        .received
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default();
+    let mut entries = Map::new();
+    entries.insert("version".to_string(), Value::String("1.1".to_string()));
+    entries.insert("host".to_string(), Value::String(hostname().to_string()));
+    entries.insert("short_message".to_string(), Value::String(record.text()));
+    entries.insert(
+        "timestamp".to_string(),
+        Number::from_f64((seconds * 1000.0).round() / 1000.0)
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+    );
+    entries.insert(
+        "level".to_string(),
+        Value::from(severity(record.level().as_deref())),
+    );
+    for (key, value) in &record.fields {
+        if key == "id" {
+            continue;
+        }
+        entries.insert(format!("_{}", key), value.clone());
+    }
+    Value::Object(entries).to_string()
+}
@@ -0,0 +1,109 @@
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::{move_sidecars, RotatorError};
+
+const LOGGER: &str = "encryptor";
+const PARTIAL_EXTENSION: &str = ".partial";
+
+#[derive(Clone, Debug)]
+pub enum Recipient {
+    Age(String),
+    Gpg(String),
+}
+
+impl Recipient {
+    // age recipients are recognised by their prefix, anything else is a GPG key id or email
+    pub fn parse(value: &str) -> Result<Recipient, String> {
+        let value = value.trim();
+        let recipient = if let Some(key) = value.strip_prefix("age:") {
+            Recipient::Age(key.to_string())
+        } else if let Some(key) = value.strip_prefix("gpg:") {
+            Recipient::Gpg(key.to_string())
+        } else if value.starts_with("age1") {
+            Recipient::Age(value.to_string())
+        } else {
+            Recipient::Gpg(value.to_string())
+        };
+        match &recipient {
+            Recipient::Age(key) | Recipient::Gpg(key) if key.is_empty() => {
+                Err(format!("Missing key of recipient '{}'", value))
+            }
+            _ => Ok(recipient),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Recipient::Age(_) => ".age",
+            Recipient::Gpg(_) => ".gpg",
+        }
+    }
+
+    fn command(&self, source: &Path, target: &Path) -> Command {
+        let mut command = match self {
+            Recipient::Age(key) => {
+                let mut command = Command::new("age");
+                command.arg("--recipient").arg(key);
+                command
+            }
+            Recipient::Gpg(key) => {
+                let mut command = Command::new("gpg");
+                command
+                    .args(["--batch", "--yes", "--quiet", "--trust-model", "always"])
+                    .arg("--recipient")
+                    .arg(key)
+                    .arg("--encrypt");
+                command
+            }
+        };
+        command
+            .arg("--output")
+            .arg(target)
+            .arg(source)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null());
+        command
+    }
+}
+
+// Replaces the plain rotation with its encrypted copy, returning the path of the latter
+pub fn encrypt(recipient: &Recipient, source: &Path) -> Result<PathBuf, RotatorError> {
+    let mut target = source.as_os_str().to_owned();
+    target.push(recipient.extension());
+    let target = PathBuf::from(target);
+    let mut partial = target.as_os_str().to_owned();
+    partial.push(PARTIAL_EXTENSION);
+    let partial = PathBuf::from(partial);
+    debug!(target: LOGGER, "Encrypting '{}' to '{}'", source.display(), target.display());
+    let output = recipient.command(source, &partial).output().map_err(|op| {
+        format!(
+            "Error while running encryption of '{}': {}",
+            source.display(),
+            op
+        )
+    })?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&partial);
+        return Err(RotatorError::from(format!(
+            "Error while encrypting '{}': {} {}",
+            source.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    fs::rename(&partial, &target).map_err(|op| {
+        format!(
+            "Error while renaming '{}' to '{}': {}",
+            partial.display(),
+            target.display(),
+            op
+        )
+    })?;
+    move_sidecars(source, &target)?;
+    fs::remove_file(source)
+        .map_err(|op| format!("Error while removing '{}': {}", source.display(), op))?;
+    Ok(target)
+}
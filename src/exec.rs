@@ -0,0 +1,333 @@
+use log::{info, warn};
+use std::fs::File;
+use std::io;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use stdout_rotator::signals::{self, Signal, SignalCursor};
+use stdout_rotator::RotatorError;
+
+use crate::input::Input;
+
+const LOGGER: &str = "exec";
+const FORWARD_INTERVAL: Duration = Duration::from_millis(100);
+// Write ends of captured descriptors are kept from here up, clear of the descriptor numbers
+// they are moved to in the command
+const MIN_PARENT_FD: i32 = 64;
+
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct ExecArgs {
+    #[arg(
+        last = true,
+        value_name = "COMMAND",
+        help = "Command spawned to produce the input instead of reading standard input, e.g. 'stdout-rotator -- mycommand --flags'. stdout-rotator exits with the exit code of the command. SIGTERM, SIGINT and SIGHUP are forwarded to the process group of the command, whose output is read until it exits"
+    )]
+    command: Vec<String>,
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "command",
+        help = "Also capture the standard error of the spawned command, interleaved with its standard output"
+    )]
+    capture_stderr: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "command",
+        help = "Attach the output of the spawned command to a pseudo-terminal, so that programs checking isatty keep their coloured, line-buffered output"
+    )]
+    pty: bool,
+    #[arg(long, value_parser = FdCapture::parse, requires = "command", help = "Capture an additional file descriptor the spawned command writes to into its own rotated file, e.g. '3=audit.log'. Can be repeated")]
+    capture_fd: Vec<FdCapture>,
+}
+
+impl ExecArgs {
+    pub fn is_enabled(&self) -> bool {
+        !self.command.is_empty()
+    }
+
+    pub fn captured_fds(&self) -> &[FdCapture] {
+        &self.capture_fd
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FdCapture {
+    pub fd: i32,
+    pub output_file: String,
+}
+
+impl FdCapture {
+    pub fn parse(value: &str) -> Result<FdCapture, String> {
+        let (fd, output_file) = value
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid capture '{}', expected <fd>=<file>", value))?;
+        let fd = fd
+            .trim()
+            .parse::<i32>()
+            .ok()
+            .filter(|fd| (3..MIN_PARENT_FD).contains(fd))
+            .ok_or_else(|| {
+                format!(
+                    "Invalid descriptor '{}', expected a number from 3 to {}",
+                    fd,
+                    MIN_PARENT_FD - 1
+                )
+            })?;
+        if output_file.is_empty() {
+            return Err(format!("Missing file of capture '{}'", value));
+        }
+        Ok(FdCapture {
+            fd,
+            output_file: output_file.to_string(),
+        })
+    }
+}
+
+pub struct Streams {
+    pub stdout: Input,
+    pub stderr: Option<Input>,
+    pub descriptors: Vec<Input>,
+}
+
+pub struct Wrapped {
+    child: Child,
+    forwarder: (Sender<()>, JoinHandle<()>),
+}
+
+impl Wrapped {
+    pub fn spawn(
+        args: &ExecArgs,
+        separate_stderr: bool,
+    ) -> Result<(Wrapped, Streams), RotatorError> {
+        let mut command = Command::new(&args.command[0]);
+        command.args(&args.command[1..]).stdin(Stdio::inherit());
+        let stderr = if separate_stderr {
+            let (reader, writer) =
+                io::pipe().map_err(|op| format!("Error while creating pipe: {}", op))?;
+            command.stderr(writer);
+            Some(Input::Pipe(reader))
+        } else {
+            None
+        };
+        let mut descriptors = vec![];
+        let mut write_ends = vec![];
+        for capture in &args.capture_fd {
+            let (reader, writer) =
+                io::pipe().map_err(|op| format!("Error while creating pipe: {}", op))?;
+            let writer = pass_descriptor(&mut command, writer, capture.fd)
+                .map_err(|op| format!("Error while passing descriptor {}: {}", capture.fd, op))?;
+            write_ends.push(writer);
+            descriptors.push(Input::Pipe(reader));
+        }
+        let input = if args.pty {
+            let (master, terminal) =
+                open_pty().map_err(|op| format!("Error while opening pseudo-terminal: {}", op))?;
+            attach_terminal(&mut command, terminal, args.capture_stderr)
+                .map_err(|op| format!("Error while opening pseudo-terminal: {}", op))?;
+            Input::Terminal(master)
+        } else {
+            let (reader, writer) =
+                io::pipe().map_err(|op| format!("Error while creating pipe: {}", op))?;
+            if args.capture_stderr {
+                let stderr = writer
+                    .try_clone()
+                    .map_err(|op| format!("Error while creating pipe: {}", op))?;
+                command.stderr(stderr);
+            }
+            command.stdout(writer);
+            in_own_group(&mut command);
+            Input::Pipe(reader)
+        };
+        let child = command
+            .spawn()
+            .map_err(|op| format!("Error while spawning '{}': {}", args.command.join(" "), op))?;
+        // The command holds the write ends: dropping them lets the input end with the child
+        drop(command);
+        drop(write_ends);
+        info!(target: LOGGER, "Spawned '{}' with pid {}", args.command.join(" "), child.id());
+        let forwarder = start_signal_forwarder(child.id());
+        Ok((
+            Wrapped { child, forwarder },
+            Streams {
+                stdout: input,
+                stderr,
+                descriptors,
+            },
+        ))
+    }
+
+    pub fn wait(mut self) -> Result<i32, RotatorError> {
+        let status = self
+            .child
+            .wait()
+            .map_err(|op| format!("Error while waiting for the spawned command: {}", op))?;
+        let (txstop, handle) = self.forwarder;
+        drop(txstop);
+        handle
+            .join()
+            .map_err(|_| "Error on join of signal forwarder".to_string())?;
+        info!(target: LOGGER, "Spawned command exited with {}", status);
+        Ok(exit_code(status))
+    }
+}
+
+// The command leads its own process group, which receives the forwarded signals. With a
+// pseudo-terminal the new session already makes it the leader
+#[cfg(unix)]
+fn in_own_group(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    command.process_group(0);
+}
+
+#[cfg(not(unix))]
+fn in_own_group(_command: &mut Command) {}
+
+fn start_signal_forwarder(pid: u32) -> (Sender<()>, JoinHandle<()>) {
+    for signal in Signal::FORWARDED {
+        signals::install(*signal);
+    }
+    let (txstop, rxstop) = mpsc::channel::<()>();
+    let handle = thread::spawn(move || {
+        let mut cursor = SignalCursor::default();
+        while let Err(RecvTimeoutError::Timeout) = rxstop.recv_timeout(FORWARD_INTERVAL) {
+            while let Some(signal) = cursor.take(Signal::FORWARDED) {
+                info!(target: LOGGER, "Forwarding {} to the spawned command", signal);
+                forward(pid, signal);
+            }
+        }
+    });
+    (txstop, handle)
+}
+
+#[cfg(unix)]
+fn forward(pid: u32, signal: Signal) {
+    if unsafe { libc::kill(-(pid as libc::pid_t), signals::number(signal)) } != 0 {
+        warn!(target: LOGGER, "Error while forwarding {}: {}", signal, io::Error::last_os_error());
+    }
+}
+
+#[cfg(not(unix))]
+fn forward(_pid: u32, _signal: Signal) {}
+
+#[cfg(unix)]
+fn pass_descriptor(
+    command: &mut Command,
+    writer: io::PipeWriter,
+    target: i32,
+) -> io::Result<std::os::unix::io::OwnedFd> {
+    use std::os::unix::io::FromRawFd;
+    use std::os::unix::process::CommandExt;
+
+    let parent = unsafe { libc::fcntl(writer.as_raw_fd(), libc::F_DUPFD_CLOEXEC, MIN_PARENT_FD) };
+    if parent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let parent = unsafe { std::os::unix::io::OwnedFd::from_raw_fd(parent) };
+    let source = parent.as_raw_fd();
+    unsafe {
+        command.pre_exec(move || {
+            if libc::dup2(source, target) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        })
+    };
+    Ok(parent)
+}
+
+#[cfg(not(unix))]
+fn pass_descriptor(
+    _command: &mut Command,
+    _writer: io::PipeWriter,
+    _target: i32,
+) -> io::Result<io::PipeWriter> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "capturing descriptors is only available on unix",
+    ))
+}
+
+#[cfg(unix)]
+fn open_pty() -> io::Result<(File, File)> {
+    use std::os::unix::io::FromRawFd;
+
+    let mut master: libc::c_int = -1;
+    let mut terminal: libc::c_int = -1;
+    let result = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut terminal,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            std::ptr::null(),
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (master, terminal) = unsafe { (File::from_raw_fd(master), File::from_raw_fd(terminal)) };
+    // Keep line endings as written instead of translating them to CRLF
+    let mut attributes: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(terminal.as_raw_fd(), &mut attributes) } == 0 {
+        attributes.c_oflag &= !libc::OPOST;
+        unsafe { libc::tcsetattr(terminal.as_raw_fd(), libc::TCSANOW, &attributes) };
+    }
+    Ok((master, terminal))
+}
+
+#[cfg(unix)]
+fn attach_terminal(command: &mut Command, terminal: File, capture_stderr: bool) -> io::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    if capture_stderr {
+        command.stderr(terminal.try_clone()?);
+    }
+    command.stdout(terminal);
+    unsafe {
+        command.pre_exec(|| {
+            // A new session makes the pseudo-terminal the controlling terminal of the command
+            if libc::setsid() < 0 || libc::ioctl(1, libc::TIOCSCTTY as _, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        })
+    };
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn open_pty() -> io::Result<(File, File)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "pseudo-terminals are only available on unix",
+    ))
+}
+
+#[cfg(not(unix))]
+fn attach_terminal(
+    _command: &mut Command,
+    _terminal: File,
+    _capture_stderr: bool,
+) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn exit_code(status: ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+#[cfg(not(unix))]
+fn exit_code(status: ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
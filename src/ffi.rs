@@ -0,0 +1,166 @@
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::io::Write;
+use std::ptr;
+use std::sync::Mutex;
+
+use crate::builder::RotatorBuilder;
+use crate::compress::CompressionFormat;
+use crate::writer::RotatingWriter;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+// What C callers hold, locked so that several threads of theirs can write to it
+pub struct Rotator {
+    writer: Mutex<RotatingWriter>,
+}
+
+fn fail(msg: String) -> c_int {
+    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(msg));
+    -1
+}
+
+fn with_rotator(
+    rotator: *const Rotator,
+    write: impl FnOnce(&mut RotatingWriter) -> std::io::Result<()>,
+) -> c_int {
+    let Some(rotator) = (unsafe { rotator.as_ref() }) else {
+        return fail("Null rotator".to_string());
+    };
+    let mut writer = match rotator.writer.lock() {
+        Ok(writer) => writer,
+        Err(_) => return fail("Rotator poisoned by a panic".to_string()),
+    };
+    match write(&mut writer) {
+        Ok(()) => 0,
+        Err(op) => fail(op.to_string()),
+    }
+}
+
+// max_size is the command line default when 0 and max_history when negative, as no rotation can
+// be kept. compression is gzip, zstd, xz, lz4 or null
+#[no_mangle]
+pub extern "C" fn rotator_open(
+    output_file: *const c_char,
+    max_size: u64,
+    max_history: i64,
+    compression: *const c_char,
+) -> *mut Rotator {
+    if output_file.is_null() {
+        fail("Null output file".to_string());
+        return ptr::null_mut();
+    }
+    let output_file = unsafe { CStr::from_ptr(output_file) }.to_string_lossy();
+    let mut builder = RotatorBuilder::new(&output_file);
+    if max_size > 0 {
+        builder = builder.max_size(max_size);
+    }
+    if max_history >= 0 {
+        builder = builder.max_history(u32::try_from(max_history).unwrap_or(u32::MAX));
+    }
+    if !compression.is_null() {
+        let name = unsafe { CStr::from_ptr(compression) }.to_string_lossy();
+        match CompressionFormat::ALL
+            .iter()
+            .find(|format| format.name().eq_ignore_ascii_case(&name))
+        {
+            Some(format) => builder = builder.compression(*format),
+            None => {
+                fail(format!("Unknown compression '{}'", name));
+                return ptr::null_mut();
+            }
+        }
+    }
+    match builder.build() {
+        Ok(writer) => Box::into_raw(Box::new(Rotator {
+            writer: Mutex::new(writer),
+        })),
+        Err(err) => {
+            fail(err.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn rotator_write(rotator: *const Rotator, data: *const u8, length: usize) -> c_int {
+    if data.is_null() && length > 0 {
+        return fail("Null data".to_string());
+    }
+    let data = match length {
+        0 => &[][..],
+        _ => unsafe { std::slice::from_raw_parts(data, length) },
+    };
+    with_rotator(rotator, |writer| writer.write_all(data))
+}
+
+#[no_mangle]
+pub extern "C" fn rotator_flush(rotator: *const Rotator) -> c_int {
+    with_rotator(rotator, |writer| writer.flush())
+}
+
+// Flushes and frees the rotator, waiting for the compression of its last rotations
+#[no_mangle]
+pub extern "C" fn rotator_close(rotator: *mut Rotator) -> c_int {
+    if rotator.is_null() {
+        return 0;
+    }
+    let result = with_rotator(rotator, |writer| writer.flush());
+    drop(unsafe { Box::from_raw(rotator) });
+    result
+}
+
+// The error of the last call failing on this thread, valid until the next one fails
+#[no_mangle]
+pub extern "C" fn rotator_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |msg| msg.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(output_file: &str, max_history: i64) {
+        let output_file = CString::new(output_file).unwrap();
+        let rotator = rotator_open(output_file.as_ptr(), 10, max_history, ptr::null());
+        assert!(!rotator.is_null());
+        for _ in 0..3 {
+            let line = b"0123456789\n";
+            assert_eq!(rotator_write(rotator, line.as_ptr(), line.len()), 0);
+        }
+        assert_eq!(rotator_close(rotator), 0);
+    }
+
+    fn rotations(directory: &std::path::Path) -> usize {
+        fs::read_dir(directory)
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with("out.log.")
+            })
+            .count()
+    }
+
+    #[test]
+    fn a_zero_max_history_keeps_no_rotation() {
+        let directory = tempfile::tempdir().unwrap();
+        write(&directory.path().join("out.log").to_string_lossy(), 0);
+        assert_eq!(rotations(directory.path()), 0);
+    }
+
+    #[test]
+    fn a_negative_max_history_is_the_default() {
+        let directory = tempfile::tempdir().unwrap();
+        write(&directory.path().join("out.log").to_string_lossy(), -1);
+        assert!(rotations(directory.path()) > 0);
+    }
+}
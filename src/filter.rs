@@ -0,0 +1,75 @@
+use regex::bytes::Regex;
+use stdout_rotator::pipeline::{Record, Stage};
+
+pub struct FilterStage {
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+}
+
+impl FilterStage {
+    pub fn new(include: Option<Regex>, exclude: Option<Regex>) -> FilterStage {
+        FilterStage { include, exclude }
+    }
+}
+
+impl Stage for FilterStage {
+    fn apply(&mut self, record: Record) -> Option<Record> {
+        if let Some(include) = &self.include {
+            if !include.is_match(&record.data) {
+                return None;
+            }
+        }
+        match &self.exclude {
+            Some(exclude) if exclude.is_match(&record.data) => None,
+            _ => Some(record),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kept(stage: &mut FilterStage, lines: &[&str]) -> Vec<String> {
+        lines
+            .iter()
+            .filter_map(|line| stage.apply(Record::new(line.as_bytes().to_vec(), true)))
+            .map(|record| record.text())
+            .collect()
+    }
+
+    const LINES: &[&str] = &["INFO started", "DEBUG polling", "ERROR failed", "INFO done"];
+
+    #[test]
+    fn only_included_lines_pass() {
+        let mut stage = FilterStage::new(Some(Regex::new("^(INFO|ERROR)").unwrap()), None);
+        assert_eq!(
+            kept(&mut stage, LINES),
+            ["INFO started", "ERROR failed", "INFO done"]
+        );
+    }
+
+    #[test]
+    fn excluded_lines_are_dropped() {
+        let mut stage = FilterStage::new(None, Some(Regex::new("DEBUG").unwrap()));
+        assert_eq!(
+            kept(&mut stage, LINES),
+            ["INFO started", "ERROR failed", "INFO done"]
+        );
+    }
+
+    #[test]
+    fn exclusions_apply_to_included_lines() {
+        let mut stage = FilterStage::new(
+            Some(Regex::new("^INFO").unwrap()),
+            Some(Regex::new("done$").unwrap()),
+        );
+        assert_eq!(kept(&mut stage, LINES), ["INFO started"]);
+    }
+
+    #[test]
+    fn everything_passes_without_patterns() {
+        let mut stage = FilterStage::new(None, None);
+        assert_eq!(kept(&mut stage, LINES), LINES);
+    }
+}
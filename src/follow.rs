@@ -0,0 +1,134 @@
+use log::info;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LOGGER: &str = "follow";
+const FOLLOW_POLL: Duration = Duration::from_millis(250);
+
+// Device and inode, to tell a file recreated at the same path from the one being read
+#[cfg(unix)]
+fn identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+// Reads a file as it grows, as tail -F does: truncation starts over from its beginning and a
+// file recreated at the same path is read from its beginning once the previous one was read
+pub struct Follower {
+    path: PathBuf,
+    file: Option<File>,
+    identity: Option<(u64, u64)>,
+    position: u64,
+    // Where it was read up to when it was truncated, for readers catching up from its rotation
+    truncated: Option<u64>,
+}
+
+impl Follower {
+    pub fn open(path: &Path, from_start: bool) -> io::Result<Follower> {
+        let mut follower = Follower {
+            path: path.to_path_buf(),
+            file: None,
+            identity: None,
+            position: 0,
+            truncated: None,
+        };
+        match follower.reopen() {
+            Ok(()) if !from_start => {
+                if let Some(file) = follower.file.as_mut() {
+                    follower.position = file.seek(io::SeekFrom::End(0))?;
+                }
+            }
+            Ok(()) => {}
+            // Followed from its beginning once it is created
+            Err(op) if op.kind() == io::ErrorKind::NotFound => {
+                info!(target: LOGGER, "'{}' does not exist yet, waiting for it", path.display());
+            }
+            Err(op) => return Err(op),
+        }
+        Ok(follower)
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        let file = File::open(&self.path)?;
+        self.identity = identity(&file.metadata()?);
+        self.file = Some(file);
+        self.position = 0;
+        Ok(())
+    }
+
+    fn has_data(&self) -> bool {
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return false;
+        };
+        self.file.is_none()
+            || identity(&metadata) != self.identity
+            || metadata.len() != self.position
+    }
+
+    pub fn wait_readable(&self, timeout: Duration) -> io::Result<bool> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.has_data() {
+                return Ok(true);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            thread::sleep(FOLLOW_POLL.min(deadline - now));
+        }
+    }
+
+    pub fn take_truncation(&mut self) -> Option<u64> {
+        self.truncated.take()
+    }
+
+    // Continues from an offset of the file opened, e.g. a checkpoint of its index
+    pub fn seek(&mut self, offset: u64) -> io::Result<()> {
+        if let Some(file) = self.file.as_mut() {
+            self.position = file.seek(io::SeekFrom::Start(offset))?;
+        }
+        Ok(())
+    }
+
+    // Fails with WouldBlock rather than returning 0, as the end of the file is not the end of the
+    // input
+    pub fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(file) = self.file.as_mut() {
+            let read = file.read(buf)?;
+            if read > 0 {
+                self.position += read as u64;
+                return Ok(read);
+            }
+        }
+        match fs::metadata(&self.path) {
+            Ok(metadata) if self.file.is_none() || identity(&metadata) != self.identity => {
+                info!(target: LOGGER, "Following the new '{}'", self.path.display());
+                match self.reopen() {
+                    Err(op) if op.kind() != io::ErrorKind::NotFound => return Err(op),
+                    _ => {}
+                }
+            }
+            Ok(metadata) if metadata.len() < self.position => {
+                info!(target: LOGGER, "'{}' was truncated, following it from its beginning", self.path.display());
+                if let Some(file) = self.file.as_mut() {
+                    file.seek(io::SeekFrom::Start(0))?;
+                }
+                self.truncated = Some(self.position);
+                self.position = 0;
+            }
+            // A removed file is waited for until it is created again
+            Ok(_) | Err(_) => {}
+        }
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+}
@@ -0,0 +1,112 @@
+use clap::ValueEnum;
+use serde_json::Value;
+use stdout_rotator::pipeline::{Record, Stage};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Raw,
+    Json,
+    JsonPretty,
+    JsonColor,
+}
+
+pub struct FormatStage {
+    format: Format,
+}
+
+impl FormatStage {
+    pub fn new(format: Format) -> FormatStage {
+        FormatStage { format }
+    }
+}
+
+impl Stage for FormatStage {
+    fn apply(&mut self, record: Record) -> Option<Record> {
+        if self.format == Format::Raw {
+            return Some(record);
+        }
+        let value = match serde_json::from_slice::<Value>(&record.data) {
+            Ok(Value::Object(mut entries)) => {
+                for (key, field) in &record.fields {
+                    entries.insert(key.clone(), field.clone());
+                }
+                Value::Object(entries)
+            }
+            Ok(value @ Value::Array(_)) => value,
+            _ if !record.fields.is_empty() => {
+                let mut entries = record.fields.clone();
+                entries.insert(
+                    "message".to_string(),
+                    Value::String(String::from_utf8_lossy(&record.data).into_owned()),
+                );
+                Value::Object(entries)
+            }
+            _ => return Some(record),
+        };
+        let formatted = match self.format {
+            Format::Raw => return Some(record),
+            Format::Json => value.to_string(),
+            Format::JsonPretty => serde_json::to_string_pretty(&value).unwrap_or_default(),
+            Format::JsonColor => {
+                let mut output = String::new();
+                colorize(&value, 0, &mut output);
+                output
+            }
+        };
+        Some(record.with_data(formatted.into_bytes()))
+    }
+}
+
+const KEY_COLOR: &str = "\x1b[1;34m";
+const STRING_COLOR: &str = "\x1b[32m";
+const NUMBER_COLOR: &str = "\x1b[36m";
+const LITERAL_COLOR: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+fn colorize(value: &Value, indent: usize, output: &mut String) {
+    let padding = "  ".repeat(indent + 1);
+    let closing = "  ".repeat(indent);
+    match value {
+        Value::Null | Value::Bool(_) => {
+            output.push_str(LITERAL_COLOR);
+            output.push_str(&value.to_string());
+            output.push_str(RESET);
+        }
+        Value::Number(_) => {
+            output.push_str(NUMBER_COLOR);
+            output.push_str(&value.to_string());
+            output.push_str(RESET);
+        }
+        Value::String(_) => {
+            output.push_str(STRING_COLOR);
+            output.push_str(&value.to_string());
+            output.push_str(RESET);
+        }
+        Value::Array(items) if items.is_empty() => output.push_str("[]"),
+        Value::Array(items) => {
+            output.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                output.push_str(&padding);
+                colorize(item, indent + 1, output);
+                output.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+            }
+            output.push_str(&closing);
+            output.push(']');
+        }
+        Value::Object(entries) if entries.is_empty() => output.push_str("{}"),
+        Value::Object(entries) => {
+            output.push_str("{\n");
+            for (i, (key, item)) in entries.iter().enumerate() {
+                output.push_str(&padding);
+                output.push_str(KEY_COLOR);
+                output.push_str(&Value::String(key.clone()).to_string());
+                output.push_str(RESET);
+                output.push_str(": ");
+                colorize(item, indent + 1, output);
+                output.push_str(if i + 1 < entries.len() { ",\n" } else { "\n" });
+            }
+            output.push_str(&closing);
+            output.push('}');
+        }
+    }
+}
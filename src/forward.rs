@@ -0,0 +1,161 @@
+use flate2::read::MultiGzDecoder;
+use serde_json::{Map, Value};
+use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::msgpack::{decode, decode_all, DecodeError, Msgpack};
+use crate::sinks::timestamp;
+
+// Chunks of fluent-bit are a few megabytes at most, anything larger is not a forward client
+const MAX_PENDING: usize = 64 * 1024 * 1024;
+// The EventTime extension of the forward protocol, seconds and nanoseconds as big endian u32
+const EVENT_TIME: i8 = 0;
+
+// Decodes the messages of the Fluentd forward protocol, in its Message, Forward, PackedForward
+// and CompressedPackedForward modes, into one JSON line per event
+pub struct ForwardDecoder {
+    pending: Vec<u8>,
+    acks: Vec<Vec<u8>>,
+}
+
+impl ForwardDecoder {
+    pub fn new() -> ForwardDecoder {
+        ForwardDecoder {
+            pending: vec![],
+            acks: vec![],
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        self.pending.extend_from_slice(data);
+        let mut lines = vec![];
+        let mut start = 0;
+        loop {
+            match decode(&self.pending[start..]) {
+                Ok((message, used)) => {
+                    start += used;
+                    lines.extend(self.events(&message)?);
+                }
+                Err(DecodeError::Incomplete) => break,
+                Err(DecodeError::Invalid(msg)) => return Err(msg),
+            }
+        }
+        self.pending.drain(..start);
+        if self.pending.len() > MAX_PENDING {
+            return Err(format!("Message larger than {} bytes", MAX_PENDING));
+        }
+        Ok((!lines.is_empty()).then_some(lines))
+    }
+
+    // Responses to the messages asking for one, due once their events were passed on
+    pub fn take_acks(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.acks)
+    }
+
+    fn events(&mut self, message: &Msgpack) -> Result<Vec<u8>, String> {
+        let Msgpack::Array(fields) = message else {
+            return Err("Forward message is not an array".to_string());
+        };
+        let tag = fields
+            .first()
+            .and_then(Msgpack::as_str)
+            .ok_or("Forward message without a tag")?;
+        let mut lines = vec![];
+        let option = match fields.get(1) {
+            // Forward mode, [tag, [[time, record], ...], option]
+            Some(Msgpack::Array(entries)) => {
+                for entry in entries {
+                    lines.extend(event(&tag, entry)?);
+                }
+                fields.get(2)
+            }
+            // PackedForward mode, [tag, <concatenated [time, record]>, option], gzipped when
+            // the option says so
+            Some(Msgpack::Str(packed)) | Some(Msgpack::Bin(packed)) => {
+                let option = fields.get(2);
+                let compressed = option
+                    .and_then(|option| option.get("compressed"))
+                    .and_then(Msgpack::as_str);
+                let entries = match compressed.as_deref() {
+                    Some("gzip") => {
+                        let mut unpacked = vec![];
+                        MultiGzDecoder::new(&packed[..])
+                            .read_to_end(&mut unpacked)
+                            .map_err(|op| format!("Invalid compressed entries: {}", op))?;
+                        decode_all(&unpacked)?
+                    }
+                    Some(other) => return Err(format!("Unsupported compression '{}'", other)),
+                    None => decode_all(packed)?,
+                };
+                for entry in &entries {
+                    lines.extend(event(&tag, entry)?);
+                }
+                option
+            }
+            // Message mode, [tag, time, record, option]
+            Some(_) => {
+                let entry = Msgpack::Array(fields[1..fields.len().min(3)].to_vec());
+                lines.extend(event(&tag, &entry)?);
+                fields.get(3)
+            }
+            None => return Err("Forward message without events".to_string()),
+        };
+        if let Some(chunk) = option.and_then(|option| option.get("chunk")) {
+            if let Some(chunk) = chunk.as_bytes() {
+                self.acks.push(ack(chunk));
+            }
+        }
+        Ok(lines)
+    }
+}
+
+fn event(tag: &str, entry: &Msgpack) -> Result<Vec<u8>, String> {
+    let (time, record) = match entry {
+        Msgpack::Array(pair) if pair.len() == 2 => (&pair[0], &pair[1]),
+        _ => return Err("Forward entry is not a [time, record] pair".to_string()),
+    };
+    let mut line = Map::new();
+    line.insert(
+        "time".to_string(),
+        Value::String(timestamp(event_time(time)?)),
+    );
+    line.insert("tag".to_string(), Value::String(tag.to_string()));
+    match record.to_json() {
+        Value::Object(fields) => line.extend(fields),
+        _ => return Err("Forward record is not a map".to_string()),
+    }
+    let mut line = Value::Object(line).to_string().into_bytes();
+    line.push(b'\n');
+    Ok(line)
+}
+
+fn event_time(time: &Msgpack) -> Result<SystemTime, String> {
+    let since_epoch = match time {
+        Msgpack::UInt(seconds) => Duration::from_secs(*seconds),
+        Msgpack::Int(seconds) => Duration::from_secs((*seconds).max(0) as u64),
+        Msgpack::Float(seconds) => Duration::try_from_secs_f64(*seconds).unwrap_or_default(),
+        Msgpack::Ext(EVENT_TIME, bytes) if bytes.len() == 8 => {
+            let seconds = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            let nanoseconds = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+            Duration::new(u64::from(seconds), nanoseconds)
+        }
+        _ => return Err("Invalid time of forward entry".to_string()),
+    };
+    Ok(UNIX_EPOCH + since_epoch)
+}
+
+// {"ack": <chunk>}
+fn ack(chunk: &[u8]) -> Vec<u8> {
+    let mut response = vec![0x81, 0xa3];
+    response.extend_from_slice(b"ack");
+    match chunk.len() {
+        length @ 0..=31 => response.push(0xa0 | length as u8),
+        length @ 32..=255 => response.extend_from_slice(&[0xd9, length as u8]),
+        length => {
+            response.push(0xda);
+            response.extend_from_slice(&(length.min(u16::MAX as usize) as u16).to_be_bytes());
+        }
+    }
+    response.extend_from_slice(&chunk[..chunk.len().min(u16::MAX as usize)]);
+    response
+}
@@ -0,0 +1,153 @@
+use maxminddb::{geoip2, Reader};
+use serde_json::Value;
+use std::net::IpAddr;
+use stdout_rotator::pipeline::{Record, Stage};
+use stdout_rotator::RotatorError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Annotation {
+    Country,
+    Asn,
+}
+
+struct FieldEnrichment {
+    field: String,
+    annotations: Vec<Annotation>,
+}
+
+pub struct GeoIpStage {
+    country: Option<Reader<Vec<u8>>>,
+    asn: Option<Reader<Vec<u8>>>,
+    fields: Vec<FieldEnrichment>,
+}
+
+impl GeoIpStage {
+    pub fn new(databases: &[String], fields: &[String]) -> Result<GeoIpStage, RotatorError> {
+        let mut country = None;
+        let mut asn = None;
+        for database in databases {
+            let reader = Reader::open_readfile(database).map_err(|op| {
+                format!("Error while opening GeoIP database '{}': {}", database, op)
+            })?;
+            if reader.metadata.database_type.contains("ASN") {
+                asn = Some(reader);
+            } else {
+                country = Some(reader);
+            }
+        }
+        let mut available = vec![];
+        if country.is_some() {
+            available.push(Annotation::Country);
+        }
+        if asn.is_some() {
+            available.push(Annotation::Asn);
+        }
+        let fields = fields
+            .iter()
+            .map(|spec| parse_field(spec, &available))
+            .collect::<Result<Vec<_>, RotatorError>>()?;
+        for enrichment in &fields {
+            for annotation in &enrichment.annotations {
+                let available = match annotation {
+                    Annotation::Country => country.is_some(),
+                    Annotation::Asn => asn.is_some(),
+                };
+                if !available {
+                    return Err(RotatorError::new(&format!(
+                        "GeoIP field '{}' requires a {:?} database but none was provided",
+                        enrichment.field, annotation
+                    )));
+                }
+            }
+        }
+        Ok(GeoIpStage {
+            country,
+            asn,
+            fields,
+        })
+    }
+}
+
+fn parse_field(spec: &str, available: &[Annotation]) -> Result<FieldEnrichment, RotatorError> {
+    let (field, annotations) = match spec.split_once('=') {
+        None if available.is_empty() => {
+            return Err(RotatorError::new(&format!(
+                "GeoIP field '{}' requires at least one GeoIP database",
+                spec
+            )))
+        }
+        None => (spec, available.to_vec()),
+        Some((field, annotations)) => {
+            let annotations = annotations
+                .split(',')
+                .map(|annotation| match annotation.trim() {
+                    "country" => Ok(Annotation::Country),
+                    "asn" => Ok(Annotation::Asn),
+                    other => Err(RotatorError::new(&format!(
+                        "Unknown GeoIP annotation '{}' for field '{}', expected 'country' or 'asn'",
+                        other, field
+                    ))),
+                })
+                .collect::<Result<Vec<_>, RotatorError>>()?;
+            (field, annotations)
+        }
+    };
+    Ok(FieldEnrichment {
+        field: field.to_string(),
+        annotations,
+    })
+}
+
+impl Stage for GeoIpStage {
+    fn apply(&mut self, mut record: Record) -> Option<Record> {
+        for enrichment in &self.fields {
+            let address = match record.fields.get(&enrichment.field) {
+                Some(Value::String(value)) => value.parse::<IpAddr>().ok(),
+                _ => None,
+            };
+            let Some(address) = address else {
+                continue;
+            };
+            // Lookups of addresses missing from the database (private ranges, ...) are skipped
+            for annotation in &enrichment.annotations {
+                match annotation {
+                    Annotation::Country => {
+                        let iso_code = self
+                            .country
+                            .as_ref()
+                            .and_then(|reader| reader.lookup::<geoip2::Country>(address).ok())
+                            .and_then(|found| found.country)
+                            .and_then(|country| country.iso_code);
+                        if let Some(iso_code) = iso_code {
+                            record.fields.insert(
+                                format!("{}_country", enrichment.field),
+                                Value::String(iso_code.to_string()),
+                            );
+                        }
+                    }
+                    Annotation::Asn => {
+                        let found = self
+                            .asn
+                            .as_ref()
+                            .and_then(|reader| reader.lookup::<geoip2::Asn>(address).ok());
+                        let Some(found) = found else {
+                            continue;
+                        };
+                        if let Some(number) = found.autonomous_system_number {
+                            record
+                                .fields
+                                .insert(format!("{}_asn", enrichment.field), Value::from(number));
+                        }
+                        if let Some(organization) = found.autonomous_system_organization {
+                            record.fields.insert(
+                                format!("{}_as_org", enrichment.field),
+                                Value::String(organization.to_string()),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Some(record)
+    }
+}
@@ -0,0 +1,128 @@
+use regex::bytes::{Regex, RegexBuilder};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use stdout_rotator::index::{open_rotations_since, open_since};
+use stdout_rotator::naming::{Naming, NamingArgs};
+use stdout_rotator::{timestamp, RotatorError};
+
+use crate::ring::RingFile;
+
+#[derive(clap::Args, Debug)]
+pub struct GrepArgs {
+    #[arg(help = "Regular expression searched in every line")]
+    pattern: String,
+    #[arg(
+        help = "File searched after its rotations, oldest first. Matches are printed after the name of the file holding them"
+    )]
+    file: String,
+    #[arg(long, default_value = None, help = "Directory where rotated files are saved. If not provided, the same directory of the file will be used")]
+    rotation_directory: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
+    #[arg(
+        short,
+        long,
+        default_value_t = false,
+        help = "Match case insensitively"
+    )]
+    ignore_case: bool,
+    #[arg(
+        short = 'v',
+        long,
+        default_value_t = false,
+        help = "Print the lines not matching instead"
+    )]
+    invert_match: bool,
+    #[arg(
+        short,
+        long,
+        default_value_t = false,
+        help = "Only print the number of matching lines of every file"
+    )]
+    count: bool,
+    #[arg(long, value_parser = timestamp, help = "Only search content written after this time (RFC 3339 timestamp or duration ago), seeking with the index written with --index, or scanning the timestamps of the lines without one. Whole rotations are skipped after the write times recorded with --manifest, or their modification time. The index granularity can include some earlier lines")]
+    since: Option<SystemTime>,
+}
+
+fn open_active(path: &Path, since: Option<SystemTime>) -> Result<Box<dyn Read>, RotatorError> {
+    match RingFile::open_existing(&path.to_string_lossy())? {
+        Some(_) if since.is_some() => {
+            Err(RotatorError::new("--since is not supported for ring files"))
+        }
+        Some(mut ring) => {
+            let mut content = vec![];
+            ring.linearize(&mut content)?;
+            Ok(Box::new(Cursor::new(content)))
+        }
+        None => open_since(path, since, None),
+    }
+}
+
+fn search(
+    regex: &Regex,
+    args: &GrepArgs,
+    path: &Path,
+    reader: Box<dyn Read>,
+    output: &mut dyn Write,
+) -> Result<u64, RotatorError> {
+    let name = path.display();
+    let mut reader = BufReader::new(reader);
+    let mut line = vec![];
+    let mut matches = 0;
+    loop {
+        line.clear();
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|op| format!("Error while reading '{}': {}", name, op))?;
+        if read == 0 {
+            break;
+        }
+        let content = line.strip_suffix(b"\n").unwrap_or(&line);
+        if regex.is_match(content) == args.invert_match {
+            continue;
+        }
+        matches += 1;
+        if !args.count {
+            write!(output, "{}:", name)
+                .and_then(|_| output.write_all(content))
+                .and_then(|_| output.write_all(b"\n"))
+                .map_err(|op| format!("Error while printing matches: {}", op))?;
+        }
+    }
+    if args.count {
+        writeln!(output, "{}:{}", name, matches)
+            .map_err(|op| format!("Error while printing matches: {}", op))?;
+    }
+    Ok(matches)
+}
+
+// Exits with 1 when nothing matched, as grep does
+pub fn run(args: GrepArgs) -> Result<i32, RotatorError> {
+    let regex = RegexBuilder::new(&args.pattern)
+        .case_insensitive(args.ignore_case)
+        .build()
+        .map_err(|op| format!("Invalid pattern '{}': {}", args.pattern, op))?;
+    // Every file is opened first, so that an unreadable one fails before anything is printed
+    let mut files = open_rotations_since(
+        &args.file,
+        args.rotation_directory.as_deref(),
+        &Naming::new(&args.naming),
+        args.since,
+    )?;
+    let active = PathBuf::from(&args.file);
+    // Without the file nor rotations, opening it reports it missing
+    if active.exists() || (files.is_empty() && args.since.is_none()) {
+        let reader = open_active(&active, args.since)?;
+        files.push((active, reader));
+    }
+    let mut stdout = io::stdout().lock();
+    let mut matches = 0;
+    for (path, reader) in files {
+        matches += search(&regex, &args, &path, reader, &mut stdout)?;
+    }
+    stdout
+        .flush()
+        .map_err(|op| format!("Error while flushing stdout: {}", op))?;
+    Ok(if matches > 0 { 0 } else { 1 })
+}
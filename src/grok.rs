@@ -0,0 +1,368 @@
+use regex::Regex;
+use serde_json::{Number, Value};
+use std::collections::HashMap;
+use std::fs;
+use stdout_rotator::pipeline::{Record, Stage};
+use stdout_rotator::RotatorError;
+
+const MAX_DEPTH: usize = 32;
+
+const BUILTIN_PATTERNS: &[(&str, &str)] = &[
+    ("USERNAME", r"[a-zA-Z0-9._-]+"),
+    ("USER", r"%{USERNAME}"),
+    (
+        "EMAILLOCALPART",
+        r"[a-zA-Z0-9!#$%&'*+/=?^_`{|}~-]+(?:\.[a-zA-Z0-9!#$%&'*+/=?^_`{|}~-]+)*",
+    ),
+    ("EMAILADDRESS", r"%{EMAILLOCALPART}@%{HOSTNAME}"),
+    ("HTTPDUSER", r"%{EMAILADDRESS}|%{USER}"),
+    ("INT", r"[+-]?[0-9]+"),
+    ("BASE10NUM", r"[+-]?(?:[0-9]+(?:\.[0-9]+)?|\.[0-9]+)"),
+    ("NUMBER", r"%{BASE10NUM}"),
+    ("BASE16NUM", r"[+-]?(?:0x)?[0-9A-Fa-f]+"),
+    ("POSINT", r"\b[1-9][0-9]*\b"),
+    ("NONNEGINT", r"\b[0-9]+\b"),
+    ("WORD", r"\b\w+\b"),
+    ("NOTSPACE", r"\S+"),
+    ("SPACE", r"\s*"),
+    ("DATA", r".*?"),
+    ("GREEDYDATA", r".*"),
+    ("QUOTEDSTRING", r#""(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'"#),
+    ("QS", r"%{QUOTEDSTRING}"),
+    (
+        "UUID",
+        r"[A-Fa-f0-9]{8}-(?:[A-Fa-f0-9]{4}-){3}[A-Fa-f0-9]{12}",
+    ),
+    (
+        "IPV4",
+        r"(?:(?:25[0-5]|2[0-4][0-9]|[01]?[0-9]{1,2})\.){3}(?:25[0-5]|2[0-4][0-9]|[01]?[0-9]{1,2})",
+    ),
+    (
+        "IPV6",
+        r"(?:[0-9A-Fa-f]{0,4}:){2,7}[0-9A-Fa-f]{0,4}(?:%[0-9A-Za-z]+)?",
+    ),
+    ("IP", r"%{IPV6}|%{IPV4}"),
+    (
+        "HOSTNAME",
+        r"\b[0-9A-Za-z][0-9A-Za-z-]{0,62}(?:\.[0-9A-Za-z][0-9A-Za-z-]{0,62})*\.?\b",
+    ),
+    ("IPORHOST", r"%{IP}|%{HOSTNAME}"),
+    ("HOSTPORT", r"%{IPORHOST}:%{POSINT}"),
+    ("UNIXPATH", r"(?:/[^/\s]*)+"),
+    ("WINPATH", r"(?:[A-Za-z]+:|\\)(?:\\[^\\?*]*)+"),
+    ("PATH", r"%{UNIXPATH}|%{WINPATH}"),
+    ("URIPROTO", r"[A-Za-z][A-Za-z0-9+\-.]+"),
+    ("URIHOST", r"%{IPORHOST}(?::%{POSINT})?"),
+    ("URIPATH", r"(?:/[A-Za-z0-9$.+!*'(){},~:;=@#%&_\-]*)+"),
+    ("URIPARAM", r"\?[A-Za-z0-9$.+!*'|(){},~@#%&/=:;_?\-\[\]<>]*"),
+    ("URIPATHPARAM", r"%{URIPATH}(?:%{URIPARAM})?"),
+    (
+        "URI",
+        r"%{URIPROTO}://(?:%{USER}(?::[^@]*)?@)?(?:%{URIHOST})?(?:%{URIPATHPARAM})?",
+    ),
+    (
+        "MONTH",
+        r"\b(?:[Jj]an(?:uary)?|[Ff]eb(?:ruary)?|[Mm]ar(?:ch)?|[Aa]pr(?:il)?|[Mm]ay|[Jj]un(?:e)?|[Jj]ul(?:y)?|[Aa]ug(?:ust)?|[Ss]ep(?:tember)?|[Oo]ct(?:ober)?|[Nn]ov(?:ember)?|[Dd]ec(?:ember)?)\b",
+    ),
+    ("MONTHNUM", r"0?[1-9]|1[0-2]"),
+    ("MONTHDAY", r"0[1-9]|[12][0-9]|3[01]|[1-9]"),
+    (
+        "DAY",
+        r"Mon(?:day)?|Tue(?:sday)?|Wed(?:nesday)?|Thu(?:rsday)?|Fri(?:day)?|Sat(?:urday)?|Sun(?:day)?",
+    ),
+    ("YEAR", r"(?:\d\d){1,2}"),
+    ("HOUR", r"2[0123]|[01]?[0-9]"),
+    ("MINUTE", r"[0-5][0-9]"),
+    ("SECOND", r"(?:[0-5]?[0-9]|60)(?:[:.,][0-9]+)?"),
+    ("TIME", r"%{HOUR}:%{MINUTE}(?::%{SECOND})?"),
+    ("ISO8601_TIMEZONE", r"Z|[+-]%{HOUR}(?::?%{MINUTE})"),
+    (
+        "TIMESTAMP_ISO8601",
+        r"%{YEAR}-%{MONTHNUM}-%{MONTHDAY}[T ]%{HOUR}:?%{MINUTE}(?::?%{SECOND})?(?:%{ISO8601_TIMEZONE})?",
+    ),
+    ("HTTPDATE", r"%{MONTHDAY}/%{MONTH}/%{YEAR}:%{TIME} %{INT}"),
+    ("SYSLOGTIMESTAMP", r"%{MONTH} +%{MONTHDAY} %{TIME}"),
+    (
+        "LOGLEVEL",
+        r"[Aa]lert|ALERT|[Tt]race|TRACE|[Dd]ebug|DEBUG|[Nn]otice|NOTICE|[Ii]nfo|INFO|[Ww]arn(?:ing)?|WARN(?:ING)?|[Ee]rr(?:or)?|ERR(?:OR)?|[Cc]rit(?:ical)?|CRIT(?:ICAL)?|[Ff]atal|FATAL|[Ss]evere|SEVERE|EMERG(?:ENCY)?|[Ee]merg(?:ency)?",
+    ),
+    (
+        "COMMONAPACHELOG",
+        r#"%{IPORHOST:clientip} %{HTTPDUSER:ident} %{HTTPDUSER:auth} \[%{HTTPDATE:timestamp}\] "(?:%{WORD:verb} %{NOTSPACE:request}(?: HTTP/%{NUMBER:httpversion})?|%{DATA:rawrequest})" %{NUMBER:response:int} (?:%{NUMBER:bytes:int}|-)"#,
+    ),
+    (
+        "COMBINEDAPACHELOG",
+        r"%{COMMONAPACHELOG} %{QS:referrer} %{QS:agent}",
+    ),
+];
+
+#[derive(Clone, Copy, Debug)]
+enum FieldType {
+    String,
+    Int,
+    Float,
+}
+
+struct Capture {
+    group: String,
+    field: String,
+    field_type: FieldType,
+}
+
+pub struct GrokPattern {
+    regex: Regex,
+    captures: Vec<Capture>,
+}
+
+pub struct GrokLibrary {
+    patterns: HashMap<String, String>,
+}
+
+impl GrokLibrary {
+    pub fn new() -> GrokLibrary {
+        GrokLibrary {
+            patterns: BUILTIN_PATTERNS
+                .iter()
+                .map(|(name, pattern)| (name.to_string(), pattern.to_string()))
+                .collect(),
+        }
+    }
+
+    pub fn load_file(&mut self, path: &str) -> Result<(), RotatorError> {
+        let content = fs::read_to_string(path)
+            .map_err(|op| format!("Error while reading grok patterns from '{}': {}", path, op))?;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once(char::is_whitespace) {
+                Some((name, pattern)) => {
+                    self.patterns
+                        .insert(name.to_string(), pattern.trim().to_string());
+                }
+                None => {
+                    return Err(RotatorError::new(&format!(
+                        "Invalid grok pattern definition in '{}': '{}'",
+                        path, line
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn compile(&self, expression: &str) -> Result<GrokPattern, RotatorError> {
+        let mut captures = vec![];
+        let expanded = self.expand(expression, 0, &mut captures)?;
+        let regex = Regex::new(&expanded).map_err(|op| {
+            format!(
+                "Error while compiling grok expression '{}': {}",
+                expression, op
+            )
+        })?;
+        Ok(GrokPattern { regex, captures })
+    }
+
+    fn expand(
+        &self,
+        expression: &str,
+        depth: usize,
+        captures: &mut Vec<Capture>,
+    ) -> Result<String, RotatorError> {
+        if depth > MAX_DEPTH {
+            return Err(RotatorError::new(&format!(
+                "Grok expression '{}' is nested too deeply, possible recursive pattern",
+                expression
+            )));
+        }
+        let mut output = String::with_capacity(expression.len());
+        let mut rest = expression;
+        while let Some(start) = rest.find("%{") {
+            output.push_str(&rest[..start]);
+            let end = rest[start..].find('}').ok_or_else(|| {
+                RotatorError::new(&format!("Unterminated grok reference in '{}'", expression))
+            })? + start;
+            let reference = &rest[start + 2..end];
+            let mut parts = reference.splitn(3, ':');
+            let name = parts.next().unwrap_or_default();
+            let pattern = self
+                .patterns
+                .get(name)
+                .ok_or_else(|| RotatorError::new(&format!("Unknown grok pattern '{}'", name)))?;
+            let inner = self.expand(pattern, depth + 1, captures)?;
+            match parts.next() {
+                Some(field) => {
+                    let field_type = match parts.next() {
+                        None | Some("string") => FieldType::String,
+                        Some("int") => FieldType::Int,
+                        Some("float") => FieldType::Float,
+                        Some(other) => {
+                            return Err(RotatorError::new(&format!(
+                                "Unknown grok field type '{}' in '{}'",
+                                other, reference
+                            )))
+                        }
+                    };
+                    let group = format!("grok{}", captures.len());
+                    output.push_str(&format!("(?P<{}>{})", group, inner));
+                    captures.push(Capture {
+                        group,
+                        field: field.to_string(),
+                        field_type,
+                    });
+                }
+                None => output.push_str(&format!("(?:{})", inner)),
+            }
+            rest = &rest[end + 1..];
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+}
+
+pub struct GrokStage {
+    patterns: Vec<GrokPattern>,
+}
+
+impl GrokStage {
+    pub fn new(patterns: Vec<GrokPattern>) -> GrokStage {
+        GrokStage { patterns }
+    }
+}
+
+impl Stage for GrokStage {
+    fn apply(&mut self, mut record: Record) -> Option<Record> {
+        let text = String::from_utf8_lossy(&record.data).into_owned();
+        for pattern in &self.patterns {
+            let Some(found) = pattern.regex.captures(&text) else {
+                continue;
+            };
+            for capture in &pattern.captures {
+                if let Some(matched) = found.name(&capture.group) {
+                    record.fields.insert(
+                        capture.field.clone(),
+                        typed(matched.as_str(), capture.field_type),
+                    );
+                }
+            }
+            break;
+        }
+        Some(record)
+    }
+}
+
+fn typed(value: &str, field_type: FieldType) -> Value {
+    let converted = match field_type {
+        FieldType::String => None,
+        FieldType::Int => value.parse::<i64>().ok().map(Number::from),
+        FieldType::Float => value.parse::<f64>().ok().and_then(Number::from_f64),
+    };
+    converted
+        .map(Value::Number)
+        .unwrap_or_else(|| Value::String(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fields(expressions: &[&str], line: &str) -> Value {
+        let library = GrokLibrary::new();
+        let patterns = expressions
+            .iter()
+            .map(|expression| library.compile(expression).unwrap())
+            .collect();
+        let record = GrokStage::new(patterns)
+            .apply(Record::new(line.as_bytes().to_vec(), true))
+            .unwrap();
+        Value::Object(record.fields)
+    }
+
+    #[test]
+    fn named_references_are_captured_and_typed() {
+        assert_eq!(
+            fields(
+                &["%{IP:client} %{WORD:method} %{INT:status:int} %{NUMBER:took:float}s"],
+                "10.0.0.1 GET 200 0.25s"
+            ),
+            json!({"client": "10.0.0.1", "method": "GET", "status": 200, "took": 0.25})
+        );
+    }
+
+    #[test]
+    fn unnamed_references_are_not_captured() {
+        assert_eq!(
+            fields(&["%{LOGLEVEL} %{GREEDYDATA:message}"], "ERROR disk full"),
+            json!({"message": "disk full"})
+        );
+    }
+
+    #[test]
+    fn the_first_matching_pattern_wins() {
+        let expressions = ["^%{INT:code:int}$", "^%{WORD:word}", "%{GREEDYDATA:rest}"];
+        assert_eq!(fields(&expressions, "42"), json!({"code": 42}));
+        assert_eq!(fields(&expressions, "hello 42"), json!({"word": "hello"}));
+        assert_eq!(fields(&["^%{INT:code}$"], "nope"), json!({}));
+    }
+
+    #[test]
+    fn apache_logs_are_parsed() {
+        let parsed = fields(
+            &["%{COMBINEDAPACHELOG}"],
+            r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /a.gif HTTP/1.0" 200 2326 "http://example.com/" "curl/8""#,
+        );
+        assert_eq!(parsed["clientip"], "127.0.0.1");
+        assert_eq!(parsed["auth"], "frank");
+        assert_eq!(parsed["timestamp"], "10/Oct/2000:13:55:36 -0700");
+        assert_eq!(parsed["verb"], "GET");
+        assert_eq!(parsed["request"], "/a.gif");
+        assert_eq!(parsed["response"], 200);
+        assert_eq!(parsed["bytes"], 2326);
+        assert_eq!(parsed["agent"], "\"curl/8\"");
+    }
+
+    #[test]
+    fn values_that_do_not_convert_stay_strings() {
+        assert_eq!(typed("12", FieldType::Int), json!(12));
+        assert_eq!(typed("1.5", FieldType::Int), json!("1.5"));
+        assert_eq!(typed("1.5", FieldType::Float), json!(1.5));
+        assert_eq!(typed("x", FieldType::Float), json!("x"));
+        assert_eq!(typed("12", FieldType::String), json!("12"));
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        let library = GrokLibrary::new();
+        for expression in ["%{NOPE}", "%{INT:x:bool}", "%{INT", "%{INT:x}("] {
+            assert!(library.compile(expression).is_err(), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn patterns_are_loaded_from_files() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("patterns");
+        fs::write(
+            &path,
+            "# comment\n\nKEY [a-z]+\nPAIR %{KEY:key}=%{INT:value:int}\nLOOP %{LOOP}\n",
+        )
+        .unwrap();
+        let mut library = GrokLibrary::new();
+        library.load_file(path.to_str().unwrap()).unwrap();
+        let pattern = library.compile("%{PAIR}").unwrap();
+        let record = GrokStage::new(vec![pattern])
+            .apply(Record::new(b"size=3".to_vec(), true))
+            .unwrap();
+        assert_eq!(
+            Value::Object(record.fields),
+            json!({"key": "size", "value": 3})
+        );
+        // Recursive patterns fail instead of overflowing the stack
+        assert!(library.compile("%{LOOP}").is_err());
+
+        fs::write(&path, "NOPATTERN\n").unwrap();
+        assert!(library.load_file(path.to_str().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use log::{debug, error, warn};
+use std::path::Path;
+use std::process::Command;
+
+use crate::RotatorError;
+
+const LOGGER: &str = "hooks";
+const ALERT_KIND: &str = "STDOUT_ROTATOR_ALERT";
+const ALERT_MESSAGE: &str = "STDOUT_ROTATOR_ALERT_MESSAGE";
+const ROTATED_OUTPUT: &str = "STDOUT_ROTATOR_OUTPUT";
+const ROTATION_TRIGGER: &str = "STDOUT_ROTATOR_TRIGGER";
+const DELETED_FILE: &str = "STDOUT_ROTATOR_FILE";
+// Set for the commands run as hooks, which can run stdout-rotator themselves
+pub const HOOK_VARIABLES: &[&str] = &[
+    ALERT_KIND,
+    ALERT_MESSAGE,
+    ROTATED_OUTPUT,
+    ROTATION_TRIGGER,
+    DELETED_FILE,
+];
+
+// Single quoted for sh, where only the single quotes themselves need escaping
+fn quoted(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value)
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+pub fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Alerter {
+    command: Option<String>,
+}
+
+impl Alerter {
+    pub fn new(command: Option<String>) -> Alerter {
+        Alerter { command }
+    }
+
+    pub fn alert(&self, kind: &str, message: &str) {
+        warn!(target: LOGGER, "Alert {}: {}", kind, message);
+        let Some(command) = &self.command else {
+            return;
+        };
+        let status = shell(command)
+            .env(ALERT_KIND, kind)
+            .env(ALERT_MESSAGE, message)
+            .status();
+        match status {
+            Ok(status) if !status.success() => {
+                warn!(target: LOGGER, "Alert command exited with {}", status)
+            }
+            Ok(_) => {}
+            Err(err) => error!(target: LOGGER, "Error while running alert command: {}", err),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum HookFailure {
+    Abort,
+    Continue,
+}
+
+#[derive(Clone, Debug)]
+pub struct PreRotateHook {
+    pub command: String,
+    pub on_failure: HookFailure,
+}
+
+impl PreRotateHook {
+    pub fn run(&self, output_file: &str, trigger: &str) -> Result<(), RotatorError> {
+        debug!(target: LOGGER, "Running pre-rotation command for '{}'", output_file);
+        let status = shell(&self.command)
+            .env(ROTATED_OUTPUT, output_file)
+            .env(ROTATION_TRIGGER, trigger)
+            .status()
+            .map_err(|op| format!("Error while running pre-rotation command: {}", op))?;
+        if !status.success() {
+            return Err(RotatorError::from(format!(
+                "Pre-rotation command exited with {}",
+                status
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PreDeleteHook {
+    pub command: String,
+}
+
+impl PreDeleteHook {
+    pub fn run(&self, file: &Path) -> Result<(), RotatorError> {
+        let file = file.to_string_lossy();
+        debug!(target: LOGGER, "Running pre-deletion command for '{}'", file);
+        let status = shell(&self.command.replace("{file}", &quoted(&file)))
+            .env(DELETED_FILE, file.as_ref())
+            .status()
+            .map_err(|op| format!("Error while running pre-deletion command: {}", op))?;
+        if !status.success() {
+            return Err(RotatorError::from(format!(
+                "Pre-deletion command exited with {}",
+                status
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+use std::sync::OnceLock;
+
+pub fn hostname() -> &'static str {
+    static HOSTNAME: OnceLock<String> = OnceLock::new();
+    HOSTNAME.get_or_init(|| lookup_hostname().unwrap_or_else(|| "localhost".to_string()))
+}
+
+#[cfg(unix)]
+fn lookup_hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    let result =
+        unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
+    if result != 0 {
+        return None;
+    }
+    let end = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+    String::from_utf8(buffer[..end].to_vec()).ok()
+}
+
+#[cfg(not(unix))]
+fn lookup_hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
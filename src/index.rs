@@ -0,0 +1,485 @@
+use regex::Regex;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::compress::open_rotation;
+use crate::manifest::{Manifest, ManifestEntry};
+use crate::naming::Naming;
+use crate::timestamps::{line_time, TIMESTAMP_PATTERN};
+use crate::{all_rotations, is_plain_rotation, RotatorError};
+
+pub const INDEX_EXTENSION: &str = ".idx";
+const ENTRY_SIZE: usize = 16;
+
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(INDEX_EXTENSION);
+    PathBuf::from(sidecar)
+}
+
+pub struct IndexWriter {
+    file: File,
+    path: PathBuf,
+    every: u64,
+    last_offset: Option<u64>,
+}
+
+impl IndexWriter {
+    pub fn create(
+        output_file: &str,
+        every: u64,
+        append: bool,
+    ) -> Result<IndexWriter, RotatorError> {
+        let path = sidecar_path(Path::new(output_file));
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(!append)
+            .open(&path)
+            .map_err(|op| format!("Error during opening of index '{}', {}", path.display(), op))?;
+        file.seek(SeekFrom::End(0))
+            .map_err(|op| format!("Error while seeking index '{}': {}", path.display(), op))?;
+        Ok(IndexWriter {
+            file,
+            path,
+            every,
+            last_offset: None,
+        })
+    }
+
+    pub fn observe(&mut self, offset: u64, time: SystemTime) -> Result<(), RotatorError> {
+        if let Some(last) = self.last_offset {
+            if offset < last + self.every {
+                return Ok(());
+            }
+        }
+        let millis = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let mut entry = [0u8; ENTRY_SIZE];
+        entry[0..8].copy_from_slice(&millis.to_le_bytes());
+        entry[8..16].copy_from_slice(&offset.to_le_bytes());
+        self.file.write_all(&entry).map_err(|op| {
+            format!(
+                "Error while writing index '{}': {}",
+                self.path.display(),
+                op
+            )
+        })?;
+        self.last_offset = Some(offset);
+        Ok(())
+    }
+
+    pub fn rotate_to(&mut self, rotation: &Path) -> Result<(), RotatorError> {
+        let target = sidecar_path(rotation);
+        self.file.seek(SeekFrom::Start(0)).map_err(|op| {
+            format!(
+                "Error while seeking index '{}': {}",
+                self.path.display(),
+                op
+            )
+        })?;
+        let mut copy = File::create(&target).map_err(|op| {
+            format!(
+                "Error during opening of index '{}', {}",
+                target.display(),
+                op
+            )
+        })?;
+        std::io::copy(&mut self.file, &mut copy).map_err(|op| {
+            format!(
+                "Error while copying index to '{}': {}",
+                target.display(),
+                op
+            )
+        })?;
+        self.reset()
+    }
+
+    pub fn reset(&mut self) -> Result<(), RotatorError> {
+        self.file
+            .set_len(0)
+            .and_then(|_| self.file.seek(SeekFrom::Start(0)))
+            .map_err(|op| {
+                format!(
+                    "Error while truncating index '{}': {}",
+                    self.path.display(),
+                    op
+                )
+            })?;
+        self.last_offset = None;
+        Ok(())
+    }
+}
+
+pub struct Index {
+    entries: Vec<(SystemTime, u64)>,
+}
+
+impl Index {
+    pub fn load(indexed: &Path) -> Result<Option<Index>, RotatorError> {
+        let path = sidecar_path(indexed);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut content = vec![];
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut content))
+            .map_err(|op| format!("Error while reading index '{}': {}", path.display(), op))?;
+        let entries = content
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| {
+                let millis = u64::from_le_bytes(entry[0..8].try_into().unwrap());
+                let offset = u64::from_le_bytes(entry[8..16].try_into().unwrap());
+                (UNIX_EPOCH + Duration::from_millis(millis), offset)
+            })
+            .collect();
+        Ok(Some(Index { entries }))
+    }
+
+    pub fn offset_since(&self, since: SystemTime) -> u64 {
+        // Last checkpoint written before `since`: everything earlier can be skipped
+        self.entries
+            .iter()
+            .take_while(|(time, _)| *time <= since)
+            .last()
+            .map(|(_, offset)| *offset)
+            .unwrap_or_default()
+    }
+}
+
+// Rotations last written before `since` hold nothing newer
+pub fn written_before(
+    path: &Path,
+    since: SystemTime,
+    recorded: Option<&ManifestEntry>,
+) -> Result<bool, RotatorError> {
+    if let Some(last_write) = recorded.and_then(|entry| entry.last_write) {
+        return Ok(last_write < since);
+    }
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|op| {
+            format!(
+                "Error while reading metadata of '{}': {}",
+                path.display(),
+                op
+            )
+        })?;
+    Ok(modified < since)
+}
+
+// Opens a rotation at the last checkpoint of its index before `since`. Plain rotations are
+// seeked into, compressed ones are decompressed up to it, as their offsets are those of their
+// content
+pub fn open_since(
+    path: &Path,
+    since: Option<SystemTime>,
+    recorded: Option<&ManifestEntry>,
+) -> Result<Box<dyn Read>, RotatorError> {
+    let since = match since {
+        Some(since) => since,
+        None => return open_rotation(path),
+    };
+    // Rotations first written after `since` are read whole, with or without an index
+    if recorded
+        .and_then(|entry| entry.first_write)
+        .is_some_and(|first_write| first_write >= since)
+    {
+        return open_rotation(path);
+    }
+    let offset = match Index::load(path)? {
+        Some(index) => index.offset_since(since),
+        None => return Ok(Box::new(SinceReader::new(open_rotation(path)?, since))),
+    };
+    if is_plain_rotation(path) {
+        let mut file = File::open(path)
+            .map_err(|op| format!("Error during opening of '{}': {}", path.display(), op))?;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|op| format!("Error while seeking '{}': {}", path.display(), op))?;
+        return Ok(Box::new(file));
+    }
+    let mut reader = open_rotation(path)?;
+    io::copy(&mut (&mut reader).take(offset), &mut io::sink())
+        .map_err(|op| format!("Error while reading '{}': {}", path.display(), op))?;
+    Ok(reader)
+}
+
+// Without an index, the lines written before `since` are told apart by their timestamps. Lines
+// without one go with the line before them, and everything after the first newer line is kept
+pub struct SinceFilter {
+    since: SystemTime,
+    timestamp_regex: Regex,
+    pending: Vec<u8>,
+    older: bool,
+    passing: bool,
+}
+
+impl SinceFilter {
+    pub fn new(since: SystemTime) -> SinceFilter {
+        SinceFilter {
+            since,
+            timestamp_regex: Regex::new(TIMESTAMP_PATTERN).unwrap(),
+            pending: vec![],
+            older: false,
+            passing: false,
+        }
+    }
+
+    pub fn passing(&self) -> bool {
+        self.passing
+    }
+
+    pub fn feed(&mut self, data: &[u8]) -> Vec<u8> {
+        if self.passing {
+            return data.to_vec();
+        }
+        let mut kept = vec![];
+        let mut start = 0;
+        for (i, byte) in data.iter().enumerate() {
+            if *byte != b'\n' {
+                continue;
+            }
+            let mut line = std::mem::take(&mut self.pending);
+            line.extend_from_slice(&data[start..=i]);
+            start = i + 1;
+            if self.keeps(&line) {
+                kept.extend_from_slice(&line);
+            }
+            if self.passing {
+                kept.extend_from_slice(&data[start..]);
+                return kept;
+            }
+        }
+        self.pending.extend_from_slice(&data[start..]);
+        kept
+    }
+
+    pub fn finish(&mut self) -> Vec<u8> {
+        let line = std::mem::take(&mut self.pending);
+        match !line.is_empty() && self.keeps(&line) {
+            true => line,
+            false => vec![],
+        }
+    }
+
+    fn keeps(&mut self, line: &[u8]) -> bool {
+        match line_time(line, &self.timestamp_regex) {
+            Some(time) if time >= self.since => self.passing = true,
+            Some(_) => self.older = true,
+            None => {}
+        }
+        self.passing || !self.older
+    }
+}
+
+struct SinceReader {
+    reader: Box<dyn Read>,
+    filter: SinceFilter,
+    chunk: Vec<u8>,
+    kept: io::Cursor<Vec<u8>>,
+    finished: bool,
+}
+
+impl SinceReader {
+    fn new(reader: Box<dyn Read>, since: SystemTime) -> SinceReader {
+        SinceReader {
+            reader,
+            filter: SinceFilter::new(since),
+            chunk: vec![0u8; 64 * 1024],
+            kept: io::Cursor::new(vec![]),
+            finished: false,
+        }
+    }
+}
+
+impl Read for SinceReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.kept.read(buf)?;
+            if read > 0 || buf.is_empty() || self.finished {
+                return Ok(read);
+            }
+            if self.filter.passing() {
+                return self.reader.read(buf);
+            }
+            let read = self.reader.read(&mut self.chunk)?;
+            let kept = match read {
+                0 => {
+                    self.finished = true;
+                    self.filter.finish()
+                }
+                _ => self.filter.feed(&self.chunk[..read]),
+            };
+            self.kept = io::Cursor::new(kept);
+        }
+    }
+}
+
+pub type OpenedRotation = (PathBuf, Box<dyn Read>);
+
+// The rotations holding content written after `since`, oldest first, each opened at it. Every
+// rotation is opened first, so that an unreadable one fails before anything is read
+pub fn open_rotations_since(
+    output_file: &str,
+    rotation_directory: Option<&str>,
+    naming: &Naming,
+    since: Option<SystemTime>,
+) -> Result<Vec<OpenedRotation>, RotatorError> {
+    let manifest = Manifest::load(output_file)?;
+    let mut opened = vec![];
+    for rotation in all_rotations(output_file, rotation_directory, naming)? {
+        let recorded = manifest
+            .as_ref()
+            .and_then(|manifest| manifest.entry(&rotation));
+        if let Some(since) = since {
+            if written_before(&rotation, since, recorded)? {
+                continue;
+            }
+        }
+        let reader = open_since(&rotation, since, recorded)?;
+        opened.push((rotation, reader));
+    }
+    Ok(opened)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINES: &str =
+        "2024-01-01T10:00:00Z old\n  continued\n2024-01-01T12:00:00Z new\n  continued\nuntimed\n";
+
+    fn since() -> SystemTime {
+        humantime::parse_rfc3339("2024-01-01T11:00:00Z").unwrap()
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    fn write_index(output_file: &str) -> IndexWriter {
+        let mut writer = IndexWriter::create(output_file, 100, false).unwrap();
+        for (offset, seconds) in [(0, 10), (50, 20), (120, 30), (300, 40)] {
+            writer.observe(offset, at(seconds)).unwrap();
+        }
+        writer
+    }
+
+    #[test]
+    fn checkpoints_are_written_every_interval() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = directory.path().join("out.log");
+        write_index(output_file.to_str().unwrap());
+        let index = Index::load(&output_file).unwrap().unwrap();
+        assert_eq!(index.entries, [(at(10), 0), (at(30), 120), (at(40), 300)]);
+    }
+
+    #[test]
+    fn offsets_are_those_of_the_last_checkpoint_before_since() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = directory.path().join("out.log");
+        write_index(output_file.to_str().unwrap());
+        let index = Index::load(&output_file).unwrap().unwrap();
+        assert_eq!(index.offset_since(at(5)), 0);
+        assert_eq!(index.offset_since(at(29)), 0);
+        assert_eq!(index.offset_since(at(30)), 120);
+        assert_eq!(index.offset_since(at(100)), 300);
+    }
+
+    #[test]
+    fn rotated_indices_follow_their_rotation() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = directory.path().join("out.log");
+        let rotation = directory.path().join("out.log.1");
+        let mut writer = write_index(output_file.to_str().unwrap());
+        writer.rotate_to(&rotation).unwrap();
+        let rotated = Index::load(&rotation).unwrap().unwrap();
+        assert_eq!(rotated.offset_since(at(35)), 120);
+        assert!(Index::load(&output_file)
+            .unwrap()
+            .unwrap()
+            .entries
+            .is_empty());
+        // The first checkpoint after a rotation is written at once
+        writer.observe(10, at(50)).unwrap();
+        let current = Index::load(&output_file).unwrap().unwrap();
+        assert_eq!(current.entries, [(at(50), 10)]);
+        assert!(Index::load(&directory.path().join("out.log.2"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn indexed_rotations_are_read_from_their_checkpoint() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("out.log.1");
+        let mut writer = IndexWriter::create(path.to_str().unwrap(), 6, false).unwrap();
+        writer.observe(0, at(10)).unwrap();
+        writer.observe(6, at(20)).unwrap();
+        fs::write(
+            &path,
+            "first
+second
+",
+        )
+        .unwrap();
+        let mut content = String::new();
+        open_since(&path, Some(at(25)), None)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "second\n");
+    }
+
+    #[test]
+    fn lines_older_than_since_are_skipped() {
+        let mut filter = SinceFilter::new(since());
+        let mut kept = filter.feed(LINES.as_bytes());
+        kept.extend(filter.finish());
+        assert_eq!(
+            String::from_utf8(kept).unwrap(),
+            "2024-01-01T12:00:00Z new\n  continued\nuntimed\n"
+        );
+        assert!(filter.passing());
+    }
+
+    #[test]
+    fn lines_split_across_chunks_are_filtered_whole() {
+        let mut filter = SinceFilter::new(since());
+        let mut kept = vec![];
+        for chunk in LINES.as_bytes().chunks(7) {
+            kept.extend(filter.feed(chunk));
+        }
+        kept.extend(filter.finish());
+        assert_eq!(
+            String::from_utf8(kept).unwrap(),
+            "2024-01-01T12:00:00Z new\n  continued\nuntimed\n"
+        );
+    }
+
+    #[test]
+    fn lines_without_timestamps_are_kept() {
+        let mut filter = SinceFilter::new(since());
+        let mut kept = filter.feed(b"first\nsecond\nlast");
+        kept.extend(filter.finish());
+        assert_eq!(kept, b"first\nsecond\nlast");
+    }
+
+    #[test]
+    fn rotations_without_index_are_scanned() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("out.log.1");
+        fs::write(&path, LINES).unwrap();
+        let mut content = String::new();
+        open_since(&path, Some(since()), None)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "2024-01-01T12:00:00Z new\n  continued\nuntimed\n");
+    }
+}
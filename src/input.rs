@@ -0,0 +1,170 @@
+use log::debug;
+use std::fs::File;
+use std::io::{self, PipeReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::follow::Follower;
+use crate::merge::Merger;
+
+const LOGGER: &str = "input";
+
+// Input read without the buffering of std::io::Stdin, so that waiting for data never leaves
+// buffered bytes unaccounted for
+pub enum Input {
+    Stdin,
+    Pipe(PipeReader),
+    Terminal(File),
+    // Reopened whenever its last writer disconnects, so that it is read until shutdown
+    Fifo { path: PathBuf, file: File },
+    Follow(Follower),
+    Merged(Merger),
+}
+
+impl Input {
+    #[cfg(unix)]
+    pub fn fifo(path: &Path) -> io::Result<Input> {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::FileTypeExt;
+
+        match std::fs::metadata(path) {
+            Ok(metadata) if !metadata.file_type().is_fifo() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("'{}' is not a named pipe", path.display()),
+                ))
+            }
+            Ok(_) => {}
+            Err(op) if op.kind() == io::ErrorKind::NotFound => {
+                let name = std::ffi::CString::new(path.as_os_str().as_bytes())
+                    .map_err(|op| io::Error::new(io::ErrorKind::InvalidInput, op))?;
+                if unsafe { libc::mkfifo(name.as_ptr(), 0o660) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Err(op) => return Err(op),
+        }
+        Ok(Input::Fifo {
+            path: path.to_path_buf(),
+            file: open_fifo(path)?,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn fifo(_path: &Path) -> io::Result<Input> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "named pipes are only available on unix",
+        ))
+    }
+
+    // Spawned commands receive the shutdown signals themselves and are read until they exit
+    pub fn drains_on_shutdown(&self) -> bool {
+        matches!(
+            self,
+            Input::Stdin | Input::Fifo { .. } | Input::Follow(_) | Input::Merged(_)
+        )
+    }
+
+    #[cfg(unix)]
+    fn fd(&self) -> libc::c_int {
+        use std::os::unix::io::AsRawFd;
+
+        match self {
+            Input::Stdin => 0,
+            Input::Pipe(pipe) => pipe.as_raw_fd(),
+            Input::Terminal(master) => master.as_raw_fd(),
+            Input::Fifo { file, .. } => file.as_raw_fd(),
+            // Not backed by a descriptor which poll could wait for
+            Input::Follow(_) | Input::Merged(_) => -1,
+        }
+    }
+
+    // Whether a read would not block, end of file included
+    pub fn wait_readable(&mut self, timeout: Duration) -> io::Result<bool> {
+        match self {
+            Input::Follow(follower) => follower.wait_readable(timeout),
+            Input::Merged(merger) => merger.wait_readable(timeout),
+            _ => self.poll(timeout),
+        }
+    }
+
+    #[cfg(unix)]
+    fn poll(&self, timeout: Duration) -> io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) } {
+            -1 => {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    Ok(false)
+                } else {
+                    Err(error)
+                }
+            }
+            0 => Ok(false),
+            _ => Ok(true),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn poll(&self, _timeout: Duration) -> io::Result<bool> {
+        Ok(true)
+    }
+}
+
+impl Read for Input {
+    #[cfg(unix)]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Follow(follower) => return follower.read(buf),
+            Input::Merged(merger) => return merger.read(buf),
+            _ => {}
+        }
+        loop {
+            let read =
+                unsafe { libc::read(self.fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+            if let (0, Input::Fifo { path, file }) = (read, &mut *self) {
+                debug!(target: LOGGER, "Every writer of '{}' disconnected, reopening", path.display());
+                *file = open_fifo(path)?;
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            if read >= 0 {
+                return Ok(read as usize);
+            }
+            let error = io::Error::last_os_error();
+            match (&*self, error.raw_os_error()) {
+                // Reading a pseudo-terminal fails once every process closed its side
+                (Input::Terminal(_), Some(libc::EIO)) => return Ok(0),
+                _ if error.kind() == io::ErrorKind::Interrupted => {}
+                _ => return Err(error),
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Stdin => io::stdin().read(buf),
+            Input::Pipe(pipe) => pipe.read(buf),
+            Input::Terminal(master) => master.read(buf),
+            Input::Fifo { file, .. } => file.read(buf),
+            Input::Follow(follower) => follower.read(buf),
+            Input::Merged(merger) => merger.read(buf),
+        }
+    }
+}
+
+// Opened without blocking, as a blocking open would wait for the first writer
+#[cfg(unix)]
+fn open_fifo(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    File::options()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+}
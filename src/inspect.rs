@@ -0,0 +1,43 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use stdout_rotator::{duration, RotatorError};
+
+use crate::stats::{epoch_seconds, StatsStore};
+
+#[derive(clap::Args, Debug)]
+pub struct InspectArgs {
+    #[arg(long, help = "Statistics database written by a run with --stats-db")]
+    stats_db: String,
+    #[arg(long, default_value = "1d", value_parser = duration, help = "How far back the report goes")]
+    history: Duration,
+    #[arg(long, default_value = "1h", value_parser = duration, help = "Period aggregated in every row of the report")]
+    bucket: Duration,
+}
+
+pub fn run(args: InspectArgs) -> Result<(), RotatorError> {
+    let store = StatsStore::open(&args.stats_db)?;
+    let since = epoch_seconds(SystemTime::now() - args.history);
+    let rows = store.history(since, args.bucket.as_secs())?;
+    println!(
+        "{:<24} {:>16} {:>12} {:>10}",
+        "period", "bytes", "lines", "rotations"
+    );
+    let (mut bytes, mut lines, mut rotations) = (0, 0, 0);
+    for row in &rows {
+        let start = UNIX_EPOCH + Duration::from_secs(row.start);
+        println!(
+            "{:<24} {:>16} {:>12} {:>10}",
+            humantime::format_rfc3339_seconds(start).to_string(),
+            row.bytes,
+            row.lines,
+            row.rotations
+        );
+        bytes += row.bytes;
+        lines += row.lines;
+        rotations += row.rotations;
+    }
+    println!(
+        "{:<24} {:>16} {:>12} {:>10}",
+        "total", bytes, lines, rotations
+    );
+    Ok(())
+}
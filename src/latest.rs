@@ -0,0 +1,70 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::RotatorError;
+
+const LATEST_EXTENSION: &str = ".latest-rotation";
+
+pub fn latest_path(output_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}", output_file, LATEST_EXTENSION))
+}
+
+// Relative links keep working when the directory holding the output is moved or mounted elsewhere
+fn link_target(link: &Path, rotation: &Path) -> PathBuf {
+    let directory = link.parent().unwrap_or(Path::new(""));
+    match rotation.strip_prefix(directory) {
+        Ok(relative) => relative
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect(),
+        Err(_) => fs::canonicalize(rotation).unwrap_or_else(|_| rotation.to_path_buf()),
+    }
+}
+
+// The link is replaced by renaming a new one over it, so readers never find it missing
+pub fn point_latest(output_file: &str, rotation: &Path) -> Result<(), RotatorError> {
+    let link = latest_path(output_file);
+    let mut partial = link.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    let _ = fs::remove_file(&partial);
+    symlink(&link_target(&link, rotation), &partial)
+        .and_then(|_| fs::rename(&partial, &link))
+        .map_err(|op| {
+            RotatorError::from(format!(
+                "Error while pointing '{}' to '{}': {}",
+                link.display(),
+                rotation.display(),
+                op
+            ))
+        })
+}
+
+// Follows a rotation renamed after the link was created, e.g. by compression
+pub fn retarget_latest(
+    output_file: &str,
+    previous: &Path,
+    rotation: &Path,
+) -> Result<(), RotatorError> {
+    let link = latest_path(output_file);
+    match fs::read_link(&link) {
+        Ok(target) if target.file_name() == previous.file_name() => {
+            point_latest(output_file, rotation)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "symbolic links are only supported on unix",
+    ))
+}
@@ -0,0 +1,1382 @@
+mod appender;
+mod async_writer;
+#[doc(hidden)]
+pub mod audit;
+mod builder;
+#[doc(hidden)]
+pub mod checksum;
+#[doc(hidden)]
+pub mod clock;
+#[doc(hidden)]
+pub mod compress;
+mod copy;
+#[doc(hidden)]
+pub mod encrypt;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[doc(hidden)]
+pub mod hooks;
+#[doc(hidden)]
+pub mod host;
+#[doc(hidden)]
+pub mod index;
+#[doc(hidden)]
+pub mod latest;
+#[doc(hidden)]
+pub mod lock;
+#[doc(hidden)]
+pub mod manifest;
+#[doc(hidden)]
+pub mod metrics;
+#[doc(hidden)]
+pub mod naming;
+#[cfg(feature = "cli")]
+mod parquet_archive;
+#[doc(hidden)]
+pub mod permissions;
+#[doc(hidden)]
+pub mod pipeline;
+#[doc(hidden)]
+pub mod reload;
+#[doc(hidden)]
+pub mod retention;
+#[doc(hidden)]
+pub mod schedule;
+#[doc(hidden)]
+pub mod signals;
+#[doc(hidden)]
+pub mod space;
+#[doc(hidden)]
+pub mod sync;
+#[doc(hidden)]
+pub mod timestamps;
+#[doc(hidden)]
+pub mod upload;
+mod watch;
+#[doc(hidden)]
+pub mod webhook;
+mod writer;
+
+pub use appender::{RotatingAppender, SharedWriter};
+pub use async_writer::AsyncRotatingWriter;
+pub use builder::{BuildError, RotatorBuilder};
+pub use compress::CompressionFormat;
+pub use writer::{RotatingWriter, WriterOptions};
+
+use audit::AuditLog;
+use checksum::{rotation_checksum, write_checksum, CHECKSUM_EXTENSION};
+use compress::{compressor, start_compression_worker, CompressionJob};
+use copy::copy_file;
+use encrypt::{encrypt, Recipient};
+use hooks::{HookFailure, PreDeleteHook, PreRotateHook};
+use index::{IndexWriter, INDEX_EXTENSION};
+use latest::point_latest;
+use lock::OutputLock;
+use log::{self, debug, error, info, warn};
+use manifest::{update_manifest, Manifest};
+use metrics::Counters;
+use naming::{Naming, NamingArgs, Numbering};
+#[cfg(feature = "cli")]
+use parquet_archive::write_parquet;
+use parse_size::parse_size;
+use permissions::Access;
+use reload::Reloader;
+use retention::KeepPolicy;
+use schedule::CronSchedule;
+use serde_json::{json, Value};
+use signals::Signal;
+use space::{filesystem_space, FreeSpace};
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicU64;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+use sync::SyncPolicy;
+use upload::{start_uploader, Uploader};
+use watch::Watcher;
+use webhook::Webhook;
+
+const LOGGER: &str = "rotator";
+
+#[doc(hidden)]
+pub fn file_size(size: &str) -> Result<u64, String> {
+    parse_size(size).map_err(|op| format!("Error while parsing size: {}", op))
+}
+
+#[doc(hidden)]
+pub fn duration(value: &str) -> Result<Duration, String> {
+    humantime::parse_duration(value).map_err(|op| format!("Error while parsing duration: {}", op))
+}
+
+#[doc(hidden)]
+pub fn timestamp(value: &str) -> Result<SystemTime, String> {
+    if let Ok(time) = humantime::parse_rfc3339_weak(value) {
+        return Ok(time);
+    }
+    let ago = humantime::parse_duration(value).map_err(|_| {
+        format!(
+            "Error while parsing time '{}': expected an RFC 3339 timestamp or a duration",
+            value
+        )
+    })?;
+    Ok(SystemTime::now() - ago)
+}
+
+#[derive(Debug)]
+#[doc(hidden)]
+pub struct RotatorError {
+    pub msg: String,
+}
+
+impl RotatorError {
+    pub fn new(msg: &str) -> RotatorError {
+        RotatorError {
+            msg: msg.to_string(),
+        }
+    }
+}
+
+impl Display for RotatorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl From<String> for RotatorError {
+    fn from(value: String) -> Self {
+        RotatorError::new(&value)
+    }
+}
+
+#[doc(hidden)]
+pub struct RotationResult {
+    pub existing_rotated: Vec<PathBuf>,
+    pub next_rotation: PathBuf,
+}
+
+impl RotationResult {
+    pub fn new(existing_rotated: Vec<PathBuf>, next_rotation: PathBuf) -> RotationResult {
+        RotationResult {
+            existing_rotated,
+            next_rotation,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[doc(hidden)]
+pub enum ArchiveFormat {
+    Text,
+    // Written with the parquet crate, which only the command line depends on
+    #[cfg(feature = "cli")]
+    Parquet,
+}
+
+#[derive(Clone, Debug)]
+#[doc(hidden)]
+pub struct RotationConfig {
+    pub max_history: u32,
+    pub keep: Option<KeepPolicy>,
+    pub max_total_size: Option<u64>,
+    pub max_age: Option<Duration>,
+    pub min_free_space: Option<FreeSpace>,
+    pub max_size: u64,
+    pub strict_max_size: bool,
+    pub rotate_on_line_boundary: bool,
+    pub rotate_every: Option<Duration>,
+    pub rotate_cron: Option<CronSchedule>,
+    pub max_file_age: Option<Duration>,
+    pub max_lines: Option<u64>,
+    pub compression: Option<CompressionFormat>,
+    pub compression_level: Option<u32>,
+    pub compression_queue: Option<Sender<CompressionJob>>,
+    pub delay_compress: bool,
+    pub archive_format: ArchiveFormat,
+    pub index_every: Option<u64>,
+    pub checksum: bool,
+    pub manifest: bool,
+    pub encryption: Option<Recipient>,
+    pub access: Access,
+    pub sync_policy: SyncPolicy,
+    pub latest_symlink: bool,
+    pub upload: Option<Uploader>,
+    pub upload_queue: Option<Sender<PathBuf>>,
+    pub pre_rotate: Option<PreRotateHook>,
+    pub pre_delete: Option<PreDeleteHook>,
+    pub webhook: Option<Webhook>,
+    pub audit: Option<AuditLog>,
+    pub reloader: Option<Arc<Reloader>>,
+    // Shared by every writer, so that retention done before the pipelines start is counted too
+    pub counters: Arc<Counters>,
+    // Advanced by the file writer on every batch, including ticks, for the systemd watchdog
+    pub heartbeat: Arc<AtomicU64>,
+    pub output_file: String,
+    pub rotation_directory: Option<String>,
+    pub naming: Naming,
+    pub watch_external: bool,
+    pub append: bool,
+    pub rotate_on_start: bool,
+    pub rotate_on_shutdown: bool,
+}
+
+impl RotationConfig {
+    // The defaults of the command line, for the outputs configured without it
+    pub fn new(output_file: &str) -> RotationConfig {
+        RotationConfig {
+            max_history: 5,
+            keep: None,
+            max_total_size: None,
+            max_age: None,
+            min_free_space: None,
+            max_size: 50_000_000,
+            strict_max_size: false,
+            rotate_on_line_boundary: false,
+            rotate_every: None,
+            rotate_cron: None,
+            max_file_age: None,
+            max_lines: None,
+            compression: None,
+            compression_level: None,
+            compression_queue: None,
+            delay_compress: false,
+            archive_format: ArchiveFormat::Text,
+            index_every: None,
+            checksum: false,
+            manifest: false,
+            encryption: None,
+            access: Access::default(),
+            sync_policy: SyncPolicy::Never,
+            latest_symlink: false,
+            upload: None,
+            upload_queue: None,
+            pre_rotate: None,
+            pre_delete: None,
+            webhook: None,
+            audit: None,
+            reloader: None,
+            counters: Arc::new(Counters::default()),
+            heartbeat: Arc::default(),
+            output_file: output_file.to_string(),
+            rotation_directory: None,
+            naming: Naming::new(&NamingArgs::default()),
+            watch_external: false,
+            append: false,
+            rotate_on_start: false,
+            rotate_on_shutdown: false,
+        }
+    }
+
+    pub fn rotations_directory(&self) -> PathBuf {
+        rotations_directory(&self.output_file, self.rotation_directory.as_deref())
+    }
+
+    pub fn extension(&self) -> String {
+        format!(
+            "{}{}",
+            self.archive_extension(),
+            self.encryption_extension()
+        )
+    }
+
+    pub fn archive_extension(&self) -> &'static str {
+        match self.archive_format {
+            #[cfg(feature = "cli")]
+            ArchiveFormat::Parquet => ".parquet",
+            ArchiveFormat::Text => self
+                .compression
+                .map(|compression| compression.extension())
+                .unwrap_or(""),
+        }
+    }
+
+    pub fn encryption_extension(&self) -> &'static str {
+        self.encryption
+            .as_ref()
+            .map(|recipient| recipient.extension())
+            .unwrap_or("")
+    }
+}
+
+// An output file ready to be written, with the workers its rotations are handed to
+#[doc(hidden)]
+pub struct OpenOutput {
+    pub config: RotationConfig,
+    pub file: File,
+    pub active: ActiveFile,
+    pub index: Option<IndexWriter>,
+    pub watcher: Option<Watcher>,
+    pub workers: Workers,
+}
+
+// Stop once the configuration holding their queues is dropped and they handled what was queued
+#[doc(hidden)]
+pub struct Workers {
+    pub compression_worker: Option<JoinHandle<()>>,
+    pub uploader: Option<JoinHandle<()>>,
+}
+
+impl Workers {
+    pub fn join(self) {
+        if let Some(handle) = self.compression_worker {
+            if handle.join().is_err() {
+                error!(target: "file_writer", "Error on join of compression worker");
+            }
+        }
+        if let Some(handle) = self.uploader {
+            if handle.join().is_err() {
+                error!(target: "file_writer", "Error on join of uploader");
+            }
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn open_output(
+    config: RotationConfig,
+    counters: &Arc<Counters>,
+) -> Result<OpenOutput, RotatorError> {
+    let output = config.output_file.as_str();
+    if let Some(parent) = Path::new(output).parent() {
+        config.access.create_dirs(parent)?;
+    }
+
+    let mut file: File = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(output)
+        .map_err(|op| format!("Error during opening of target file '{}', {}", output, op))?;
+    let mut active = ActiveFile::new(SystemTime::now(), &config);
+    if config.append || config.rotate_on_start {
+        let length = file
+            .seek(io::SeekFrom::End(0))
+            .map_err(|op| format!("Error while seeking {}: {}", output, op))?;
+        if length > 0 {
+            info!(target: LOGGER, "Resuming the {} bytes of '{}'", length, output);
+            active = resumed_file(&mut file, &config)
+                .map_err(|op| format!("Error while reading existing file '{}', {}", output, op))?;
+        }
+        if config.rotate_on_start {
+            active.requested = Some(Trigger::Startup(length));
+        }
+    } else {
+        file.set_len(0)
+            .map_err(|op| format!("Error during truncate of file '{}', {}", output, op))?;
+    }
+    let mut index = config
+        .index_every
+        .map(|every| IndexWriter::create(output, every, config.append || config.rotate_on_start))
+        .transpose()?;
+    secure_output(&config)?;
+    let watcher = if config.watch_external {
+        Some(Watcher::start(
+            output,
+            config.rotation_directory.as_deref(),
+        )?)
+    } else {
+        None
+    };
+    let mut config = config;
+    let uploader = config.upload.clone().map(|uploader| {
+        let output = config.output_file.as_str();
+        info!(target: LOGGER, "Uploading rotations of '{}' to {:?}", output, uploader.remote);
+        // Renumbered rotations keep changing names, so the same remote name is uploaded again
+        if config.naming.renumbers() {
+            warn!(target: LOGGER, "Rotations of '{}' are renumbered, their uploads overwrite each other", output);
+        }
+        let (txupload, handle) = start_uploader(
+            uploader,
+            counters.clone(),
+            config.audit.clone(),
+            config.output_file.clone(),
+        );
+        config.upload_queue = Some(txupload);
+        handle
+    });
+    let compression_worker = match config.compression {
+        // Renumbered rotations are compressed inline so that no rename races the worker
+        Some(format) if !config.naming.renumbers() => {
+            let (txjob, handle) = start_compression_worker(counters.clone(), config.audit.clone());
+            sweep_uncompressed(&config, format, &txjob)?;
+            config.compression_queue = Some(txjob);
+            Some(handle)
+        }
+        _ => None,
+    };
+    if perform_rotation(&mut file, &config, index.as_mut(), &mut active)? {
+        counters.record_rotation();
+    }
+    Ok(OpenOutput {
+        config,
+        file,
+        active,
+        index,
+        watcher,
+        workers: Workers {
+            compression_worker,
+            uploader,
+        },
+    })
+}
+
+#[doc(hidden)]
+pub fn write_batch(
+    file: &mut File,
+    data: &[u8],
+    time: SystemTime,
+    config: &RotationConfig,
+    index: &mut Option<IndexWriter>,
+    active: &mut ActiveFile,
+    counters: &Counters,
+) -> Result<(), RotatorError> {
+    let mut remaining = data;
+    loop {
+        if perform_rotation(file, config, index.as_mut(), active)? {
+            counters.record_rotation();
+        }
+        if remaining.is_empty() {
+            return Ok(());
+        }
+        let offset = file
+            .stream_position()
+            .map_err(|op| format!("Error while reading position: {}", op))?;
+        if let Some(index) = index.as_mut() {
+            index.observe(offset, time)?;
+        }
+        let mut split = match config.max_lines {
+            Some(max_lines) => line_split(remaining, max_lines.saturating_sub(active.lines)),
+            None => remaining.len(),
+        };
+        // A postponed rotation leaves the file at its size limit, which the batch then goes over
+        if config.strict_max_size && offset < config.max_size {
+            split = split.min((config.max_size - offset) as usize);
+        }
+        let (segment, rest) = remaining.split_at(split);
+        file.write_all(segment)
+            .map_err(|op| format!("Error while writing to {}: {}", config.output_file, op))?;
+        active.lines += segment.iter().filter(|b| **b == b'\n').count() as u64;
+        active.last_write = SystemTime::now();
+        remaining = rest;
+    }
+}
+
+fn line_split(data: &[u8], lines: u64) -> usize {
+    if lines == 0 {
+        return data.len();
+    }
+    data.iter()
+        .enumerate()
+        .filter(|(_, b)| **b == b'\n')
+        .nth(lines as usize - 1)
+        .map(|(position, _)| position + 1)
+        .unwrap_or(data.len())
+}
+
+#[doc(hidden)]
+pub struct ActiveFile {
+    pub opened: SystemTime,
+    // Taken from the same clock as the opening time, as both are recorded in the manifest
+    pub last_write: SystemTime,
+    pub scheduled: Option<SystemTime>,
+    pub lines: u64,
+    pub requested: Option<Trigger>,
+    // Set when the pre-rotation command aborted a rotation, which is only retried after it
+    pub postponed: Option<Instant>,
+}
+
+impl ActiveFile {
+    pub fn new(opened: SystemTime, config: &RotationConfig) -> ActiveFile {
+        ActiveFile {
+            opened,
+            last_write: opened,
+            scheduled: config
+                .rotate_cron
+                .as_ref()
+                .and_then(|schedule| schedule.next_after(opened)),
+            lines: 0,
+            requested: None,
+            postponed: None,
+        }
+    }
+}
+
+fn resumed_file(file: &mut File, config: &RotationConfig) -> io::Result<ActiveFile> {
+    // The last write is the best estimate of the period the existing content belongs to
+    let modified = file.metadata()?.modified()?;
+    let mut active = ActiveFile::new(modified, config);
+    if config.max_lines.is_some() || config.manifest {
+        let position = file.stream_position()?;
+        file.seek(io::SeekFrom::Start(0))?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            active.lines += buffer[..read].iter().filter(|b| **b == b'\n').count() as u64;
+        }
+        file.seek(io::SeekFrom::Start(position))?;
+    }
+    Ok(active)
+}
+
+#[derive(Clone, Copy)]
+#[doc(hidden)]
+pub enum Trigger {
+    Startup(u64),
+    Signal(Signal),
+    Size(u64),
+    Interval(Duration),
+    Schedule(SystemTime),
+    Age(Duration),
+    Lines(u64),
+}
+
+impl Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Trigger::Startup(size) => write!(f, "Previous run left {} bytes", size),
+            Trigger::Signal(signal) => write!(f, "Received {}", signal),
+            Trigger::Size(size) => write!(f, "File size reached {} bytes", size),
+            Trigger::Interval(interval) => write!(
+                f,
+                "Rotation interval of {} elapsed",
+                humantime::format_duration(*interval)
+            ),
+            Trigger::Lines(lines) => write!(f, "File reached {} lines", lines),
+            Trigger::Age(age) => {
+                write!(f, "File age exceeded {}", humantime::format_duration(*age))
+            }
+            Trigger::Schedule(time) => write!(
+                f,
+                "Scheduled rotation time {} reached",
+                humantime::format_rfc3339_seconds(*time)
+            ),
+        }
+    }
+}
+
+fn interval_period(time: SystemTime, interval: Duration) -> u128 {
+    let elapsed = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    elapsed / interval.as_millis().max(1)
+}
+
+fn rotation_trigger(
+    position: u64,
+    config: &RotationConfig,
+    active: &ActiveFile,
+    now: SystemTime,
+) -> Option<Trigger> {
+    if active.requested.is_some() {
+        return active.requested;
+    }
+    if position > config.max_size || (config.strict_max_size && position >= config.max_size) {
+        return Some(Trigger::Size(position));
+    }
+    if let Some(max_lines) = config.max_lines {
+        if active.lines >= max_lines {
+            return Some(Trigger::Lines(active.lines));
+        }
+    }
+    if let Some(interval) = config.rotate_every {
+        if interval_period(now, interval) != interval_period(active.opened, interval) {
+            return Some(Trigger::Interval(interval));
+        }
+    }
+    if let Some(age) = config.max_file_age {
+        if now.duration_since(active.opened).unwrap_or_default() >= age {
+            return Some(Trigger::Age(age));
+        }
+    }
+    if let Some(scheduled) = active.scheduled {
+        if now >= scheduled {
+            return Some(Trigger::Schedule(scheduled));
+        }
+    }
+    None
+}
+
+fn sweep_uncompressed(
+    config: &RotationConfig,
+    format: CompressionFormat,
+    queue: &Sender<CompressionJob>,
+) -> Result<(), RotatorError> {
+    let existing = all_rotations(
+        &config.output_file,
+        config.rotation_directory.as_deref(),
+        &config.naming,
+    )?;
+    let newest = existing.last().cloned();
+    let plain: Vec<PathBuf> = existing
+        .into_iter()
+        .filter(|path| is_plain_rotation(path))
+        .filter(|path| !(config.delay_compress && Some(path) == newest.as_ref()))
+        .collect();
+    if !plain.is_empty() {
+        info!(target: LOGGER, "Compressing {} existing uncompressed rotations", plain.len());
+    }
+    for source in plain {
+        let mut target = source.as_os_str().to_owned();
+        target.push(format.extension());
+        queue
+            .send(CompressionJob {
+                source,
+                target: PathBuf::from(target),
+                format,
+                level: config.compression_level,
+                checksum: config.checksum,
+                encryption: config.encryption.clone(),
+                access: config.access,
+                latest: config.latest_symlink.then(|| config.output_file.clone()),
+                manifest: config.manifest.then(|| config.output_file.clone()),
+                upload_queue: config.upload_queue.clone(),
+            })
+            .map_err(|op| format!("Error while queueing compression: {}", op))?;
+    }
+    Ok(())
+}
+
+fn stage_rotation(
+    current_file: &mut File,
+    config: &RotationConfig,
+    next_rotation: &Path,
+    format: CompressionFormat,
+) -> Result<PathBuf, RotatorError> {
+    let output_file = config.output_file.as_str();
+    let next = next_rotation.to_str().unwrap();
+    let staged = PathBuf::from(next.strip_suffix(format.extension()).unwrap_or(next));
+    if fs::rename(output_file, &staged).is_ok() {
+        *current_file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(output_file)
+            .map_err(|op| {
+                format!(
+                    "Error during opening of target file '{}', {}",
+                    output_file, op
+                )
+            })?;
+        return Ok(staged);
+    }
+    // Renaming across filesystems fails, copy the rotation before truncating instead
+    let mut target = File::create(&staged)
+        .map_err(|op| format!("Error during opening of '{}': {}", staged.display(), op))?;
+    copy_file(current_file, &mut target).map_err(|op| {
+        format!(
+            "Error while copying {} to {}: {}",
+            output_file,
+            staged.display(),
+            op
+        )
+    })?;
+    current_file
+        .set_len(0)
+        .map_err(|op| format!("Error while truncating {}: {}", output_file, op))?;
+    current_file.seek(io::SeekFrom::Start(0)).map_err(|op| {
+        format!(
+            "Error while seeking to beginning of {}: {}",
+            output_file, op
+        )
+    })?;
+    Ok(staged)
+}
+
+fn notify(config: &RotationConfig, event: &str, details: Value) {
+    if let Some(audit) = &config.audit {
+        audit.record(event, details.clone());
+    }
+    if let Some(webhook) = &config.webhook {
+        webhook.notify(event, details);
+    }
+}
+
+fn notify_rotation(config: &RotationConfig, trigger: &Trigger, rotation: Option<&Path>) {
+    notify(
+        config,
+        "rotation",
+        json!({
+            "output_file": config.output_file,
+            "rotation": rotation.map(|rotation| rotation.to_string_lossy()),
+            "trigger": trigger.to_string(),
+        }),
+    );
+}
+
+fn notify_cleanup(config: &RotationConfig, reason: &str, removed: &[PathBuf]) {
+    if !removed.is_empty() {
+        notify(
+            config,
+            "cleanup",
+            json!({
+                "output_file": config.output_file,
+                "reason": reason,
+                "removed": removed.iter().map(|path| path.to_string_lossy()).collect::<Vec<_>>(),
+            }),
+        );
+    }
+}
+
+// Errors while the filesystem of the output file has no space left are reported as such
+#[doc(hidden)]
+pub fn notify_error(config: &RotationConfig, event: &str, error: &str) {
+    if config.webhook.is_none() && config.audit.is_none() {
+        return;
+    }
+    let directory = match Path::new(&config.output_file).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let event = match filesystem_space(directory) {
+        Ok((0, _)) => "disk_full",
+        _ => event,
+    };
+    notify(
+        config,
+        event,
+        json!({
+            "output_file": config.output_file,
+            "error": error,
+        }),
+    );
+}
+
+const PRE_ROTATE_RETRY: Duration = Duration::from_secs(30);
+
+#[doc(hidden)]
+pub fn perform_rotation(
+    current_file: &mut File,
+    config: &RotationConfig,
+    index: Option<&mut IndexWriter>,
+    active: &mut ActiveFile,
+) -> Result<bool, RotatorError> {
+    let output_file = config.output_file.as_str();
+    let current_position = current_file.stream_position().unwrap();
+    let now = SystemTime::now();
+    if current_position == 0 {
+        // Nothing was written yet: the file starts its epoch with its first line
+        *active = ActiveFile::new(now, config);
+        return Ok(false);
+    }
+    let trigger = match rotation_trigger(current_position, config, active, now) {
+        Some(trigger) => trigger,
+        None => return Ok(false),
+    };
+    if let Some(hook) = &config.pre_rotate {
+        if active.postponed.is_some_and(|until| Instant::now() < until) {
+            return Ok(false);
+        }
+        if let Err(err) = hook.run(output_file, &trigger.to_string()) {
+            if hook.on_failure == HookFailure::Abort {
+                warn!(target: LOGGER, "{}, postponing the rotation by {:?}", err.msg, PRE_ROTATE_RETRY);
+                active.postponed = Some(Instant::now() + PRE_ROTATE_RETRY);
+                return Ok(false);
+            }
+            warn!(target: LOGGER, "{}, rotating anyway", err.msg);
+        }
+    }
+    info!(target: LOGGER, "{}, rotating", trigger);
+    let lines = active.lines;
+    let written = (Some(active.opened), Some(active.last_write));
+    *active = ActiveFile::new(now, config);
+    let rotation_result = next_file(
+        &config.extension(),
+        output_file,
+        config.rotation_directory.as_deref(),
+        &config.naming,
+    )?;
+    if config.max_history == 0 {
+        cleanup_rotations(config.max_history, 0, config, &rotation_result)?;
+        if let Some(index) = index {
+            index.reset()?;
+        }
+        current_file
+            .set_len(0)
+            .map_err(|op| format!("Error while truncating {}: {}", output_file, op))?;
+        current_file.seek(io::SeekFrom::Start(0)).map_err(|op| {
+            format!(
+                "Error while seeking to beginning of {}: {}",
+                output_file, op
+            )
+        })?;
+        record_manifest(config, |manifest| manifest.written += current_position)?;
+        notify_rotation(config, &trigger, None);
+        return Ok(true);
+    }
+    // The rotation about to be created counts towards the size budget with its uncompressed size
+    cleanup_rotations(
+        config.max_history - 1,
+        current_position,
+        config,
+        &rotation_result,
+    )?;
+    let rotation_result = if config.naming.renumbers() {
+        renumber_rotations(config)?;
+        next_file(
+            &config.extension(),
+            output_file,
+            config.rotation_directory.as_deref(),
+            &config.naming,
+        )?
+    } else {
+        rotation_result
+    };
+    current_file
+        .flush()
+        .map_err(|op| format!("Error while flushing {}: {}", output_file, op))?;
+    current_file.seek(io::SeekFrom::Start(0)).map_err(|op| {
+        format!(
+            "Error while seeking to beginning of {}: {}",
+            output_file, op
+        )
+    })?;
+    // Encryption applies last, to the archived rotation
+    let next = rotation_result.next_rotation.to_str().unwrap();
+    let archived = PathBuf::from(
+        next.strip_suffix(config.encryption_extension())
+            .unwrap_or(next),
+    );
+    if let (Some(format), Some(queue)) = (config.compression, &config.compression_queue) {
+        let staged = stage_rotation(current_file, config, &archived, format)?;
+        if let Some(index) = index {
+            index.rotate_to(&staged)?;
+        }
+        secure_output(config)?;
+        secure_rotation(&config.access, &staged)?;
+        // The rotation stays uncompressed until the next one
+        if config.delay_compress && config.checksum {
+            write_checksum(&staged)?;
+        }
+        if config.latest_symlink {
+            point_latest(output_file, &staged)?;
+        }
+        // The checksum of a rotation compressed right away is recorded by the compression
+        let sha256 = match config.manifest && config.delay_compress {
+            true => Some(rotation_checksum(&staged)?),
+            false => None,
+        };
+        record_manifest(config, |manifest| {
+            manifest.push(&staged, current_position, lines, written, sha256)
+        })?;
+        let pending = if config.delay_compress {
+            // The previous rotation was kept uncompressed until now, unless the cleanup removed it
+            rotation_result
+                .existing_rotated
+                .last()
+                .filter(|path| is_plain_rotation(path) && path.exists())
+                .cloned()
+        } else {
+            Some(staged)
+        };
+        if let Some(source) = pending {
+            let mut target = source.as_os_str().to_owned();
+            target.push(format.extension());
+            queue
+                .send(CompressionJob {
+                    source,
+                    target: PathBuf::from(target),
+                    format,
+                    level: config.compression_level,
+                    checksum: config.checksum,
+                    encryption: config.encryption.clone(),
+                    access: config.access,
+                    latest: config.latest_symlink.then(|| config.output_file.clone()),
+                    manifest: config.manifest.then(|| config.output_file.clone()),
+                    upload_queue: config.upload_queue.clone(),
+                })
+                .map_err(|op| format!("Error while queueing compression: {}", op))?;
+        }
+        notify_rotation(config, &trigger, Some(&archived));
+        return Ok(true);
+    }
+    let mut target: File = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&archived)
+        .map_err(|op| {
+            format!(
+                "Error during opening of target file '{}', {}",
+                &archived.display(),
+                op
+            )
+        })?;
+    match (config.archive_format, config.compression) {
+        #[cfg(feature = "cli")]
+        (ArchiveFormat::Parquet, _) => {
+            write_parquet(&mut *current_file, target).map_err(|op| {
+                format!(
+                    "Error while archiving {} to {}: {}",
+                    output_file,
+                    &archived.display(),
+                    op
+                )
+            })?;
+        }
+        (_, Some(format)) => {
+            compressor(format, config.compression_level)?
+                .compress(&mut *current_file, target)
+                .map_err(|op| {
+                    format!(
+                        "Error while compressing {} to {}: {}",
+                        output_file,
+                        &archived.display(),
+                        op
+                    )
+                })?;
+        }
+        (_, None) => {
+            copy_file(current_file, &mut target).map_err(|op| {
+                format!(
+                    "Error while copying {} to {}: {}",
+                    output_file,
+                    &archived.display(),
+                    op
+                )
+            })?;
+            target
+                .flush()
+                .map_err(|op| format!("Error while flushing file: {}", op))?;
+        }
+    }
+    if let Some(index) = index {
+        index.rotate_to(&archived)?;
+    }
+    let rotated = match &config.encryption {
+        Some(recipient) => encrypt(recipient, &archived)?,
+        None => archived,
+    };
+    if config.checksum {
+        write_checksum(&rotated)?;
+    }
+    secure_rotation(&config.access, &rotated)?;
+    if config.latest_symlink {
+        point_latest(output_file, &rotated)?;
+    }
+    if config.manifest {
+        let sha256 = rotation_checksum(&rotated)?;
+        record_manifest(config, |manifest| {
+            manifest.push(&rotated, current_position, lines, written, Some(sha256))
+        })?;
+    }
+    notify_rotation(config, &trigger, Some(&rotated));
+    if let Some(queue) = &config.upload_queue {
+        queue
+            .send(rotated)
+            .map_err(|op| format!("Error while queueing upload: {}", op))?;
+    }
+    current_file
+        .set_len(0)
+        .map_err(|op| format!("Error while truncating {}: {}", output_file, op))?;
+    current_file.seek(io::SeekFrom::Start(0)).map_err(|op| {
+        format!(
+            "Error while seeking to beginning of {}: {}",
+            output_file, op
+        )
+    })?;
+    Ok(true)
+}
+
+#[doc(hidden)]
+pub fn next_file(
+    extension: &str,
+    output_file: &str,
+    rotation_directory: Option<&str>,
+    naming: &Naming,
+) -> Result<RotationResult, RotatorError> {
+    let base_path = PathBuf::from(output_file);
+    let base_parent = base_path
+        .parent()
+        .unwrap()
+        .to_path_buf()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let base_parent = if base_parent.is_empty() {
+        ".".to_string()
+    } else {
+        base_parent
+    };
+    let parent = rotation_directory.unwrap_or(&base_parent);
+    log::debug!(target: LOGGER, "parent={}", &parent);
+    let paths = fs::read_dir(parent)
+        .map_err(|op| format!("Error while listing files of '{}': {}", &parent, op))?;
+    let base_name = base_path.file_name().unwrap().to_str().unwrap();
+    let mut names = vec![];
+    for path_result in paths {
+        let path = path_result
+            .map_err(|op| format!("Error while listing files of '{}': {}", parent, op))?;
+        names.push(path.file_name().to_str().unwrap().to_string());
+    }
+    // Rotations of every format share the naming and the retention
+    let scan = naming.scan(base_name, names, ROTATION_EXTENSIONS);
+    let mut output_path = PathBuf::from(&parent);
+    output_path.push(format!("{}{}", scan.next, extension));
+    let existing_rotated: Vec<PathBuf> = scan
+        .rotations
+        .iter()
+        .map(|name| PathBuf::from(&parent).join(name))
+        .collect();
+    log::debug!(target: LOGGER, "next_file={}, existing={:?}", &output_path.display(), &existing_rotated);
+    Ok(RotationResult::new(existing_rotated, output_path))
+}
+
+#[doc(hidden)]
+pub const ROTATION_EXTENSIONS: &[&str] = &[
+    "",
+    ".gz",
+    ".zst",
+    ".xz",
+    ".lz4",
+    ".parquet",
+    ".age",
+    ".gz.age",
+    ".zst.age",
+    ".xz.age",
+    ".lz4.age",
+    ".parquet.age",
+    ".gpg",
+    ".gz.gpg",
+    ".zst.gpg",
+    ".xz.gpg",
+    ".lz4.gpg",
+    ".parquet.gpg",
+];
+const SIDECAR_EXTENSIONS: &[&str] = &[INDEX_EXTENSION, CHECKSUM_EXTENSION];
+
+#[doc(hidden)]
+pub fn is_plain_rotation(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    !ROTATION_EXTENSIONS
+        .iter()
+        .any(|extension| !extension.is_empty() && name.ends_with(extension))
+}
+
+#[doc(hidden)]
+pub fn existing_sidecars(path: &Path) -> Vec<PathBuf> {
+    SIDECAR_EXTENSIONS
+        .iter()
+        .map(|extension| {
+            let mut sidecar = path.as_os_str().to_owned();
+            sidecar.push(extension);
+            PathBuf::from(sidecar)
+        })
+        .filter(|sidecar| sidecar.exists())
+        .collect()
+}
+
+fn remove_sidecars(path: &Path) -> Result<(), RotatorError> {
+    for sidecar in existing_sidecars(path) {
+        debug!(target: LOGGER, "Removing '{}'", sidecar.display());
+        fs::remove_file(&sidecar)
+            .map_err(|op| format!("Error while removing '{}': {}", sidecar.display(), op))?;
+    }
+    Ok(())
+}
+
+#[doc(hidden)]
+pub fn rotations_directory(output_file: &str, rotation_directory: Option<&str>) -> PathBuf {
+    match rotation_directory {
+        Some(directory) => PathBuf::from(directory),
+        None => match Path::new(output_file).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        },
+    }
+}
+
+#[doc(hidden)]
+pub fn all_rotations(
+    output_file: &str,
+    rotation_directory: Option<&str>,
+    naming: &Naming,
+) -> Result<Vec<PathBuf>, RotatorError> {
+    Ok(next_file("", output_file, rotation_directory, naming)?.existing_rotated)
+}
+
+#[doc(hidden)]
+pub fn renumber_rotations(config: &RotationConfig) -> Result<(), RotatorError> {
+    let base_name = Path::new(&config.output_file)
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let existing = all_rotations(
+        &config.output_file,
+        config.rotation_directory.as_deref(),
+        &config.naming,
+    )?;
+    // Oldest first renames every rotation to a free index: shifting starts from the highest
+    // index, compacting from the lowest
+    let mut renamed = vec![];
+    for (position, source) in existing.iter().enumerate() {
+        let name = source.file_name().unwrap().to_str().unwrap();
+        let index = match config.naming.index_of(base_name, name, ROTATION_EXTENSIONS) {
+            Some(index) => index,
+            None => continue,
+        };
+        let renumbered = match config.naming.numbering() {
+            Numbering::Increment => continue,
+            Numbering::Shift => index + 1,
+            Numbering::Compact => position as i64 + 1,
+        };
+        if renumbered == index {
+            continue;
+        }
+        let target = source.with_file_name(
+            config
+                .naming
+                .with_index(base_name, name, ROTATION_EXTENSIONS, renumbered)
+                .unwrap(),
+        );
+        rename_rotation(source, &target)?;
+        renamed.push((source.clone(), target));
+    }
+    if renamed.is_empty() {
+        return Ok(());
+    }
+    update_manifest(&config.output_file, false, |manifest| {
+        for (source, target) in &renamed {
+            manifest.rename(source, target);
+        }
+    })
+}
+
+// Rotations are only recorded with --manifest
+#[doc(hidden)]
+pub fn record_manifest(
+    config: &RotationConfig,
+    change: impl FnOnce(&mut Manifest),
+) -> Result<(), RotatorError> {
+    match config.manifest {
+        true => update_manifest(&config.output_file, true, change),
+        false => Ok(()),
+    }
+}
+
+// Removed rotations are dropped from an existing manifest, whoever removes them
+fn reconcile_manifest(config: &RotationConfig, removed: &[PathBuf]) -> Result<(), RotatorError> {
+    match removed.is_empty() {
+        true => Ok(()),
+        false => update_manifest(&config.output_file, false, |manifest| {
+            manifest.forget(removed)
+        }),
+    }
+}
+
+#[doc(hidden)]
+pub fn secure_output(config: &RotationConfig) -> Result<(), RotatorError> {
+    secure_rotation(&config.access, Path::new(&config.output_file))
+}
+
+fn secure_rotation(access: &Access, path: &Path) -> Result<(), RotatorError> {
+    access.apply_file(path)?;
+    for sidecar in existing_sidecars(path) {
+        access.apply_file(&sidecar)?;
+    }
+    Ok(())
+}
+
+fn rename_rotation(source: &Path, target: &Path) -> Result<(), RotatorError> {
+    debug!(target: LOGGER, "Renaming '{}' to '{}'", source.display(), target.display());
+    fs::rename(source, target).map_err(|op| {
+        format!(
+            "Error while renaming '{}' to '{}': {}",
+            source.display(),
+            target.display(),
+            op
+        )
+    })?;
+    move_sidecars(source, target)
+}
+
+#[doc(hidden)]
+pub fn move_sidecars(source: &Path, target: &Path) -> Result<(), RotatorError> {
+    for sidecar in existing_sidecars(source) {
+        let mut sidecar_target = target.as_os_str().to_owned();
+        sidecar_target.push(&sidecar.as_os_str().to_string_lossy()[source.as_os_str().len()..]);
+        fs::rename(&sidecar, &sidecar_target)
+            .map_err(|op| format!("Error while renaming '{}': {}", sidecar.display(), op))?;
+    }
+    Ok(())
+}
+
+// Removes a rotation and its sidecars unless the pre-delete command refuses it. Whether it was
+// removed is returned
+fn delete_rotation(config: &RotationConfig, rotation: &Path) -> Result<bool, RotatorError> {
+    if let Some(hook) = &config.pre_delete {
+        if let Err(err) = hook.run(rotation) {
+            warn!(target: LOGGER, "{}, keeping '{}'", err.msg, rotation.display());
+            return Ok(false);
+        }
+    }
+    debug!(target: LOGGER, "Removing '{}'", rotation.display());
+    match fs::remove_file(rotation) {
+        Ok(()) => {}
+        // Replaced by its compressed version meanwhile, which the next cleanup considers
+        Err(op) if op.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(op) => {
+            return Err(RotatorError::from(format!(
+                "Error while removing '{}': {}",
+                rotation.display(),
+                op
+            )))
+        }
+    }
+    config.counters.record_deletion();
+    remove_sidecars(rotation)?;
+    Ok(true)
+}
+
+#[doc(hidden)]
+pub fn ensure_free_space(config: &RotationConfig) -> Result<(), RotatorError> {
+    let min_free_space = match config.min_free_space {
+        Some(min_free_space) => min_free_space,
+        None => return Ok(()),
+    };
+    let directory = config.rotations_directory();
+    let space = || {
+        filesystem_space(&directory).map_err(|op| {
+            format!(
+                "Error while reading free space of '{}': {}",
+                directory.display(),
+                op
+            )
+        })
+    };
+    let (available, total) = space()?;
+    if available >= min_free_space.required(total) {
+        return Ok(());
+    }
+    let rotations = all_rotations(
+        &config.output_file,
+        config.rotation_directory.as_deref(),
+        &config.naming,
+    )?;
+    let mut available = available;
+    let mut removed = vec![];
+    for rotation in rotations {
+        info!(target: LOGGER, "Free space of {} bytes is below {}, removing '{}'", available, min_free_space, rotation.display());
+        if !delete_rotation(config, &rotation)? {
+            continue;
+        }
+        removed.push(rotation);
+        let (now_available, total) = space()?;
+        available = now_available;
+        if available >= min_free_space.required(total) {
+            break;
+        }
+    }
+    notify_cleanup(config, "min_free_space", &removed);
+    reconcile_manifest(config, &removed)
+}
+
+#[doc(hidden)]
+pub fn cleanup_rotations(
+    max_files: u32,
+    reserved: u64,
+    config: &RotationConfig,
+    rotation_result: &RotationResult,
+) -> Result<(), RotatorError> {
+    let existing = &rotation_result.existing_rotated;
+    let modified: Vec<SystemTime> = existing
+        .iter()
+        .map(|path| {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or_else(|_| SystemTime::now())
+        })
+        .collect();
+    let mut remove: Vec<bool> = match (&config.keep, max_files) {
+        (Some(keep), 1..) => keep
+            .retained(&modified)
+            .into_iter()
+            .map(|retained| !retained)
+            .collect(),
+        _ => {
+            let to_remove = existing
+                .len()
+                .saturating_sub(usize::try_from(max_files).unwrap());
+            (0..existing.len())
+                .map(|position| position < to_remove)
+                .collect()
+        }
+    };
+    if let Some(max_total_size) = config.max_total_size {
+        let sizes: Vec<u64> = existing
+            .iter()
+            .map(|path| fs::metadata(path).map(|m| m.len()).unwrap_or_default())
+            .collect();
+        let mut total = reserved
+            + (0..existing.len())
+                .filter(|position| !remove[*position])
+                .map(|position| sizes[position])
+                .sum::<u64>();
+        for position in 0..existing.len() {
+            if total <= max_total_size {
+                break;
+            }
+            if !remove[position] {
+                debug!(target: LOGGER, "Rotations take {} bytes, above the budget of {} bytes", total, max_total_size);
+                remove[position] = true;
+                total -= sizes[position];
+            }
+        }
+    }
+    if let Some(max_age) = config.max_age {
+        let now = SystemTime::now();
+        for (position, modified) in modified.iter().enumerate() {
+            if now.duration_since(*modified).unwrap_or_default() > max_age {
+                remove[position] = true;
+            }
+        }
+    }
+    let mut removed = vec![];
+    for (file_to_clean, _) in existing.iter().zip(remove).filter(|(_, remove)| *remove) {
+        if delete_rotation(config, file_to_clean)? {
+            removed.push(file_to_clean.clone());
+        }
+    }
+    notify_cleanup(config, "retention", &removed);
+    reconcile_manifest(config, &removed)
+}
+
+#[doc(hidden)]
+pub fn lock_output(config: &RotationConfig, wait: bool) -> Result<OutputLock, RotatorError> {
+    if let Some(parent) = Path::new(&config.output_file).parent() {
+        config.access.create_dirs(parent)?;
+    }
+    OutputLock::acquire(&config.output_file, wait)
+}
+
+#[doc(hidden)]
+pub fn prepare_rotations(config: &RotationConfig) -> Result<(), RotatorError> {
+    if let Some(parent) = Path::new(&config.output_file).parent() {
+        config.access.create_dirs(parent)?;
+    }
+    if let Some(directory) = &config.rotation_directory {
+        config.access.create_dirs(Path::new(directory))?;
+    }
+    let rotation_result = next_file(
+        &config.extension(),
+        &config.output_file,
+        config.rotation_directory.as_deref(),
+        &config.naming,
+    )?;
+    cleanup_rotations(config.max_history, 0, config, &rotation_result)?;
+    if config.naming.numbering() == Numbering::Compact {
+        renumber_rotations(config)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,332 @@
+use log::{info, warn};
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use stdout_rotator::host::hostname;
+use stdout_rotator::signals::shutdown_signal;
+use stdout_rotator::RotatorError;
+
+use crate::forward::ForwardDecoder;
+use crate::merge::Lines;
+use crate::syslog_input::{normalise, SyslogFrames};
+
+const LOGGER: &str = "listen";
+const LISTEN_POLL: Duration = Duration::from_millis(100);
+const MAX_DATAGRAM: usize = 64 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ListenAddress {
+    Tcp(String),
+    Udp(String),
+    Unix(PathBuf),
+}
+
+impl ListenAddress {
+    pub fn parse(value: &str) -> Result<ListenAddress, String> {
+        match value.split_once("://") {
+            Some(("tcp", address)) if !address.is_empty() => {
+                Ok(ListenAddress::Tcp(address.to_string()))
+            }
+            Some(("udp", address)) if !address.is_empty() => {
+                Ok(ListenAddress::Udp(address.to_string()))
+            }
+            _ => match value.strip_prefix("unix:") {
+                Some(path) if !path.is_empty() => Ok(ListenAddress::Unix(PathBuf::from(path))),
+                _ => Err(format!(
+                    "Invalid listen address '{}', expected tcp://host:port, udp://host:port or unix:/path",
+                    value
+                )),
+            },
+        }
+    }
+}
+
+// What is received, lines to rotate as they are, syslog messages to normalise into lines or
+// Fluentd forward messages whose events become JSON lines
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Lines,
+    Syslog,
+    Forward,
+}
+
+enum Framing {
+    Lines(Lines),
+    Syslog(SyslogFrames),
+    Forward(ForwardDecoder),
+}
+
+impl Framing {
+    fn new(protocol: Protocol, host: &str) -> Framing {
+        match protocol {
+            Protocol::Lines => Framing::Lines(Lines::new(None)),
+            Protocol::Syslog => Framing::Syslog(SyslogFrames::new(host)),
+            Protocol::Forward => Framing::Forward(ForwardDecoder::new()),
+        }
+    }
+
+    fn push(&mut self, data: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            Framing::Lines(lines) => Ok(lines.push(data)),
+            Framing::Syslog(frames) => Ok(frames.push(data)),
+            Framing::Forward(decoder) => decoder.push(data),
+        }
+    }
+
+    fn finish(&mut self) -> Option<Vec<u8>> {
+        match self {
+            Framing::Lines(lines) => lines.finish(),
+            Framing::Syslog(frames) => frames.finish(),
+            // An incomplete message is useless
+            Framing::Forward(_) => None,
+        }
+    }
+
+    fn responses(&mut self) -> Vec<Vec<u8>> {
+        match self {
+            Framing::Forward(decoder) => decoder.take_acks(),
+            _ => vec![],
+        }
+    }
+}
+
+impl std::fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenAddress::Tcp(address) => write!(f, "tcp://{}", address),
+            ListenAddress::Udp(address) => write!(f, "udp://{}", address),
+            ListenAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+// Binds the address and passes on the lines received, until shutdown
+pub fn start_listener(
+    address: &ListenAddress,
+    protocol: Protocol,
+    max_connections: usize,
+    sender: Sender<Vec<u8>>,
+) -> Result<(), RotatorError> {
+    let bound = |op: io::Error| format!("Error while listening on '{}': {}", address, op);
+    match address {
+        ListenAddress::Tcp(host) => {
+            let listener = TcpListener::bind(host).map_err(bound)?;
+            listener.set_nonblocking(true).map_err(bound)?;
+            info!(target: LOGGER, "Listening on '{}'", address);
+            thread::spawn(move || accept_connections(listener, protocol, max_connections, sender));
+        }
+        ListenAddress::Udp(_) if protocol == Protocol::Forward => {
+            return Err(RotatorError::from(format!(
+                "The forward protocol is not supported over UDP, as '{}' asks",
+                address
+            )))
+        }
+        ListenAddress::Udp(host) => {
+            let socket = UdpSocket::bind(host).map_err(bound)?;
+            socket.set_read_timeout(Some(LISTEN_POLL)).map_err(bound)?;
+            info!(target: LOGGER, "Listening on '{}'", address);
+            thread::spawn(move || receive_datagrams(socket, protocol, sender));
+        }
+        #[cfg(unix)]
+        ListenAddress::Unix(path) => {
+            let listener = bind_unix(path).map_err(bound)?;
+            info!(target: LOGGER, "Listening on '{}'", address);
+            let path = path.clone();
+            thread::spawn(move || {
+                accept_connections(listener, protocol, max_connections, sender);
+                let _ = std::fs::remove_file(&path);
+            });
+        }
+        #[cfg(not(unix))]
+        ListenAddress::Unix(_) => {
+            return Err(RotatorError::from(format!(
+                "Listening on '{}' is only supported on unix",
+                address
+            )))
+        }
+    }
+    Ok(())
+}
+
+// A socket left behind by a previous run is replaced, any other file is kept
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path) -> io::Result<std::os::unix::net::UnixListener> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    let listener = UnixListener::bind(path)?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+trait Acceptor: Send + 'static {
+    type Stream: Read + Write + Send + 'static;
+
+    // The accepted connection reads with a timeout, so that shutdown is noticed. It comes with a
+    // description of the peer and its host
+    fn accept_connection(&self) -> io::Result<(Self::Stream, String, String)>;
+}
+
+impl Acceptor for TcpListener {
+    type Stream = TcpStream;
+
+    fn accept_connection(&self) -> io::Result<(TcpStream, String, String)> {
+        let (stream, peer) = self.accept()?;
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(LISTEN_POLL))?;
+        Ok((stream, peer.to_string(), peer.ip().to_string()))
+    }
+}
+
+#[cfg(unix)]
+impl Acceptor for std::os::unix::net::UnixListener {
+    type Stream = std::os::unix::net::UnixStream;
+
+    fn accept_connection(&self) -> io::Result<(Self::Stream, String, String)> {
+        use std::os::unix::io::AsRawFd;
+
+        let (stream, _) = self.accept()?;
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(LISTEN_POLL))?;
+        // Local clients have no address of their own, they are told apart by descriptor
+        let peer = format!("local client on descriptor {}", stream.as_raw_fd());
+        Ok((stream, peer, hostname().to_string()))
+    }
+}
+
+fn accept_connections<A: Acceptor>(
+    listener: A,
+    protocol: Protocol,
+    max_connections: usize,
+    sender: Sender<Vec<u8>>,
+) {
+    let connections = Arc::new(AtomicUsize::new(0));
+    while shutdown_signal().is_none() {
+        let (stream, peer, host) = match listener.accept_connection() {
+            Ok(accepted) => accepted,
+            Err(op) if op.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(LISTEN_POLL);
+                continue;
+            }
+            Err(op) => {
+                warn!(target: LOGGER, "Error while accepting connection: {}", op);
+                thread::sleep(LISTEN_POLL);
+                continue;
+            }
+        };
+        if connections.load(Ordering::Acquire) >= max_connections {
+            warn!(target: LOGGER, "Refusing connection from {}, already {} connections", peer, max_connections);
+            continue;
+        }
+        info!(target: LOGGER, "Connection from {}", peer);
+        connections.fetch_add(1, Ordering::AcqRel);
+        let connections = connections.clone();
+        let sender = sender.clone();
+        thread::spawn(move || {
+            read_connection(stream, Framing::new(protocol, &host), &sender);
+            connections.fetch_sub(1, Ordering::AcqRel);
+            info!(target: LOGGER, "Connection from {} closed", peer);
+        });
+    }
+}
+
+// Each connection is framed on its own, so that lines sent concurrently never mix
+fn read_connection<S: Read + Write>(mut stream: S, mut framing: Framing, sender: &Sender<Vec<u8>>) {
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            // Timing out means what was received so far was read, so the connection can be
+            // dropped on shutdown
+            Err(op) if is_timeout(&op) => {
+                if shutdown_signal().is_some() {
+                    break;
+                }
+                continue;
+            }
+            Err(op) => {
+                warn!(target: LOGGER, "Error while reading connection: {}", op);
+                break;
+            }
+        };
+        match framing.push(&buffer[..read]) {
+            Ok(Some(chunk)) => {
+                if sender.send(chunk).is_err() {
+                    return;
+                }
+            }
+            Ok(None) => {}
+            Err(msg) => {
+                warn!(target: LOGGER, "Closing connection sending invalid data: {}", msg);
+                return;
+            }
+        }
+        // Acknowledged once handed over to be written
+        for response in framing.responses() {
+            if let Err(op) = stream.write_all(&response) {
+                warn!(target: LOGGER, "Error while acknowledging: {}", op);
+                return;
+            }
+        }
+    }
+    if let Some(chunk) = framing.finish() {
+        let _ = sender.send(chunk);
+    }
+}
+
+fn is_timeout(op: &io::Error) -> bool {
+    matches!(
+        op.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+// Every datagram holds whole lines, the last one terminated when the sender did not, or a
+// single syslog message
+fn receive_datagrams(socket: UdpSocket, protocol: Protocol, sender: Sender<Vec<u8>>) {
+    let mut buffer = vec![0u8; MAX_DATAGRAM];
+    loop {
+        let (read, peer) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(op) if is_timeout(&op) => {
+                if shutdown_signal().is_some() {
+                    break;
+                }
+                continue;
+            }
+            Err(op) => {
+                warn!(target: LOGGER, "Error while receiving datagram: {}", op);
+                continue;
+            }
+        };
+        if read == 0 {
+            continue;
+        }
+        let chunk = match protocol {
+            Protocol::Lines => {
+                let mut chunk = buffer[..read].to_vec();
+                if !chunk.ends_with(b"\n") {
+                    chunk.push(b'\n');
+                }
+                chunk
+            }
+            Protocol::Syslog => normalise(&buffer[..read], &peer.ip().to_string()),
+            // Refused over UDP when listening starts
+            Protocol::Forward => continue,
+        };
+        if sender.send(chunk).is_err() {
+            return;
+        }
+    }
+}
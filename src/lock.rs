@@ -0,0 +1,153 @@
+use log::info;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::RotatorError;
+
+const LOGGER: &str = "lock";
+const LOCK_EXTENSION: &str = ".lock";
+
+pub fn lock_path(output_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}", output_file, LOCK_EXTENSION))
+}
+
+// Held for the lifetime of the process: the output file itself is renamed by rotations, so
+// the lock is taken on a file next to it, removed once released
+pub struct OutputLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl OutputLock {
+    pub fn acquire(output_file: &str, wait: bool) -> Result<OutputLock, RotatorError> {
+        let path = lock_path(output_file);
+        let mut file = loop {
+            let file = File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+                .map_err(|op| {
+                    format!(
+                        "Error during opening of lock file '{}': {}",
+                        path.display(),
+                        op
+                    )
+                })?;
+            if !try_lock(&file, false)
+                .map_err(|op| format!("Error while locking '{}': {}", path.display(), op))?
+            {
+                let owner = owner(file.try_clone().ok());
+                if !wait {
+                    return Err(held_error(output_file, &owner));
+                }
+                info!(target: LOGGER, "Waiting for the instance writing '{}'{} to exit", output_file, owner);
+                try_lock(&file, true)
+                    .map_err(|op| format!("Error while locking '{}': {}", path.display(), op))?;
+            }
+            // The previous holder removes the file when releasing it, so the lock obtained
+            // meanwhile is the one of a file nobody else can find anymore
+            if same_file(&file, &path) {
+                break file;
+            }
+        };
+        file.set_len(0)
+            .and_then(|_| file.rewind())
+            .and_then(|_| writeln!(file, "{}", std::process::id()))
+            .map_err(|op| format!("Error while writing lock file '{}': {}", path.display(), op))?;
+        Ok(OutputLock { file, path })
+    }
+
+    // Fails as acquiring would when another instance holds the lock, without taking it
+    pub fn check_free(output_file: &str) -> Result<(), RotatorError> {
+        let path = lock_path(output_file);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(op) if op.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(op) => {
+                return Err(RotatorError::from(format!(
+                    "Error during opening of lock file '{}': {}",
+                    path.display(),
+                    op
+                )))
+            }
+        };
+        match try_lock(&file, false)
+            .map_err(|op| format!("Error while locking '{}': {}", path.display(), op))?
+        {
+            true => Ok(()),
+            false => Err(held_error(output_file, &owner(Some(file)))),
+        }
+    }
+}
+
+impl Drop for OutputLock {
+    fn drop(&mut self) {
+        // Removed while still held, so that no other instance is locking it meanwhile
+        if same_file(&self.file, &self.path) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn owner(file: Option<File>) -> String {
+    let mut owner = String::new();
+    if let Some(mut file) = file {
+        let _ = file.read_to_string(&mut owner);
+    }
+    match owner.trim() {
+        "" => String::new(),
+        pid => format!(" (pid {})", pid),
+    }
+}
+
+fn held_error(output_file: &str, owner: &str) -> RotatorError {
+    RotatorError::from(format!(
+        "'{}' is already written by another stdout-rotator instance{}",
+        output_file, owner
+    ))
+}
+
+#[cfg(unix)]
+fn same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(locked), Ok(current)) => locked.dev() == current.dev() && locked.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+#[cfg(unix)]
+fn try_lock(file: &File, wait: bool) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    let operation = if wait {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_EX | libc::LOCK_NB
+    };
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(true);
+        }
+        let error = io::Error::last_os_error();
+        match error.kind() {
+            io::ErrorKind::WouldBlock => return Ok(false),
+            io::ErrorKind::Interrupted => {}
+            _ => return Err(error),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn try_lock(_file: &File, _wait: bool) -> io::Result<bool> {
+    Ok(true)
+}
@@ -0,0 +1,128 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use stdout_rotator::manifest::Manifest;
+use stdout_rotator::naming::{Naming, NamingArgs};
+use stdout_rotator::{all_rotations, is_plain_rotation, RotatorError, ROTATION_EXTENSIONS};
+
+#[derive(clap::Args, Debug)]
+pub struct LsArgs {
+    #[arg(help = "File whose rotations are listed, oldest first, followed by the file itself")]
+    file: String,
+    #[arg(long, default_value = None, help = "Directory where rotated files are saved. If not provided, the same directory of the file will be used")]
+    rotation_directory: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Print a JSON array with an object per file instead of a table"
+    )]
+    json: bool,
+}
+
+struct Entry {
+    path: PathBuf,
+    index: Option<i64>,
+    active: bool,
+    // Content size, only known without reading the file when it is stored plain or is in the
+    // manifest
+    size: Option<u64>,
+    compressed_size: Option<u64>,
+    created: SystemTime,
+    lines: Option<u64>,
+}
+
+impl Entry {
+    fn new(
+        path: PathBuf,
+        index: Option<i64>,
+        active: bool,
+        manifest: Option<&Manifest>,
+    ) -> Result<Entry, RotatorError> {
+        let metadata = fs::metadata(&path).map_err(|op| {
+            format!(
+                "Error while reading metadata of '{}': {}",
+                path.display(),
+                op
+            )
+        })?;
+        // A rotation is last written when it is created, and compressing it keeps that time
+        let created = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+        let (size, compressed_size) = match active || is_plain_rotation(&path) {
+            true => (Some(metadata.len()), None),
+            false => (None, Some(metadata.len())),
+        };
+        let recorded = manifest.and_then(|manifest| manifest.entry(&path));
+        Ok(Entry {
+            size: size.or(recorded.map(|entry| entry.size())),
+            lines: recorded.map(|entry| entry.lines),
+            path,
+            index,
+            active,
+            compressed_size,
+            created,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "file": self.path.display().to_string(),
+            "index": self.index,
+            "active": self.active,
+            "size": self.size,
+            "compressed_size": self.compressed_size,
+            "created": humantime::format_rfc3339_seconds(self.created).to_string(),
+            "lines": self.lines,
+        })
+    }
+}
+
+fn or_dash<T: ToString>(value: Option<T>) -> String {
+    value.map_or("-".to_string(), |value| value.to_string())
+}
+
+pub fn run(args: LsArgs) -> Result<(), RotatorError> {
+    let naming = Naming::new(&args.naming);
+    let base_name = Path::new(&args.file)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let manifest = Manifest::load(&args.file)?;
+    let mut entries = vec![];
+    for rotation in all_rotations(&args.file, args.rotation_directory.as_deref(), &naming)? {
+        let name = rotation.file_name().unwrap().to_string_lossy().to_string();
+        let index = naming.index_of(&base_name, &name, ROTATION_EXTENSIONS);
+        entries.push(Entry::new(rotation, index, false, manifest.as_ref())?);
+    }
+    let active = PathBuf::from(&args.file);
+    if active.exists() {
+        entries.push(Entry::new(active, None, true, None)?);
+    }
+    if args.json {
+        let entries: Vec<Value> = entries.iter().map(Entry::to_json).collect();
+        println!("{}", Value::Array(entries));
+        return Ok(());
+    }
+    println!(
+        "{:>6} {:>12} {:>12} {:<20} {:>10} file",
+        "index", "size", "compressed", "created", "lines"
+    );
+    for entry in &entries {
+        let index = match entry.active {
+            true => "active".to_string(),
+            false => or_dash(entry.index),
+        };
+        println!(
+            "{:>6} {:>12} {:>12} {:<20} {:>10} {}",
+            index,
+            or_dash(entry.size),
+            or_dash(entry.compressed_size),
+            humantime::format_rfc3339_seconds(entry.created).to_string(),
+            or_dash(entry.lines),
+            entry.path.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,375 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::compress::rotation_format;
+use crate::RotatorError;
+
+pub const MANIFEST_EXTENSION: &str = ".manifest.json";
+const TEMPORARY_EXTENSION: &str = ".tmp";
+
+// Rotations and the compression worker update the manifest from different threads
+static UPDATES: Mutex<()> = Mutex::new(());
+
+#[derive(Clone, Debug)]
+pub struct ManifestEntry {
+    // Name of the rotation in the rotation directory
+    pub file: String,
+    // Range of the rotation in everything written to the output file
+    pub start: u64,
+    pub end: u64,
+    pub first_write: Option<SystemTime>,
+    pub last_write: Option<SystemTime>,
+    pub lines: u64,
+    pub sha256: Option<String>,
+    pub compression: Option<String>,
+}
+
+fn time_json(time: Option<SystemTime>) -> Value {
+    time.map_or(Value::Null, |time| {
+        Value::String(humantime::format_rfc3339_millis(time).to_string())
+    })
+}
+
+fn json_time(value: &Value) -> Option<SystemTime> {
+    value
+        .as_str()
+        .and_then(|time| humantime::parse_rfc3339(time).ok())
+}
+
+fn json_string(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+impl ManifestEntry {
+    fn to_json(&self) -> Value {
+        json!({
+            "file": self.file,
+            "start": self.start,
+            "end": self.end,
+            "first_write": time_json(self.first_write),
+            "last_write": time_json(self.last_write),
+            "lines": self.lines,
+            "sha256": self.sha256,
+            "compression": self.compression,
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<ManifestEntry> {
+        Some(ManifestEntry {
+            file: json_string(&value["file"])?,
+            start: value["start"].as_u64()?,
+            end: value["end"].as_u64()?,
+            first_write: json_time(&value["first_write"]),
+            last_write: json_time(&value["last_write"]),
+            lines: value["lines"].as_u64().unwrap_or_default(),
+            sha256: json_string(&value["sha256"]),
+            compression: json_string(&value["compression"]),
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+
+    // The file now holding the rotation, e.g. once compressed
+    pub fn stored_as(&mut self, path: &Path, sha256: Option<String>) {
+        self.file = file_name(path);
+        self.compression = rotation_format(path).map(|format| format.name().to_string());
+        self.sha256 = sha256;
+    }
+}
+
+// The rotations of an output file, oldest first
+#[derive(Debug, Default)]
+pub struct Manifest {
+    // Bytes written to the rotations so far, where the next one starts
+    pub written: u64,
+    pub rotations: Vec<ManifestEntry>,
+}
+
+pub fn manifest_path(output_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}", output_file, MANIFEST_EXTENSION))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+impl Manifest {
+    pub fn load(output_file: &str) -> Result<Option<Manifest>, RotatorError> {
+        let path = manifest_path(output_file);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(op) if op.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(op) => {
+                return Err(RotatorError::from(format!(
+                    "Error while reading '{}': {}",
+                    path.display(),
+                    op
+                )))
+            }
+        };
+        let value: Value = serde_json::from_str(&content)
+            .map_err(|op| format!("Invalid manifest '{}': {}", path.display(), op))?;
+        let rotations = value["rotations"]
+            .as_array()
+            .map(|rotations| {
+                rotations
+                    .iter()
+                    .filter_map(ManifestEntry::from_json)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Some(Manifest {
+            written: value["written"].as_u64().unwrap_or_default(),
+            rotations,
+        }))
+    }
+
+    // Written aside and renamed over, so that readers never see a partial manifest
+    fn save(&self, output_file: &str) -> Result<(), RotatorError> {
+        let path = manifest_path(output_file);
+        let temporary = PathBuf::from(format!("{}{}", path.display(), TEMPORARY_EXTENSION));
+        let content = json!({
+            "written": self.written,
+            "rotations": self.rotations.iter().map(ManifestEntry::to_json).collect::<Vec<_>>(),
+        });
+        fs::write(&temporary, format!("{:#}\n", content))
+            .map_err(|op| format!("Error while writing '{}': {}", temporary.display(), op))?;
+        fs::rename(&temporary, &path).map_err(|op| {
+            RotatorError::from(format!(
+                "Error while renaming '{}' to '{}': {}",
+                temporary.display(),
+                path.display(),
+                op
+            ))
+        })
+    }
+
+    pub fn entry(&self, rotation: &Path) -> Option<&ManifestEntry> {
+        let name = file_name(rotation);
+        self.rotations.iter().find(|entry| entry.file == name)
+    }
+
+    pub fn entry_mut(&mut self, rotation: &Path) -> Option<&mut ManifestEntry> {
+        let name = file_name(rotation);
+        self.rotations.iter_mut().find(|entry| entry.file == name)
+    }
+
+    // A rotation of the given size, lines and write times, starting where the previous one ended
+    pub fn push(
+        &mut self,
+        rotation: &Path,
+        size: u64,
+        lines: u64,
+        written: (Option<SystemTime>, Option<SystemTime>),
+        sha256: Option<String>,
+    ) {
+        let mut entry = ManifestEntry {
+            file: String::new(),
+            start: self.written,
+            end: self.written + size,
+            first_write: written.0,
+            last_write: written.1,
+            lines,
+            sha256: None,
+            compression: None,
+        };
+        entry.stored_as(rotation, sha256);
+        self.written += size;
+        self.rotations
+            .retain(|existing| existing.file != entry.file);
+        self.rotations.push(entry);
+    }
+
+    pub fn forget(&mut self, removed: &[PathBuf]) {
+        let names: Vec<String> = removed.iter().map(|path| file_name(path)).collect();
+        self.rotations.retain(|entry| !names.contains(&entry.file));
+    }
+
+    // Rotations whose file is gone, e.g. removed by hand
+    pub fn prune(&mut self, directory: &Path) {
+        self.rotations
+            .retain(|entry| directory.join(&entry.file).exists());
+    }
+
+    pub fn rename(&mut self, source: &Path, target: &Path) {
+        let target = file_name(target);
+        if let Some(entry) = self.entry_mut(source) {
+            entry.file = target;
+        }
+    }
+}
+
+// Applies a change to the manifest of the output file when it exists, or when `create` is set
+pub fn update_manifest(
+    output_file: &str,
+    create: bool,
+    change: impl FnOnce(&mut Manifest),
+) -> Result<(), RotatorError> {
+    let _updating = UPDATES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    update_locked(output_file, create, change)
+}
+
+fn update_locked(
+    output_file: &str,
+    create: bool,
+    change: impl FnOnce(&mut Manifest),
+) -> Result<(), RotatorError> {
+    let mut manifest = match Manifest::load(output_file)? {
+        Some(manifest) => manifest,
+        None if create => Manifest::default(),
+        None => return Ok(()),
+    };
+    change(&mut manifest);
+    manifest.save(output_file)
+}
+
+// Removes a rotation once replaced by another file, e.g. its compressed version, its entry
+// following it in the same update so that no cleanup finds it under neither name
+pub fn replace_rotation(
+    output_file: Option<&str>,
+    source: &Path,
+    target: &Path,
+) -> Result<(), RotatorError> {
+    let _updating = UPDATES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    fs::remove_file(source)
+        .map_err(|op| format!("Error while removing '{}': {}", source.display(), op))?;
+    match output_file {
+        Some(output_file) => update_locked(output_file, false, |manifest| {
+            manifest.rename(source, target)
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn files(output_file: &str) -> Vec<String> {
+        Manifest::load(output_file)
+            .unwrap()
+            .unwrap()
+            .rotations
+            .into_iter()
+            .map(|entry| entry.file)
+            .collect()
+    }
+
+    #[test]
+    fn rotations_are_pushed_after_each_other() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = directory.path().join("out.log");
+        let output_file = output_file.to_str().unwrap();
+        let first = UNIX_EPOCH + Duration::from_millis(1_000_250);
+        let last = first + Duration::from_secs(5);
+        update_manifest(output_file, true, |manifest| {
+            manifest.push(
+                &directory.path().join("out.log.1"),
+                10,
+                2,
+                (Some(first), Some(last)),
+                None,
+            );
+            manifest.push(
+                &directory.path().join("out.log.2.gz"),
+                5,
+                1,
+                (None, None),
+                None,
+            );
+        })
+        .unwrap();
+        let manifest = Manifest::load(output_file).unwrap().unwrap();
+        assert_eq!(manifest.written, 15);
+        let entry = manifest.entry(Path::new("out.log.1")).unwrap();
+        assert_eq!((entry.start, entry.end, entry.lines), (0, 10, 2));
+        assert_eq!(
+            (entry.first_write, entry.last_write),
+            (Some(first), Some(last))
+        );
+        assert_eq!(entry.compression, None);
+        let entry = manifest.entry(Path::new("out.log.2.gz")).unwrap();
+        assert_eq!((entry.start, entry.end), (10, 15));
+        assert_eq!(entry.compression.as_deref(), Some("gzip"));
+    }
+
+    #[test]
+    fn manifests_are_only_created_when_asked() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = directory.path().join("out.log");
+        let output_file = output_file.to_str().unwrap();
+        update_manifest(output_file, false, |manifest| manifest.written = 1).unwrap();
+        assert!(Manifest::load(output_file).unwrap().is_none());
+    }
+
+    #[test]
+    fn updates_keep_the_entries_of_missing_files() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = directory.path().join("out.log");
+        let output_file = output_file.to_str().unwrap();
+        let rotation = directory.path().join("out.log.1");
+        update_manifest(output_file, true, |manifest| {
+            manifest.push(&rotation, 10, 1, (None, None), None)
+        })
+        .unwrap();
+        update_manifest(output_file, false, |manifest| manifest.written += 1).unwrap();
+        assert_eq!(files(output_file), vec!["out.log.1"]);
+
+        update_manifest(output_file, false, |manifest| {
+            manifest.prune(directory.path())
+        })
+        .unwrap();
+        assert!(files(output_file).is_empty());
+    }
+
+    #[test]
+    fn replaced_rotations_are_renamed() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = directory.path().join("out.log");
+        let output_file = output_file.to_str().unwrap();
+        let source = directory.path().join("out.log.1");
+        let target = directory.path().join("out.log.1.gz");
+        fs::write(&source, "line\n").unwrap();
+        fs::write(&target, "compressed").unwrap();
+        update_manifest(output_file, true, |manifest| {
+            manifest.push(&source, 5, 1, (None, None), None)
+        })
+        .unwrap();
+        replace_rotation(Some(output_file), &source, &target).unwrap();
+        assert!(!source.exists());
+        assert_eq!(files(output_file), vec!["out.log.1.gz"]);
+    }
+
+    #[test]
+    fn removed_rotations_are_forgotten() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = directory.path().join("out.log");
+        let output_file = output_file.to_str().unwrap();
+        let rotations: Vec<PathBuf> = (1..=3)
+            .map(|index| directory.path().join(format!("out.log.{}", index)))
+            .collect();
+        update_manifest(output_file, true, |manifest| {
+            for rotation in &rotations {
+                manifest.push(rotation, 1, 1, (None, None), None);
+            }
+        })
+        .unwrap();
+        update_manifest(output_file, false, |manifest| {
+            manifest.forget(&rotations[..2])
+        })
+        .unwrap();
+        assert_eq!(files(output_file), vec!["out.log.3"]);
+    }
+}
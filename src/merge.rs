@@ -0,0 +1,202 @@
+use log::{error, info};
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+use stdout_rotator::signals::shutdown_signal;
+
+use crate::input::Input;
+
+const LOGGER: &str = "merge";
+const SOURCE_POLL: Duration = Duration::from_millis(100);
+// A line growing past this is passed on in pieces rather than held indefinitely
+const MAX_PARTIAL_LINE: usize = 64 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceSpec {
+    pub name: Option<String>,
+    pub path: PathBuf,
+}
+
+impl SourceSpec {
+    // Either a path or name=path, the name then prefixing the lines of the source
+    pub fn parse(value: &str) -> Result<SourceSpec, String> {
+        let (name, path) = match value.split_once('=') {
+            Some((name, path)) if !name.is_empty() && !name.contains('/') => {
+                (Some(name.to_string()), path)
+            }
+            _ => (None, value),
+        };
+        if path.is_empty() {
+            return Err(format!("Missing path of input '{}'", value));
+        }
+        Ok(SourceSpec {
+            name,
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+// Interleaves line by line what several inputs, each read on its own thread, send it. The merged
+// input ends once every sender is dropped
+pub struct Merger {
+    receiver: Receiver<Vec<u8>>,
+    pending: Vec<u8>,
+    offset: usize,
+    ended: bool,
+}
+
+impl Merger {
+    pub fn new() -> (Merger, Sender<Vec<u8>>) {
+        let (sender, receiver) = mpsc::channel::<Vec<u8>>();
+        let merger = Merger {
+            receiver,
+            pending: vec![],
+            offset: 0,
+            ended: false,
+        };
+        (merger, sender)
+    }
+
+    pub fn wait_readable(&mut self, timeout: Duration) -> io::Result<bool> {
+        if self.offset < self.pending.len() || self.ended {
+            return Ok(true);
+        }
+        // On shutdown the sources drain their inputs and stop, which ends the merged input
+        let received = if shutdown_signal().is_some() {
+            self.receiver
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            self.receiver.recv_timeout(timeout)
+        };
+        match received {
+            Ok(chunk) => {
+                self.pending = chunk;
+                self.offset = 0;
+                Ok(true)
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(false),
+            Err(RecvTimeoutError::Disconnected) => {
+                self.ended = true;
+                Ok(true)
+            }
+        }
+    }
+}
+
+impl Read for Merger {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.offset == self.pending.len() && !self.ended {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let read = buf.len().min(self.pending.len() - self.offset);
+        buf[..read].copy_from_slice(&self.pending[self.offset..self.offset + read]);
+        self.offset += read;
+        Ok(read)
+    }
+}
+
+// Holds back incomplete lines, so that only complete ones are passed on and the lines of
+// different sources never mix
+pub struct Lines {
+    prefix: Option<Vec<u8>>,
+    partial: Vec<u8>,
+}
+
+impl Lines {
+    pub fn new(name: Option<&str>) -> Lines {
+        Lines {
+            prefix: name.map(|name| format!("[{}] ", name).into_bytes()),
+            partial: vec![],
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        self.partial.extend_from_slice(data);
+        let complete = match self.partial.iter().rposition(|b| *b == b'\n') {
+            Some(end) => end + 1,
+            None if self.partial.len() > MAX_PARTIAL_LINE => self.partial.len(),
+            None => return None,
+        };
+        let rest = self.partial.split_off(complete);
+        let lines = std::mem::replace(&mut self.partial, rest);
+        Some(self.prefixed(lines))
+    }
+
+    // What is left once the source ended, terminated as a line of its own
+    pub fn finish(&mut self) -> Option<Vec<u8>> {
+        if self.partial.is_empty() {
+            return None;
+        }
+        let mut lines = std::mem::take(&mut self.partial);
+        lines.push(b'\n');
+        Some(self.prefixed(lines))
+    }
+
+    fn prefixed(&self, lines: Vec<u8>) -> Vec<u8> {
+        let Some(prefix) = &self.prefix else {
+            return lines;
+        };
+        let mut chunk = Vec::with_capacity(lines.len() + prefix.len());
+        for line in lines.split_inclusive(|b| *b == b'\n') {
+            chunk.extend_from_slice(prefix);
+            chunk.extend_from_slice(line);
+        }
+        chunk
+    }
+}
+
+pub fn spawn_source(source: SourceSpec, input: Input, sender: Sender<Vec<u8>>) {
+    thread::spawn(move || read_source(source, input, sender));
+}
+
+fn read_source(source: SourceSpec, mut input: Input, sender: Sender<Vec<u8>>) {
+    let path = source.path.display();
+    let mut lines = Lines::new(source.name.as_deref());
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let wait = if shutdown_signal().is_some() {
+            Duration::ZERO
+        } else {
+            SOURCE_POLL
+        };
+        let readable = match input.wait_readable(wait) {
+            Ok(readable) => readable,
+            Err(op) => {
+                error!(target: LOGGER, "Impossible to wait for '{}': {}", path, op);
+                break;
+            }
+        };
+        if !readable {
+            if wait.is_zero() {
+                break;
+            }
+            continue;
+        }
+        let read = match input.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(op) if op.kind() == io::ErrorKind::WouldBlock => {
+                if wait.is_zero() {
+                    break;
+                }
+                continue;
+            }
+            Err(op) => {
+                error!(target: LOGGER, "Impossible to read from '{}': {}", path, op);
+                break;
+            }
+        };
+        if let Some(chunk) = lines.push(&buffer[..read]) {
+            if sender.send(chunk).is_err() {
+                return;
+            }
+        }
+    }
+    if let Some(chunk) = lines.finish() {
+        let _ = sender.send(chunk);
+    }
+    info!(target: LOGGER, "Stopped reading '{}'", path);
+}
@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Default, Debug)]
+pub struct Counters {
+    pub bytes_in: AtomicU64,
+    pub lines_in: AtomicU64,
+    pub rotations: AtomicU64,
+    pub dropped_file_chunks: AtomicU64,
+    pub deleted_files: AtomicU64,
+    pub errors: AtomicU64,
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Snapshot {
+    pub bytes_in: u64,
+    pub lines_in: u64,
+    pub rotations: u64,
+    pub dropped_file_chunks: u64,
+    pub deleted_files: u64,
+    pub errors: u64,
+}
+
+impl Counters {
+    pub fn record_input(&self, chunk: &[u8]) {
+        let lines = chunk.iter().filter(|b| **b == b'\n').count();
+        self.bytes_in
+            .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        self.lines_in.fetch_add(lines as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_rotation(&self) {
+        self.rotations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped_file_chunk(&self) -> u64 {
+        self.dropped_file_chunks.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn record_deletion(&self) {
+        self.deleted_files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            lines_in: self.lines_in.load(Ordering::Relaxed),
+            rotations: self.rotations.load(Ordering::Relaxed),
+            dropped_file_chunks: self.dropped_file_chunks.load(Ordering::Relaxed),
+            deleted_files: self.deleted_files.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
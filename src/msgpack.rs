@@ -0,0 +1,339 @@
+use serde_json::{Map, Number, Value};
+
+// Deep enough for any record, shallow enough that hostile input cannot exhaust the stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Msgpack {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    // Strings are kept as bytes, as invalid UTF-8 is only dealt with when rendered
+    Str(Vec<u8>),
+    Bin(Vec<u8>),
+    Array(Vec<Msgpack>),
+    Map(Vec<(Msgpack, Msgpack)>),
+    Ext(i8, Vec<u8>),
+}
+
+impl Msgpack {
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Msgpack::Str(bytes) | Msgpack::Bin(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<String> {
+        self.as_bytes()
+            .map(|bytes| String::from_utf8_lossy(bytes).to_string())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Msgpack> {
+        let Msgpack::Map(entries) = self else {
+            return None;
+        };
+        entries
+            .iter()
+            .find(|(name, _)| name.as_bytes() == Some(key.as_bytes()))
+            .map(|(_, value)| value)
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            Msgpack::Nil | Msgpack::Ext(..) => Value::Null,
+            Msgpack::Bool(value) => Value::Bool(*value),
+            Msgpack::Int(value) => Value::from(*value),
+            Msgpack::UInt(value) => Value::from(*value),
+            Msgpack::Float(value) => Number::from_f64(*value).map_or(Value::Null, Value::Number),
+            Msgpack::Str(bytes) | Msgpack::Bin(bytes) => {
+                Value::String(String::from_utf8_lossy(bytes).to_string())
+            }
+            Msgpack::Array(values) => Value::Array(values.iter().map(Msgpack::to_json).collect()),
+            Msgpack::Map(entries) => {
+                let mut map = Map::new();
+                for (key, value) in entries {
+                    let key = match key.as_str() {
+                        Some(key) => key,
+                        None => key.to_json().to_string(),
+                    };
+                    map.insert(key, value.to_json());
+                }
+                Value::Object(map)
+            }
+        }
+    }
+}
+
+pub enum DecodeError {
+    // More bytes are needed for the value to be complete
+    Incomplete,
+    Invalid(String),
+}
+
+// The first value of the data and the number of bytes it took
+pub fn decode(data: &[u8]) -> Result<(Msgpack, usize), DecodeError> {
+    let mut decoder = Decoder { data, position: 0 };
+    let value = decoder.value(0)?;
+    Ok((value, decoder.position))
+}
+
+// Every value of the data, which must hold nothing else
+pub fn decode_all(mut data: &[u8]) -> Result<Vec<Msgpack>, String> {
+    let mut values = vec![];
+    while !data.is_empty() {
+        match decode(data) {
+            Ok((value, used)) => {
+                values.push(value);
+                data = &data[used..];
+            }
+            Err(DecodeError::Incomplete) => return Err("Truncated msgpack value".to_string()),
+            Err(DecodeError::Invalid(msg)) => return Err(msg),
+        }
+    }
+    Ok(values)
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(DecodeError::Incomplete)?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, size: usize) -> Result<u64, DecodeError> {
+        Ok(self
+            .take(size)?
+            .iter()
+            .fold(0u64, |value, byte| value << 8 | u64::from(*byte)))
+    }
+
+    fn length(&mut self, size: usize) -> Result<usize, DecodeError> {
+        Ok(self.uint(size)? as usize)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Msgpack, DecodeError> {
+        if depth > MAX_DEPTH {
+            return Err(DecodeError::Invalid(
+                "Msgpack value nested too deeply".to_string(),
+            ));
+        }
+        let marker = self.take(1)?[0];
+        let value = match marker {
+            0x00..=0x7f => Msgpack::UInt(u64::from(marker)),
+            0x80..=0x8f => self.map(usize::from(marker & 0x0f), depth)?,
+            0x90..=0x9f => self.array(usize::from(marker & 0x0f), depth)?,
+            0xa0..=0xbf => Msgpack::Str(self.take(usize::from(marker & 0x1f))?.to_vec()),
+            0xc0 => Msgpack::Nil,
+            0xc2 => Msgpack::Bool(false),
+            0xc3 => Msgpack::Bool(true),
+            0xc4..=0xc6 => {
+                let length = self.length(1 << (marker - 0xc4))?;
+                Msgpack::Bin(self.take(length)?.to_vec())
+            }
+            0xc7..=0xc9 => {
+                let length = self.length(1 << (marker - 0xc7))?;
+                let kind = self.take(1)?[0] as i8;
+                Msgpack::Ext(kind, self.take(length)?.to_vec())
+            }
+            0xca => Msgpack::Float(f64::from(f32::from_bits(self.uint(4)? as u32))),
+            0xcb => Msgpack::Float(f64::from_bits(self.uint(8)?)),
+            0xcc..=0xcf => Msgpack::UInt(self.uint(1 << (marker - 0xcc))?),
+            0xd0..=0xd3 => {
+                let size = 1 << (marker - 0xd0);
+                let value = self.uint(size)?;
+                // Sign extended from its size
+                let shift = 64 - size * 8;
+                Msgpack::Int(((value << shift) as i64) >> shift)
+            }
+            0xd4..=0xd8 => {
+                let kind = self.take(1)?[0] as i8;
+                Msgpack::Ext(kind, self.take(1 << (marker - 0xd4))?.to_vec())
+            }
+            0xd9..=0xdb => {
+                let length = self.length(1 << (marker - 0xd9))?;
+                Msgpack::Str(self.take(length)?.to_vec())
+            }
+            0xdc | 0xdd => {
+                let length = self.length(2 << (marker - 0xdc))?;
+                self.array(length, depth)?
+            }
+            0xde | 0xdf => {
+                let length = self.length(2 << (marker - 0xde))?;
+                self.map(length, depth)?
+            }
+            0xe0..=0xff => Msgpack::Int(i64::from(marker as i8)),
+            0xc1 => {
+                return Err(DecodeError::Invalid(
+                    "Invalid msgpack marker 0xc1".to_string(),
+                ))
+            }
+        };
+        Ok(value)
+    }
+
+    // Capacities are bounded by what is left, so that a bogus length allocates nothing
+    fn array(&mut self, length: usize, depth: usize) -> Result<Msgpack, DecodeError> {
+        let mut values = Vec::with_capacity(length.min(self.data.len() - self.position));
+        for _ in 0..length {
+            values.push(self.value(depth + 1)?);
+        }
+        Ok(Msgpack::Array(values))
+    }
+
+    fn map(&mut self, length: usize, depth: usize) -> Result<Msgpack, DecodeError> {
+        let mut entries = Vec::with_capacity(length.min(self.data.len() - self.position));
+        for _ in 0..length {
+            let key = self.value(depth + 1)?;
+            let value = self.value(depth + 1)?;
+            entries.push((key, value));
+        }
+        Ok(Msgpack::Map(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn decoded(data: &[u8]) -> Msgpack {
+        match decode(data) {
+            Ok((value, used)) => {
+                assert_eq!(used, data.len());
+                value
+            }
+            Err(DecodeError::Incomplete) => panic!("incomplete"),
+            Err(DecodeError::Invalid(msg)) => panic!("{}", msg),
+        }
+    }
+
+    #[test]
+    fn scalars_are_decoded() {
+        assert_eq!(decoded(&[0xc0]), Msgpack::Nil);
+        assert_eq!(decoded(&[0xc2]), Msgpack::Bool(false));
+        assert_eq!(decoded(&[0xc3]), Msgpack::Bool(true));
+        assert_eq!(decoded(&[0x7f]), Msgpack::UInt(127));
+        assert_eq!(decoded(&[0xff]), Msgpack::Int(-1));
+        assert_eq!(decoded(&[0xe0]), Msgpack::Int(-32));
+        assert_eq!(decoded(&[0xcc, 0xff]), Msgpack::UInt(255));
+        assert_eq!(decoded(&[0xcd, 0x01, 0x00]), Msgpack::UInt(256));
+        assert_eq!(
+            decoded(&[0xcf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            Msgpack::UInt(u64::MAX)
+        );
+        assert_eq!(
+            decoded(&[0xca, 0x3f, 0xc0, 0x00, 0x00]),
+            Msgpack::Float(1.5)
+        );
+        assert_eq!(
+            decoded(&[0xcb, 0xc0, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+            Msgpack::Float(-2.5)
+        );
+    }
+
+    #[test]
+    fn signed_integers_are_sign_extended() {
+        assert_eq!(decoded(&[0xd0, 0x80]), Msgpack::Int(-128));
+        assert_eq!(decoded(&[0xd1, 0xff, 0x00]), Msgpack::Int(-256));
+        assert_eq!(
+            decoded(&[0xd2, 0x7f, 0xff, 0xff, 0xff]),
+            Msgpack::Int(i64::from(i32::MAX))
+        );
+        assert_eq!(
+            decoded(&[0xd3, 0x80, 0, 0, 0, 0, 0, 0, 0]),
+            Msgpack::Int(i64::MIN)
+        );
+    }
+
+    #[test]
+    fn strings_binaries_and_extensions_are_decoded() {
+        assert_eq!(decoded(b"\xa3abc"), Msgpack::Str(b"abc".to_vec()));
+        assert_eq!(decoded(b"\xd9\x03abc"), Msgpack::Str(b"abc".to_vec()));
+        assert_eq!(decoded(b"\xda\x00\x01a"), Msgpack::Str(b"a".to_vec()));
+        assert_eq!(decoded(b"\xc4\x02\x00\x01"), Msgpack::Bin(vec![0, 1]));
+        assert_eq!(decoded(b"\xd4\x05\x2a"), Msgpack::Ext(5, vec![0x2a]));
+        assert_eq!(decoded(b"\xc7\x01\xff\x2a"), Msgpack::Ext(-1, vec![0x2a]));
+    }
+
+    #[test]
+    fn collections_are_decoded() {
+        assert_eq!(
+            decoded(&[0x92, 0x01, 0xc0]),
+            Msgpack::Array(vec![Msgpack::UInt(1), Msgpack::Nil])
+        );
+        assert_eq!(
+            decoded(&[0xdc, 0x00, 0x01, 0xc3]),
+            Msgpack::Array(vec![Msgpack::Bool(true)])
+        );
+        let map = decoded(b"\x82\xa1a\x01\xa1b\x91\xa1c");
+        assert_eq!(map.get("a"), Some(&Msgpack::UInt(1)));
+        assert_eq!(
+            map.get("b"),
+            Some(&Msgpack::Array(vec![Msgpack::Str(b"c".to_vec())]))
+        );
+        assert_eq!(map.get("c"), None);
+        assert_eq!(decoded(&[0xde, 0x00, 0x00]), Msgpack::Map(vec![]));
+    }
+
+    #[test]
+    fn only_the_first_value_is_decoded() {
+        let (value, used) = decode(&[0x01, 0x02]).ok().unwrap();
+        assert_eq!(value, Msgpack::UInt(1));
+        assert_eq!(used, 1);
+        assert_eq!(
+            decode_all(&[0x01, 0xa1, b'x', 0xc0]).unwrap(),
+            vec![Msgpack::UInt(1), Msgpack::Str(b"x".to_vec()), Msgpack::Nil]
+        );
+    }
+
+    #[test]
+    fn truncated_values_are_incomplete() {
+        for data in [
+            &[][..],
+            &[0xcd, 0x01],
+            b"\xa3ab",
+            &[0x92, 0x01],
+            &[0x81, 0xa1, b'a'],
+            &[0xdb, 0xff, 0xff, 0xff, 0xff],
+        ] {
+            assert!(matches!(decode(data), Err(DecodeError::Incomplete)));
+        }
+        assert!(decode_all(&[0x01, 0xcd]).is_err());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert!(matches!(decode(&[0xc1]), Err(DecodeError::Invalid(_))));
+        let nested = vec![0x91; MAX_DEPTH + 2];
+        assert!(matches!(decode(&nested), Err(DecodeError::Invalid(_))));
+        // A bogus length fails on the missing data rather than allocating it
+        assert!(matches!(
+            decode(&[0xdd, 0xff, 0xff, 0xff, 0xff]),
+            Err(DecodeError::Incomplete)
+        ));
+    }
+
+    #[test]
+    fn values_are_rendered_as_json() {
+        let map =
+            decoded(b"\x83\xa1a\x01\xa1b\x92\xc3\xff\x01\x93\xc0\xcb\x7f\xf8\0\0\0\0\0\0\xa1\xff");
+        assert_eq!(
+            map.to_json(),
+            json!({"a": 1, "b": [true, -1], "1": [null, null, "\u{fffd}"]})
+        );
+        assert_eq!(decoded(b"\xa2\xc3\x28").as_str().unwrap(), "\u{fffd}(");
+    }
+}
@@ -0,0 +1,484 @@
+use regex::{Captures, Regex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::clock::civil_time;
+use crate::host::hostname;
+
+const DATE_FIELDS: &[char] = &['Y', 'm', 'd', 'H', 'M', 'S', 's'];
+const INDEX_KEY: usize = 7;
+const DEFAULT_DATE_FORMAT: &str = "%Y%m%d";
+
+#[derive(Clone, Debug)]
+enum Token {
+    Literal(String),
+    Basename,
+    Index,
+    Date(char),
+    DateSlot,
+    Pid,
+    Hostname,
+}
+
+#[derive(Clone, Debug)]
+pub struct DateFormat {
+    tokens: Vec<Token>,
+}
+
+impl DateFormat {
+    pub fn parse(format: &str) -> Result<DateFormat, String> {
+        let mut tokens = vec![];
+        let mut literal = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                literal.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => literal.push('%'),
+                Some(field) if DATE_FIELDS.contains(&field) => {
+                    if !literal.is_empty() {
+                        tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                    }
+                    tokens.push(Token::Date(field));
+                }
+                Some(other) => {
+                    return Err(format!(
+                    "Unsupported specifier '%{}' in '{}', expected one of %Y %m %d %H %M %S %s %%",
+                    other, format
+                ))
+                }
+                None => return Err(format!("Incomplete specifier at the end of '{}'", format)),
+            }
+        }
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+        if !tokens.iter().any(|token| matches!(token, Token::Date(_))) {
+            return Err(format!("'{}' does not contain any date specifier", format));
+        }
+        if literal_contains_separator(&tokens) {
+            return Err(format!("'{}' must not contain path separators", format));
+        }
+        Ok(DateFormat { tokens })
+    }
+}
+
+fn literal_contains_separator(tokens: &[Token]) -> bool {
+    tokens.iter().any(|token| match token {
+        Token::Literal(literal) => literal.contains('/') || literal.contains('\\'),
+        _ => false,
+    })
+}
+
+#[derive(Clone, Debug)]
+pub struct Template {
+    tokens: Vec<Token>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut tokens = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                tokens.push(Token::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unterminated placeholder in '{}'", template))?;
+            tokens.push(match &rest[start + 1..start + end] {
+                "basename" => Token::Basename,
+                "index" => Token::Index,
+                "date" => Token::DateSlot,
+                "pid" => Token::Pid,
+                "hostname" => Token::Hostname,
+                other => {
+                    return Err(format!(
+                        "Unknown placeholder '{{{}}}' in '{}', expected one of {{basename}} {{index}} {{date}} {{pid}} {{hostname}}",
+                        other, template
+                    ))
+                }
+            });
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            tokens.push(Token::Literal(rest.to_string()));
+        }
+        if !tokens
+            .iter()
+            .any(|token| matches!(token, Token::Index | Token::DateSlot))
+        {
+            return Err(format!(
+                "'{}' must contain {{index}} or {{date}} to tell rotations apart",
+                template
+            ));
+        }
+        if literal_contains_separator(&tokens) {
+            return Err(format!("'{}' must not contain path separators", template));
+        }
+        Ok(Template { tokens })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Numbering {
+    #[default]
+    Increment,
+    Shift,
+    Compact,
+}
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "cli", derive(clap::Args))]
+pub struct NamingArgs {
+    #[cfg_attr(feature = "cli", arg(long, value_parser = DateFormat::parse, help = "Name rotated files after their rotation time with a strftime-like format (%Y %m %d %H %M %S %s), e.g. '%Y-%m-%d_%H%M%S', instead of a numeric index. Also the format of {date} in --rotation-template, which defaults to %Y%m%d"))]
+    rotation_name_format: Option<DateFormat>,
+    #[cfg_attr(feature = "cli", arg(long, value_parser = Template::parse, help = "Template of the rotated file names with the {basename}, {index}, {date}, {pid} and {hostname} placeholders, e.g. '{hostname}-{basename}.{date}.{index}'. The compression extension is appended to it"))]
+    rotation_template: Option<Template>,
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = Numbering::Increment, help = "How rotation indices are assigned: increment gives every new rotation the next index, shift renames every existing rotation N to N+1 so that the newest is always 1, compact renumbers the retained rotations from 1 after every cleanup so that indices never exceed --max-history"))]
+    numbering: Numbering,
+}
+
+#[derive(Clone, Debug)]
+pub struct Naming {
+    tokens: Vec<Token>,
+    numbering: Numbering,
+}
+
+pub struct Scan {
+    pub rotations: Vec<String>,
+    pub next: String,
+}
+
+impl Naming {
+    pub fn new(args: &NamingArgs) -> Naming {
+        let template = match (&args.rotation_template, &args.rotation_name_format) {
+            (Some(template), _) => template.tokens.clone(),
+            (None, Some(_)) => vec![
+                Token::Basename,
+                Token::Literal(".".to_string()),
+                Token::DateSlot,
+            ],
+            (None, None) => vec![
+                Token::Basename,
+                Token::Literal(".".to_string()),
+                Token::Index,
+            ],
+        };
+        let date = match &args.rotation_name_format {
+            Some(format) => format.tokens.clone(),
+            None => DateFormat::parse(DEFAULT_DATE_FORMAT).unwrap().tokens,
+        };
+        let mut tokens = vec![];
+        for token in template {
+            match token {
+                Token::DateSlot => tokens.extend(date.iter().cloned()),
+                other => tokens.push(other),
+            }
+        }
+        Naming {
+            tokens,
+            numbering: args.numbering,
+        }
+    }
+
+    pub fn numbering(&self) -> Numbering {
+        self.numbering
+    }
+
+    pub fn renumbers(&self) -> bool {
+        self.numbering != Numbering::Increment
+    }
+
+    pub fn has_index(&self) -> bool {
+        self.tokens
+            .iter()
+            .any(|token| matches!(token, Token::Index))
+    }
+
+    fn pattern(&self, base_name: &str, extensions: &[&str]) -> Regex {
+        let mut pattern = String::from("^(?<stem>");
+        let mut dates = 0;
+        for token in &self.tokens {
+            match token {
+                Token::Literal(literal) => pattern.push_str(&regex::escape(literal)),
+                Token::Basename => pattern.push_str(&regex::escape(base_name)),
+                Token::Index => pattern.push_str("(?<index>[0-9]+)"),
+                Token::Date(field) => {
+                    let digits = match field {
+                        'Y' => "{4}",
+                        's' => "+",
+                        _ => "{2}",
+                    };
+                    pattern.push_str(&format!("(?<date{}>[0-9]{})", dates, digits));
+                    dates += 1;
+                }
+                Token::Pid => pattern.push_str("[0-9]+"),
+                Token::Hostname => pattern.push_str(&regex::escape(hostname())),
+                Token::DateSlot => {}
+            }
+        }
+        if !self.has_index() {
+            // Rotations stamped within the same period are told apart by a sequence number
+            pattern.push_str("(?:-(?<seq>[0-9]+))?");
+        }
+        let extensions = extensions
+            .iter()
+            .map(|extension| regex::escape(extension))
+            .collect::<Vec<String>>()
+            .join("|");
+        pattern.push_str(&format!(")(?:{})$", extensions));
+        Regex::new(&pattern).unwrap()
+    }
+
+    fn sort_key(&self, capture: &Captures) -> Vec<i64> {
+        let number = |name: &str| {
+            capture
+                .name(name)
+                .and_then(|value| value.as_str().parse::<i64>().ok())
+                .unwrap_or_default()
+        };
+        let mut date = [0i64; INDEX_KEY];
+        let mut dates = 0;
+        for token in &self.tokens {
+            if let Token::Date(field) = token {
+                let position = DATE_FIELDS.iter().position(|f| f == field).unwrap();
+                date[position] = number(&format!("date{}", dates));
+                dates += 1;
+            }
+        }
+        let mut key = date.to_vec();
+        key.push(match self.numbering {
+            Numbering::Increment | Numbering::Compact => number("index"),
+            // Shifted rotations get older as their index grows
+            Numbering::Shift => -number("index"),
+        });
+        key.push(number("seq"));
+        key
+    }
+
+    fn render(&self, base_name: &str, index: i64, time: SystemTime) -> String {
+        let seconds = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let civil = civil_time(seconds);
+        let mut name = String::new();
+        for token in &self.tokens {
+            match token {
+                Token::Literal(literal) => name.push_str(literal),
+                Token::Basename => name.push_str(base_name),
+                Token::Index => name.push_str(&index.to_string()),
+                Token::Date('Y') => name.push_str(&format!("{:04}", civil.year)),
+                Token::Date('m') => name.push_str(&format!("{:02}", civil.month)),
+                Token::Date('d') => name.push_str(&format!("{:02}", civil.day)),
+                Token::Date('H') => name.push_str(&format!("{:02}", civil.hour)),
+                Token::Date('M') => name.push_str(&format!("{:02}", civil.minute)),
+                Token::Date('S') => name.push_str(&format!("{:02}", civil.second)),
+                Token::Date(_) => name.push_str(&seconds.to_string()),
+                Token::Pid => name.push_str(&std::process::id().to_string()),
+                Token::Hostname => name.push_str(hostname()),
+                Token::DateSlot => {}
+            }
+        }
+        name
+    }
+
+    pub fn scan(&self, base_name: &str, names: Vec<String>, extensions: &[&str]) -> Scan {
+        let pattern = self.pattern(base_name, extensions);
+        let mut matched: Vec<(Vec<i64>, String, String)> = names
+            .into_iter()
+            .filter_map(|name| {
+                let capture = pattern.captures(&name)?;
+                let key = self.sort_key(&capture);
+                let stem = capture["stem"].to_string();
+                Some((key, stem, name))
+            })
+            .collect();
+        matched.sort();
+        let next = if self.numbering == Numbering::Shift {
+            self.render(base_name, 1, SystemTime::now())
+        } else if self.has_index() {
+            let maximum = matched
+                .iter()
+                .map(|(key, _, _)| key[INDEX_KEY])
+                .max()
+                .unwrap_or_default();
+            self.render(base_name, maximum + 1, SystemTime::now())
+        } else {
+            let stem = self.render(base_name, 0, SystemTime::now());
+            let taken = |candidate: &str| matched.iter().any(|(_, other, _)| other == candidate);
+            let mut next = stem.clone();
+            let mut sequence = 0;
+            while taken(&next) {
+                sequence += 1;
+                next = format!("{}-{}", stem, sequence);
+            }
+            next
+        };
+        Scan {
+            rotations: matched.into_iter().map(|(_, _, name)| name).collect(),
+            next,
+        }
+    }
+
+    pub fn with_index(
+        &self,
+        base_name: &str,
+        name: &str,
+        extensions: &[&str],
+        index: i64,
+    ) -> Option<String> {
+        let capture = self.pattern(base_name, extensions).captures(name)?;
+        let position = capture.name("index")?;
+        Some(format!(
+            "{}{}{}",
+            &name[..position.start()],
+            index,
+            &name[position.end()..]
+        ))
+    }
+
+    pub fn index_of(&self, base_name: &str, name: &str, extensions: &[&str]) -> Option<i64> {
+        self.pattern(base_name, extensions)
+            .captures(name)?
+            .name("index")?
+            .as_str()
+            .parse()
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const EXTENSIONS: &[&str] = &["", ".gz"];
+
+    fn naming(template: Option<&str>, format: Option<&str>, numbering: Numbering) -> Naming {
+        Naming::new(&NamingArgs {
+            rotation_name_format: format.map(|format| DateFormat::parse(format).unwrap()),
+            rotation_template: template.map(|template| Template::parse(template).unwrap()),
+            numbering,
+        })
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn rotations_are_scanned_in_index_order() {
+        let naming = naming(None, None, Numbering::Increment);
+        let scan = naming.scan(
+            "out.log",
+            names(&[
+                "out.log.10",
+                "out.log.2.gz",
+                "other.log.3",
+                "out.log.x",
+                "out.log.1",
+            ]),
+            EXTENSIONS,
+        );
+        assert_eq!(scan.rotations, ["out.log.1", "out.log.2.gz", "out.log.10"]);
+        assert_eq!(scan.next, "out.log.11");
+    }
+
+    #[test]
+    fn rendered_names_are_scanned_back() {
+        let naming = naming(
+            Some("[{basename}]+(copy){index}.*"),
+            None,
+            Numbering::Increment,
+        );
+        let rendered: Vec<String> = (1..=3)
+            .map(|index| naming.render("app.log", index, SystemTime::now()))
+            .collect();
+        assert_eq!(rendered[0], "[app.log]+(copy)1.*");
+        for (index, name) in (1..).zip(&rendered) {
+            assert_eq!(naming.index_of("app.log", name, EXTENSIONS), Some(index));
+        }
+        // The metacharacters only match themselves
+        let mut candidates = rendered.clone();
+        candidates.extend(names(&[
+            "app.log]+(copy)4.*",
+            "[appXlog]+(copy)5.*",
+            "[app.log]+copy6.*",
+        ]));
+        let scan = naming.scan("app.log", candidates, EXTENSIONS);
+        assert_eq!(scan.rotations, rendered);
+        assert_eq!(scan.next, "[app.log]+(copy)4.*");
+    }
+
+    #[test]
+    fn dated_rotations_are_scanned_in_time_order() {
+        let naming = naming(None, Some("%Y-%m-%d_%H%M%S"), Numbering::Increment);
+        let older = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let newer = older + Duration::from_secs(86400 * 40);
+        let rendered = [
+            naming.render("out.log", 0, older),
+            naming.render("out.log", 0, newer),
+        ];
+        let mut candidates = vec![format!("{}-1", rendered[0])];
+        candidates.extend(rendered.iter().rev().cloned());
+        let scan = naming.scan("out.log", candidates, EXTENSIONS);
+        assert_eq!(
+            scan.rotations,
+            [
+                rendered[0].clone(),
+                format!("{}-1", rendered[0]),
+                rendered[1].clone()
+            ]
+        );
+        assert!(!naming.has_index());
+    }
+
+    #[test]
+    fn rotations_of_the_same_period_get_a_sequence_number() {
+        let naming = naming(None, Some("%Y"), Numbering::Increment);
+        let current = naming.render("out.log", 0, SystemTime::now());
+        let scan = naming.scan("out.log", vec![current.clone()], EXTENSIONS);
+        assert_eq!(scan.next, format!("{}-1", current));
+    }
+
+    #[test]
+    fn shifted_rotations_get_older_as_their_index_grows() {
+        let naming = naming(None, None, Numbering::Shift);
+        let scan = naming.scan(
+            "out.log",
+            names(&["out.log.1", "out.log.3", "out.log.2"]),
+            EXTENSIONS,
+        );
+        assert_eq!(scan.rotations, ["out.log.3", "out.log.2", "out.log.1"]);
+        assert_eq!(scan.next, "out.log.1");
+    }
+
+    #[test]
+    fn indices_are_replaced_in_place() {
+        let naming = naming(Some("{basename}-{index}.old"), None, Numbering::Compact);
+        assert_eq!(
+            naming.with_index("out.log", "out.log-7.old.gz", EXTENSIONS, 2),
+            Some("out.log-2.old.gz".to_string())
+        );
+        assert_eq!(
+            naming.with_index("out.log", "out.log.7", EXTENSIONS, 2),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        assert!(Template::parse("{basename}.log").is_err());
+        assert!(Template::parse("{basename}.{count}").is_err());
+        assert!(Template::parse("{basename}.{index").is_err());
+        assert!(Template::parse("archive/{basename}.{index}").is_err());
+        assert!(DateFormat::parse("%Y-%q").is_err());
+        assert!(DateFormat::parse("rotation").is_err());
+        assert!(DateFormat::parse("%Y%").is_err());
+        assert!(DateFormat::parse("%Y/%m").is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use stdout_rotator::file_size;
+
+// An output file, optionally followed by the sizes that apply to it rather than the global ones
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputSpec {
+    pub path: String,
+    pub max_size: Option<u64>,
+    pub max_history: Option<u32>,
+}
+
+impl OutputSpec {
+    pub fn parse(value: &str) -> Result<OutputSpec, String> {
+        let mut parts = value.split(',');
+        let path = parts.next().unwrap_or_default().trim();
+        if path.is_empty() {
+            return Err(format!("Missing path of output file '{}'", value));
+        }
+        let mut spec = OutputSpec {
+            path: path.to_string(),
+            max_size: None,
+            max_history: None,
+        };
+        for part in parts {
+            match part.trim().split_once('=') {
+                Some(("max-size", size)) => spec.max_size = Some(file_size(size)?),
+                Some(("max-history", history)) => {
+                    spec.max_history = Some(history.parse::<u32>().map_err(|op| {
+                        format!("Invalid max-history '{}' of '{}': {}", history, path, op)
+                    })?)
+                }
+                _ => {
+                    return Err(format!(
+                        "Invalid setting '{}' of output file '{}', expected max-size=<size> or max-history=<count>",
+                        part, path
+                    ))
+                }
+            }
+        }
+        Ok(spec)
+    }
+}
@@ -0,0 +1,109 @@
+use clap::ValueEnum;
+use log::{info, warn};
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use stdout_rotator::metrics::Counters;
+use stdout_rotator::pipeline::Batch;
+
+const LOGGER: &str = "overflow";
+const REPORT_EVERY: u64 = 1000;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    Block,
+    DropOldest,
+}
+
+struct State {
+    queue: VecDeque<Batch>,
+    closed: bool,
+}
+
+pub struct DropOldestQueue {
+    state: Mutex<State>,
+    available: Condvar,
+    capacity: usize,
+    counters: Arc<Counters>,
+}
+
+impl DropOldestQueue {
+    pub fn push(&self, batch: Batch) {
+        let mut state = self.state.lock().unwrap();
+        if state.queue.len() >= self.capacity {
+            state.queue.pop_front();
+            let dropped = self.counters.record_dropped_file_chunk();
+            if dropped % REPORT_EVERY == 1 {
+                warn!(target: LOGGER, "File queue full, {} chunks dropped so far", dropped);
+            }
+        }
+        state.queue.push_back(batch);
+        self.available.notify_one();
+    }
+
+    fn pop(&self) -> Option<Batch> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(batch) = state.queue.pop_front() {
+                return Some(batch);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.available.wait(state).unwrap();
+        }
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.available.notify_all();
+    }
+}
+
+pub struct QueueSender {
+    queue: Arc<DropOldestQueue>,
+}
+
+impl QueueSender {
+    pub fn push(&self, batch: Batch) {
+        self.queue.push(batch);
+    }
+}
+
+impl Drop for QueueSender {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+pub fn start_drop_oldest_bridge(
+    capacity: usize,
+    counters: Arc<Counters>,
+    txfile: Sender<Batch>,
+) -> (QueueSender, Sender<bool>, JoinHandle<()>) {
+    let queue = Arc::new(DropOldestQueue {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            closed: false,
+        }),
+        available: Condvar::new(),
+        capacity: capacity.max(1),
+        counters: counters.clone(),
+    });
+    let (txcomplete, rxcomplete) = mpsc::channel::<bool>();
+    let bridge_queue = queue.clone();
+    let handle = thread::spawn(move || {
+        while let Some(batch) = bridge_queue.pop() {
+            if txfile.send(batch).is_err() || rxcomplete.recv().is_err() {
+                warn!(target: LOGGER, "File writer stopped, discarding queued chunks");
+                break;
+            }
+        }
+        let dropped = counters.snapshot().dropped_file_chunks;
+        if dropped > 0 {
+            info!(target: LOGGER, "{} chunks were dropped for the file under overload", dropped);
+        }
+    });
+    (QueueSender { queue }, txcomplete, handle)
+}
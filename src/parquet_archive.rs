@@ -0,0 +1,135 @@
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use regex::Regex;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::sync::Arc;
+
+use crate::pipeline::Record;
+use crate::timestamps::{TIMESTAMP_FIELDS, TIMESTAMP_PATTERN};
+use crate::RotatorError;
+
+const ROW_GROUP_SIZE: usize = 65536;
+const SCHEMA: &str = "
+message log_line {
+    OPTIONAL BYTE_ARRAY ts (UTF8);
+    OPTIONAL BYTE_ARRAY level (UTF8);
+    REQUIRED BYTE_ARRAY message (UTF8);
+    OPTIONAL BYTE_ARRAY fields (UTF8);
+}
+";
+
+#[derive(Default)]
+struct Columns {
+    ts: Vec<Option<String>>,
+    level: Vec<Option<String>>,
+    message: Vec<Option<String>>,
+    fields: Vec<Option<String>>,
+}
+
+impl Columns {
+    fn push(&mut self, line: Vec<u8>, timestamp_regex: &Regex) {
+        let record = Record::new(line, true);
+        let text = record.text();
+        let entries = match serde_json::from_str::<Value>(&text) {
+            Ok(Value::Object(entries)) => Some(entries),
+            _ => None,
+        };
+        let ts = match &entries {
+            Some(entries) => TIMESTAMP_FIELDS
+                .iter()
+                .find_map(|name| entries.get(*name))
+                .map(|value| match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                }),
+            None => timestamp_regex
+                .captures(&text)
+                .map(|capture| capture["ts"].to_string()),
+        };
+        self.ts.push(ts);
+        self.level.push(record.level());
+        self.fields
+            .push(entries.map(|entries| Value::Object(entries).to_string()));
+        self.message.push(Some(text));
+    }
+
+    fn len(&self) -> usize {
+        self.message.len()
+    }
+}
+
+pub fn write_parquet<R: Read>(input: R, output: File) -> Result<(), RotatorError> {
+    let schema = Arc::new(
+        parse_message_type(SCHEMA).map_err(|op| format!("Invalid parquet schema: {}", op))?,
+    );
+    let properties = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+    let mut writer = SerializedFileWriter::new(output, schema, properties)
+        .map_err(|op| format!("Error while creating parquet writer: {}", op))?;
+    let timestamp_regex = Regex::new(TIMESTAMP_PATTERN).unwrap();
+    let mut reader = BufReader::new(input);
+    let mut columns = Columns::default();
+    loop {
+        let mut line = vec![];
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|op| format!("Error while reading lines to archive: {}", op))?;
+        if read == 0 {
+            break;
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        columns.push(line, &timestamp_regex);
+        if columns.len() >= ROW_GROUP_SIZE {
+            write_row_group(&mut writer, std::mem::take(&mut columns))?;
+        }
+    }
+    if columns.len() > 0 {
+        write_row_group(&mut writer, columns)?;
+    }
+    writer
+        .close()
+        .map_err(|op| format!("Error while finishing parquet file: {}", op))?;
+    Ok(())
+}
+
+fn write_row_group(
+    writer: &mut SerializedFileWriter<File>,
+    columns: Columns,
+) -> Result<(), RotatorError> {
+    let mut row_group = writer
+        .next_row_group()
+        .map_err(|op| format!("Error while starting parquet row group: {}", op))?;
+    for column in [columns.ts, columns.level, columns.message, columns.fields] {
+        let mut column_writer = row_group
+            .next_column()
+            .map_err(|op| format!("Error while starting parquet column: {}", op))?
+            .ok_or_else(|| RotatorError::new("Parquet schema has fewer columns than expected"))?;
+        let definition_levels: Vec<i16> = column.iter().map(|v| i16::from(v.is_some())).collect();
+        let values: Vec<ByteArray> = column
+            .into_iter()
+            .flatten()
+            .map(|v| ByteArray::from(v.into_bytes()))
+            .collect();
+        column_writer
+            .typed::<ByteArrayType>()
+            .write_batch(&values, Some(&definition_levels), None)
+            .map_err(|op| format!("Error while writing parquet column: {}", op))?;
+        column_writer
+            .close()
+            .map_err(|op| format!("Error while closing parquet column: {}", op))?;
+    }
+    row_group
+        .close()
+        .map_err(|op| format!("Error while closing parquet row group: {}", op))?;
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use std::fs;
+use std::path::Path;
+
+use crate::RotatorError;
+
+// Permissions of the files and directories stdout-rotator creates, set explicitly so that
+// the umask does not apply
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Access {
+    pub file_mode: Option<u32>,
+    pub dir_mode: Option<u32>,
+    pub owner: Option<Owner>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Owner {
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+impl Owner {
+    // user, user:group or :group, by name or number
+    pub fn parse(value: &str) -> Result<Owner, String> {
+        let (user, group) = match value.split_once(':') {
+            Some((user, group)) => (user, group),
+            None => (value, ""),
+        };
+        let uid = match user {
+            "" => None,
+            user => Some(lookup_user(user).ok_or_else(|| format!("Unknown user '{}'", user))?),
+        };
+        let gid = match group {
+            "" => None,
+            group => Some(lookup_group(group).ok_or_else(|| format!("Unknown group '{}'", group))?),
+        };
+        if uid.is_none() && gid.is_none() {
+            return Err(format!(
+                "Invalid owner '{}', expected <user>, <user>:<group> or :<group>",
+                value
+            ));
+        }
+        Ok(Owner { uid, gid })
+    }
+}
+
+pub fn parse_mode(value: &str) -> Result<u32, String> {
+    let digits = value.trim().trim_start_matches("0o");
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or_else(|| {
+            format!(
+                "Invalid mode '{}', expected octal permissions as 0640",
+                value
+            )
+        })
+}
+
+impl Access {
+    pub fn apply_file(&self, path: &Path) -> Result<(), RotatorError> {
+        // Changing the owner clears the setuid and setgid bits, so it goes first
+        if let Some(owner) = self.owner {
+            set_owner(path, owner)?;
+        }
+        if let Some(mode) = self.file_mode {
+            set_mode(path, mode)?;
+        }
+        Ok(())
+    }
+
+    // Only the directories created here get the directory mode, existing ones are untouched
+    pub fn create_dirs(&self, path: &Path) -> Result<(), RotatorError> {
+        if path.as_os_str().is_empty() || path.is_dir() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            self.create_dirs(parent)?;
+        }
+        if let Err(op) = fs::create_dir(path) {
+            if op.kind() != std::io::ErrorKind::AlreadyExists {
+                return Err(RotatorError::from(format!(
+                    "Failure during creation of directory '{}': {}",
+                    path.display(),
+                    op
+                )));
+            }
+            return Ok(());
+        }
+        if let Some(owner) = self.owner {
+            set_owner(path, owner)?;
+        }
+        if let Some(mode) = self.dir_mode {
+            set_mode(path, mode)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<(), RotatorError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(|op| {
+        RotatorError::from(format!(
+            "Error while setting mode {:o} of '{}': {}",
+            mode,
+            path.display(),
+            op
+        ))
+    })
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<(), RotatorError> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_owner(path: &Path, owner: Owner) -> Result<(), RotatorError> {
+    std::os::unix::fs::chown(path, owner.uid, owner.gid).map_err(|op| {
+        RotatorError::from(format!(
+            "Error while changing owner of '{}': {}",
+            path.display(),
+            op
+        ))
+    })
+}
+
+#[cfg(not(unix))]
+fn set_owner(_path: &Path, _owner: Owner) -> Result<(), RotatorError> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn lookup_user(user: &str) -> Option<u32> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Some(uid);
+    }
+    let name = std::ffi::CString::new(user).ok()?;
+    let entry = unsafe { libc::getpwnam(name.as_ptr()) };
+    if entry.is_null() {
+        return None;
+    }
+    Some(unsafe { (*entry).pw_uid })
+}
+
+#[cfg(unix)]
+fn lookup_group(group: &str) -> Option<u32> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Some(gid);
+    }
+    let name = std::ffi::CString::new(group).ok()?;
+    let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+    if entry.is_null() {
+        return None;
+    }
+    Some(unsafe { (*entry).gr_gid })
+}
+
+#[cfg(not(unix))]
+fn lookup_user(user: &str) -> Option<u32> {
+    user.parse::<u32>().ok()
+}
+
+#[cfg(not(unix))]
+fn lookup_group(group: &str) -> Option<u32> {
+    group.parse::<u32>().ok()
+}
@@ -0,0 +1,233 @@
+use regex::bytes::Regex;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+pub type Fields = Map<String, Value>;
+
+const LEVEL_FIELDS: &[&str] = &["level", "severity", "loglevel", "lvl"];
+const LEVEL_SCAN_TOKENS: usize = 5;
+const MULTILINE_IDLE: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub data: Vec<u8>,
+    pub terminated: bool,
+    pub fields: Fields,
+    pub received: SystemTime,
+}
+
+impl Record {
+    pub fn new(data: Vec<u8>, terminated: bool) -> Record {
+        Record {
+            data,
+            terminated,
+            fields: Fields::new(),
+            received: SystemTime::now(),
+        }
+    }
+
+    pub fn with_data(self, data: Vec<u8>) -> Record {
+        Record { data, ..self }
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.data)
+            .trim_end_matches('\r')
+            .to_string()
+    }
+
+    pub fn level(&self) -> Option<String> {
+        if let Some(level) = level_from_fields(&self.fields) {
+            return Some(level);
+        }
+        let text = self.text();
+        if text.starts_with('{') {
+            if let Ok(Value::Object(entries)) = serde_json::from_str::<Value>(&text) {
+                return level_from_fields(&entries);
+            }
+        }
+        text.split_whitespace()
+            .take(LEVEL_SCAN_TOKENS)
+            .find_map(|token| {
+                let token = match token.split_once('=') {
+                    Some((key, value)) if LEVEL_FIELDS.contains(&key.to_lowercase().as_str()) => {
+                        value
+                    }
+                    Some(_) => return None,
+                    None if token.chars().any(|c| c.is_lowercase()) => return None,
+                    None => token,
+                };
+                normalise_level(token.trim_matches(|c: char| !c.is_alphanumeric()))
+            })
+    }
+}
+
+fn level_from_fields(fields: &Fields) -> Option<String> {
+    LEVEL_FIELDS
+        .iter()
+        .find_map(|name| match fields.get(*name) {
+            Some(Value::String(level)) => {
+                Some(normalise_level(level).unwrap_or_else(|| level.to_uppercase()))
+            }
+            _ => None,
+        })
+}
+
+pub fn normalise_level(level: &str) -> Option<String> {
+    let normalised = match level.to_uppercase().as_str() {
+        "TRACE" => "TRACE",
+        "DEBUG" => "DEBUG",
+        "INFO" => "INFO",
+        "NOTICE" => "NOTICE",
+        "WARN" | "WARNING" => "WARN",
+        "ERR" | "ERROR" => "ERROR",
+        "CRIT" | "CRITICAL" => "CRITICAL",
+        "FATAL" | "EMERG" | "EMERGENCY" | "ALERT" => "FATAL",
+        _ => return None,
+    };
+    Some(normalised.to_string())
+}
+
+pub type Batch = Arc<Vec<Record>>;
+
+pub trait Stage: Send {
+    fn apply(&mut self, record: Record) -> Option<Record>;
+}
+
+#[derive(Default)]
+pub struct LineFramer {
+    pending: Vec<u8>,
+    start: Option<Regex>,
+    grouped: Option<Record>,
+    updated: Option<Instant>,
+}
+
+impl LineFramer {
+    pub fn new() -> LineFramer {
+        LineFramer {
+            pending: vec![],
+            start: None,
+            grouped: None,
+            updated: None,
+        }
+    }
+
+    // Lines not matching the start of a record, such as the frames of a stack trace, are
+    // appended to the record before them
+    pub fn multiline(start: Regex) -> LineFramer {
+        LineFramer {
+            start: Some(start),
+            ..LineFramer::new()
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Record> {
+        let mut records = vec![];
+        let mut start = 0;
+        for (i, byte) in chunk.iter().enumerate() {
+            if *byte == b'\n' {
+                let mut data = std::mem::take(&mut self.pending);
+                data.extend_from_slice(&chunk[start..i]);
+                self.push(Record::new(data, true), &mut records);
+                start = i + 1;
+            }
+        }
+        self.pending.extend_from_slice(&chunk[start..]);
+        records
+    }
+
+    fn push(&mut self, line: Record, records: &mut Vec<Record>) {
+        let Some(start) = &self.start else {
+            records.push(line);
+            return;
+        };
+        self.updated = Some(Instant::now());
+        match self.grouped.as_mut() {
+            Some(grouped) if !start.is_match(&line.data) => {
+                grouped.data.push(b'\n');
+                grouped.data.extend_from_slice(&line.data);
+                grouped.terminated = line.terminated;
+            }
+            _ => {
+                if let Some(grouped) = self.grouped.replace(line) {
+                    records.push(grouped);
+                }
+            }
+        }
+    }
+
+    // A grouped record is complete once no continuation line arrived for a while
+    pub fn flush(&mut self) -> Option<Record> {
+        let idle = self
+            .updated
+            .is_some_and(|updated| updated.elapsed() >= MULTILINE_IDLE);
+        if idle && self.pending.is_empty() {
+            self.grouped.take()
+        } else {
+            None
+        }
+    }
+
+    pub fn finish(&mut self) -> Vec<Record> {
+        let mut records = vec![];
+        if !self.pending.is_empty() {
+            let line = Record::new(std::mem::take(&mut self.pending), false);
+            self.push(line, &mut records);
+        }
+        records.extend(self.grouped.take());
+        records
+    }
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline { stages: vec![] }
+    }
+
+    pub fn push(&mut self, stage: Box<dyn Stage>) {
+        self.stages.push(stage);
+    }
+
+    pub fn extend(&mut self, other: Pipeline) {
+        self.stages.extend(other.stages);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    pub fn apply(&mut self, records: &[Record]) -> Vec<Record> {
+        records
+            .iter()
+            .filter_map(|record| {
+                self.stages
+                    .iter_mut()
+                    .try_fold(record.clone(), |current, stage| stage.apply(current))
+            })
+            .collect()
+    }
+
+    pub fn render(&mut self, records: &[Record]) -> Vec<u8> {
+        if self.stages.is_empty() {
+            return render(records);
+        }
+        render(&self.apply(records))
+    }
+}
+
+pub fn render(records: &[Record]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(records.iter().map(|r| r.data.len() + 1).sum());
+    for record in records {
+        output.extend_from_slice(&record.data);
+        if record.terminated {
+            output.push(b'\n');
+        }
+    }
+    output
+}
@@ -0,0 +1,53 @@
+use stdout_rotator::host::hostname;
+use stdout_rotator::pipeline::{Record, Stage};
+
+#[derive(Clone, Debug)]
+pub struct Prefix {
+    expanded: String,
+}
+
+impl Prefix {
+    pub fn parse(template: &str) -> Result<Prefix, String> {
+        let mut expanded = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            expanded.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| format!("Unclosed placeholder in prefix '{}'", template))?;
+            match &rest[start + 1..end] {
+                "hostname" => expanded.push_str(hostname()),
+                "pid" => expanded.push_str(&std::process::id().to_string()),
+                other => {
+                    return Err(format!(
+                        "Unknown placeholder '{{{}}}' in prefix, expected {{hostname}} or {{pid}}",
+                        other
+                    ))
+                }
+            }
+            rest = &rest[end + 1..];
+        }
+        expanded.push_str(rest);
+        Ok(Prefix { expanded })
+    }
+}
+
+pub struct PrefixStage {
+    prefix: Prefix,
+}
+
+impl PrefixStage {
+    pub fn new(prefix: Prefix) -> PrefixStage {
+        PrefixStage { prefix }
+    }
+}
+
+impl Stage for PrefixStage {
+    fn apply(&mut self, record: Record) -> Option<Record> {
+        let mut data = Vec::with_capacity(self.prefix.expanded.len() + record.data.len());
+        data.extend_from_slice(self.prefix.expanded.as_bytes());
+        data.extend_from_slice(&record.data);
+        Some(record.with_data(data))
+    }
+}
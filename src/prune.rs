@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use stdout_rotator::naming::{Naming, NamingArgs, Numbering};
+use stdout_rotator::retention::KeepPolicy;
+use stdout_rotator::{
+    all_rotations, cleanup_rotations, duration, file_size, next_file, renumber_rotations,
+    RotationConfig, RotatorError,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct PruneArgs {
+    #[arg(help = "File whose rotations are pruned. The file itself is never removed")]
+    file: String,
+    #[arg(long, default_value = None, help = "Directory where rotated files are saved. If not provided, the same directory of the file will be used")]
+    rotation_directory: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
+    #[arg(short, long, help = "Maximum number of rotated files retained")]
+    max_history: Option<u32>,
+    #[arg(long, value_parser = KeepPolicy::parse, conflicts_with = "max_history", help = "Thin rotations over time instead of keeping the last --max-history ones, keeping the newest rotation of each of the most recent periods of every tier, e.g. 'daily=7,weekly=4,monthly=12'. Tiers are hourly, daily, weekly, monthly and yearly")]
+    keep: Option<KeepPolicy>,
+    #[arg(long, value_parser = file_size, help = "Maximum combined size of the rotated files retained. The oldest rotations are removed first")]
+    max_total_size: Option<u64>,
+    #[arg(long, value_parser = duration, help = "Remove rotated files last modified longer ago than this duration (e.g. 14d)")]
+    max_age: Option<Duration>,
+}
+
+pub fn run(args: PruneArgs) -> Result<(), RotatorError> {
+    if args.max_history.is_none()
+        && args.keep.is_none()
+        && args.max_total_size.is_none()
+        && args.max_age.is_none()
+    {
+        return Err(RotatorError::new(
+            "Nothing to prune without --max-history, --keep, --max-total-size or --max-age",
+        ));
+    }
+    let mut config = RotationConfig::new(&args.file);
+    // Without --max-history, rotations are only removed by the other policies
+    config.max_history = args.max_history.unwrap_or(u32::MAX);
+    config.keep = args.keep;
+    config.max_total_size = args.max_total_size;
+    config.max_age = args.max_age;
+    config.rotation_directory = args.rotation_directory;
+    config.naming = Naming::new(&args.naming);
+    let rotations = || {
+        all_rotations(
+            &config.output_file,
+            config.rotation_directory.as_deref(),
+            &config.naming,
+        )
+    };
+    let before: Vec<PathBuf> = rotations()?;
+    let rotation_result = next_file(
+        &config.extension(),
+        &config.output_file,
+        config.rotation_directory.as_deref(),
+        &config.naming,
+    )?;
+    cleanup_rotations(config.max_history, 0, &config, &rotation_result)?;
+    let after = rotations()?;
+    for path in before.iter().filter(|path| !after.contains(path)) {
+        println!("removed {}", path.display());
+    }
+    if config.naming.numbering() == Numbering::Compact {
+        renumber_rotations(&config)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use stdout_rotator::checksum::{checksum_path, rotation_checksum, write_checksum};
+use stdout_rotator::compress::{compressor, open_rotation, rotation_format, CompressionFormat};
+use stdout_rotator::latest::retarget_latest;
+use stdout_rotator::manifest::{manifest_path, replace_rotation, update_manifest};
+use stdout_rotator::naming::{Naming, NamingArgs};
+use stdout_rotator::{all_rotations, move_sidecars, RotatorError};
+
+const PARTIAL_EXTENSION: &str = ".partial";
+// Rotations which cannot be read back as text
+const OPAQUE_EXTENSIONS: &[&str] = &[".age", ".gpg", ".parquet"];
+
+#[derive(clap::Args, Debug)]
+pub struct CompressArgs {
+    #[arg(
+        help = "File whose rotations are compressed in place, keeping their names and indices. The file itself is left as it is"
+    )]
+    file: String,
+    #[arg(long, default_value = None, help = "Directory where rotated files are saved. If not provided, the same directory of the file will be used")]
+    rotation_directory: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
+    #[arg(
+        long,
+        value_enum,
+        help = "Compression applied to the rotations, which are recompressed when compressed with another one"
+    )]
+    format: CompressionFormat,
+    #[arg(
+        long,
+        help = "Compression level, or preset for xz. Defaults to 6 for gzip and xz, 3 for zstd and 0 for lz4"
+    )]
+    compress_level: Option<u32>,
+}
+
+fn compress_rotation(
+    args: &CompressArgs,
+    source: &Path,
+    target: &Path,
+) -> Result<(), RotatorError> {
+    let mut partial = target.as_os_str().to_owned();
+    partial.push(PARTIAL_EXTENSION);
+    let partial = PathBuf::from(partial);
+    let metadata = fs::metadata(source).map_err(|op| {
+        format!(
+            "Error while reading metadata of '{}': {}",
+            source.display(),
+            op
+        )
+    })?;
+    let compressor = compressor(args.format, args.compress_level)?;
+    let mut input = open_rotation(source)?;
+    let output = File::create(&partial)
+        .map_err(|op| format!("Error during opening of '{}': {}", partial.display(), op))?;
+    let result = output
+        .set_permissions(metadata.permissions())
+        .and_then(|_| output.try_clone())
+        .and_then(|clone| compressor.compress(&mut input, clone))
+        // Retention goes by the modification time, which stays the one of the rotation
+        .and_then(|_| metadata.modified())
+        .and_then(|modified| output.set_modified(modified));
+    if let Err(op) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(RotatorError::from(format!(
+            "Error while compressing '{}': {}",
+            source.display(),
+            op
+        )));
+    }
+    fs::rename(&partial, target).map_err(|op| {
+        format!(
+            "Error while renaming '{}' to '{}': {}",
+            partial.display(),
+            target.display(),
+            op
+        )
+    })?;
+    let checksum = checksum_path(source).exists();
+    move_sidecars(source, target)?;
+    replace_rotation(Some(&args.file), source, target)?;
+    // The checksum of the previous file was moved along with the other sidecars
+    if checksum {
+        write_checksum(target)?;
+    }
+    if manifest_path(&args.file).exists() {
+        let sha256 = rotation_checksum(target)?;
+        update_manifest(&args.file, false, |manifest| {
+            if let Some(entry) = manifest.entry_mut(target) {
+                entry.stored_as(target, Some(sha256));
+            }
+        })?;
+    }
+    retarget_latest(&args.file, source, target)
+}
+
+pub fn run(args: CompressArgs) -> Result<(), RotatorError> {
+    // Also checked before anything is written
+    compressor(args.format, args.compress_level)?;
+    let rotations = all_rotations(
+        &args.file,
+        args.rotation_directory.as_deref(),
+        &Naming::new(&args.naming),
+    )?;
+    for rotation in rotations {
+        let name = rotation.file_name().unwrap().to_string_lossy().to_string();
+        if OPAQUE_EXTENSIONS
+            .iter()
+            .any(|extension| name.ends_with(extension))
+        {
+            println!("skipped {}", rotation.display());
+            continue;
+        }
+        let current = rotation_format(&rotation);
+        if current == Some(args.format) {
+            continue;
+        }
+        let stem = match current {
+            Some(format) => name.strip_suffix(format.extension()).unwrap(),
+            None => &name,
+        };
+        let target = rotation.with_file_name(format!("{}{}", stem, args.format.extension()));
+        compress_rotation(&args, &rotation, &target)?;
+        println!("compressed {} to {}", rotation.display(), target.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use regex::bytes::Regex;
+use stdout_rotator::pipeline::{Record, Stage};
+
+#[derive(Clone, Debug)]
+pub struct Redaction {
+    pattern: Regex,
+    replacement: String,
+}
+
+impl Redaction {
+    pub fn parse(value: &str) -> Result<Redaction, String> {
+        let (pattern, replacement) = value.split_once("=>").ok_or_else(|| {
+            format!(
+                "Invalid redaction '{}', expected <pattern>=><replacement>",
+                value
+            )
+        })?;
+        let pattern = Regex::new(pattern)
+            .map_err(|op| format!("Invalid redaction pattern '{}': {}", pattern, op))?;
+        Ok(Redaction {
+            pattern,
+            replacement: replacement.to_string(),
+        })
+    }
+}
+
+pub struct RedactStage {
+    redactions: Vec<Redaction>,
+}
+
+impl RedactStage {
+    pub fn new(redactions: Vec<Redaction>) -> RedactStage {
+        RedactStage { redactions }
+    }
+}
+
+impl Stage for RedactStage {
+    fn apply(&mut self, mut record: Record) -> Option<Record> {
+        let mut data = std::mem::take(&mut record.data);
+        for redaction in &self.redactions {
+            if redaction.pattern.is_match(&data) {
+                data = redaction
+                    .pattern
+                    .replace_all(&data, redaction.replacement.as_bytes())
+                    .into_owned();
+            }
+        }
+        Some(record.with_data(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redacted(redactions: &[&str], line: &str) -> String {
+        let redactions = redactions
+            .iter()
+            .map(|redaction| Redaction::parse(redaction).unwrap())
+            .collect();
+        let record = Record::new(line.as_bytes().to_vec(), true);
+        RedactStage::new(redactions).apply(record).unwrap().text()
+    }
+
+    #[test]
+    fn every_match_is_replaced() {
+        assert_eq!(
+            redacted(&["token=\\w+=>token=XXX"], "token=abc user=me token=def"),
+            "token=XXX user=me token=XXX"
+        );
+    }
+
+    #[test]
+    fn replacements_refer_to_groups() {
+        assert_eq!(
+            redacted(
+                &["(\\d{4})-\\d{4}-\\d{4}-(\\d{4})=>$1-****-****-$2"],
+                "card 1234-5678-9012-3456"
+            ),
+            "card 1234-****-****-3456"
+        );
+    }
+
+    #[test]
+    fn redactions_apply_in_order() {
+        assert_eq!(
+            redacted(&["secret=>hidden", "hidden=>[removed]"], "a secret value"),
+            "a [removed] value"
+        );
+    }
+
+    #[test]
+    fn lines_without_matches_are_unchanged() {
+        assert_eq!(
+            redacted(&["token=\\w+=>token=XXX"], "plain line"),
+            "plain line"
+        );
+    }
+
+    #[test]
+    fn invalid_redactions_are_rejected() {
+        assert!(Redaction::parse("token=\\w+").is_err());
+        assert!(Redaction::parse("(unclosed=>x").is_err());
+        assert!(Redaction::parse("a=>b=>c").is_ok());
+    }
+}
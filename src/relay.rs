@@ -0,0 +1,127 @@
+use clap::{Command, Id};
+use log::info;
+use stdout_rotator::{AsyncRotatingWriter, RotationConfig, RotatorBuilder, RotatorError};
+use tokio::io::{self, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::cli::Mirror;
+
+const LOGGER: &str = "relay";
+// The options the builder of the library supports, every other one is rejected with the relay
+const RELAYED_OPTIONS: &[&str] = &[
+    "async_relay",
+    "append",
+    "buffer_size",
+    "compress",
+    "compress_level",
+    "config",
+    "gunzip",
+    "help",
+    "log_config",
+    "max_age",
+    "max_file_age",
+    "max_history",
+    "max_lines",
+    "max_size",
+    "max_total_size",
+    "mirror",
+    "no_stdout",
+    "output_file",
+    "rotate_every",
+    "version",
+];
+
+pub fn restrict(command: Command) -> Command {
+    let unsupported: Vec<Id> = command
+        .get_arguments()
+        .map(|arg| arg.get_id().clone())
+        .filter(|id| !RELAYED_OPTIONS.contains(&id.as_str()))
+        .collect();
+    command.mut_arg("async_relay", |arg| arg.conflicts_with_all(unsupported))
+}
+
+fn builder(config: &RotationConfig) -> RotatorBuilder {
+    let mut builder = RotatorBuilder::new(&config.output_file)
+        .max_size(config.max_size)
+        .max_history(config.max_history)
+        .append(config.append);
+    if let Some(max_total_size) = config.max_total_size {
+        builder = builder.max_total_size(max_total_size);
+    }
+    if let Some(max_age) = config.max_age {
+        builder = builder.max_age(max_age);
+    }
+    if let Some(interval) = config.rotate_every {
+        builder = builder.rotate_every(interval);
+    }
+    if let Some(age) = config.max_file_age {
+        builder = builder.max_file_age(age);
+    }
+    if let Some(lines) = config.max_lines {
+        builder = builder.max_lines(lines);
+    }
+    if let Some(format) = config.compression {
+        builder = builder.compression(format);
+    }
+    if let Some(level) = config.compression_level {
+        builder = builder.compression_level(level);
+    }
+    builder
+}
+
+async fn relay(
+    writer: &mut AsyncRotatingWriter,
+    mut mirror: Option<Box<dyn AsyncWrite + Unpin>>,
+    buffer_size: usize,
+) -> Result<(), RotatorError> {
+    let mut stdin = io::stdin();
+    let mut buffer = vec![0u8; buffer_size.max(1)];
+    loop {
+        let read = stdin
+            .read(&mut buffer)
+            .await
+            .map_err(|op| format!("Error while reading from stdin: {}", op))?;
+        if read == 0 {
+            break;
+        }
+        if let Some(mirror) = mirror.as_mut() {
+            let replicated = match mirror.write_all(&buffer[..read]).await {
+                Ok(()) => mirror.flush().await,
+                Err(op) => Err(op),
+            };
+            replicated.map_err(|op| format!("Error while replicating the input: {}", op))?;
+        }
+        writer
+            .write_all(&buffer[..read])
+            .await
+            .map_err(|op| format!("Error while writing to the output file: {}", op))?;
+    }
+    writer
+        .shutdown()
+        .await
+        .map_err(|op| RotatorError::from(format!("Error while closing the output file: {}", op)))
+}
+
+// Relays the standard input through the AsyncRotatingWriter on a current thread tokio runtime,
+// with the rotation options of the library
+pub fn run(
+    config: &RotationConfig,
+    mirror: Option<Mirror>,
+    buffer_size: u32,
+) -> Result<i32, RotatorError> {
+    info!(target: LOGGER, "Relaying the input to '{}' asynchronously", config.output_file);
+    let mut writer = AsyncRotatingWriter::new(
+        builder(config)
+            .build()
+            .map_err(|op| RotatorError::from(op.to_string()))?,
+    );
+    let mirror: Option<Box<dyn AsyncWrite + Unpin>> = match mirror {
+        None => None,
+        Some(Mirror::Stdout) => Some(Box::new(io::stdout())),
+        Some(Mirror::Stderr) => Some(Box::new(io::stderr())),
+    };
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .map_err(|op| format!("Error while starting the tokio runtime: {}", op))?;
+    runtime.block_on(relay(&mut writer, mirror, buffer_size as usize))?;
+    Ok(0)
+}
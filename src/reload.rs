@@ -0,0 +1,149 @@
+use log::{error, info};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::compress::{compressor, CompressionFormat};
+use crate::retention::KeepPolicy;
+use crate::space::FreeSpace;
+use crate::{RotationConfig, RotatorError};
+
+const LOGGER: &str = "reload";
+
+// The settings a running writer can take on without reopening its output
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    max_size: u64,
+    max_history: u32,
+    keep: Option<KeepPolicy>,
+    max_total_size: Option<u64>,
+    max_age: Option<Duration>,
+    min_free_space: Option<FreeSpace>,
+    compression: Option<CompressionFormat>,
+    compression_level: Option<u32>,
+}
+
+impl Settings {
+    pub fn of(config: &RotationConfig) -> Settings {
+        Settings {
+            max_size: config.max_size,
+            max_history: config.max_history,
+            keep: config.keep.clone(),
+            max_total_size: config.max_total_size,
+            max_age: config.max_age,
+            min_free_space: config.min_free_space,
+            compression: config.compression,
+            compression_level: config.compression_level,
+        }
+    }
+
+    // Only what changed is applied, so settings specific to a capture or route survive a
+    // reload that does not touch them
+    fn apply(&self, previous: &Settings, config: &mut RotationConfig) {
+        if self.max_size != previous.max_size {
+            config.max_size = self.max_size;
+        }
+        if self.max_history != previous.max_history {
+            config.max_history = self.max_history;
+        }
+        if self.keep != previous.keep {
+            config.keep = self.keep.clone();
+        }
+        if self.max_total_size != previous.max_total_size {
+            config.max_total_size = self.max_total_size;
+        }
+        if self.max_age != previous.max_age {
+            config.max_age = self.max_age;
+        }
+        if self.min_free_space != previous.min_free_space {
+            config.min_free_space = self.min_free_space;
+        }
+        if self.compression != previous.compression {
+            config.compression = self.compression;
+        }
+        if self.compression_level != previous.compression_level {
+            config.compression_level = self.compression_level;
+        }
+    }
+}
+
+// Where the settings are read again from, such as the command line and its configuration file
+pub trait Source: Debug + Send + Sync {
+    fn settings(&self) -> Result<Settings, RotatorError>;
+}
+
+#[derive(Debug)]
+pub struct Reloader {
+    source: Box<dyn Source>,
+    generation: AtomicU64,
+    settings: Mutex<Arc<Settings>>,
+}
+
+impl Reloader {
+    pub fn new(source: Box<dyn Source>, config: &RotationConfig) -> Reloader {
+        Reloader {
+            source,
+            generation: AtomicU64::new(0),
+            settings: Mutex::new(Arc::new(Settings::of(config))),
+        }
+    }
+
+    fn parse(&self) -> Result<Settings, RotatorError> {
+        let settings = self.source.settings()?;
+        if let Some(format) = settings.compression {
+            compressor(format, settings.compression_level)?;
+        }
+        Ok(settings)
+    }
+
+    // A configuration that does not parse is reported and the running one is kept
+    pub fn reload(&self) {
+        let settings = match self.parse() {
+            Ok(settings) => settings,
+            Err(err) => {
+                error!(target: LOGGER, "Keeping the current configuration: {}", err.msg);
+                return;
+            }
+        };
+        let mut current = self.settings.lock().unwrap();
+        if **current == settings {
+            info!(target: LOGGER, "Configuration reloaded, no changes to apply");
+            return;
+        }
+        info!(target: LOGGER, "Configuration reloaded, applying {:?}", settings);
+        *current = Arc::new(settings);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+// Every writer tracks the settings it last applied
+pub struct ReloadCursor {
+    reloader: Arc<Reloader>,
+    generation: u64,
+    settings: Arc<Settings>,
+}
+
+impl ReloadCursor {
+    pub fn new(reloader: Arc<Reloader>) -> ReloadCursor {
+        let generation = reloader.generation.load(Ordering::Acquire);
+        let settings = reloader.settings.lock().unwrap().clone();
+        ReloadCursor {
+            reloader,
+            generation,
+            settings,
+        }
+    }
+
+    pub fn update(&mut self, config: &mut RotationConfig) -> bool {
+        let generation = self.reloader.generation.load(Ordering::Acquire);
+        if generation == self.generation {
+            return false;
+        }
+        let settings = self.reloader.settings.lock().unwrap().clone();
+        settings.apply(&self.settings, config);
+        self.generation = generation;
+        self.settings = settings;
+        true
+    }
+}
@@ -0,0 +1,138 @@
+use regex::Regex;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use stdout_rotator::compress::open_rotation;
+use stdout_rotator::naming::{Naming, NamingArgs};
+use stdout_rotator::timestamps::{line_time, TIMESTAMP_PATTERN};
+use stdout_rotator::{all_rotations, duration, RotatorError};
+
+#[derive(clap::Args, Debug)]
+pub struct ReplayArgs {
+    #[arg(help = "File printed after its rotations, oldest first")]
+    file: String,
+    #[arg(long, default_value = None, help = "Directory where rotated files are saved. If not provided, the same directory of the file will be used")]
+    rotation_directory: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
+    #[arg(long, value_parser = speed, help = "Pace the lines after the timestamps they start with or hold in a JSON ts, time, timestamp or @timestamp field, this many times faster than they were written, e.g. 1x for the original pace or 2x. Lines without a timestamp are printed right away. Without it, lines are printed as fast as possible")]
+    speed: Option<f64>,
+    #[arg(long, value_parser = duration, requires = "speed", help = "Longest wait between two lines, shortening the idle periods of the replay")]
+    max_delay: Option<Duration>,
+}
+
+fn speed(value: &str) -> Result<f64, String> {
+    let trimmed = value.trim();
+    let factor = trimmed
+        .strip_suffix(['x', 'X'])
+        .unwrap_or(trimmed)
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid speed '{}', expected e.g. 2x or 0.5x", value))?;
+    if !factor.is_finite() || factor <= 0.0 {
+        return Err(format!("Speed '{}' must be above 0", value));
+    }
+    Ok(factor)
+}
+
+// When every line is due, following the gaps between their timestamps
+struct Pacer {
+    speed: f64,
+    max_delay: Option<Duration>,
+    previous: Option<SystemTime>,
+    due: Instant,
+}
+
+impl Pacer {
+    fn new(speed: f64, max_delay: Option<Duration>) -> Pacer {
+        Pacer {
+            speed,
+            max_delay,
+            previous: None,
+            due: Instant::now(),
+        }
+    }
+
+    fn wait(&mut self, time: SystemTime) {
+        if let Some(previous) = self.previous {
+            // Lines out of order are printed right away
+            let gap = time.duration_since(previous).unwrap_or_default();
+            let mut delay = gap.div_f64(self.speed);
+            if let Some(max_delay) = self.max_delay {
+                delay = delay.min(max_delay);
+            }
+            self.due += delay;
+        } else {
+            self.due = Instant::now();
+        }
+        self.previous = Some(time);
+        let now = Instant::now();
+        if self.due > now {
+            thread::sleep(self.due - now);
+        }
+    }
+}
+
+pub fn run(args: ReplayArgs) -> Result<(), RotatorError> {
+    let mut files: Vec<PathBuf> = all_rotations(
+        &args.file,
+        args.rotation_directory.as_deref(),
+        &Naming::new(&args.naming),
+    )?;
+    let active = PathBuf::from(&args.file);
+    if active.exists() || files.is_empty() {
+        files.push(active);
+    }
+    // Every file is opened first, so that an unreadable one fails before anything is printed
+    let mut readers = vec![];
+    for path in &files {
+        readers.push(open_rotation(path)?);
+    }
+    let timestamp_regex = Regex::new(TIMESTAMP_PATTERN).unwrap();
+    let mut pacer = args.speed.map(|speed| Pacer::new(speed, args.max_delay));
+    let mut stdout = io::stdout().lock();
+    let mut line = vec![];
+    for (path, reader) in files.iter().zip(readers) {
+        let mut reader = BufReader::new(reader);
+        loop {
+            line.clear();
+            let read = reader
+                .read_until(b'\n', &mut line)
+                .map_err(|op| format!("Error while reading '{}': {}", path.display(), op))?;
+            if read == 0 {
+                break;
+            }
+            if let Some(pacer) = pacer.as_mut() {
+                if let Some(time) = line_time(&line, &timestamp_regex) {
+                    pacer.wait(time);
+                }
+            }
+            stdout
+                .write_all(&line)
+                .and_then(|_| match pacer {
+                    Some(_) => stdout.flush(),
+                    None => Ok(()),
+                })
+                .map_err(|op| format!("Error while printing '{}': {}", path.display(), op))?;
+        }
+    }
+    stdout
+        .flush()
+        .map_err(|op| format!("Error while flushing stdout: {}", op))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speeds_are_positive_factors() {
+        assert_eq!(speed("2x"), Ok(2.0));
+        assert_eq!(speed("0.5X"), Ok(0.5));
+        assert_eq!(speed(" 3 "), Ok(3.0));
+        for invalid in ["0x", "-1x", "x", "fast", "infx", "NaN"] {
+            assert!(speed(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::clock::civil_time;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tier {
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Tier {
+    fn period(&self, time: SystemTime) -> (i64, u32, u32, u32) {
+        let seconds = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let civil = civil_time(seconds);
+        match self {
+            Tier::Hourly => (civil.year, civil.month, civil.day, civil.hour),
+            Tier::Daily => (civil.year, civil.month, civil.day, 0),
+            Tier::Weekly => {
+                // Weeks start on Monday and are identified by the date of that day
+                let since_monday = (civil.weekday as i64 + 6) % 7;
+                let monday = civil_time(seconds - since_monday * 86400);
+                (monday.year, monday.month, monday.day, 0)
+            }
+            Tier::Monthly => (civil.year, civil.month, 0, 0),
+            Tier::Yearly => (civil.year, 0, 0, 0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeepPolicy {
+    tiers: Vec<(Tier, usize)>,
+}
+
+impl KeepPolicy {
+    pub fn parse(expression: &str) -> Result<KeepPolicy, String> {
+        let mut tiers = vec![];
+        for part in expression.split(',').map(str::trim) {
+            let (name, count) = part.split_once('=').ok_or_else(|| {
+                format!("Invalid retention tier '{}', expected <tier>=<count>", part)
+            })?;
+            let tier = match name.trim() {
+                "hourly" => Tier::Hourly,
+                "daily" => Tier::Daily,
+                "weekly" => Tier::Weekly,
+                "monthly" => Tier::Monthly,
+                "yearly" => Tier::Yearly,
+                other => {
+                    return Err(format!(
+                        "Unknown retention tier '{}', expected one of hourly daily weekly monthly yearly",
+                        other
+                    ))
+                }
+            };
+            let count = count
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid count '{}' of retention tier '{}'", count, name))?;
+            if tiers.iter().any(|(other, _)| *other == tier) {
+                return Err(format!("Retention tier '{}' is repeated", name));
+            }
+            tiers.push((tier, count));
+        }
+        Ok(KeepPolicy { tiers })
+    }
+
+    // Every tier keeps the newest rotation of each of its most recent periods, as with
+    // grandfather-father-son backup rotation. Times are ordered from the oldest.
+    pub fn retained(&self, times: &[SystemTime]) -> Vec<bool> {
+        let mut retained = vec![false; times.len()];
+        for (tier, count) in &self.tiers {
+            let mut last = None;
+            let mut kept = 0;
+            for position in (0..times.len()).rev() {
+                if kept == *count {
+                    break;
+                }
+                let period = tier.period(times[position]);
+                if last != Some(period) {
+                    last = Some(period);
+                    retained[position] = true;
+                    kept += 1;
+                }
+            }
+        }
+        retained
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Periods follow the local time, as the rotations do
+    fn local(year: i32, month: i32, day: i32, hour: i32, minute: i32) -> SystemTime {
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        tm.tm_year = year - 1900;
+        tm.tm_mon = month - 1;
+        tm.tm_mday = day;
+        tm.tm_hour = hour;
+        tm.tm_min = minute;
+        tm.tm_isdst = -1;
+        let seconds = unsafe { libc::mktime(&mut tm) };
+        UNIX_EPOCH + Duration::from_secs(seconds as u64)
+    }
+
+    fn retained(expression: &str, times: &[SystemTime]) -> Vec<bool> {
+        KeepPolicy::parse(expression).unwrap().retained(times)
+    }
+
+    #[test]
+    fn days_end_at_midnight() {
+        let times = [
+            local(2024, 7, 1, 10, 0),
+            local(2024, 7, 1, 23, 59),
+            local(2024, 7, 2, 0, 0),
+            local(2024, 7, 2, 12, 0),
+        ];
+        assert_eq!(retained("daily=2", &times), [false, true, false, true]);
+    }
+
+    #[test]
+    fn only_the_most_recent_periods_are_kept() {
+        let times = [
+            local(2024, 7, 1, 12, 0),
+            local(2024, 7, 2, 12, 0),
+            local(2024, 7, 3, 8, 0),
+            local(2024, 7, 3, 12, 0),
+        ];
+        assert_eq!(retained("daily=2", &times), [false, true, false, true]);
+        assert_eq!(retained("daily=0", &times), [false; 4]);
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        let times = [
+            local(2024, 7, 1, 0, 0),
+            local(2024, 7, 7, 23, 59),
+            local(2024, 7, 8, 0, 0),
+        ];
+        assert_eq!(retained("weekly=5", &times), [false, true, true]);
+    }
+
+    #[test]
+    fn months_end_with_their_last_day() {
+        let times = [
+            local(2023, 12, 31, 23, 59),
+            local(2024, 1, 1, 0, 0),
+            local(2024, 2, 29, 23, 59),
+            local(2024, 3, 1, 0, 0),
+            local(2024, 3, 31, 23, 59),
+        ];
+        assert_eq!(
+            retained("monthly=5", &times),
+            [true, true, true, false, true]
+        );
+    }
+
+    #[test]
+    fn every_tier_keeps_its_own_rotations() {
+        let times = [
+            local(2024, 5, 20, 12, 0),
+            local(2024, 6, 3, 12, 0),
+            local(2024, 6, 10, 12, 0),
+            local(2024, 7, 1, 9, 0),
+            local(2024, 7, 1, 18, 0),
+        ];
+        assert_eq!(
+            retained("daily=1,weekly=2,monthly=2", &times),
+            [false, false, true, false, true]
+        );
+        assert_eq!(
+            retained("monthly=3", &times),
+            [true, false, true, false, true]
+        );
+    }
+
+    #[test]
+    fn invalid_policies_are_rejected() {
+        assert!(KeepPolicy::parse("daily").is_err());
+        assert!(KeepPolicy::parse("daily=x").is_err());
+        assert!(KeepPolicy::parse("fortnightly=2").is_err());
+        assert!(KeepPolicy::parse("daily=2, daily=3").is_err());
+        assert!(KeepPolicy::parse("daily=7, weekly=4").is_ok());
+    }
+}
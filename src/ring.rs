@@ -0,0 +1,297 @@
+use log::{error, info, warn};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use stdout_rotator::metrics::Counters;
+use stdout_rotator::permissions::Access;
+use stdout_rotator::pipeline::{Batch, Pipeline};
+use stdout_rotator::RotatorError;
+
+const MAGIC: &[u8; 8] = b"SRRING01";
+const HEADER_SIZE: u64 = 64;
+const LOGGER: &str = "ring_writer";
+
+pub struct RingFile {
+    file: File,
+    capacity: u64,
+    head: u64,
+    length: u64,
+}
+
+impl RingFile {
+    // The header is part of the size, which the file never goes over
+    pub fn open(path: &str, size: u64, access: &Access) -> Result<RingFile, RotatorError> {
+        if size <= HEADER_SIZE {
+            return Err(RotatorError::from(format!(
+                "Ring file size must be greater than its {} bytes header",
+                HEADER_SIZE
+            )));
+        }
+        let capacity = size - HEADER_SIZE;
+        if let Some(parent) = Path::new(path).parent() {
+            access.create_dirs(parent)?;
+        }
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|op| format!("Error during opening of ring file '{}', {}", path, op))?;
+        let file = match RingFile::from_file(file)? {
+            Ok(ring) if ring.capacity == capacity => {
+                info!(target: LOGGER, "Continuing ring file '{}' holding {} bytes", path, ring.length);
+                return Ok(ring);
+            }
+            Ok(ring) => {
+                warn!(
+                    target: LOGGER,
+                    "Ring file '{}' has capacity {} instead of {}, reinitialising",
+                    path, ring.capacity, capacity
+                );
+                ring.file
+            }
+            Err(file) => file,
+        };
+        file.set_len(HEADER_SIZE + capacity)
+            .map_err(|op| format!("Error while preallocating ring file '{}': {}", path, op))?;
+        let mut ring = RingFile {
+            file,
+            capacity,
+            head: 0,
+            length: 0,
+        };
+        ring.write_header()?;
+        Ok(ring)
+    }
+
+    pub fn open_existing(path: &str) -> Result<Option<RingFile>, RotatorError> {
+        let file =
+            File::open(path).map_err(|op| format!("Error during opening of '{}': {}", path, op))?;
+        Ok(RingFile::from_file(file)?.ok())
+    }
+
+    fn from_file(mut file: File) -> Result<Result<RingFile, File>, RotatorError> {
+        let mut header = [0u8; HEADER_SIZE as usize];
+        let size = file
+            .metadata()
+            .map_err(|op| format!("Error while reading ring file metadata: {}", op))?
+            .len();
+        if size < HEADER_SIZE {
+            return Ok(Err(file));
+        }
+        file.seek(SeekFrom::Start(0))
+            .and_then(|_| file.read_exact(&mut header))
+            .map_err(|op| format!("Error while reading ring file header: {}", op))?;
+        if &header[0..8] != MAGIC {
+            return Ok(Err(file));
+        }
+        let field = |i: usize| u64::from_le_bytes(header[i..i + 8].try_into().unwrap());
+        let (capacity, head, length) = (field(8), field(16), field(24));
+        if capacity == 0 || head >= capacity || length > capacity || size < HEADER_SIZE + capacity {
+            return Ok(Err(file));
+        }
+        Ok(Ok(RingFile {
+            file,
+            capacity,
+            head,
+            length,
+        }))
+    }
+
+    fn write_header(&mut self) -> Result<(), RotatorError> {
+        let mut header = [0u8; HEADER_SIZE as usize];
+        header[0..8].copy_from_slice(MAGIC);
+        header[8..16].copy_from_slice(&self.capacity.to_le_bytes());
+        header[16..24].copy_from_slice(&self.head.to_le_bytes());
+        header[24..32].copy_from_slice(&self.length.to_le_bytes());
+        self.file
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| self.file.write_all(&header))
+            .map_err(|op| {
+                RotatorError::from(format!("Error while writing ring file header: {}", op))
+            })
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<(), RotatorError> {
+        let data = if data.len() as u64 > self.capacity {
+            &data[data.len() - self.capacity as usize..]
+        } else {
+            data
+        };
+        let first = data.len().min((self.capacity - self.head) as usize);
+        self.write_at(self.head, &data[..first])?;
+        self.write_at(0, &data[first..])?;
+        self.head = (self.head + data.len() as u64) % self.capacity;
+        self.length = (self.length + data.len() as u64).min(self.capacity);
+        self.write_header()
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> Result<(), RotatorError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.file
+            .seek(SeekFrom::Start(HEADER_SIZE + offset))
+            .and_then(|_| self.file.write_all(data))
+            .map_err(|op| RotatorError::from(format!("Error while writing ring file: {}", op)))
+    }
+
+    pub fn linearize<W: Write>(&mut self, output: &mut W) -> Result<(), RotatorError> {
+        let start = (self.head + self.capacity - self.length) % self.capacity;
+        let mut remaining = self.length;
+        let mut skip_partial = self.length == self.capacity;
+        let mut offset = start;
+        let mut buffer = vec![0u8; 64 * 1024];
+        while remaining > 0 {
+            let size = remaining
+                .min(self.capacity - offset)
+                .min(buffer.len() as u64) as usize;
+            self.file
+                .seek(SeekFrom::Start(HEADER_SIZE + offset))
+                .and_then(|_| self.file.read_exact(&mut buffer[..size]))
+                .map_err(|op| format!("Error while reading ring file: {}", op))?;
+            let mut chunk = &buffer[..size];
+            if skip_partial {
+                // A full ring has overwritten the beginning of its oldest line
+                match chunk.iter().position(|b| *b == b'\n') {
+                    Some(position) => {
+                        chunk = &chunk[position + 1..];
+                        skip_partial = false;
+                    }
+                    None => chunk = &[],
+                }
+            }
+            output
+                .write_all(chunk)
+                .map_err(|op| format!("Error while writing ring file content: {}", op))?;
+            remaining -= size as u64;
+            offset = (offset + size as u64) % self.capacity;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+pub fn start_ring_writing(
+    mut ring: RingFile,
+    mut pipeline: Pipeline,
+    rxfile: Receiver<Batch>,
+    txcomplete: Sender<bool>,
+    counters: Arc<Counters>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut stop = false;
+        while !stop {
+            let read_result = rxfile.recv();
+            if let Err(result) = read_result {
+                stop = true;
+                warn!(target: LOGGER, "Error while reading result: {}", result);
+                continue;
+            }
+            let read = pipeline.render(&read_result.unwrap());
+            if let Err(result) = ring.write(&read) {
+                stop = true;
+                counters.record_error();
+                error!(target: LOGGER, "Error while writing result to ring file: {}", result);
+                continue;
+            }
+            if let Err(result) = txcomplete.send(true) {
+                stop = true;
+                warn!(target: LOGGER, "Error while sending confirmation: {}", result);
+            }
+        }
+        if let Err(result) = ring.flush() {
+            counters.record_error();
+            error!(target: LOGGER, "Error while flushing ring file: {}", result);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn content(ring: &mut RingFile) -> String {
+        let mut output = vec![];
+        ring.linearize(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn the_file_never_goes_over_its_size() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("ring.log");
+        let mut ring = RingFile::open(&path.to_string_lossy(), 100, &Access::default()).unwrap();
+        for line in 0..50 {
+            ring.write(format!("line {}\n", line).as_bytes()).unwrap();
+            assert_eq!(fs::metadata(&path).unwrap().len(), 100);
+        }
+        assert!(RingFile::open(&path.to_string_lossy(), HEADER_SIZE, &Access::default()).is_err());
+    }
+
+    #[test]
+    fn wrapped_rings_start_at_their_oldest_complete_line() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("ring.log");
+        let mut ring = RingFile::open(
+            &path.to_string_lossy(),
+            HEADER_SIZE + 20,
+            &Access::default(),
+        )
+        .unwrap();
+        ring.write(b"first\nsecond\n").unwrap();
+        assert_eq!(content(&mut ring), "first\nsecond\n");
+        ring.write(b"third\nfourth\n").unwrap();
+        assert_eq!(content(&mut ring), "third\nfourth\n");
+        // Longer than the ring, only its end is kept
+        ring.write(b"0123456789\nabcdefghijklmnop\n").unwrap();
+        assert_eq!(content(&mut ring), "abcdefghijklmnop\n");
+    }
+
+    #[test]
+    fn reopened_rings_continue_where_they_stopped() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory
+            .path()
+            .join("ring.log")
+            .to_string_lossy()
+            .to_string();
+        let size = HEADER_SIZE + 20;
+        let mut ring = RingFile::open(&path, size, &Access::default()).unwrap();
+        ring.write(b"first\nsecond\nthird\n").unwrap();
+        drop(ring);
+        let mut ring = RingFile::open(&path, size, &Access::default()).unwrap();
+        ring.write(b"fourth\n").unwrap();
+        assert_eq!(content(&mut ring), "third\nfourth\n");
+        let mut existing = RingFile::open_existing(&path).unwrap().unwrap();
+        assert_eq!(content(&mut existing), "third\nfourth\n");
+        // A different size starts the ring over
+        let mut ring = RingFile::open(&path, size + 1, &Access::default()).unwrap();
+        assert_eq!(content(&mut ring), "");
+    }
+
+    #[test]
+    fn parent_directories_get_the_directory_mode() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("logs/ring.log");
+        let access = Access {
+            dir_mode: Some(0o700),
+            ..Access::default()
+        };
+        RingFile::open(&path.to_string_lossy(), 100, &access).unwrap();
+        let mode = fs::metadata(directory.path().join("logs"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}
@@ -0,0 +1,316 @@
+use log::{error, info, warn};
+use regex::bytes::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use stdout_rotator::metrics::Counters;
+use stdout_rotator::pipeline::{normalise_level, Batch, Record};
+use stdout_rotator::{prepare_rotations, RotationConfig, RotatorError};
+
+use crate::cli::{start_file_writing, FileStages};
+
+const LOGGER: &str = "router";
+pub const DEFAULT_LEVEL_PATTERN: &str =
+    r"(?i)\b(TRACE|DEBUG|INFO|NOTICE|WARN|WARNING|ERROR|CRITICAL|FATAL)\b";
+
+#[derive(Clone, Debug)]
+pub struct LevelHistory {
+    level: String,
+    max_history: u32,
+}
+
+impl LevelHistory {
+    pub fn parse(value: &str) -> Result<LevelHistory, String> {
+        let (level, max_history) = value.split_once('=').ok_or_else(|| {
+            format!(
+                "Invalid level history '{}', expected <level>=<count>",
+                value
+            )
+        })?;
+        let max_history = max_history
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid count '{}' of level '{}'", max_history, level))?;
+        Ok(LevelHistory {
+            level: level_name(level.trim()),
+            max_history,
+        })
+    }
+}
+
+pub struct Route {
+    output_file: String,
+    max_history: Option<u32>,
+}
+
+// Lines without a route stay in the output file
+pub trait Router: Send {
+    fn route(&self, record: &Record, output_file: &str) -> Option<Route>;
+}
+
+pub struct LevelRouter {
+    pattern: Regex,
+    histories: Vec<LevelHistory>,
+}
+
+impl LevelRouter {
+    pub fn new(pattern: Regex, histories: Vec<LevelHistory>) -> LevelRouter {
+        LevelRouter { pattern, histories }
+    }
+}
+
+impl Router for LevelRouter {
+    fn route(&self, record: &Record, output_file: &str) -> Option<Route> {
+        let captures = self.pattern.captures(&record.data)?;
+        let matched = captures.get(1).or_else(|| captures.get(0))?;
+        let level = level_name(&String::from_utf8_lossy(matched.as_bytes()));
+        let max_history = self
+            .histories
+            .iter()
+            .find(|history| history.level == level)
+            .map(|history| history.max_history);
+        Some(Route {
+            output_file: level_file(output_file, &sanitise(&level)),
+            max_history,
+        })
+    }
+}
+
+pub struct DemuxRouter {
+    pattern: Regex,
+    template: Vec<Part>,
+}
+
+enum Part {
+    Literal(String),
+    Group(String),
+}
+
+impl DemuxRouter {
+    pub fn new(pattern: Regex, template: &str) -> Result<DemuxRouter, RotatorError> {
+        let mut parts = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            parts.push(Part::Literal(rest[..start].to_string()));
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| {
+                    RotatorError::from(format!("Unclosed placeholder in template '{}'", template))
+                })?;
+            let group = &rest[start + 1..end];
+            let known = match group.parse::<usize>() {
+                Ok(index) => index < pattern.captures_len(),
+                Err(_) => pattern.capture_names().any(|name| name == Some(group)),
+            };
+            if !known {
+                return Err(RotatorError::from(format!(
+                    "Placeholder '{{{}}}' of template '{}' is not a group of the demux regex",
+                    group, template
+                )));
+            }
+            parts.push(Part::Group(group.to_string()));
+            rest = &rest[end + 1..];
+        }
+        parts.push(Part::Literal(rest.to_string()));
+        Ok(DemuxRouter {
+            pattern,
+            template: parts,
+        })
+    }
+}
+
+impl Router for DemuxRouter {
+    fn route(&self, record: &Record, _output_file: &str) -> Option<Route> {
+        let captures = self.pattern.captures(&record.data)?;
+        let mut output_file = String::new();
+        for part in &self.template {
+            match part {
+                Part::Literal(literal) => output_file.push_str(literal),
+                Part::Group(group) => {
+                    let matched = match group.parse::<usize>() {
+                        Ok(index) => captures.get(index),
+                        Err(_) => captures.name(group),
+                    }?;
+                    output_file.push_str(&sanitise(&String::from_utf8_lossy(matched.as_bytes())));
+                }
+            }
+        }
+        Some(Route {
+            output_file,
+            max_history: None,
+        })
+    }
+}
+
+fn level_name(level: &str) -> String {
+    normalise_level(level)
+        .unwrap_or_else(|| level.to_string())
+        .to_lowercase()
+}
+
+// Values come from the input and must not change the directory of the routed file
+fn sanitise(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// output.log is split into output.error.log, output.warn.log, ...
+fn level_file(output_file: &str, level: &str) -> String {
+    let path = Path::new(output_file);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, level, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, level),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+struct RouteWriter {
+    txfile: Sender<Batch>,
+    handle: JoinHandle<()>,
+}
+
+struct Routes {
+    config: RotationConfig,
+    stages: FileStages,
+    txcomplete: Sender<bool>,
+    counters: Arc<Counters>,
+    writers: HashMap<Option<String>, RouteWriter>,
+}
+
+impl Routes {
+    fn start(
+        &mut self,
+        route: Option<&Route>,
+        prepare: bool,
+    ) -> Result<Option<String>, RotatorError> {
+        let key = route.map(|route| route.output_file.clone());
+        if !self.writers.contains_key(&key) {
+            let mut config = self.config.clone();
+            if let Some(route) = route {
+                config.output_file = route.output_file.clone();
+                config.max_history = route.max_history.unwrap_or(config.max_history);
+            }
+            if prepare {
+                prepare_rotations(&config)?;
+            }
+            info!(target: LOGGER, "Routing lines to '{}'", config.output_file);
+            let (txfile, rxfile) = mpsc::channel::<Batch>();
+            let handle = start_file_writing(
+                config,
+                self.stages.pipeline("stdout"),
+                rxfile,
+                self.txcomplete.clone(),
+                self.counters.clone(),
+            )?;
+            self.writers
+                .insert(key.clone(), RouteWriter { txfile, handle });
+        }
+        Ok(key)
+    }
+}
+
+// Every route has its own file writer, started on the first line routed to it. Batches are
+// acknowledged once all of their routes wrote their share.
+pub fn start_routed_writing(
+    router: Box<dyn Router>,
+    config: RotationConfig,
+    stages: FileStages,
+    rxfile: Receiver<Batch>,
+    txcomplete: Sender<bool>,
+    counters: Arc<Counters>,
+) -> Result<JoinHandle<()>, RotatorError> {
+    let (txroutecomplete, rxroutecomplete) = mpsc::channel::<bool>();
+    let mut routes = Routes {
+        config,
+        stages,
+        txcomplete: txroutecomplete,
+        counters,
+        writers: HashMap::new(),
+    };
+    // The output file itself was prepared at startup
+    routes.start(None, false)?;
+    Ok(thread::spawn(move || {
+        let mut stop = false;
+        while !stop {
+            let batch = match rxfile.recv() {
+                Ok(batch) => batch,
+                Err(result) => {
+                    stop = true;
+                    warn!(target: LOGGER, "Error while reading result: {}", result);
+                    continue;
+                }
+            };
+            if batch.is_empty() {
+                for writer in routes.writers.values() {
+                    let _ = writer.txfile.send(batch.clone());
+                }
+                continue;
+            }
+            let mut groups: Vec<(Option<Route>, Vec<Record>)> = vec![];
+            for record in batch.iter() {
+                let route = router.route(record, &routes.config.output_file);
+                let key = route.as_ref().map(|route| &route.output_file);
+                match groups
+                    .iter_mut()
+                    .find(|(other, _)| other.as_ref().map(|other| &other.output_file) == key)
+                {
+                    Some((_, records)) => records.push(record.clone()),
+                    None => groups.push((route, vec![record.clone()])),
+                }
+            }
+            let mut pending = 0;
+            for (route, records) in groups {
+                let key = match routes.start(route.as_ref(), true) {
+                    Ok(key) => key,
+                    Err(result) => {
+                        routes.counters.record_error();
+                        error!(target: LOGGER, "Error while starting route, writing its lines to the output file: {}", result);
+                        None
+                    }
+                };
+                match routes.writers[&key].txfile.send(Arc::new(records)) {
+                    Ok(()) => pending += 1,
+                    Err(result) => {
+                        stop = true;
+                        routes.counters.record_error();
+                        error!(target: LOGGER, "Error while routing lines: {}", result);
+                    }
+                }
+            }
+            for _ in 0..pending {
+                if let Err(result) = rxroutecomplete.recv() {
+                    stop = true;
+                    warn!(target: LOGGER, "Error while receiving confirmation: {}", result);
+                }
+            }
+            if stop {
+                continue;
+            }
+            if let Err(result) = txcomplete.send(true) {
+                stop = true;
+                warn!(target: LOGGER, "Error while sending confirmation: {}", result);
+            }
+        }
+        for (_, writer) in routes.writers.drain() {
+            drop(writer.txfile);
+            if writer.handle.join().is_err() {
+                error!(target: LOGGER, "Error on join of routed file writer");
+            }
+        }
+    }))
+}
@@ -0,0 +1,307 @@
+use log::debug;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::clock::{civil_time, CivilTime};
+use crate::pipeline::Batch;
+
+const LOGGER: &str = "scheduler";
+const SEARCH_LIMIT: usize = 100_000;
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+#[derive(Clone, Debug)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<CronSchedule, String> {
+        let expanded = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "Cron expression '{}' must have 5 fields: minute hour day-of-month month day-of-week",
+                expression
+            ));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7, WEEKDAYS, 0)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        let schedule = CronSchedule {
+            expression: expression.to_string(),
+            minutes: parse_field(fields[0], 0, 59, &[], 0)?,
+            hours: parse_field(fields[1], 0, 23, &[], 0)?,
+            days: parse_field(fields[2], 1, 31, &[], 1)?,
+            months: parse_field(fields[3], 1, 12, MONTHS, 1)?,
+            weekdays,
+            days_restricted: !fields[2].starts_with('*'),
+            weekdays_restricted: !fields[4].starts_with('*'),
+        };
+        if schedule.next_after(SystemTime::now()).is_none() {
+            return Err(format!("Cron expression '{}' never fires", expression));
+        }
+        Ok(schedule)
+    }
+
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let seconds = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let mut candidate = seconds - seconds.rem_euclid(60) + 60;
+        for _ in 0..SEARCH_LIMIT {
+            let civil = civil_time(candidate);
+            if !self.day_matches(&civil) || self.hours & (1 << civil.hour) == 0 {
+                candidate += (60 - civil.minute as i64) * 60;
+                continue;
+            }
+            if self.minutes & (1 << civil.minute) == 0 {
+                candidate += 60;
+                continue;
+            }
+            return Some(UNIX_EPOCH + Duration::from_secs(candidate as u64));
+        }
+        None
+    }
+
+    fn day_matches(&self, civil: &CivilTime) -> bool {
+        if self.months & (1 << civil.month) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << civil.day) != 0;
+        let weekday = self.weekdays & (1 << civil.weekday) != 0;
+        // As in cron, a restricted day of month and day of week match when either does
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+}
+
+impl std::fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+fn parse_field(
+    field: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+    name_offset: u32,
+) -> Result<u64, String> {
+    let value = |token: &str| -> Result<u32, String> {
+        let parsed = match names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(token))
+        {
+            Some(position) => position as u32 + name_offset,
+            None => token
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid cron value '{}'", token))?,
+        };
+        if parsed < min || parsed > max {
+            return Err(format!(
+                "Cron value '{}' outside of range {}-{}",
+                token, min, max
+            ));
+        }
+        Ok(parsed)
+    };
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("Invalid cron step '{}'", step))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            None if step > 1 => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+        if start > end {
+            return Err(format!("Invalid cron range '{}'", range));
+        }
+        for bit in (start..=end).step_by(step as usize) {
+            mask |= 1 << bit;
+        }
+    }
+    Ok(mask)
+}
+
+pub fn start_rotation_scheduler(
+    schedule: CronSchedule,
+    txfiles: Vec<Sender<Batch>>,
+) -> (Sender<()>, JoinHandle<()>) {
+    let (txstop, rxstop) = mpsc::channel::<()>();
+    let handle = thread::spawn(move || {
+        while let Some(next) = schedule.next_after(SystemTime::now()) {
+            debug!(target: LOGGER, "Next scheduled rotation at {}", humantime::format_rfc3339_seconds(next));
+            let wait = next.duration_since(SystemTime::now()).unwrap_or_default();
+            match rxstop.recv_timeout(wait) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => break,
+            }
+            // An empty batch wakes the file writers up so they can rotate without new input
+            let sent = txfiles
+                .iter()
+                .filter(|txfile| txfile.send(Arc::new(vec![])).is_ok())
+                .count();
+            if sent == 0 {
+                break;
+            }
+        }
+    });
+    (txstop, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Schedules are in local time, so results are checked against their civil time
+    fn civil(time: SystemTime) -> CivilTime {
+        civil_time(time.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64)
+    }
+
+    fn start() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_760_000_000)
+    }
+
+    #[test]
+    fn fields_expand_to_masks() {
+        assert_eq!(parse_field("*", 0, 59, &[], 0).unwrap(), (1 << 60) - 1);
+        assert_eq!(parse_field("5", 0, 59, &[], 0).unwrap(), 1 << 5);
+        assert_eq!(
+            parse_field("1-3,10", 0, 59, &[], 0).unwrap(),
+            0b1110 | 1 << 10
+        );
+        assert_eq!(
+            parse_field("*/20", 0, 59, &[], 0).unwrap(),
+            1 | 1 << 20 | 1 << 40
+        );
+        assert_eq!(
+            parse_field("30/10", 0, 59, &[], 0).unwrap(),
+            1 << 30 | 1 << 40 | 1 << 50
+        );
+        assert_eq!(
+            parse_field("jan,Mar", 1, 12, MONTHS, 1).unwrap(),
+            1 << 1 | 1 << 3
+        );
+        assert_eq!(parse_field("mon-fri", 0, 7, WEEKDAYS, 0).unwrap(), 0b111110);
+    }
+
+    #[test]
+    fn invalid_fields_are_rejected() {
+        assert!(parse_field("60", 0, 59, &[], 0).is_err());
+        assert!(parse_field("0", 1, 31, &[], 1).is_err());
+        assert!(parse_field("5-1", 0, 59, &[], 0).is_err());
+        assert!(parse_field("*/0", 0, 59, &[], 0).is_err());
+        assert!(parse_field("abc", 0, 59, &[], 0).is_err());
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("* * * * * *").is_err());
+        assert!(CronSchedule::parse("0 0 31 2 *").is_err());
+    }
+
+    #[test]
+    fn sunday_is_0_or_7() {
+        let zero = CronSchedule::parse("0 0 * * 0").unwrap();
+        let seven = CronSchedule::parse("0 0 * * 7").unwrap();
+        assert_eq!(zero.weekdays, 1);
+        assert_eq!(seven.weekdays, 1);
+    }
+
+    #[test]
+    fn macros_expand() {
+        let daily = CronSchedule::parse("@daily").unwrap();
+        let next = civil(daily.next_after(start()).unwrap());
+        assert_eq!((next.hour, next.minute), (0, 0));
+        let hourly = CronSchedule::parse("@hourly").unwrap();
+        assert_eq!(civil(hourly.next_after(start()).unwrap()).minute, 0);
+        assert_eq!(CronSchedule::parse("@weekly").unwrap().weekdays, 1);
+        assert_eq!(hourly.to_string(), "@hourly");
+    }
+
+    #[test]
+    fn next_is_the_first_matching_minute_after() {
+        let schedule = CronSchedule::parse("*/15 * * * *").unwrap();
+        let mut time = start();
+        for _ in 0..10 {
+            let next = schedule.next_after(time).unwrap();
+            assert!(next > time);
+            assert!(next.duration_since(time).unwrap() <= Duration::from_secs(15 * 60));
+            assert_eq!(civil(next).minute % 15, 0);
+            assert_eq!(civil(next).second, 0);
+            time = next;
+        }
+    }
+
+    #[test]
+    fn every_minute_fires_on_the_next_minute() {
+        let schedule = CronSchedule::parse("* * * * *").unwrap();
+        let next = schedule.next_after(start()).unwrap();
+        assert!(next.duration_since(start()).unwrap() <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn weekday_and_hour_are_matched() {
+        let schedule = CronSchedule::parse("30 12 * * mon").unwrap();
+        let next = schedule.next_after(start()).unwrap();
+        let civil = civil(next);
+        assert_eq!((civil.hour, civil.minute, civil.weekday), (12, 30, 1));
+        assert!(next.duration_since(start()).unwrap() <= Duration::from_secs(7 * 86400));
+    }
+
+    #[test]
+    fn restricted_day_and_weekday_match_either() {
+        let schedule = CronSchedule::parse("0 0 13 * fri").unwrap();
+        let mut time = start();
+        let mut weekdays = 0;
+        let mut days = 0;
+        for _ in 0..40 {
+            let next = civil(schedule.next_after(time).unwrap());
+            assert!(next.day == 13 || next.weekday == 5);
+            weekdays += usize::from(next.weekday == 5);
+            days += usize::from(next.day == 13);
+            time = schedule.next_after(time).unwrap();
+        }
+        assert!(weekdays > 0 && days > 0);
+    }
+
+    #[test]
+    fn month_restricts_the_days() {
+        let schedule = CronSchedule::parse("0 6 1 feb *").unwrap();
+        let next = civil(schedule.next_after(start()).unwrap());
+        assert_eq!((next.month, next.day, next.hour), (2, 1, 6));
+    }
+}
@@ -0,0 +1,155 @@
+use log::debug;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::pipeline::Batch;
+use crate::reload::Reloader;
+
+const LOGGER: &str = "signals";
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static RECEIVED: [AtomicU32; 4] = [const { AtomicU32::new(0) }; 4];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    Hangup,
+    User1,
+    Terminate,
+    Interrupt,
+}
+
+impl Signal {
+    const ROTATION: &'static [Signal] = &[Signal::Hangup, Signal::User1];
+    pub const FORWARDED: &'static [Signal] =
+        &[Signal::Hangup, Signal::Terminate, Signal::Interrupt];
+    const SHUTDOWN: &'static [Signal] = &[Signal::Terminate, Signal::Interrupt];
+    const ALL: &'static [Signal] = &[
+        Signal::Hangup,
+        Signal::User1,
+        Signal::Terminate,
+        Signal::Interrupt,
+    ];
+
+    fn received(&self) -> u32 {
+        RECEIVED[*self as usize].load(Ordering::Acquire)
+    }
+
+    #[cfg(unix)]
+    pub fn exit_code(&self) -> i32 {
+        128 + number(*self)
+    }
+
+    #[cfg(not(unix))]
+    pub fn exit_code(&self) -> i32 {
+        1
+    }
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Signal::Hangup => f.write_str("SIGHUP"),
+            Signal::User1 => f.write_str("SIGUSR1"),
+            Signal::Terminate => f.write_str("SIGTERM"),
+            Signal::Interrupt => f.write_str("SIGINT"),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn number(signal: Signal) -> libc::c_int {
+    match signal {
+        Signal::Hangup => libc::SIGHUP,
+        Signal::User1 => libc::SIGUSR1,
+        Signal::Terminate => libc::SIGTERM,
+        Signal::Interrupt => libc::SIGINT,
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn record(number: libc::c_int) {
+    // Only async-signal-safe work is allowed here: the writer and reader pick the signal up later
+    for signal in Signal::ALL {
+        if self::number(*signal) == number {
+            if Signal::SHUTDOWN.contains(signal) && shutdown_signal().is_some() {
+                // A second shutdown signal stops waiting for the drain
+                unsafe { libc::_exit(signal.exit_code()) };
+            }
+            RECEIVED[*signal as usize].fetch_add(1, Ordering::AcqRel);
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn install(signal: Signal) {
+    let handler: extern "C" fn(libc::c_int) = record;
+    unsafe { libc::signal(number(signal), handler as libc::sighandler_t) };
+}
+
+#[cfg(not(unix))]
+pub fn install(_signal: Signal) {}
+
+pub fn install_shutdown_handlers() {
+    for signal in Signal::SHUTDOWN {
+        install(*signal);
+    }
+}
+
+pub fn shutdown_signal() -> Option<Signal> {
+    Signal::SHUTDOWN
+        .iter()
+        .copied()
+        .find(|signal| signal.received() > 0)
+}
+
+// Every consumer of rotation signals tracks which deliveries it has already seen
+#[derive(Default)]
+pub struct SignalCursor {
+    seen: [u32; 4],
+}
+
+impl SignalCursor {
+    pub fn take_rotation(&mut self) -> Option<Signal> {
+        self.take(Signal::ROTATION)
+    }
+
+    pub fn take(&mut self, signals: &[Signal]) -> Option<Signal> {
+        for signal in signals {
+            let received = signal.received();
+            if self.seen[*signal as usize] != received {
+                self.seen[*signal as usize] = received;
+                return Some(*signal);
+            }
+        }
+        None
+    }
+}
+
+pub fn start_signal_watcher(
+    txfiles: Vec<Sender<Batch>>,
+    reloader: Option<Arc<Reloader>>,
+) -> (Sender<()>, JoinHandle<()>) {
+    for signal in Signal::ROTATION {
+        install(*signal);
+    }
+    let (txstop, rxstop) = mpsc::channel::<()>();
+    let handle = thread::spawn(move || {
+        let mut cursor = SignalCursor::default();
+        while let Err(RecvTimeoutError::Timeout) = rxstop.recv_timeout(POLL_INTERVAL) {
+            while let Some(signal) = cursor.take_rotation() {
+                debug!(target: LOGGER, "Received {}", signal);
+                if let (Signal::Hangup, Some(reloader)) = (signal, &reloader) {
+                    reloader.reload();
+                }
+                // Wakes the file writers up, which consume the signal
+                for txfile in &txfiles {
+                    let _ = txfile.send(Arc::new(vec![]));
+                }
+            }
+        }
+    });
+    (txstop, handle)
+}
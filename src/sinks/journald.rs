@@ -0,0 +1,115 @@
+use log::{error, warn};
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use stdout_rotator::pipeline::{Batch, Record};
+use stdout_rotator::RotatorError;
+
+use crate::sinks::level_severity;
+
+const LOGGER: &str = "journald_sink";
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+// Native journal protocol: one datagram per entry, with fields as KEY=value lines, or as the
+// key followed by the little endian length of the value when the value spans several lines
+fn push_field(entry: &mut Vec<u8>, key: &str, value: &[u8]) {
+    entry.extend_from_slice(key.as_bytes());
+    if value.contains(&b'\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value);
+    entry.push(b'\n');
+}
+
+pub struct JournaldSink {
+    #[cfg(unix)]
+    socket: std::os::unix::net::UnixDatagram,
+    identifier: String,
+    // Without an explicit priority, the level of the line decides it
+    priority: Option<u8>,
+}
+
+impl JournaldSink {
+    #[cfg(unix)]
+    pub fn open(identifier: &str, priority: Option<u8>) -> Result<JournaldSink, RotatorError> {
+        let socket = std::os::unix::net::UnixDatagram::unbound()
+            .and_then(|socket| socket.connect(JOURNAL_SOCKET).map(|_| socket))
+            .map_err(|op| {
+                format!(
+                    "Error while connecting to journald at '{}': {}",
+                    JOURNAL_SOCKET, op
+                )
+            })?;
+        Ok(JournaldSink {
+            socket,
+            identifier: identifier.to_string(),
+            priority,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn open(_identifier: &str, _priority: Option<u8>) -> Result<JournaldSink, RotatorError> {
+        Err(RotatorError::new("journald is only available on unix"))
+    }
+
+    fn entry(&self, record: &Record) -> Vec<u8> {
+        let priority = self.priority.unwrap_or_else(|| level_severity(record));
+        let mut entry = vec![];
+        push_field(&mut entry, "MESSAGE", record.text().as_bytes());
+        push_field(&mut entry, "PRIORITY", priority.to_string().as_bytes());
+        push_field(&mut entry, "SYSLOG_IDENTIFIER", self.identifier.as_bytes());
+        entry
+    }
+
+    #[cfg(unix)]
+    fn write(&self, records: &[Record]) -> Result<(), RotatorError> {
+        for record in records {
+            if let Err(op) = self.socket.send(&self.entry(record)) {
+                // Entries too large for a datagram are dropped rather than stopping the sink
+                if op.raw_os_error() == Some(libc::EMSGSIZE) {
+                    warn!(target: LOGGER, "Dropping a line of {} bytes, too large for journald", record.data.len());
+                    continue;
+                }
+                return Err(RotatorError::from(format!(
+                    "Error while sending to journald: {}",
+                    op
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn write(&self, _records: &[Record]) -> Result<(), RotatorError> {
+        Ok(())
+    }
+}
+
+pub fn start_journald_sink(
+    sink: JournaldSink,
+    rxsink: Receiver<Batch>,
+    txcomplete: Sender<bool>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut stop = false;
+        while !stop {
+            let read_result = rxsink.recv();
+            if let Err(result) = read_result {
+                stop = true;
+                warn!(target: LOGGER, "Error while reading result: {}", result);
+                continue;
+            }
+            if let Err(result) = sink.write(&read_result.unwrap()) {
+                stop = true;
+                error!(target: LOGGER, "Error while writing to journald: {}", result);
+                continue;
+            }
+            if let Err(result) = txcomplete.send(true) {
+                stop = true;
+                warn!(target: LOGGER, "Error while sending acknowledgment: {}", result);
+            }
+        }
+    })
+}
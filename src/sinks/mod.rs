@@ -0,0 +1,48 @@
+pub mod journald;
+pub mod sqlite;
+pub mod syslog;
+pub mod tee;
+
+use std::time::SystemTime;
+use stdout_rotator::pipeline::Record;
+
+pub fn timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339_millis(time).to_string()
+}
+
+// Syslog severities, which journald uses as priorities as well
+pub fn parse_severity(value: &str) -> Result<u8, String> {
+    let severity = match value.trim().to_lowercase().as_str() {
+        "emerg" => 0,
+        "alert" => 1,
+        "crit" => 2,
+        "err" | "error" => 3,
+        "warning" | "warn" => 4,
+        "notice" => 5,
+        "info" => 6,
+        "debug" => 7,
+        number => number
+            .parse::<u8>()
+            .ok()
+            .filter(|severity| *severity <= 7)
+            .ok_or_else(|| {
+                format!(
+                    "Invalid severity '{}', expected 0 to 7 or a name from emerg to debug",
+                    value
+                )
+            })?,
+    };
+    Ok(severity)
+}
+
+// Lines without a recognised level are informational
+pub fn level_severity(record: &Record) -> u8 {
+    match record.level().as_deref() {
+        Some("FATAL") | Some("CRITICAL") => 2,
+        Some("ERROR") => 3,
+        Some("WARN") => 4,
+        Some("NOTICE") => 5,
+        Some("DEBUG") | Some("TRACE") => 7,
+        _ => 6,
+    }
+}
@@ -0,0 +1,135 @@
+use log::{debug, error, warn};
+use rusqlite::{params, Connection};
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use stdout_rotator::pipeline::{Batch, Record};
+use stdout_rotator::RotatorError;
+
+use crate::sinks::timestamp;
+
+const LOGGER: &str = "sqlite_sink";
+
+pub struct SqliteSink {
+    connection: Connection,
+    max_size: Option<u64>,
+}
+
+impl SqliteSink {
+    pub fn open(path: &str, max_size: Option<u64>) -> Result<SqliteSink, RotatorError> {
+        let connection = Connection::open(path)
+            .map_err(|op| format!("Error while opening SQLite sink '{}': {}", path, op))?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS lines (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    ts TEXT NOT NULL,
+                    level TEXT,
+                    message TEXT NOT NULL,
+                    fields TEXT
+                );
+                CREATE INDEX IF NOT EXISTS lines_ts ON lines (ts);",
+            )
+            .map_err(|op| format!("Error while initialising SQLite sink '{}': {}", path, op))?;
+        Ok(SqliteSink {
+            connection,
+            max_size,
+        })
+    }
+
+    fn write(&mut self, records: &[Record]) -> Result<(), RotatorError> {
+        let transaction = self
+            .connection
+            .transaction()
+            .map_err(|op| format!("Error while starting SQLite transaction: {}", op))?;
+        {
+            let mut statement = transaction
+                .prepare_cached(
+                    "INSERT INTO lines (ts, level, message, fields) VALUES (?1, ?2, ?3, ?4)",
+                )
+                .map_err(|op| format!("Error while preparing SQLite insert: {}", op))?;
+            for record in records {
+                let fields = if record.fields.is_empty() {
+                    None
+                } else {
+                    Some(serde_json::Value::Object(record.fields.clone()).to_string())
+                };
+                statement
+                    .execute(params![
+                        timestamp(record.received),
+                        record.level(),
+                        record.text(),
+                        fields
+                    ])
+                    .map_err(|op| format!("Error while inserting into SQLite sink: {}", op))?;
+            }
+        }
+        transaction
+            .commit()
+            .map_err(|op| format!("Error while committing SQLite transaction: {}", op))?;
+        self.prune()
+    }
+
+    fn used_size(&self) -> Result<u64, RotatorError> {
+        self.connection
+            .query_row(
+                "SELECT (page_count - freelist_count) * page_size FROM pragma_page_count(), pragma_freelist_count(), pragma_page_size()",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|op| RotatorError::from(format!("Error while measuring SQLite sink: {}", op)))
+    }
+
+    fn prune(&mut self) -> Result<(), RotatorError> {
+        let Some(max_size) = self.max_size else {
+            return Ok(());
+        };
+        let mut used = self.used_size()?;
+        while used > max_size {
+            let rows: u64 = self
+                .connection
+                .query_row("SELECT COUNT(*) FROM lines", [], |row| row.get(0))
+                .map_err(|op| format!("Error while counting SQLite sink lines: {}", op))?;
+            if rows == 0 {
+                warn!(target: LOGGER, "SQLite sink is empty but still exceeds {} bytes", max_size);
+                break;
+            }
+            let to_remove = (rows / 10).max(1);
+            debug!(target: LOGGER, "Pruning {} lines, {} bytes in use", to_remove, used);
+            self.connection
+                .execute(
+                    "DELETE FROM lines WHERE id IN (SELECT id FROM lines ORDER BY id LIMIT ?1)",
+                    params![to_remove],
+                )
+                .map_err(|op| format!("Error while pruning SQLite sink: {}", op))?;
+            used = self.used_size()?;
+        }
+        Ok(())
+    }
+}
+
+pub fn start_sqlite_sink(
+    mut sink: SqliteSink,
+    rxsink: Receiver<Batch>,
+    txcomplete: Sender<bool>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut stop = false;
+        while !stop {
+            let read_result = rxsink.recv();
+            if let Err(result) = read_result {
+                stop = true;
+                warn!(target: LOGGER, "Error while reading result: {}", result);
+                continue;
+            }
+            if let Err(result) = sink.write(&read_result.unwrap()) {
+                stop = true;
+                error!(target: LOGGER, "Error while writing to SQLite sink: {}", result);
+                continue;
+            }
+            if let Err(result) = txcomplete.send(true) {
+                stop = true;
+                warn!(target: LOGGER, "Error while sending acknowledgment: {}", result);
+            }
+        }
+    })
+}
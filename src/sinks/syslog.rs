@@ -0,0 +1,273 @@
+use clap::ValueEnum;
+use log::{debug, error, warn};
+use std::io::Write;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use stdout_rotator::clock::civil_time;
+use stdout_rotator::host::hostname;
+use stdout_rotator::pipeline::{Batch, Record};
+use stdout_rotator::RotatorError;
+
+use crate::sinks::{level_severity, timestamp};
+use crate::stats::epoch_seconds;
+
+const LOGGER: &str = "syslog_sink";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(5);
+pub const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyslogFormat {
+    Rfc3164,
+    Rfc5424,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transport {
+    Udp(String),
+    Tcp(String),
+}
+
+impl Transport {
+    pub fn parse(value: &str) -> Result<Transport, String> {
+        match value.split_once("://") {
+            Some(("udp", address)) if !address.is_empty() => {
+                Ok(Transport::Udp(address.to_string()))
+            }
+            Some(("tcp", address)) if !address.is_empty() => {
+                Ok(Transport::Tcp(address.to_string()))
+            }
+            _ => Err(format!(
+                "Invalid syslog target '{}', expected udp://<host>:<port> or tcp://<host>:<port>",
+                value
+            )),
+        }
+    }
+}
+
+pub fn parse_facility(value: &str) -> Result<u8, String> {
+    const NAMES: [&str; 12] = [
+        "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron",
+        "authpriv", "ftp",
+    ];
+    let value = value.trim().to_lowercase();
+    if let Some(position) = NAMES.iter().position(|name| *name == value) {
+        return Ok(position as u8);
+    }
+    if let Some(local) = value.strip_prefix("local") {
+        if let Some(local) = local.parse::<u8>().ok().filter(|local| *local <= 7) {
+            return Ok(16 + local);
+        }
+    }
+    value
+        .parse::<u8>()
+        .ok()
+        .filter(|facility| *facility <= 23)
+        .ok_or_else(|| {
+            format!(
+                "Invalid facility '{}', expected 0 to 23 or a name as user, daemon or local0",
+                value
+            )
+        })
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    // Reconnected lazily after a failure, no earlier than the backoff allows
+    Tcp {
+        address: String,
+        stream: Option<TcpStream>,
+        retry: Instant,
+    },
+}
+
+pub struct SyslogSink {
+    connection: Connection,
+    format: SyslogFormat,
+    facility: u8,
+    // Without an explicit severity, the level of the line decides it
+    severity: Option<u8>,
+    app_name: String,
+}
+
+impl SyslogSink {
+    pub fn open(
+        transport: &Transport,
+        format: SyslogFormat,
+        facility: u8,
+        severity: Option<u8>,
+        app_name: &str,
+    ) -> Result<SyslogSink, RotatorError> {
+        let connection = match transport {
+            Transport::Udp(address) => {
+                let target = resolve(address)?;
+                let local = if target.is_ipv4() {
+                    "0.0.0.0:0"
+                } else {
+                    "[::]:0"
+                };
+                let socket = UdpSocket::bind(local)
+                    .and_then(|socket| socket.connect(target).map(|_| socket))
+                    .map_err(|op| {
+                        format!("Error while connecting to syslog at '{}': {}", address, op)
+                    })?;
+                Connection::Udp(socket)
+            }
+            Transport::Tcp(address) => Connection::Tcp {
+                stream: Some(connect(address)?),
+                address: address.clone(),
+                retry: Instant::now(),
+            },
+        };
+        Ok(SyslogSink {
+            connection,
+            format,
+            facility,
+            severity,
+            app_name: app_name.to_string(),
+        })
+    }
+
+    fn message(&self, record: &Record) -> String {
+        let priority = u32::from(self.facility) * 8
+            + u32::from(self.severity.unwrap_or_else(|| level_severity(record)));
+        let host = hostname();
+        let pid = std::process::id();
+        let text = record.text();
+        match self.format {
+            SyslogFormat::Rfc5424 => format!(
+                "<{}>1 {} {} {} {} - - {}",
+                priority,
+                timestamp(record.received),
+                host,
+                self.app_name,
+                pid,
+                text
+            ),
+            SyslogFormat::Rfc3164 => {
+                let civil = civil_time(epoch_seconds(record.received) as i64);
+                format!(
+                    "<{}>{} {:>2} {:02}:{:02}:{:02} {} {}[{}]: {}",
+                    priority,
+                    MONTHS[civil.month as usize - 1],
+                    civil.day,
+                    civil.hour,
+                    civil.minute,
+                    civil.second,
+                    host,
+                    self.app_name,
+                    pid,
+                    text
+                )
+            }
+        }
+    }
+
+    fn write(&mut self, records: &[Record]) -> Result<(), RotatorError> {
+        let messages: Vec<String> = records.iter().map(|record| self.message(record)).collect();
+        match &mut self.connection {
+            Connection::Udp(socket) => {
+                for message in messages {
+                    // Datagrams are best effort, as syslog over UDP is
+                    if let Err(op) = socket.send(message.as_bytes()) {
+                        debug!(target: LOGGER, "Error while sending to syslog: {}", op);
+                    }
+                }
+                Ok(())
+            }
+            Connection::Tcp {
+                address,
+                stream,
+                retry,
+            } => {
+                if stream.is_none() && Instant::now() >= *retry {
+                    match connect(address) {
+                        Ok(connected) => *stream = Some(connected),
+                        Err(err) => {
+                            *retry = Instant::now() + RECONNECT_BACKOFF;
+                            warn!(target: LOGGER, "{}", err.msg);
+                        }
+                    }
+                }
+                let Some(connected) = stream.as_mut() else {
+                    debug!(target: LOGGER, "Dropping {} lines while syslog is unreachable", messages.len());
+                    return Ok(());
+                };
+                // Octet counting framing of RFC 6587, newline framing for the legacy format
+                let mut payload = vec![];
+                for message in messages {
+                    match self.format {
+                        SyslogFormat::Rfc5424 => {
+                            payload.extend_from_slice(format!("{} ", message.len()).as_bytes());
+                            payload.extend_from_slice(message.as_bytes());
+                        }
+                        SyslogFormat::Rfc3164 => {
+                            payload.extend_from_slice(message.as_bytes());
+                            payload.push(b'\n');
+                        }
+                    }
+                }
+                if let Err(op) = connected.write_all(&payload) {
+                    warn!(target: LOGGER, "Error while sending to syslog at '{}', reconnecting: {}", address, op);
+                    *stream = None;
+                    *retry = Instant::now() + RECONNECT_BACKOFF;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn resolve(address: &str) -> Result<SocketAddr, RotatorError> {
+    address
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or_else(|| {
+            RotatorError::from(format!(
+                "Invalid syslog address '{}', expected <host>:<port>",
+                address
+            ))
+        })
+}
+
+fn connect(address: &str) -> Result<TcpStream, RotatorError> {
+    let target = resolve(address)?;
+    TcpStream::connect_timeout(&target, CONNECT_TIMEOUT).map_err(|op| {
+        RotatorError::from(format!(
+            "Error while connecting to syslog at '{}': {}",
+            address, op
+        ))
+    })
+}
+
+pub fn start_syslog_sink(
+    mut sink: SyslogSink,
+    rxsink: Receiver<Batch>,
+    txcomplete: Sender<bool>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut stop = false;
+        while !stop {
+            let read_result = rxsink.recv();
+            if let Err(result) = read_result {
+                stop = true;
+                warn!(target: LOGGER, "Error while reading result: {}", result);
+                continue;
+            }
+            if let Err(result) = sink.write(&read_result.unwrap()) {
+                stop = true;
+                error!(target: LOGGER, "Error while writing to syslog: {}", result);
+                continue;
+            }
+            if let Err(result) = txcomplete.send(true) {
+                stop = true;
+                warn!(target: LOGGER, "Error while sending acknowledgment: {}", result);
+            }
+        }
+    })
+}
@@ -0,0 +1,127 @@
+use clap::ValueEnum;
+use log::{error, info, warn};
+use std::io::Write;
+use std::process::{Child, Stdio};
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use stdout_rotator::hooks::shell;
+use stdout_rotator::pipeline::{render, Batch};
+use stdout_rotator::RotatorError;
+
+const LOGGER: &str = "tee_sink";
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TeeExit {
+    // Keep going without the copy
+    Ignore,
+    Restart,
+    // Stop reading the input, as a pipeline would
+    Fail,
+}
+
+pub struct TeeSink {
+    command: String,
+    on_exit: TeeExit,
+    child: Option<Child>,
+    restart: Instant,
+}
+
+impl TeeSink {
+    pub fn spawn(command: &str, on_exit: TeeExit) -> Result<TeeSink, RotatorError> {
+        Ok(TeeSink {
+            command: command.to_string(),
+            on_exit,
+            child: Some(spawn(command)?),
+            restart: Instant::now(),
+        })
+    }
+
+    fn write(&mut self, batch: &Batch) -> Result<(), RotatorError> {
+        if self.child.is_none()
+            && self.on_exit == TeeExit::Restart
+            && Instant::now() >= self.restart
+        {
+            info!(target: LOGGER, "Restarting '{}'", self.command);
+            self.restart = Instant::now() + RESTART_BACKOFF;
+            match spawn(&self.command) {
+                Ok(child) => self.child = Some(child),
+                Err(err) => warn!(target: LOGGER, "{}", err.msg),
+            }
+        }
+        let Some(child) = self.child.as_mut() else {
+            return Ok(());
+        };
+        let written = match child.stdin.as_mut() {
+            Some(stdin) => stdin.write_all(&render(batch)),
+            None => return Ok(()),
+        };
+        if let Err(op) = written {
+            // The command exited or closed its input
+            let status = self.close();
+            match self.on_exit {
+                TeeExit::Fail => {
+                    return Err(RotatorError::from(format!(
+                        "'{}' stopped reading ({}): {}",
+                        self.command, status, op
+                    )))
+                }
+                TeeExit::Ignore => {
+                    warn!(target: LOGGER, "'{}' stopped reading ({}), no longer copying the input to it", self.command, status)
+                }
+                TeeExit::Restart => {
+                    warn!(target: LOGGER, "'{}' stopped reading ({}), restarting it", self.command, status)
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> String {
+        let Some(mut child) = self.child.take() else {
+            return String::new();
+        };
+        drop(child.stdin.take());
+        match child.wait() {
+            Ok(status) => status.to_string(),
+            Err(op) => op.to_string(),
+        }
+    }
+}
+
+fn spawn(command: &str) -> Result<Child, RotatorError> {
+    shell(command)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|op| RotatorError::from(format!("Error while running '{}': {}", command, op)))
+}
+
+pub fn start_tee_sink(
+    mut sink: TeeSink,
+    rxsink: Receiver<Batch>,
+    txcomplete: Sender<bool>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut stop = false;
+        while !stop {
+            let read_result = rxsink.recv();
+            if let Err(result) = read_result {
+                stop = true;
+                warn!(target: LOGGER, "Error while reading result: {}", result);
+                continue;
+            }
+            // Still acknowledged on failure, the reader then fails sending the next batch
+            if let Err(result) = sink.write(&read_result.unwrap()) {
+                stop = true;
+                error!(target: LOGGER, "Error while writing to command: {}", result);
+            }
+            if let Err(result) = txcomplete.send(true) {
+                stop = true;
+                warn!(target: LOGGER, "Error while sending acknowledgment: {}", result);
+            }
+        }
+        // Closing its input lets the command finish with the last lines
+        sink.close();
+    })
+}
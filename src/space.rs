@@ -0,0 +1,67 @@
+use std::io;
+use std::path::Path;
+
+use crate::file_size;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FreeSpace {
+    Bytes(u64),
+    Percent(f64),
+}
+
+impl FreeSpace {
+    pub fn parse(value: &str) -> Result<FreeSpace, String> {
+        match value.trim().strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|percent| (0.0..=100.0).contains(percent))
+                .map(FreeSpace::Percent)
+                .ok_or_else(|| format!("Invalid percentage '{}', expected 0% to 100%", value)),
+            None => file_size(value).map(FreeSpace::Bytes),
+        }
+    }
+
+    pub fn required(&self, total: u64) -> u64 {
+        match self {
+            FreeSpace::Bytes(bytes) => *bytes,
+            FreeSpace::Percent(percent) => (total as f64 * percent / 100.0) as u64,
+        }
+    }
+}
+
+impl std::fmt::Display for FreeSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FreeSpace::Bytes(bytes) => write!(f, "{} bytes", bytes),
+            FreeSpace::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn filesystem_space(path: &Path) -> io::Result<(u64, u64)> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|op| io::Error::new(io::ErrorKind::InvalidInput, op))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fragment = stat.f_frsize as u64;
+    Ok((
+        stat.f_bavail as u64 * fragment,
+        stat.f_blocks as u64 * fragment,
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn filesystem_space(_path: &Path) -> io::Result<(u64, u64)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "free space is only available on unix",
+    ))
+}
@@ -0,0 +1,229 @@
+use log::info;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use stdout_rotator::checksum::rotation_checksum;
+use stdout_rotator::compress::{compressor, CompressionFormat};
+use stdout_rotator::naming::{Naming, NamingArgs};
+use stdout_rotator::{
+    cleanup_rotations, file_size, lock_output, next_file, prepare_rotations, record_manifest,
+    renumber_rotations, RotationConfig, RotatorError,
+};
+
+const LOGGER: &str = "split";
+const SPLIT_EXTENSION: &str = ".split";
+
+#[derive(clap::Args, Debug)]
+pub struct SplitArgs {
+    #[arg(
+        help = "Existing file to split. It keeps its newest lines and its older ones become its rotations, oldest first"
+    )]
+    file: String,
+    #[arg(long, default_value = None, help = "Directory where rotated files are saved. If not provided, the same directory of the file will be used")]
+    rotation_directory: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
+    #[arg(long, default_value = "50MB", value_parser = file_size, help = "Maximum size of every rotation. Rotations end on a line boundary, a longer line taking a rotation of its own")]
+    max_size: u64,
+    #[arg(
+        short,
+        long,
+        default_value_t = 5,
+        help = "Maximum number of rotated files retained. Older parts of the file are discarded"
+    )]
+    max_history: u32,
+    #[arg(
+        short,
+        long,
+        default_value_t = false,
+        help = "Activates gunzip compression of rotated files"
+    )]
+    gunzip: bool,
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with = "gunzip",
+        help = "Compression applied to rotated files. --gunzip is equivalent to --compress gzip"
+    )]
+    compress: Option<CompressionFormat>,
+    #[arg(
+        long,
+        help = "Compression level, or preset for xz. Defaults to 6 for gzip and xz, 3 for zstd and 0 for lz4"
+    )]
+    compress_level: Option<u32>,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Record the rotations in the manifest of the file, as maintained with --manifest"
+    )]
+    manifest: bool,
+}
+
+// Offsets where rotations end with their line counts, the last one being the end of the file
+fn chunk_ends(file: &File, max_size: u64) -> io::Result<Vec<(u64, u64)>> {
+    let mut reader = BufReader::new(file);
+    let mut ends = vec![];
+    let mut start = 0;
+    let mut position = 0;
+    let mut lines = 0;
+    loop {
+        let line = reader.skip_until(b'\n')? as u64;
+        if line == 0 {
+            break;
+        }
+        if position > start && position + line - start > max_size {
+            ends.push((position, lines));
+            start = position;
+            lines = 0;
+        }
+        position += line;
+        lines += 1;
+    }
+    ends.push((position, lines));
+    Ok(ends)
+}
+
+fn copy_range(
+    file: &mut File,
+    start: u64,
+    end: u64,
+    target: &Path,
+    compression: Option<CompressionFormat>,
+    level: Option<u32>,
+) -> Result<(), RotatorError> {
+    let mut partial = target.as_os_str().to_owned();
+    partial.push(SPLIT_EXTENSION);
+    let partial = PathBuf::from(partial);
+    file.seek(SeekFrom::Start(start))
+        .map_err(|op| format!("Error while seeking to {}: {}", start, op))?;
+    // Rotations and the rewritten file keep the permissions of the file split
+    let permissions = file
+        .metadata()
+        .map(|metadata| metadata.permissions())
+        .map_err(|op| format!("Error while reading metadata: {}", op))?;
+    let mut range = file.take(end - start);
+    let mut output = File::create(&partial)
+        .map_err(|op| format!("Error during opening of '{}': {}", partial.display(), op))?;
+    output.set_permissions(permissions).map_err(|op| {
+        format!(
+            "Error while setting permissions of '{}': {}",
+            partial.display(),
+            op
+        )
+    })?;
+    let result = match compression {
+        Some(format) => compressor(format, level)?.compress(&mut range, output),
+        None => io::copy(&mut range, &mut output).and_then(|_| output.sync_all()),
+    };
+    if let Err(op) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(RotatorError::from(format!(
+            "Error while writing '{}': {}",
+            partial.display(),
+            op
+        )));
+    }
+    fs::rename(&partial, target).map_err(|op| {
+        RotatorError::from(format!(
+            "Error while renaming '{}' to '{}': {}",
+            partial.display(),
+            target.display(),
+            op
+        ))
+    })
+}
+
+fn rotate_range(
+    config: &RotationConfig,
+    file: &mut File,
+    start: u64,
+    (end, lines): (u64, u64),
+) -> Result<(), RotatorError> {
+    let next = || {
+        next_file(
+            &config.extension(),
+            &config.output_file,
+            config.rotation_directory.as_deref(),
+            &config.naming,
+        )
+    };
+    let rotation_result = next()?;
+    cleanup_rotations(
+        config.max_history - 1,
+        end - start,
+        config,
+        &rotation_result,
+    )?;
+    let rotation_result = if config.naming.renumbers() {
+        renumber_rotations(config)?;
+        next()?
+    } else {
+        rotation_result
+    };
+    copy_range(
+        file,
+        start,
+        end,
+        &rotation_result.next_rotation,
+        config.compression,
+        config.compression_level,
+    )?;
+    if config.manifest {
+        let rotation = &rotation_result.next_rotation;
+        let sha256 = rotation_checksum(rotation)?;
+        record_manifest(config, |manifest| {
+            manifest.push(rotation, end - start, lines, (None, None), Some(sha256))
+        })?;
+    }
+    println!("created {}", rotation_result.next_rotation.display());
+    Ok(())
+}
+
+pub fn run(args: SplitArgs) -> Result<(), RotatorError> {
+    let mut config = RotationConfig::new(&args.file);
+    config.max_size = args.max_size;
+    config.max_history = args.max_history;
+    config.rotation_directory = args.rotation_directory;
+    config.naming = Naming::new(&args.naming);
+    config.compression = args
+        .compress
+        .or(args.gunzip.then_some(CompressionFormat::Gzip));
+    config.compression_level = args.compress_level;
+    config.manifest = args.manifest;
+    if config.max_size == 0 {
+        return Err(RotatorError::new("--max-size must be above 0 bytes"));
+    }
+    // Also checked before anything is written
+    if let Some(format) = config.compression {
+        compressor(format, config.compression_level)?;
+    }
+    let _lock = lock_output(&config, false)?;
+    let mut file = File::open(&args.file)
+        .map_err(|op| format!("Error during opening of '{}': {}", args.file, op))?;
+    let ends = chunk_ends(&file, config.max_size)
+        .map_err(|op| format!("Error while reading '{}': {}", args.file, op))?;
+    let ((tail_end, _), rotated) = ends.split_last().unwrap();
+    if rotated.is_empty() {
+        println!("{} is not larger than {} bytes", args.file, config.max_size);
+        return Ok(());
+    }
+    prepare_rotations(&config)?;
+    // Rotations retention would remove right away are never written
+    let discarded = rotated.len().saturating_sub(config.max_history as usize);
+    if discarded > 0 {
+        info!(target: LOGGER, "Discarding the {} oldest parts of '{}' beyond --max-history", discarded, args.file);
+    }
+    let mut start = match discarded {
+        0 => 0,
+        discarded => rotated[discarded - 1].0,
+    };
+    record_manifest(&config, |manifest| manifest.written += start)?;
+    for end in &rotated[discarded..] {
+        rotate_range(&config, &mut file, start, *end)?;
+        start = end.0;
+    }
+    let kept = PathBuf::from(&args.file);
+    copy_range(&mut file, start, *tail_end, &kept, None, None)?;
+    println!("kept {} bytes in {}", tail_end - start, args.file);
+    Ok(())
+}
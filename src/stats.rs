@@ -0,0 +1,124 @@
+use log::{debug, error};
+use rusqlite::{params, Connection};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use stdout_rotator::metrics::{Counters, Snapshot};
+use stdout_rotator::RotatorError;
+
+const LOGGER: &str = "stats";
+
+pub struct StatsStore {
+    connection: Connection,
+}
+
+pub struct StatsRow {
+    pub start: u64,
+    pub bytes: u64,
+    pub lines: u64,
+    pub rotations: u64,
+}
+
+impl StatsStore {
+    pub fn open(path: &str) -> Result<StatsStore, RotatorError> {
+        let connection = Connection::open(path)
+            .map_err(|op| format!("Error while opening statistics database '{}': {}", path, op))?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS throughput (
+                    start INTEGER NOT NULL,
+                    end INTEGER NOT NULL,
+                    bytes INTEGER NOT NULL,
+                    lines INTEGER NOT NULL,
+                    rotations INTEGER NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS throughput_start ON throughput (start);",
+            )
+            .map_err(|op| {
+                format!(
+                    "Error while initialising statistics database '{}': {}",
+                    path, op
+                )
+            })?;
+        Ok(StatsStore { connection })
+    }
+
+    fn insert(&self, start: u64, end: u64, delta: &Snapshot) -> Result<(), RotatorError> {
+        self.connection
+            .execute(
+                "INSERT INTO throughput (start, end, bytes, lines, rotations) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![start, end, delta.bytes_in, delta.lines_in, delta.rotations],
+            )
+            .map_err(|op| format!("Error while recording statistics: {}", op))?;
+        Ok(())
+    }
+
+    pub fn history(&self, since: u64, bucket: u64) -> Result<Vec<StatsRow>, RotatorError> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT (start / ?2) * ?2 AS bucket, SUM(bytes), SUM(lines), SUM(rotations)
+                 FROM throughput WHERE start >= ?1 GROUP BY bucket ORDER BY bucket",
+            )
+            .map_err(|op| format!("Error while querying statistics: {}", op))?;
+        let rows = statement
+            .query_map(params![since, bucket.max(1)], |row| {
+                Ok(StatsRow {
+                    start: row.get(0)?,
+                    bytes: row.get(1)?,
+                    lines: row.get(2)?,
+                    rotations: row.get(3)?,
+                })
+            })
+            .map_err(|op| format!("Error while querying statistics: {}", op))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|op| RotatorError::from(format!("Error while reading statistics: {}", op)))
+    }
+}
+
+pub fn epoch_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn delta(current: &Snapshot, previous: &Snapshot) -> Snapshot {
+    Snapshot {
+        bytes_in: current.bytes_in - previous.bytes_in,
+        lines_in: current.lines_in - previous.lines_in,
+        rotations: current.rotations - previous.rotations,
+        dropped_file_chunks: current.dropped_file_chunks - previous.dropped_file_chunks,
+        deleted_files: current.deleted_files - previous.deleted_files,
+        errors: current.errors - previous.errors,
+    }
+}
+
+pub fn start_stats_recorder(
+    store: StatsStore,
+    interval: Duration,
+    counters: Arc<Counters>,
+) -> (Sender<()>, JoinHandle<()>) {
+    let (txstop, rxstop) = mpsc::channel::<()>();
+    let handle = thread::spawn(move || {
+        let mut previous = counters.snapshot();
+        let mut start = epoch_seconds(SystemTime::now());
+        let mut stop = false;
+        while !stop {
+            stop = match rxstop.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => false,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => true,
+            };
+            let current = counters.snapshot();
+            let end = epoch_seconds(SystemTime::now());
+            let interval_delta = delta(&current, &previous);
+            debug!(target: LOGGER, "Recording {:?} for {}..{}", interval_delta, start, end);
+            if let Err(err) = store.insert(start, end, &interval_delta) {
+                error!(target: LOGGER, "{}", err);
+            }
+            previous = current;
+            start = end;
+        }
+    });
+    (txstop, handle)
+}
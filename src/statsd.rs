@@ -0,0 +1,101 @@
+use log::{debug, warn};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use stdout_rotator::metrics::{Counters, Snapshot};
+use stdout_rotator::RotatorError;
+
+use crate::stats::delta;
+
+const LOGGER: &str = "statsd";
+
+pub struct StatsdClient {
+    socket: UdpSocket,
+    prefix: String,
+    // DogStatsD tags, appended to every metric
+    tags: Vec<String>,
+}
+
+impl StatsdClient {
+    pub fn connect(
+        address: &str,
+        prefix: &str,
+        tags: Vec<String>,
+    ) -> Result<StatsdClient, RotatorError> {
+        let target: SocketAddr = address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| {
+                format!(
+                    "Invalid statsd address '{}', expected <host>:<port>",
+                    address
+                )
+            })?;
+        let local = if target.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)
+            .and_then(|socket| socket.connect(target).map(|_| socket))
+            .map_err(|op| format!("Error while connecting to statsd at '{}': {}", address, op))?;
+        Ok(StatsdClient {
+            socket,
+            prefix: prefix.trim_end_matches('.').to_string(),
+            tags,
+        })
+    }
+
+    fn metric(&self, name: &str, value: u64) -> String {
+        let metric = match self.prefix.as_str() {
+            "" => format!("{}:{}|c", name, value),
+            prefix => format!("{}.{}:{}|c", prefix, name, value),
+        };
+        match self.tags.as_slice() {
+            [] => metric,
+            tags => format!("{}|#{}", metric, tags.join(",")),
+        }
+    }
+
+    // All counters go in a single datagram, one metric per line
+    fn send(&self, counters: &Snapshot) {
+        let payload = [
+            self.metric("bytes_in", counters.bytes_in),
+            self.metric("lines_in", counters.lines_in),
+            self.metric("rotations", counters.rotations),
+            self.metric("dropped_file_chunks", counters.dropped_file_chunks),
+        ]
+        .join("\n");
+        // Nobody listening is not an error worth stopping for, metrics are best effort
+        if let Err(op) = self.socket.send(payload.as_bytes()) {
+            warn!(target: LOGGER, "Error while sending metrics: {}", op);
+        }
+    }
+}
+
+pub fn start_statsd_reporter(
+    client: StatsdClient,
+    interval: Duration,
+    counters: Arc<Counters>,
+) -> (Sender<()>, JoinHandle<()>) {
+    let (txstop, rxstop) = mpsc::channel::<()>();
+    let handle = thread::spawn(move || {
+        let mut previous = counters.snapshot();
+        let mut stop = false;
+        while !stop {
+            stop = match rxstop.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => false,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => true,
+            };
+            let current = counters.snapshot();
+            let interval_delta = delta(&current, &previous);
+            debug!(target: LOGGER, "Sending {:?}", interval_delta);
+            client.send(&interval_delta);
+            previous = current;
+        }
+    });
+    (txstop, handle)
+}
@@ -0,0 +1,33 @@
+use serde_json::json;
+use std::fs;
+use std::io::{self, Write};
+use std::time::Instant;
+use stdout_rotator::metrics::Counters;
+use stdout_rotator::RotatorError;
+
+// Written once on exit, as a single JSON object so that batch jobs can parse it directly
+pub fn write_summary(
+    summary_file: Option<&str>,
+    started: Instant,
+    counters: &Counters,
+    exit_code: i32,
+) -> Result<(), RotatorError> {
+    let counters = counters.snapshot();
+    let summary = json!({
+        "duration_seconds": started.elapsed().as_secs_f64(),
+        "bytes_processed": counters.bytes_in,
+        "lines_processed": counters.lines_in,
+        "rotations": counters.rotations,
+        "files_deleted": counters.deleted_files,
+        "dropped_file_chunks": counters.dropped_file_chunks,
+        "errors": counters.errors,
+        "exit_code": exit_code,
+    });
+    match summary_file {
+        Some(path) => fs::write(path, format!("{}\n", summary))
+            .map_err(|op| format!("Error while writing summary to '{}': {}", path, op))?,
+        None => writeln!(io::stderr(), "{}", summary)
+            .map_err(|op| format!("Error while writing summary: {}", op))?,
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::{duration, file_size};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncPolicy {
+    #[default]
+    Never,
+    EveryWrite,
+    EveryBytes(u64),
+    Interval(Duration),
+}
+
+impl SyncPolicy {
+    pub fn parse(value: &str) -> Result<SyncPolicy, String> {
+        match value.trim().split_once('=') {
+            None if value.trim() == "never" => Ok(SyncPolicy::Never),
+            None if value.trim() == "every-write" => Ok(SyncPolicy::EveryWrite),
+            Some(("every-n-bytes", bytes)) => match file_size(bytes)? {
+                0 => Err("The bytes between synchronisations must be positive".to_string()),
+                bytes => Ok(SyncPolicy::EveryBytes(bytes)),
+            },
+            Some(("interval", interval)) => match duration(interval)? {
+                interval if interval.is_zero() => {
+                    Err("The interval between synchronisations must be positive".to_string())
+                }
+                interval => Ok(SyncPolicy::Interval(interval)),
+            },
+            _ => Err(format!(
+                "Invalid sync policy '{}', expected never, every-write, every-n-bytes=<size> or interval=<duration>",
+                value
+            )),
+        }
+    }
+}
+
+// Tracks what was written since the last fsync of the output file
+pub struct Durability {
+    policy: SyncPolicy,
+    unsynced: u64,
+    synced: Instant,
+}
+
+impl Durability {
+    pub fn new(policy: SyncPolicy) -> Durability {
+        Durability {
+            policy,
+            unsynced: 0,
+            synced: Instant::now(),
+        }
+    }
+
+    // How long the writer can wait for input before the next synchronisation is due
+    pub fn wait(&self) -> Option<Duration> {
+        match self.policy {
+            SyncPolicy::Interval(interval) if self.unsynced > 0 => {
+                Some(interval.saturating_sub(self.synced.elapsed()))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn written(&mut self, file: &File, bytes: u64) -> io::Result<()> {
+        if self.policy == SyncPolicy::Never || bytes == 0 {
+            return Ok(());
+        }
+        self.unsynced += bytes;
+        let due = match self.policy {
+            SyncPolicy::Never => false,
+            SyncPolicy::EveryWrite => true,
+            SyncPolicy::EveryBytes(bytes) => self.unsynced >= bytes,
+            SyncPolicy::Interval(interval) => self.synced.elapsed() >= interval,
+        };
+        if due {
+            self.sync(file)?;
+        }
+        Ok(())
+    }
+
+    pub fn sync(&mut self, file: &File) -> io::Result<()> {
+        file.sync_data()?;
+        self.unsynced = 0;
+        self.synced = Instant::now();
+        Ok(())
+    }
+}
@@ -0,0 +1,352 @@
+use std::time::SystemTime;
+use stdout_rotator::clock::civil_time;
+
+use crate::sinks::syslog::MONTHS;
+use crate::sinks::timestamp;
+use crate::stats::epoch_seconds;
+
+// Named as the levels lines are split and filtered by
+const SEVERITIES: [&str; 8] = [
+    "FATAL", "FATAL", "CRITICAL", "ERROR", "WARNING", "NOTICE", "INFO", "DEBUG",
+];
+// user.notice, which RFC 3164 assumes for messages without a priority
+const DEFAULT_PRIORITY: u32 = 13;
+// A frame growing past this without ending is passed on as it is
+const MAX_FRAME: usize = 64 * 1024;
+
+// Splits a stream into syslog messages, framed by octet counting or by newlines as RFC 6587
+// describes, and normalises each of them into a line
+pub struct SyslogFrames {
+    host: String,
+    pending: Vec<u8>,
+}
+
+impl SyslogFrames {
+    // The host is the one of messages not naming theirs, as the peer they were received from
+    pub fn new(host: &str) -> SyslogFrames {
+        SyslogFrames {
+            host: host.to_string(),
+            pending: vec![],
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        self.pending.extend_from_slice(data);
+        let mut lines = vec![];
+        let mut start = 0;
+        while let Some((message, next)) = frame(&self.pending[start..]) {
+            if !message.is_empty() {
+                lines.extend(normalise(message, &self.host));
+            }
+            start += next;
+        }
+        self.pending.drain(..start);
+        if self.pending.len() > MAX_FRAME {
+            lines.extend(normalise(&std::mem::take(&mut self.pending), &self.host));
+        }
+        (!lines.is_empty()).then_some(lines)
+    }
+
+    pub fn finish(&mut self) -> Option<Vec<u8>> {
+        let pending = std::mem::take(&mut self.pending);
+        let message = trimmed(&pending);
+        (!message.is_empty()).then(|| normalise(message, &self.host))
+    }
+}
+
+// The next complete message and where the one after it starts
+fn frame(data: &[u8]) -> Option<(&[u8], usize)> {
+    let digits = data.iter().take_while(|b| b.is_ascii_digit()).count();
+    if digits > 0 && data.get(digits) == Some(&b' ') {
+        let length: usize = std::str::from_utf8(&data[..digits]).ok()?.parse().ok()?;
+        let end = digits + 1 + length;
+        return (data.len() >= end).then(|| (&data[digits + 1..end], end));
+    }
+    let end = data.iter().position(|b| *b == b'\n' || *b == 0)?;
+    Some((trimmed(&data[..end]), end + 1))
+}
+
+fn trimmed(message: &[u8]) -> &[u8] {
+    let end = message
+        .iter()
+        .rposition(|b| !matches!(*b, b'\n' | b'\r' | 0))
+        .map_or(0, |end| end + 1);
+    &message[..end]
+}
+
+// A message as <timestamp> <host> <SEVERITY> <app>[<pid>]: <text>, the severity put where the
+// level of a line is looked for
+pub fn normalise(message: &[u8], host: &str) -> Vec<u8> {
+    let message = String::from_utf8_lossy(trimmed(message));
+    let (priority, rest) = priority(&message);
+    let severity = SEVERITIES[(priority % 8) as usize];
+    let parsed = match rest.strip_prefix("1 ") {
+        Some(rest) => rfc5424(rest),
+        None => rfc3164(rest),
+    };
+    let line = match parsed {
+        Some(Parsed {
+            timestamp,
+            host: sender,
+            tag,
+            text,
+        }) => {
+            let host = sender.unwrap_or(host);
+            match tag {
+                Some(tag) => format!("{} {} {} {}: {}", timestamp, host, severity, tag, text),
+                None => format!("{} {} {} {}", timestamp, host, severity, text),
+            }
+        }
+        None => format!(
+            "{} {} {} {}",
+            timestamp(SystemTime::now()),
+            host,
+            severity,
+            rest
+        ),
+    };
+    let mut line = line.trim_end().to_string().into_bytes();
+    line.push(b'\n');
+    line
+}
+
+struct Parsed<'a> {
+    timestamp: String,
+    host: Option<&'a str>,
+    tag: Option<String>,
+    text: &'a str,
+}
+
+fn priority(message: &str) -> (u32, &str) {
+    let parsed = message.strip_prefix('<').and_then(|rest| {
+        let (priority, rest) = rest.split_once('>')?;
+        let priority = priority
+            .parse::<u32>()
+            .ok()
+            .filter(|priority| *priority <= 191)?;
+        Some((priority, rest))
+    });
+    parsed.unwrap_or((DEFAULT_PRIORITY, message))
+}
+
+fn nil(field: &str) -> Option<&str> {
+    Some(field).filter(|field| *field != "-" && !field.is_empty())
+}
+
+fn tag(app: Option<&str>, pid: Option<&str>) -> Option<String> {
+    match (app, pid) {
+        (Some(app), Some(pid)) => Some(format!("{}[{}]", app, pid)),
+        (Some(app), None) => Some(app.to_string()),
+        (None, _) => None,
+    }
+}
+
+// TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA [MSG], the message id and structured
+// data being dropped
+fn rfc5424(rest: &str) -> Option<Parsed<'_>> {
+    let mut fields = rest.splitn(6, ' ');
+    let time = fields.next()?;
+    let host = fields.next()?;
+    let app = fields.next()?;
+    let pid = fields.next()?;
+    let _message_id = fields.next()?;
+    let rest = fields.next().unwrap_or_default();
+    let text = skip_structured_data(rest)?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    Some(Parsed {
+        timestamp: nil(time)
+            .map(str::to_string)
+            .unwrap_or_else(|| timestamp(SystemTime::now())),
+        host: nil(host),
+        tag: tag(nil(app), nil(pid)),
+        text,
+    })
+}
+
+fn skip_structured_data(rest: &str) -> Option<&str> {
+    if let Some(text) = rest.strip_prefix('-') {
+        return Some(text.strip_prefix(' ').unwrap_or(text));
+    }
+    let bytes = rest.as_bytes();
+    let mut position = 0;
+    // Elements follow each other without spaces, their quoted values escaping ] with a backslash
+    while bytes.get(position) == Some(&b'[') {
+        let mut quoted = false;
+        loop {
+            position += 1;
+            match bytes.get(position)? {
+                b'\\' if quoted => position += 1,
+                b'"' => quoted = !quoted,
+                b']' if !quoted => break,
+                _ => {}
+            }
+        }
+        position += 1;
+    }
+    if position == 0 {
+        return None;
+    }
+    let text = &rest[position..];
+    Some(text.strip_prefix(' ').unwrap_or(text))
+}
+
+// Mmm dd hh:mm:ss HOSTNAME TAG: MSG, the year missing from the timestamp being the current one
+fn rfc3164(rest: &str) -> Option<Parsed<'_>> {
+    let stamp = rest.get(..15).filter(|stamp| stamp.is_ascii())?;
+    let month = MONTHS.iter().position(|month| stamp.starts_with(month))? + 1;
+    let day = stamp[4..6].trim_start().parse::<u32>().ok()?;
+    let time = &stamp[7..];
+    let valid = time.len() == 8
+        && time
+            .split(':')
+            .all(|part| part.len() == 2 && part.parse::<u8>().is_ok());
+    if !valid || rest.as_bytes().get(15) != Some(&b' ') {
+        return None;
+    }
+    let year = civil_time(epoch_seconds(SystemTime::now()) as i64).year;
+    let (host, rest) = rest[16..].split_once(' ').unwrap_or((&rest[16..], ""));
+    let (tag, text) = match rest.split_once(": ") {
+        Some((tag, text)) if !tag.is_empty() && !tag.contains(' ') => (Some(tag.to_string()), text),
+        _ => (None, rest),
+    };
+    Some(Parsed {
+        timestamp: format!("{}-{:02}-{:02}T{}", year, month, day, time),
+        host: nil(host),
+        tag,
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(message: &str) -> String {
+        String::from_utf8(normalise(message.as_bytes(), "peer")).unwrap()
+    }
+
+    // Lines whose timestamp is the time they were received, without it
+    fn received(message: &str) -> String {
+        let line = line(message);
+        let (_, rest) = line.split_once(' ').unwrap();
+        rest.to_string()
+    }
+
+    #[test]
+    fn priorities_are_parsed_and_bounded() {
+        assert_eq!(priority("<34>rest"), (34, "rest"));
+        assert_eq!(priority("<0>rest"), (0, "rest"));
+        assert_eq!(priority("<191>rest"), (191, "rest"));
+        assert_eq!(priority("<192>rest"), (DEFAULT_PRIORITY, "<192>rest"));
+        assert_eq!(priority("<x>rest"), (DEFAULT_PRIORITY, "<x>rest"));
+        assert_eq!(priority("rest"), (DEFAULT_PRIORITY, "rest"));
+    }
+
+    #[test]
+    fn rfc5424_messages_are_normalised() {
+        assert_eq!(
+            line("<165>1 2003-10-11T22:14:15.003Z mymachine su 77 ID47 - 'su root' failed\n"),
+            "2003-10-11T22:14:15.003Z mymachine NOTICE su[77]: 'su root' failed\n"
+        );
+        assert_eq!(
+            line("<11>1 2003-10-11T22:14:15Z - app - - - \u{feff}text"),
+            "2003-10-11T22:14:15Z peer ERROR app: text\n"
+        );
+        assert_eq!(
+            line("<14>1 2003-10-11T22:14:15Z host - - - -"),
+            "2003-10-11T22:14:15Z host INFO\n"
+        );
+        assert!(line("<14>1 - host app - - - text").ends_with(" host INFO app: text\n"));
+    }
+
+    #[test]
+    fn structured_data_is_skipped() {
+        assert_eq!(skip_structured_data("- text"), Some("text"));
+        assert_eq!(skip_structured_data("-"), Some(""));
+        assert_eq!(
+            skip_structured_data("[id a=\"1\"][other b=\"x\\]y\"] text"),
+            Some("text")
+        );
+        assert_eq!(skip_structured_data("[id a=\"]\"]"), Some(""));
+        assert_eq!(skip_structured_data("[id a=\"1\" text"), None);
+        assert_eq!(skip_structured_data("text"), None);
+        assert_eq!(
+            line("<15>1 2003-10-11T22:14:15Z host app 1 - [x@1 k=\"v\"] text"),
+            "2003-10-11T22:14:15Z host DEBUG app[1]: text\n"
+        );
+    }
+
+    #[test]
+    fn rfc3164_messages_are_normalised() {
+        let year = civil_time(epoch_seconds(SystemTime::now()) as i64).year;
+        assert_eq!(
+            line("<34>Oct 11 22:14:15 mymachine su: 'su root' failed"),
+            format!(
+                "{}-10-11T22:14:15 mymachine CRITICAL su: 'su root' failed\n",
+                year
+            )
+        );
+        assert_eq!(
+            line("<13>Feb  5 01:02:03 host sshd[42]: accepted"),
+            format!("{}-02-05T01:02:03 host NOTICE sshd[42]: accepted\n", year)
+        );
+        assert_eq!(
+            line("<12>Feb  5 01:02:03 host no tag here"),
+            format!("{}-02-05T01:02:03 host WARNING no tag here\n", year)
+        );
+    }
+
+    #[test]
+    fn unparsed_messages_keep_their_text() {
+        assert_eq!(received("<8>just text"), "peer FATAL just text\n");
+        assert_eq!(received("no priority"), "peer NOTICE no priority\n");
+        assert_eq!(
+            received("<30>Oct 11 2:14:15 host text"),
+            "peer INFO Oct 11 2:14:15 host text\n"
+        );
+        assert_eq!(
+            received("<30>Foo 11 22:14:15 host text"),
+            "peer INFO Foo 11 22:14:15 host text\n"
+        );
+    }
+
+    #[test]
+    fn frames_are_counted_or_newline_delimited() {
+        assert_eq!(frame(b"5 hello6 world!"), Some((&b"hello"[..], 7)));
+        assert_eq!(frame(b"5 hel"), None);
+        assert_eq!(frame(b"hello\r\nworld"), Some((&b"hello"[..], 7)));
+        assert_eq!(frame(b"hello\0world"), Some((&b"hello"[..], 6)));
+        assert_eq!(frame(b"hello"), None);
+        assert_eq!(frame(b"12abc\n"), Some((&b"12abc"[..], 6)));
+    }
+
+    #[test]
+    fn messages_split_across_pushes_are_joined() {
+        let mut frames = SyslogFrames::new("peer");
+        assert_eq!(
+            frames.push(b"<14>1 2003-10-11T22:14:15Z h a - - - fi"),
+            None
+        );
+        assert_eq!(
+            frames.push(b"rst\n\n38 <14>1 2003-10-11T22:14:15Z h"),
+            Some(b"2003-10-11T22:14:15Z h INFO a: first\n".to_vec())
+        );
+        assert_eq!(
+            frames.push(b" b - - - x<13>last"),
+            Some(b"2003-10-11T22:14:15Z h INFO b: x\n".to_vec())
+        );
+        let last = String::from_utf8(frames.finish().unwrap()).unwrap();
+        assert!(last.ends_with(" peer NOTICE last\n"));
+        assert_eq!(frames.finish(), None);
+    }
+
+    #[test]
+    fn overlong_frames_are_passed_on() {
+        let mut frames = SyslogFrames::new("peer");
+        let lines = frames.push(&vec![b'x'; MAX_FRAME + 1]).unwrap();
+        let text = lines.iter().position(|b| *b == b'x').unwrap();
+        assert!(lines[..text].ends_with(b" peer NOTICE "));
+        assert_eq!(lines.len(), text + MAX_FRAME + 2);
+        assert_eq!(frames.finish(), None);
+    }
+}
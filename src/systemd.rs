@@ -0,0 +1,122 @@
+use log::{debug, info, warn};
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use stdout_rotator::pipeline::Batch;
+use stdout_rotator::RotatorError;
+
+const LOGGER: &str = "systemd";
+
+// Client of the sd_notify protocol: newline separated assignments sent as datagrams to the
+// socket systemd passes in NOTIFY_SOCKET
+pub struct Notifier {
+    #[cfg(unix)]
+    socket: std::os::unix::net::UnixDatagram,
+}
+
+impl Notifier {
+    #[cfg(unix)]
+    pub fn from_env() -> Result<Option<Notifier>, RotatorError> {
+        use std::os::unix::net::UnixDatagram;
+
+        let path = match env::var_os("NOTIFY_SOCKET") {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let socket = UnixDatagram::unbound()
+            .map_err(|op| format!("Error while creating notification socket: {}", op))?;
+        let connected = match path.to_string_lossy().strip_prefix('@') {
+            // Abstract sockets have no path on the filesystem
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                std::os::unix::net::SocketAddr::from_abstract_name(name)
+                    .and_then(|address| socket.connect_addr(&address))
+            }
+            _ => socket.connect(&path),
+        };
+        connected.map_err(|op| {
+            format!(
+                "Error while connecting to notification socket '{}': {}",
+                path.to_string_lossy(),
+                op
+            )
+        })?;
+        Ok(Some(Notifier { socket }))
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_env() -> Result<Option<Notifier>, RotatorError> {
+        Ok(None)
+    }
+
+    #[cfg(unix)]
+    pub fn notify(&self, state: &str) {
+        debug!(target: LOGGER, "Notifying {}", state);
+        if let Err(op) = self.socket.send(state.as_bytes()) {
+            warn!(target: LOGGER, "Error while notifying systemd: {}", op);
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn notify(&self, _state: &str) {}
+}
+
+// The watchdog is only enabled for this process when systemd sets WATCHDOG_USEC for it
+pub fn watchdog_interval() -> Option<Duration> {
+    let pid = env::var("WATCHDOG_PID").ok();
+    if pid.is_some_and(|pid| pid.trim() != std::process::id().to_string()) {
+        return None;
+    }
+    env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|usec| usec.trim().parse::<u64>().ok())
+        .filter(|usec| *usec > 0)
+        .map(Duration::from_micros)
+}
+
+// Pings are only sent while every file writer handles the ticks sent to it, so that a stuck
+// writer makes systemd restart the process
+pub fn start_watchdog(
+    notifier: Arc<Notifier>,
+    interval: Duration,
+    txfiles: Vec<Sender<Batch>>,
+    heartbeats: Vec<Arc<AtomicU64>>,
+) -> (Sender<()>, JoinHandle<()>) {
+    let (txstop, rxstop) = mpsc::channel::<()>();
+    let handle = thread::spawn(move || {
+        let beats = || {
+            heartbeats
+                .iter()
+                .map(|heartbeat| heartbeat.load(Ordering::Acquire))
+                .collect::<Vec<u64>>()
+        };
+        let mut seen = beats();
+        loop {
+            for txfile in &txfiles {
+                let _ = txfile.send(Arc::new(vec![]));
+            }
+            // Pinging twice per interval leaves the writers half an interval to answer
+            if let Ok(()) | Err(RecvTimeoutError::Disconnected) = rxstop.recv_timeout(interval / 2)
+            {
+                break;
+            }
+            let current = beats();
+            if current
+                .iter()
+                .zip(&seen)
+                .all(|(current, seen)| current > seen)
+            {
+                notifier.notify("WATCHDOG=1");
+            } else {
+                warn!(target: LOGGER, "A file writer did not respond, skipping the watchdog ping");
+            }
+            seen = current;
+        }
+    });
+    info!(target: LOGGER, "Pinging the systemd watchdog every {:?}", interval / 2);
+    (txstop, handle)
+}
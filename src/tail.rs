@@ -0,0 +1,245 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use stdout_rotator::compress::open_rotation;
+use stdout_rotator::index::{open_rotations_since, Index, SinceFilter};
+use stdout_rotator::naming::{Naming, NamingArgs};
+use stdout_rotator::{all_rotations, timestamp, RotatorError};
+
+use crate::follow::Follower;
+
+const TAIL_WAIT: Duration = Duration::from_secs(1);
+
+#[derive(clap::Args, Debug)]
+pub struct TailArgs {
+    #[arg(help = "File managed by stdout-rotator whose last lines are printed")]
+    file: String,
+    #[arg(long, default_value = None, help = "Directory where rotated files are saved. If not provided, the same directory of the file will be used")]
+    rotation_directory: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
+    #[arg(
+        short = 'n',
+        long,
+        default_value_t = 10,
+        help = "Number of last lines printed, taken from the newest rotations when the file holds fewer"
+    )]
+    lines: usize,
+    #[arg(
+        short,
+        long,
+        default_value_t = false,
+        help = "Keep printing what is written to the file, continuing with the new file after every rotation"
+    )]
+    follow: bool,
+    #[arg(long, value_parser = timestamp, conflicts_with = "lines", help = "Print the content written after this time (RFC 3339 timestamp or duration ago) instead of the last lines, seeking with the index written with --index, or scanning the timestamps of the lines without one. Whole rotations are skipped after the write times recorded with --manifest, or their modification time")]
+    since: Option<SystemTime>,
+}
+
+// The last lines of what is pushed, the last one possibly not ended yet
+struct LastLines {
+    count: usize,
+    lines: VecDeque<Vec<u8>>,
+}
+
+impl LastLines {
+    fn new(count: usize) -> LastLines {
+        LastLines {
+            count,
+            lines: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let end = data
+                .iter()
+                .position(|b| *b == b'\n')
+                .map_or(data.len(), |position| position + 1);
+            match self.lines.back_mut() {
+                Some(last) if !last.ends_with(b"\n") => last.extend_from_slice(&data[..end]),
+                _ => self.lines.push_back(data[..end].to_vec()),
+            }
+            data = &data[end..];
+            // One more line is kept while the last one is not ended
+            while self.lines.len() > self.count + 1
+                || (self.lines.len() > self.count
+                    && self.lines.back().is_some_and(|last| last.ends_with(b"\n")))
+            {
+                self.lines.pop_front();
+            }
+        }
+    }
+
+    fn complete(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|line| line.ends_with(b"\n"))
+            .count()
+    }
+}
+
+fn read_rotation(path: &Path, count: usize) -> Result<LastLines, RotatorError> {
+    let mut reader = open_rotation(path)?;
+    let mut last = LastLines::new(count);
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader
+            .read(&mut buffer)
+            .map_err(|op| format!("Error while reading '{}': {}", path.display(), op))?;
+        if read == 0 {
+            return Ok(last);
+        }
+        last.push(&buffer[..read]);
+    }
+}
+
+fn rotations(args: &TailArgs) -> Result<Vec<PathBuf>, RotatorError> {
+    all_rotations(
+        &args.file,
+        args.rotation_directory.as_deref(),
+        &Naming::new(&args.naming),
+    )
+}
+
+// The last lines of the rotations, for a file holding fewer lines than asked
+fn rotated_lines(args: &TailArgs, mut count: usize) -> Result<Vec<Vec<u8>>, RotatorError> {
+    let mut lines = vec![];
+    for rotation in rotations(args)?.iter().rev() {
+        if count == 0 {
+            break;
+        }
+        let last = read_rotation(rotation, count)?;
+        let taken: Vec<Vec<u8>> = last.lines.into_iter().rev().take(count).collect();
+        count -= taken.len();
+        lines.extend(taken);
+    }
+    lines.reverse();
+    Ok(lines)
+}
+
+// When the file was truncated by a rotation copied across filesystems, what was not read yet is
+// only left in the newest rotation
+fn catch_up(args: &TailArgs, position: u64, output: &mut dyn Write) -> Result<(), RotatorError> {
+    let newest = match rotations(args)?.pop() {
+        Some(newest) => newest,
+        None => return Ok(()),
+    };
+    let mut reader = open_rotation(&newest)?;
+    io::copy(&mut (&mut reader).take(position), &mut io::sink())
+        .and_then(|_| io::copy(&mut reader, output))
+        .map_err(|op| format!("Error while printing '{}': {}", newest.display(), op))?;
+    Ok(())
+}
+
+fn print(output: &mut dyn Write, data: &[u8]) -> Result<(), RotatorError> {
+    output
+        .write_all(data)
+        .and_then(|_| output.flush())
+        .map_err(|op| RotatorError::from(format!("Error while printing: {}", op)))
+}
+
+// What was written after --since, the file being read up to its end
+fn print_since(
+    args: &TailArgs,
+    since: SystemTime,
+    follower: &mut Follower,
+    output: &mut dyn Write,
+) -> Result<(), RotatorError> {
+    let path = PathBuf::from(&args.file);
+    let rotations = open_rotations_since(
+        &args.file,
+        args.rotation_directory.as_deref(),
+        &Naming::new(&args.naming),
+        Some(since),
+    )?;
+    for (rotation, mut reader) in rotations {
+        io::copy(&mut reader, output)
+            .map_err(|op| format!("Error while printing '{}': {}", rotation.display(), op))?;
+    }
+    let mut filter = None;
+    if path.exists() {
+        match Index::load(&path)? {
+            Some(index) => follower
+                .seek(index.offset_since(since))
+                .map_err(|op| format!("Error while seeking '{}': {}", args.file, op))?,
+            None => filter = Some(SinceFilter::new(since)),
+        }
+    }
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        match follower.read(&mut buffer) {
+            Ok(read) => match filter.as_mut() {
+                Some(filter) => print(output, &filter.feed(&buffer[..read]))?,
+                None => print(output, &buffer[..read])?,
+            },
+            Err(op) if op.kind() == io::ErrorKind::WouldBlock => {
+                if let Some(filter) = filter.as_mut() {
+                    print(output, &filter.finish())?;
+                }
+                return Ok(());
+            }
+            Err(op) => return Err(format!("Error while reading '{}': {}", args.file, op).into()),
+        }
+    }
+}
+
+fn follow(
+    args: &TailArgs,
+    follower: &mut Follower,
+    output: &mut dyn Write,
+) -> Result<(), RotatorError> {
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        match follower.read(&mut buffer) {
+            Ok(read) => print(output, &buffer[..read])?,
+            Err(op) if op.kind() == io::ErrorKind::WouldBlock => {
+                if let Some(position) = follower.take_truncation() {
+                    catch_up(args, position, output)?;
+                }
+                follower
+                    .wait_readable(TAIL_WAIT)
+                    .map_err(|op| format!("Error while waiting for '{}': {}", args.file, op))?;
+            }
+            Err(op) => return Err(format!("Error while reading '{}': {}", args.file, op).into()),
+        }
+    }
+}
+
+pub fn run(args: TailArgs) -> Result<(), RotatorError> {
+    let path = PathBuf::from(&args.file);
+    let mut follower = Follower::open(&path, true)
+        .map_err(|op| format!("Error during opening of '{}': {}", args.file, op))?;
+    let mut stdout = io::stdout().lock();
+    let mut buffer = vec![0u8; 64 * 1024];
+    if let Some(since) = args.since {
+        print_since(&args, since, &mut follower, &mut stdout)?;
+        return match args.follow {
+            true => follow(&args, &mut follower, &mut stdout),
+            false => Ok(()),
+        };
+    }
+    let mut last = LastLines::new(args.lines);
+    // What the file holds now, the lines printed first
+    loop {
+        match follower.read(&mut buffer) {
+            Ok(read) => last.push(&buffer[..read]),
+            Err(op) if op.kind() == io::ErrorKind::WouldBlock => break,
+            Err(op) => return Err(format!("Error while reading '{}': {}", args.file, op).into()),
+        }
+    }
+    let missing = args.lines.saturating_sub(last.complete());
+    if missing > 0 {
+        for line in rotated_lines(&args, missing)? {
+            print(&mut stdout, &line)?;
+        }
+    }
+    for line in &last.lines {
+        print(&mut stdout, line)?;
+    }
+    match args.follow {
+        true => follow(&args, &mut follower, &mut stdout),
+        false => Ok(()),
+    }
+}
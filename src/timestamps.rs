@@ -0,0 +1,117 @@
+use regex::Regex;
+use serde_json::Value;
+use std::time::{Duration, SystemTime};
+
+pub const TIMESTAMP_FIELDS: &[&str] = &["ts", "time", "timestamp", "@timestamp"];
+// A timestamp starting the line, possibly in brackets
+pub const TIMESTAMP_PATTERN: &str =
+    r"^\[?(?<ts>\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?)";
+
+// RFC 3339 timestamps, with a space or a comma accepted as written by most loggers
+fn parse_time(value: &str) -> Option<SystemTime> {
+    let value = value.replace(',', ".");
+    let (base, offset) = match value.rfind(['+', '-']) {
+        // The date separators come before the time
+        Some(position) if position > 10 => (&value[..position], Some(&value[position..])),
+        _ => (value.as_str(), None),
+    };
+    let time = humantime::parse_rfc3339_weak(base.trim_end_matches('Z')).ok()?;
+    let Some(offset) = offset else {
+        return Some(time);
+    };
+    let digits: String = offset[1..].chars().filter(|c| *c != ':').collect();
+    if digits.len() != 4 {
+        return None;
+    }
+    let hours: u64 = digits[..2].parse().ok()?;
+    let minutes: u64 = digits[2..].parse().ok()?;
+    let shift = Duration::from_secs(hours * 3600 + minutes * 60);
+    // Local time ahead of UTC is shifted back to it
+    match offset.starts_with('+') {
+        true => time.checked_sub(shift),
+        false => time.checked_add(shift),
+    }
+}
+
+pub fn line_time(line: &[u8], timestamp_regex: &Regex) -> Option<SystemTime> {
+    let text = String::from_utf8_lossy(line);
+    let text = text.trim_end();
+    if let Ok(Value::Object(entries)) = serde_json::from_str::<Value>(text) {
+        return TIMESTAMP_FIELDS
+            .iter()
+            .find_map(|name| entries.get(*name))
+            .and_then(Value::as_str)
+            .and_then(parse_time);
+    }
+    let capture = timestamp_regex.captures(text)?;
+    parse_time(&capture["ts"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    // 2024-01-02T03:04:05Z
+    const SECONDS: u64 = 1_704_164_645;
+
+    fn at(seconds: u64, millis: u64) -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(seconds) + Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn utc_times_are_parsed() {
+        assert_eq!(parse_time("2024-01-02T03:04:05Z"), at(SECONDS, 0));
+        assert_eq!(parse_time("2024-01-02T03:04:05"), at(SECONDS, 0));
+        assert_eq!(parse_time("2024-01-02 03:04:05.250"), at(SECONDS, 250));
+        assert_eq!(parse_time("2024-01-02 03:04:05,250Z"), at(SECONDS, 250));
+    }
+
+    #[test]
+    fn offsets_are_shifted_to_utc() {
+        assert_eq!(parse_time("2024-01-02T04:04:05+01:00"), at(SECONDS, 0));
+        assert_eq!(parse_time("2024-01-02T04:34:05+0130"), at(SECONDS, 0));
+        assert_eq!(parse_time("2024-01-01T22:04:05.5-05:00"), at(SECONDS, 500));
+        assert_eq!(parse_time("2024-01-02T03:04:05+00:00"), at(SECONDS, 0));
+    }
+
+    #[test]
+    fn invalid_times_are_rejected() {
+        for invalid in [
+            "",
+            "yesterday",
+            "2024-13-02T03:04:05Z",
+            "2024-01-02T03:04:05+1",
+            "2024-01-02T03:04:05+01:0x",
+            "2024-01-02T03:04:05+01:00:00",
+        ] {
+            assert_eq!(parse_time(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn lines_are_timed_by_their_prefix_or_json_field() {
+        let regex = Regex::new(TIMESTAMP_PATTERN).unwrap();
+        assert_eq!(
+            line_time(b"2024-01-02T03:04:05Z INFO started\n", &regex),
+            at(SECONDS, 0)
+        );
+        assert_eq!(
+            line_time(b"[2024-01-02 04:04:05,100+01:00] started\n", &regex),
+            at(SECONDS, 100)
+        );
+        assert_eq!(
+            line_time(
+                br#"{"level":"info","@timestamp":"2024-01-02T03:04:05Z"}"#,
+                &regex
+            ),
+            at(SECONDS, 0)
+        );
+        assert_eq!(
+            line_time(br#"{"ts":"2024-01-02T03:04:05Z","time":"later"}"#, &regex),
+            at(SECONDS, 0)
+        );
+        assert_eq!(line_time(b"INFO 2024-01-02T03:04:05Z\n", &regex), None);
+        assert_eq!(line_time(br#"{"ts":1704164645}"#, &regex), None);
+    }
+}
@@ -0,0 +1,256 @@
+use log::{debug, error, info, warn};
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::audit::AuditLog;
+use crate::manifest::update_manifest;
+use crate::metrics::Counters;
+use crate::{existing_sidecars, remove_sidecars, RotatorError};
+
+const LOGGER: &str = "uploader";
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct S3Location {
+    pub bucket: String,
+    pub prefix: String,
+}
+
+impl S3Location {
+    pub fn parse(value: &str) -> Result<S3Location, String> {
+        let location = value.trim().strip_prefix("s3://").ok_or_else(|| {
+            format!(
+                "Invalid S3 location '{}', expected s3://<bucket>/<prefix>",
+                value
+            )
+        })?;
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        if bucket.is_empty() {
+            return Err(format!("Missing bucket in S3 location '{}'", value));
+        }
+        Ok(S3Location {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SftpLocation {
+    // user@host, or just the host to connect as the current user
+    pub destination: String,
+    pub directory: String,
+}
+
+impl SftpLocation {
+    pub fn parse(value: &str) -> Result<SftpLocation, String> {
+        let value = value.trim();
+        let host_start = value.find('@').map(|at| at + 1).unwrap_or(0);
+        let (destination, directory) = value[host_start..]
+            .find(':')
+            .map(|colon| value.split_at(host_start + colon))
+            .ok_or_else(|| {
+                format!(
+                    "Invalid SFTP location '{}', expected user@host:/path",
+                    value
+                )
+            })?;
+        if destination.len() == host_start {
+            return Err(format!("Missing host in SFTP location '{}'", value));
+        }
+        Ok(SftpLocation {
+            destination: destination.to_string(),
+            directory: directory[1..].trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Remote {
+    // Uploaded with the AWS CLI, which reads the credentials from its usual sources
+    S3 {
+        location: S3Location,
+        endpoint: Option<String>,
+    },
+    // Copied with scp in batch mode, so that only keys and never passwords are used
+    Sftp {
+        location: SftpLocation,
+        identity: Option<PathBuf>,
+    },
+}
+
+impl Remote {
+    fn url(&self, name: &str) -> String {
+        match self {
+            Remote::S3 { location, .. } if location.prefix.is_empty() => {
+                format!("s3://{}/{}", location.bucket, name)
+            }
+            Remote::S3 { location, .. } => {
+                format!("s3://{}/{}/{}", location.bucket, location.prefix, name)
+            }
+            // Without a directory, scp copies to the home directory of the user
+            Remote::Sftp { location, .. } if location.directory.is_empty() => {
+                format!("{}:{}", location.destination, name)
+            }
+            Remote::Sftp { location, .. } => {
+                format!("{}:{}/{}", location.destination, location.directory, name)
+            }
+        }
+    }
+
+    fn command(&self, path: &Path, name: &str) -> Command {
+        match self {
+            Remote::S3 { endpoint, .. } => {
+                let mut command = Command::new("aws");
+                if let Some(endpoint) = endpoint {
+                    command.arg("--endpoint-url").arg(endpoint);
+                }
+                command
+                    .args(["s3", "cp", "--only-show-errors"])
+                    .arg(path)
+                    .arg(self.url(name));
+                command
+            }
+            Remote::Sftp { identity, .. } => {
+                let mut command = Command::new("scp");
+                command.args(["-q", "-B", "-p"]);
+                if let Some(identity) = identity {
+                    command.arg("-i").arg(identity);
+                }
+                command.arg(path).arg(self.url(name));
+                command
+            }
+        }
+    }
+
+    fn upload(&self, path: &Path) -> Result<(), RotatorError> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| format!("Invalid rotation '{}'", path.display()))?;
+        let output = self
+            .command(path, &name)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .output()
+            .map_err(|op| format!("Error while running upload of '{}': {}", path.display(), op))?;
+        if !output.status.success() {
+            return Err(RotatorError::from(format!(
+                "Error while uploading '{}' to '{}': {} {}",
+                path.display(),
+                self.url(&name),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Uploader {
+    pub remote: Remote,
+    pub retries: u32,
+    pub delete_local: bool,
+}
+
+impl Uploader {
+    fn upload_with_retries(&self, path: &Path) -> Result<(), RotatorError> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 0;
+        loop {
+            match self.remote.upload(path) {
+                Ok(()) => return Ok(()),
+                Err(err) if attempt < self.retries && path.exists() => {
+                    attempt += 1;
+                    warn!(target: LOGGER, "{}, retrying in {:?}", err.msg, backoff);
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // The sidecars follow the rotation, so that the remote copy can be verified and searched too
+    fn run(
+        &self,
+        path: &Path,
+        counters: &Counters,
+        output_file: &str,
+    ) -> Result<bool, RotatorError> {
+        if !path.exists() {
+            debug!(target: LOGGER, "'{}' was removed before upload", path.display());
+            return Ok(false);
+        }
+        let sidecars = existing_sidecars(path);
+        self.upload_with_retries(path)?;
+        for sidecar in &sidecars {
+            self.upload_with_retries(sidecar)?;
+        }
+        if self.delete_local {
+            debug!(target: LOGGER, "Removing uploaded '{}'", path.display());
+            fs::remove_file(path)
+                .map_err(|op| format!("Error while removing '{}': {}", path.display(), op))?;
+            counters.record_deletion();
+            remove_sidecars(path)?;
+            update_manifest(output_file, false, |manifest| {
+                manifest.forget(&[path.to_path_buf()])
+            })?;
+        }
+        Ok(true)
+    }
+}
+
+// Rotations removed once uploaded leave the manifest of the output file, as cleaned up ones do
+pub fn start_uploader(
+    uploader: Uploader,
+    counters: Arc<Counters>,
+    audit: Option<AuditLog>,
+    output_file: String,
+) -> (Sender<PathBuf>, JoinHandle<()>) {
+    let (txupload, rxupload) = mpsc::channel::<PathBuf>();
+    let handle = thread::spawn(move || {
+        for path in rxupload {
+            debug!(target: LOGGER, "Uploading '{}'", path.display());
+            match uploader.run(&path, &counters, &output_file) {
+                Ok(true) => {
+                    info!(target: LOGGER, "Uploaded '{}'", path.display());
+                    if let Some(audit) = &audit {
+                        let name = path.file_name().unwrap_or_default().to_string_lossy();
+                        audit.record(
+                            "upload",
+                            json!({
+                                "rotation": path.to_string_lossy(),
+                                "url": uploader.remote.url(&name),
+                                "removed_local": uploader.delete_local,
+                            }),
+                        );
+                    }
+                }
+                Ok(false) => {}
+                Err(result) => {
+                    counters.record_error();
+                    error!(target: LOGGER, "Error while uploading rotation: {}", result);
+                    if let Some(audit) = &audit {
+                        audit.record(
+                            "upload_error",
+                            json!({
+                                "rotation": path.to_string_lossy(),
+                                "error": result.msg,
+                            }),
+                        );
+                    }
+                }
+            }
+        }
+    });
+    (txupload, handle)
+}
@@ -0,0 +1,106 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use stdout_rotator::checksum::{checksum_path, rotation_checksum};
+use stdout_rotator::compress::{open_rotation, rotation_format};
+use stdout_rotator::manifest::{Manifest, ManifestEntry};
+use stdout_rotator::naming::{Naming, NamingArgs};
+use stdout_rotator::{all_rotations, rotations_directory, RotatorError, ROTATION_EXTENSIONS};
+
+#[derive(clap::Args, Debug)]
+pub struct VerifyArgs {
+    #[arg(
+        help = "File whose rotations are verified: compressed ones are decompressed, checksums written with --checksum or recorded with --manifest are compared and missing indices, rotations missing from the manifest and manifest rotations missing from the disk are reported. Exits with 1 when any check fails"
+    )]
+    file: String,
+    #[arg(long, default_value = None, help = "Directory where rotated files are saved. If not provided, the same directory of the file will be used")]
+    rotation_directory: Option<String>,
+    #[command(flatten)]
+    naming: NamingArgs,
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Do not report missing indices, e.g. for rotations thinned with --keep"
+    )]
+    ignore_gaps: bool,
+}
+
+// The problem found with the rotation, if any
+fn check(path: &Path, recorded: Option<&ManifestEntry>) -> Result<Option<String>, RotatorError> {
+    let sidecar = checksum_path(path);
+    if sidecar.exists() {
+        let expected = fs::read_to_string(&sidecar)
+            .map_err(|op| format!("Error while reading '{}': {}", sidecar.display(), op))?;
+        let expected = expected.split_whitespace().next().unwrap_or_default();
+        if rotation_checksum(path)? != expected.to_lowercase() {
+            return Ok(Some(format!("checksum differs from {}", sidecar.display())));
+        }
+    } else if let Some(expected) = recorded.and_then(|entry| entry.sha256.as_ref()) {
+        if rotation_checksum(path)? != *expected {
+            return Ok(Some("checksum differs from the manifest".to_string()));
+        }
+    }
+    if rotation_format(path).is_some() {
+        let mut reader = open_rotation(path)?;
+        if let Err(op) = io::copy(&mut reader, &mut io::sink()) {
+            return Ok(Some(format!("cannot be decompressed: {}", op)));
+        }
+    }
+    Ok(None)
+}
+
+pub fn run(args: VerifyArgs) -> Result<i32, RotatorError> {
+    let naming = Naming::new(&args.naming);
+    let base_name = Path::new(&args.file)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let rotations = all_rotations(&args.file, args.rotation_directory.as_deref(), &naming)?;
+    let manifest = Manifest::load(&args.file)?;
+    let mut failures = 0;
+    let mut indices = vec![];
+    for rotation in &rotations {
+        let recorded = manifest
+            .as_ref()
+            .and_then(|manifest| manifest.entry(rotation));
+        match check(rotation, recorded)? {
+            Some(problem) => {
+                println!("corrupt {}: {}", rotation.display(), problem);
+                failures += 1;
+            }
+            None => println!("ok {}", rotation.display()),
+        }
+        if manifest.is_some() && recorded.is_none() {
+            println!(
+                "unrecorded {} missing from the manifest",
+                rotation.display()
+            );
+            failures += 1;
+        }
+        let name = rotation.file_name().unwrap().to_string_lossy().to_string();
+        if let Some(index) = naming.index_of(&base_name, &name, ROTATION_EXTENSIONS) {
+            indices.push(index);
+        }
+    }
+    // Rotations the manifest records are only dropped from it when removed by stdout-rotator
+    let directory = rotations_directory(&args.file, args.rotation_directory.as_deref());
+    for entry in manifest.iter().flat_map(|manifest| &manifest.rotations) {
+        if !directory.join(&entry.file).exists() {
+            println!(
+                "missing {} recorded in the manifest",
+                directory.join(&entry.file).display()
+            );
+            failures += 1;
+        }
+    }
+    if !args.ignore_gaps {
+        indices.sort();
+        for pair in indices.windows(2) {
+            for missing in pair[0] + 1..pair[1] {
+                println!("missing rotation {} of {}", missing, args.file);
+                failures += 1;
+            }
+        }
+    }
+    Ok(if failures > 0 { 1 } else { 0 })
+}
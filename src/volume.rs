@@ -0,0 +1,81 @@
+use log::debug;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use stdout_rotator::hooks::Alerter;
+use stdout_rotator::metrics::Counters;
+
+const LOGGER: &str = "volume_monitor";
+
+#[derive(Clone, Debug)]
+pub struct VolumeConfig {
+    pub interval: Duration,
+    pub window: usize,
+    pub spike_factor: Option<f64>,
+    pub drop_factor: Option<f64>,
+}
+
+struct Baseline {
+    name: &'static str,
+    history: VecDeque<u64>,
+}
+
+impl Baseline {
+    fn new(name: &'static str) -> Baseline {
+        Baseline {
+            name,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn observe(&mut self, value: u64, config: &VolumeConfig, alerter: &Alerter) {
+        if self.history.len() >= config.window {
+            let mean = self.history.iter().sum::<u64>() as f64 / self.history.len() as f64;
+            debug!(target: LOGGER, "{}: current={} baseline={:.1}", self.name, value, mean);
+            if let Some(factor) = config.spike_factor {
+                if mean > 0.0 && value as f64 > mean * factor {
+                    alerter.alert(
+                        "volume-spike",
+                        &format!(
+                            "{} per interval spiked to {} against a baseline of {:.1} (factor {})",
+                            self.name, value, mean, factor
+                        ),
+                    );
+                }
+            }
+            if let Some(factor) = config.drop_factor {
+                if mean > 0.0 && (value as f64) < mean / factor {
+                    alerter.alert(
+                        "volume-drop",
+                        &format!(
+                            "{} per interval dropped to {} against a baseline of {:.1} (factor {})",
+                            self.name, value, mean, factor
+                        ),
+                    );
+                }
+            }
+            self.history.pop_front();
+        }
+        self.history.push_back(value);
+    }
+}
+
+pub fn start_volume_monitor(
+    config: VolumeConfig,
+    counters: Arc<Counters>,
+    alerter: Alerter,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut bytes = Baseline::new("bytes");
+        let mut lines = Baseline::new("lines");
+        let mut previous = counters.snapshot();
+        loop {
+            thread::sleep(config.interval);
+            let current = counters.snapshot();
+            bytes.observe(current.bytes_in - previous.bytes_in, &config, &alerter);
+            lines.observe(current.lines_in - previous.lines_in, &config, &alerter);
+            previous = current;
+        }
+    })
+}
@@ -0,0 +1,162 @@
+use log::debug;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::RotatorError;
+
+const LOGGER: &str = "watcher";
+
+#[derive(Clone)]
+pub struct Watcher {
+    changed: Arc<AtomicBool>,
+}
+
+impl Watcher {
+    pub fn start(
+        output_file: &str,
+        rotation_directory: Option<&str>,
+    ) -> Result<Watcher, RotatorError> {
+        let output = Path::new(output_file);
+        let base_name = output
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("Invalid output file '{}'", output_file))?
+            .to_string();
+        let output_directory = match output.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let mut directories = vec![output_directory];
+        if let Some(directory) = rotation_directory {
+            let directory = PathBuf::from(directory);
+            if !directories.contains(&directory) {
+                directories.push(directory);
+            }
+        }
+        let watcher = Watcher {
+            changed: Arc::new(AtomicBool::new(false)),
+        };
+        watch(directories, base_name, watcher.changed.clone())?;
+        Ok(watcher)
+    }
+
+    pub fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::AcqRel)
+    }
+}
+
+fn is_managed(name: &str, base_name: &str) -> bool {
+    // Rotation templates can place the base name anywhere in the rotated file names
+    name.contains(base_name)
+}
+
+#[cfg(target_os = "linux")]
+fn watch(
+    directories: Vec<PathBuf>,
+    base_name: String,
+    changed: Arc<AtomicBool>,
+) -> Result<(), RotatorError> {
+    use log::warn;
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(RotatorError::from(format!(
+            "Error while initialising inotify: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    let mask = libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_MOVED_FROM
+        | libc::IN_MOVED_TO
+        | libc::IN_DELETE_SELF
+        | libc::IN_MOVE_SELF;
+    for directory in &directories {
+        let path = CString::new(directory.as_os_str().as_bytes())
+            .map_err(|op| format!("Invalid directory '{}': {}", directory.display(), op))?;
+        if unsafe { libc::inotify_add_watch(fd, path.as_ptr(), mask) } < 0 {
+            let error = std::io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(RotatorError::from(format!(
+                "Error while watching '{}': {}",
+                directory.display(),
+                error
+            )));
+        }
+    }
+    thread::spawn(move || {
+        let header = std::mem::size_of::<libc::inotify_event>();
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let read =
+                unsafe { libc::read(fd, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) };
+            if read < 0 {
+                let error = std::io::Error::last_os_error();
+                if error.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                warn!(target: LOGGER, "Error while reading inotify events, stopping watch: {}", error);
+                break;
+            }
+            let mut offset = 0;
+            while offset + header <= read as usize {
+                let event = &buffer[offset..];
+                let event_mask = u32::from_ne_bytes(event[4..8].try_into().unwrap());
+                let length = u32::from_ne_bytes(event[12..16].try_into().unwrap()) as usize;
+                let name = &event[header..header + length];
+                let name = String::from_utf8_lossy(
+                    &name[..name.iter().position(|b| *b == 0).unwrap_or(length)],
+                );
+                if event_mask & (libc::IN_DELETE_SELF | libc::IN_MOVE_SELF) != 0
+                    || is_managed(&name, &base_name)
+                {
+                    debug!(target: LOGGER, "External change to '{}' (mask {:#x})", name, event_mask);
+                    changed.store(true, Ordering::Release);
+                }
+                offset += header + length;
+            }
+        }
+        unsafe { libc::close(fd) };
+    });
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watch(
+    directories: Vec<PathBuf>,
+    base_name: String,
+    changed: Arc<AtomicBool>,
+) -> Result<(), RotatorError> {
+    use std::time::Duration;
+
+    let listing = move || {
+        let mut names = vec![];
+        for directory in &directories {
+            if let Ok(entries) = std::fs::read_dir(directory) {
+                for entry in entries.flatten() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if is_managed(&name, &base_name) {
+                        names.push(entry.path());
+                    }
+                }
+            }
+        }
+        names.sort();
+        names
+    };
+    let mut previous = listing();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        let current = listing();
+        if current != previous {
+            debug!(target: LOGGER, "External change to rotation files: {:?}", current);
+            changed.store(true, Ordering::Release);
+            previous = current;
+        }
+    });
+    Ok(())
+}
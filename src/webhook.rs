@@ -0,0 +1,85 @@
+use log::{debug, warn};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+use crate::host::hostname;
+
+const LOGGER: &str = "webhook";
+const REQUEST_TIMEOUT_SECONDS: &str = "10";
+
+// Events are posted by a single worker, in order, so that a slow endpoint never blocks a writer
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    sender: Sender<Option<Value>>,
+}
+
+impl Webhook {
+    pub fn start(url: &str) -> (Webhook, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel::<Option<Value>>();
+        let url = url.to_string();
+        let handle = thread::spawn(move || {
+            while let Ok(Some(event)) = receiver.recv() {
+                post(&url, &event);
+            }
+        });
+        (Webhook { sender }, handle)
+    }
+
+    pub fn notify(&self, event: &str, details: Value) {
+        // Events raised after the worker stopped are dropped
+        let _ = self.sender.send(Some(event_payload(event, details)));
+    }
+
+    // The events queued so far are still posted before the worker exits
+    pub fn stop(&self) {
+        let _ = self.sender.send(None);
+    }
+}
+
+// Also the records of the audit file
+pub fn event_payload(event: &str, details: Value) -> Value {
+    let mut payload = json!({
+        "event": event,
+        "timestamp": humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+        "host": hostname(),
+    });
+    if let (Some(payload), Value::Object(details)) = (payload.as_object_mut(), details) {
+        payload.extend(details);
+    }
+    payload
+}
+
+fn post(url: &str, event: &Value) {
+    debug!(target: LOGGER, "Posting {}", event);
+    let child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time"])
+        .arg(REQUEST_TIMEOUT_SECONDS)
+        .args(["--header", "Content-Type: application/json"])
+        .args(["--data-binary", "@-"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(op) => {
+            warn!(target: LOGGER, "Error while running curl for the webhook: {}", op);
+            return;
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(event.to_string().as_bytes());
+    }
+    match child.wait_with_output() {
+        Ok(output) if !output.status.success() => {
+            warn!(target: LOGGER, "Error while posting to webhook '{}': {} {}", url, output.status, String::from_utf8_lossy(&output.stderr).trim())
+        }
+        Ok(_) => {}
+        Err(op) => warn!(target: LOGGER, "Error while posting to webhook '{}': {}", url, op),
+    }
+}
@@ -0,0 +1,165 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime};
+
+use crate::compress::CompressionFormat;
+use crate::index::IndexWriter;
+use crate::lock::OutputLock;
+use crate::{
+    ensure_free_space, lock_output, open_output, prepare_rotations, write_batch, ActiveFile,
+    OpenOutput, RotationConfig, RotatorError, Workers,
+};
+
+// What a RotatingWriter rotates on and keeps, the defaults being those of the command line
+#[derive(Clone, Debug)]
+pub struct WriterOptions {
+    pub max_size: u64,
+    pub max_history: u32,
+    pub max_total_size: Option<u64>,
+    pub max_age: Option<Duration>,
+    pub rotate_every: Option<Duration>,
+    pub max_file_age: Option<Duration>,
+    pub max_lines: Option<u64>,
+    pub compression: Option<CompressionFormat>,
+    pub compression_level: Option<u32>,
+    // Keep writing to an existing output file instead of truncating it
+    pub append: bool,
+}
+
+impl Default for WriterOptions {
+    fn default() -> WriterOptions {
+        let defaults = RotationConfig::new("");
+        WriterOptions {
+            max_size: defaults.max_size,
+            max_history: defaults.max_history,
+            max_total_size: None,
+            max_age: None,
+            rotate_every: None,
+            max_file_age: None,
+            max_lines: None,
+            compression: None,
+            compression_level: None,
+            append: false,
+        }
+    }
+}
+
+// Writes to a file rotated exactly as the binary rotates its output. Time based triggers are
+// evaluated on every write, as nothing else wakes the writer up
+pub struct RotatingWriter {
+    config: RotationConfig,
+    file: File,
+    active: ActiveFile,
+    index: Option<IndexWriter>,
+    // A trailing partial line waits for its end, so that rotations never split a line written
+    // in several calls
+    held: Vec<u8>,
+    workers: Option<Workers>,
+    _lock: OutputLock,
+}
+
+impl RotatingWriter {
+    pub fn open(output_file: &str, options: WriterOptions) -> io::Result<RotatingWriter> {
+        let mut config = RotationConfig::new(output_file);
+        config.max_size = options.max_size;
+        config.max_history = options.max_history;
+        config.max_total_size = options.max_total_size;
+        config.max_age = options.max_age;
+        config.rotate_every = options.rotate_every;
+        config.max_file_age = options.max_file_age;
+        config.max_lines = options.max_lines;
+        config.compression = options.compression;
+        config.compression_level = options.compression_level;
+        config.append = options.append;
+        RotatingWriter::start(config).map_err(io_error)
+    }
+
+    fn start(config: RotationConfig) -> Result<RotatingWriter, RotatorError> {
+        let lock = lock_output(&config, false)?;
+        prepare_rotations(&config)?;
+        let counters = config.counters.clone();
+        let OpenOutput {
+            config,
+            file,
+            active,
+            index,
+            watcher: _,
+            workers,
+        } = open_output(config, &counters)?;
+        Ok(RotatingWriter {
+            config,
+            file,
+            active,
+            index,
+            held: vec![],
+            workers: Some(workers),
+            _lock: lock,
+        })
+    }
+}
+
+impl std::fmt::Debug for RotatingWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RotatingWriter")
+            .field("output_file", &self.config.output_file)
+            .finish()
+    }
+}
+
+fn io_error(err: RotatorError) -> io::Error {
+    io::Error::other(err.msg)
+}
+
+impl RotatingWriter {
+    fn write_data(&mut self, data: &[u8]) -> Result<(), RotatorError> {
+        ensure_free_space(&self.config)?;
+        write_batch(
+            &mut self.file,
+            data,
+            SystemTime::now(),
+            &self.config,
+            &mut self.index,
+            &mut self.active,
+            &self.config.counters,
+        )
+    }
+}
+
+impl Write for RotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = std::mem::take(&mut self.held);
+        data.extend_from_slice(buf);
+        let complete = data
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map(|position| position + 1)
+            .unwrap_or(0);
+        if (data.len() - complete) as u64 <= self.config.max_size {
+            self.held = data.split_off(complete);
+        }
+        if !data.is_empty() {
+            self.write_data(&data).map_err(io_error)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let held = std::mem::take(&mut self.held);
+        if !held.is_empty() {
+            self.write_data(&held).map_err(io_error)?;
+        }
+        self.file.flush()
+    }
+}
+
+// Rotations still being compressed are finished before the writer is gone
+impl Drop for RotatingWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+        self.config.compression_queue = None;
+        self.config.upload_queue = None;
+        if let Some(workers) = self.workers.take() {
+            workers.join();
+        }
+    }
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{rotator, run, wait, TIMEOUT};
+
+fn clean(directory: &std::path::Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_stdout-rotator"))
+        .current_dir(directory)
+        .arg("clean")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn lock_files_are_removed_on_exit() {
+    let directory = tempfile::tempdir().unwrap();
+    let status = run(directory.path(), &["--output-file", "out.log"], b"line\n");
+    assert!(status.success());
+    assert!(directory.path().join("out.log").exists());
+    assert!(!directory.path().join("out.log.lock").exists());
+}
+
+#[test]
+fn clean_removes_stale_lock_files() {
+    let directory = tempfile::tempdir().unwrap();
+    fs::write(directory.path().join("out.log"), "line\n").unwrap();
+    fs::write(directory.path().join("out.log.lock"), "1\n").unwrap();
+
+    let output = clean(
+        directory.path(),
+        &["--output-file", "out.log", "--include-active", "--dry-run"],
+    );
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("would remove out.log.lock"));
+    assert!(directory.path().join("out.log.lock").exists());
+
+    let output = clean(
+        directory.path(),
+        &["--output-file", "out.log", "--include-active"],
+    );
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("removed out.log.lock"));
+    assert!(!directory.path().join("out.log").exists());
+    assert!(!directory.path().join("out.log.lock").exists());
+}
+
+#[test]
+fn clean_refuses_to_remove_files_being_written() {
+    let directory = tempfile::tempdir().unwrap();
+    let mut child = rotator(directory.path())
+        .args(["--output-file", "out.log"])
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"line\n").unwrap();
+    let lock = directory.path().join("out.log.lock");
+    let deadline = Instant::now() + TIMEOUT;
+    while !lock.exists() {
+        assert!(Instant::now() < deadline, "the output was never locked");
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    for args in [
+        vec!["--include-active"],
+        vec!["--include-active", "--dry-run"],
+    ] {
+        let output = clean(
+            directory.path(),
+            &[&["--output-file", "out.log"], &args[..]].concat(),
+        );
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("already written"));
+    }
+    assert!(directory.path().join("out.log").exists());
+    assert!(lock.exists());
+
+    drop(stdin);
+    assert!(wait(child, TIMEOUT).success());
+    assert!(!lock.exists());
+}
+
+#[test]
+fn clean_removes_the_latest_rotation_pointer() {
+    let directory = tempfile::tempdir().unwrap();
+    let args = [
+        "--output-file",
+        "out.log",
+        "--max-size",
+        "10",
+        "--latest-symlink",
+    ];
+    assert!(run(
+        directory.path(),
+        &args,
+        common::numbered_lines("line", 5).as_bytes()
+    )
+    .success());
+    let link = directory.path().join("out.log.latest-rotation");
+    assert!(fs::read_link(&link).is_ok());
+
+    let output = clean(directory.path(), &["--output-file", "out.log"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("removed out.log.latest-rotation"));
+    assert!(fs::symlink_metadata(&link).is_err());
+    assert!(directory.path().join("out.log").exists());
+}
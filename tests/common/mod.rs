@@ -0,0 +1,107 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn rotator(directory: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_stdout-rotator"));
+    command
+        .current_dir(directory)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    command
+}
+
+// Fails the test rather than hanging it when the rotator never exits
+pub fn wait(mut child: Child, timeout: Duration) -> ExitStatus {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status;
+        }
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            let mut stderr = String::new();
+            if let Some(mut pipe) = child.stderr.take() {
+                let _ = pipe.read_to_string(&mut stderr);
+            }
+            panic!(
+                "stdout-rotator did not exit within {:?}: {}",
+                timeout, stderr
+            );
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+// Writes every chunk after the previous one waited for, then closes the input
+pub fn run_with_chunks(
+    directory: &Path,
+    args: &[&str],
+    chunks: &[(&[u8], Duration)],
+) -> ExitStatus {
+    let mut child = rotator(directory).args(args).spawn().unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for (chunk, pause) in chunks {
+        stdin.write_all(chunk).unwrap();
+        stdin.flush().unwrap();
+        thread::sleep(*pause);
+    }
+    drop(stdin);
+    wait(child, TIMEOUT)
+}
+
+pub fn run(directory: &Path, args: &[&str], input: &[u8]) -> ExitStatus {
+    run_with_chunks(directory, args, &[(input, Duration::ZERO)])
+}
+
+pub fn numbered_lines(prefix: &str, count: usize) -> String {
+    (1..=count).map(|i| format!("{} {}\n", prefix, i)).collect()
+}
+
+// Rotations of `base` with their index, oldest first
+pub fn rotations(directory: &Path, base: &str) -> Vec<(u64, PathBuf)> {
+    let mut rotations: Vec<(u64, PathBuf)> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?.to_string();
+            let rest = name.strip_prefix(&format!("{}.", base))?;
+            let index = rest.split('.').next()?.parse::<u64>().ok()?;
+            let extension = &rest[rest.find('.').unwrap_or(rest.len())..];
+            matches!(extension, "" | ".gz").then_some((index, path))
+        })
+        .collect();
+    rotations.sort();
+    rotations
+}
+
+pub fn read_rotation(path: &Path) -> String {
+    let mut content = String::new();
+    let file = fs::File::open(path).unwrap();
+    if path.extension().is_some_and(|extension| extension == "gz") {
+        flate2::read::MultiGzDecoder::new(file)
+            .read_to_string(&mut content)
+            .unwrap();
+    } else {
+        (&file).read_to_string(&mut content).unwrap();
+    }
+    content
+}
+
+// What was kept of the output file, oldest rotation first
+pub fn kept_content(directory: &Path, base: &str) -> String {
+    let mut content: String = rotations(directory, base)
+        .iter()
+        .map(|(_, path)| read_rotation(path))
+        .collect();
+    content.push_str(&fs::read_to_string(directory.join(base)).unwrap_or_default());
+    content
+}
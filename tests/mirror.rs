@@ -0,0 +1,34 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use common::{rotator, wait, TIMEOUT};
+
+#[test]
+fn partial_lines_are_mirrored_right_away() {
+    let directory = tempfile::tempdir().unwrap();
+    // The file encoding frames the input into lines
+    let mut child = rotator(directory.path())
+        .stdout(Stdio::piped())
+        .args(["--output-file", "out.log", "--file-encoding", "json"])
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let (txmirrored, rxmirrored) = mpsc::channel();
+    thread::spawn(move || {
+        let mut prompt = [0; 8];
+        stdout.read_exact(&mut prompt).unwrap();
+        txmirrored.send(prompt).unwrap();
+    });
+    stdin.write_all(b"Answer? ").unwrap();
+    stdin.flush().unwrap();
+    let mirrored = rxmirrored.recv_timeout(Duration::from_secs(5));
+    drop(stdin);
+    assert!(wait(child, TIMEOUT).success());
+    assert_eq!(&mirrored.unwrap(), b"Answer? ");
+}
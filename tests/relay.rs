@@ -0,0 +1,59 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use common::{kept_content, numbered_lines, rotations, run, run_with_chunks};
+use std::time::Duration;
+
+#[test]
+fn the_async_relay_rotates_the_input() {
+    let directory = tempfile::tempdir().unwrap();
+    let chunks: Vec<String> = (0..5)
+        .map(|chunk| numbered_lines(&format!("chunk {}", chunk), 20))
+        .collect();
+    let chunks: Vec<(&[u8], Duration)> = chunks
+        .iter()
+        .map(|chunk| (chunk.as_bytes(), Duration::from_millis(50)))
+        .collect();
+    let status = run_with_chunks(
+        directory.path(),
+        &[
+            "--async-relay",
+            "--output-file",
+            "out.log",
+            "--max-size",
+            "200",
+            "--max-history",
+            "100",
+            "--gunzip",
+        ],
+        &chunks,
+    );
+    assert!(status.success());
+    assert!(rotations(directory.path(), "out.log").len() > 1);
+    let expected: String = chunks
+        .iter()
+        .map(|(chunk, _)| String::from_utf8_lossy(chunk).to_string())
+        .collect();
+    assert_eq!(kept_content(directory.path(), "out.log"), expected);
+}
+
+#[test]
+fn options_the_async_relay_does_not_support_are_rejected() {
+    let directory = tempfile::tempdir().unwrap();
+    for option in [
+        ["--redact", "a=>b"],
+        ["--sqlite-sink", "lines.db"],
+        ["--pre-rotate-cmd", "true"],
+        ["--output-file", "other.log"],
+    ] {
+        let mut args = vec!["--async-relay", "--output-file", "out.log"];
+        args.extend(option);
+        assert!(
+            !run(directory.path(), &args, b"line\n").success(),
+            "{:?}",
+            option
+        );
+    }
+    assert!(!directory.path().join("out.log").exists());
+}
@@ -0,0 +1,246 @@
+mod common;
+
+use std::fs;
+use std::time::Duration;
+
+use common::{kept_content, numbered_lines, rotations, run, run_with_chunks};
+
+#[test]
+fn size_rotation_keeps_the_newest_content() {
+    let directory = tempfile::tempdir().unwrap();
+    let input = numbered_lines("line", 5000);
+    let status = run(
+        directory.path(),
+        &["--output-file", "out.log", "--max-size", "8KB", "-m", "3"],
+        input.as_bytes(),
+    );
+    assert!(status.success());
+    let rotations = rotations(directory.path(), "out.log");
+    assert_eq!(rotations.len(), 3);
+    for (_, path) in &rotations {
+        assert!(path.metadata().unwrap().len() >= 8192);
+    }
+    // Retention removed the oldest rotations, what is left is the end of the input
+    let kept = kept_content(directory.path(), "out.log");
+    assert!(kept.len() < input.len());
+    assert!(input.ends_with(&kept));
+}
+
+#[test]
+fn rotations_are_compressed() {
+    let directory = tempfile::tempdir().unwrap();
+    let input = numbered_lines("line", 5000);
+    let status = run(
+        directory.path(),
+        &[
+            "--output-file",
+            "out.log",
+            "--max-size",
+            "8KB",
+            "-m",
+            "100",
+            "--gunzip",
+        ],
+        input.as_bytes(),
+    );
+    assert!(status.success());
+    let rotations = rotations(directory.path(), "out.log");
+    assert!(rotations.len() > 3);
+    for (_, path) in &rotations {
+        assert_eq!(path.extension().unwrap(), "gz");
+    }
+    assert_eq!(kept_content(directory.path(), "out.log"), input);
+}
+
+#[test]
+fn delayed_compression_keeps_a_single_rotation_uncompressed() {
+    let directory = tempfile::tempdir().unwrap();
+    let chunks: Vec<String> = (0..4)
+        .map(|chunk| numbered_lines(&format!("chunk {}", chunk), 20))
+        .collect();
+    let chunks: Vec<(&[u8], Duration)> = chunks
+        .iter()
+        .map(|chunk| (chunk.as_bytes(), Duration::from_millis(100)))
+        .collect();
+    let status = run_with_chunks(
+        directory.path(),
+        &[
+            "--output-file",
+            "out.log",
+            "--max-size",
+            "100",
+            "-m",
+            "1",
+            "--gunzip",
+            "--delay-compress",
+        ],
+        &chunks,
+    );
+    assert!(status.success());
+    let rotations = rotations(directory.path(), "out.log");
+    assert_eq!(rotations.len(), 1);
+    assert_eq!(
+        common::read_rotation(&rotations[0].1),
+        numbered_lines("chunk 3", 20)
+    );
+    let names: Vec<String> = fs::read_dir(directory.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert!(
+        names.iter().all(|name| !name.contains(".gz")),
+        "{:?}",
+        names
+    );
+}
+
+#[test]
+fn time_rotation_splits_at_the_interval() {
+    let directory = tempfile::tempdir().unwrap();
+    let first = numbered_lines("first", 10);
+    let second = numbered_lines("second", 10);
+    let status = run_with_chunks(
+        directory.path(),
+        &["--output-file", "out.log", "--rotate-every", "1s"],
+        &[
+            (first.as_bytes(), Duration::from_millis(2500)),
+            (second.as_bytes(), Duration::ZERO),
+        ],
+    );
+    assert!(status.success());
+    let rotations = rotations(directory.path(), "out.log");
+    assert!(!rotations.is_empty());
+    assert_eq!(common::read_rotation(&rotations[0].1), first);
+    assert_eq!(
+        kept_content(directory.path(), "out.log"),
+        format!("{}{}", first, second)
+    );
+}
+
+#[test]
+fn cleanup_applies_to_existing_rotations_on_start() {
+    let directory = tempfile::tempdir().unwrap();
+    for index in 1..=6 {
+        std::fs::write(
+            directory.path().join(format!("out.log.{}", index)),
+            format!("rotation {}\n", index),
+        )
+        .unwrap();
+    }
+    let status = run(
+        directory.path(),
+        &["--output-file", "out.log", "-m", "2"],
+        b"line\n",
+    );
+    assert!(status.success());
+    let indices: Vec<u64> = rotations(directory.path(), "out.log")
+        .into_iter()
+        .map(|(index, _)| index)
+        .collect();
+    assert_eq!(indices, vec![5, 6]);
+}
+
+#[test]
+fn strict_max_size_goes_over_the_limit_while_rotation_is_postponed() {
+    let directory = tempfile::tempdir().unwrap();
+    let input = numbered_lines("line", 100);
+    let status = run(
+        directory.path(),
+        &[
+            "--output-file",
+            "out.log",
+            "--max-size",
+            "10",
+            "--strict-max-size",
+            "--pre-rotate-cmd",
+            "false",
+        ],
+        input.as_bytes(),
+    );
+    assert!(status.success());
+    assert!(rotations(directory.path(), "out.log").is_empty());
+    assert_eq!(
+        fs::read_to_string(directory.path().join("out.log")).unwrap(),
+        input
+    );
+}
+
+#[test]
+fn free_space_cleanup_runs_the_pre_delete_command() {
+    let directory = tempfile::tempdir().unwrap();
+    for index in 1..=3 {
+        fs::write(
+            directory.path().join(format!("out.log.{}", index)),
+            format!("rotation {}\n", index),
+        )
+        .unwrap();
+    }
+    // No filesystem has all of its space free, so every rotation the command allows is removed
+    let status = run(
+        directory.path(),
+        &[
+            "--output-file",
+            "out.log",
+            "--min-free-space",
+            "100%",
+            "--pre-delete-cmd",
+            "case {file} in *.2) exit 1;; esac",
+        ],
+        b"line\n",
+    );
+    assert!(status.success());
+    let indices: Vec<u64> = rotations(directory.path(), "out.log")
+        .into_iter()
+        .map(|(index, _)| index)
+        .collect();
+    assert_eq!(indices, vec![2]);
+}
+
+#[test]
+fn the_manifest_follows_compressed_rotations() {
+    let directory = tempfile::tempdir().unwrap();
+    let chunks: Vec<String> = (0..4)
+        .map(|chunk| numbered_lines(&format!("chunk {}", chunk), 20))
+        .collect();
+    let chunks: Vec<(&[u8], Duration)> = chunks
+        .iter()
+        .map(|chunk| (chunk.as_bytes(), Duration::from_millis(100)))
+        .collect();
+    let status = run_with_chunks(
+        directory.path(),
+        &[
+            "--output-file",
+            "out.log",
+            "--max-size",
+            "100",
+            "--gunzip",
+            "--manifest",
+        ],
+        &chunks,
+    );
+    assert!(status.success());
+    let manifest: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(directory.path().join("out.log.manifest.json")).unwrap(),
+    )
+    .unwrap();
+    let mut recorded: Vec<String> = manifest["rotations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            let first = humantime::parse_rfc3339(entry["first_write"].as_str().unwrap()).unwrap();
+            let last = humantime::parse_rfc3339(entry["last_write"].as_str().unwrap()).unwrap();
+            assert!(first <= last);
+            entry["file"].as_str().unwrap().to_string()
+        })
+        .collect();
+    recorded.sort();
+    let mut kept: Vec<String> = rotations(directory.path(), "out.log")
+        .into_iter()
+        .map(|(_, path)| path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    kept.sort();
+    assert!(kept.len() > 1);
+    assert!(kept.iter().all(|file| file.ends_with(".gz")));
+    assert_eq!(recorded, kept);
+}
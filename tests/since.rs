@@ -0,0 +1,76 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::run;
+
+fn subcommand(directory: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_stdout-rotator"))
+        .current_dir(directory)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+// Written without --index, the rotation holding both older and newer lines
+fn unindexed(directory: &Path) {
+    fs::write(
+        directory.join("out.log.1"),
+        "2024-01-01T10:00:00Z old\n2024-01-01T12:00:00Z rotated\n",
+    )
+    .unwrap();
+    fs::write(directory.join("out.log"), "2024-01-01T13:00:00Z active\n").unwrap();
+}
+
+#[test]
+fn subcommands_scan_timestamps_without_an_index() {
+    let directory = tempfile::tempdir().unwrap();
+    unindexed(directory.path());
+    let since = "2024-01-01T11:00:00Z";
+    let expected = "2024-01-01T12:00:00Z rotated\n2024-01-01T13:00:00Z active\n";
+    for args in [
+        vec!["cat", "out.log", "--since", since],
+        vec!["tail", "out.log", "--since", since],
+    ] {
+        let output = subcommand(directory.path(), &args);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            expected,
+            "{:?}",
+            args
+        );
+    }
+    let output = subcommand(
+        directory.path(),
+        &["grep", "-c", "2024", "out.log", "--since", since],
+    );
+    assert!(output.status.success());
+    let counts = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        counts.lines().all(|line| line.ends_with(":1")),
+        "{}",
+        counts
+    );
+}
+
+#[test]
+fn index_every_requires_the_index() {
+    let directory = tempfile::tempdir().unwrap();
+    let args = ["--output-file", "out.log", "--index-every", "1KB"];
+    assert!(!run(directory.path(), &args, b"line\n").success());
+    let args = [
+        "--output-file",
+        "out.log",
+        "--index",
+        "--index-every",
+        "1KB",
+    ];
+    assert!(run(directory.path(), &args, b"line\n").success());
+}
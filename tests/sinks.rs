@@ -0,0 +1,99 @@
+mod common;
+
+use std::fs;
+
+use common::run;
+
+#[test]
+fn sinks_receive_redacted_and_filtered_lines() {
+    let directory = tempfile::tempdir().unwrap();
+    let status = run(
+        directory.path(),
+        &[
+            "--output-file",
+            "out.log",
+            "--redact",
+            "token=\\w+=>token=XXX",
+            "--file-exclude-regex",
+            "debug",
+            "--tee-cmd",
+            "cat > tee.log",
+        ],
+        b"token=SECRET123 hello\ndebug line\ninfo line\n",
+    );
+    assert!(status.success());
+    let expected = "token=XXX hello\ninfo line\n";
+    assert_eq!(
+        fs::read_to_string(directory.path().join("out.log")).unwrap(),
+        expected
+    );
+    assert_eq!(
+        fs::read_to_string(directory.path().join("tee.log")).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn the_sqlite_sink_stores_redacted_lines() {
+    let directory = tempfile::tempdir().unwrap();
+    let status = run(
+        directory.path(),
+        &[
+            "--output-file",
+            "out.log",
+            "--redact",
+            "token=\\w+=>token=XXX",
+            "--sqlite-sink",
+            "lines.db",
+        ],
+        b"token=SECRET123 hello\n",
+    );
+    assert!(status.success());
+    let connection = rusqlite::Connection::open(directory.path().join("lines.db")).unwrap();
+    let messages: Vec<String> = connection
+        .prepare("SELECT message FROM lines")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(|message| message.unwrap())
+        .collect();
+    assert_eq!(messages, vec!["token=XXX hello"]);
+}
+
+#[test]
+fn sinks_choose_their_encoding() {
+    let directory = tempfile::tempdir().unwrap();
+    let status = run(
+        directory.path(),
+        &[
+            "--output-file",
+            "out.log",
+            "--tee-cmd",
+            "cat > tee.log",
+            "--tee-encoding",
+            "json",
+        ],
+        b"first\nsecond\n",
+    );
+    assert!(status.success());
+    assert_eq!(
+        fs::read_to_string(directory.path().join("out.log")).unwrap(),
+        "first\nsecond\n"
+    );
+    let messages: Vec<String> = fs::read_to_string(directory.path().join("tee.log"))
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+            entry["msg"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert_eq!(messages, vec!["first", "second"]);
+}
+
+#[test]
+fn sink_encodings_require_their_sink() {
+    let directory = tempfile::tempdir().unwrap();
+    let args = ["--output-file", "out.log", "--tee-encoding", "json"];
+    assert!(!run(directory.path(), &args, b"line\n").success());
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::process::Command;
+
+use common::{numbered_lines, rotations, rotator, wait, TIMEOUT};
+
+#[test]
+fn rotations_removed_once_uploaded_leave_the_manifest() {
+    let directory = tempfile::tempdir().unwrap();
+    // Stands in for scp, accepting every upload
+    let bin = directory.path().join("bin");
+    fs::create_dir(&bin).unwrap();
+    let scp = bin.join("scp");
+    fs::write(&scp, "#!/bin/sh\nexit 0\n").unwrap();
+    fs::set_permissions(&scp, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), env::var("PATH").unwrap());
+
+    let mut child = rotator(directory.path())
+        .env("PATH", path)
+        .args([
+            "--output-file",
+            "out.log",
+            "--max-size",
+            "50",
+            "--manifest",
+            "--sftp-upload",
+            "backup@host:/logs",
+            "--upload-delete-local",
+        ])
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin
+        .write_all(numbered_lines("line", 20).as_bytes())
+        .unwrap();
+    drop(stdin);
+    assert!(wait(child, TIMEOUT).success());
+    assert!(rotations(directory.path(), "out.log").is_empty());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_stdout-rotator"))
+        .current_dir(directory.path())
+        .args(["verify", "out.log"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
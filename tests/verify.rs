@@ -0,0 +1,43 @@
+mod common;
+
+use std::fs;
+use std::process::{Command, Output};
+
+use common::{numbered_lines, rotations, run};
+
+fn verify(directory: &std::path::Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_stdout-rotator"))
+        .current_dir(directory)
+        .args(["verify", "out.log"])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn rotations_missing_from_the_manifest_are_reported() {
+    let directory = tempfile::tempdir().unwrap();
+    let input = numbered_lines("line", 20);
+    let args = ["--output-file", "out.log", "--max-size", "50", "--manifest"];
+    assert!(run(directory.path(), &args, input.as_bytes()).success());
+    let output = verify(directory.path());
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let newest = rotations(directory.path(), "out.log").last().unwrap().0;
+    let unrecorded = directory.path().join(format!("out.log.{}", newest + 1));
+    fs::write(&unrecorded, "line\n").unwrap();
+    let output = verify(directory.path());
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let expected = format!(
+        "{} missing from the manifest",
+        unrecorded.file_name().unwrap().to_string_lossy()
+    );
+    assert!(stdout
+        .lines()
+        .any(|line| line.starts_with("unrecorded ") && line.ends_with(&expected)));
+    assert_eq!(stdout.matches("unrecorded").count(), 1);
+}